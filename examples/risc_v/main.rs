@@ -1,11 +1,46 @@
 extern crate nandverse;
 
-use nandverse::bus::{bus_to_num, to_bus};
+use nandverse::bus::Bus;
+use nandverse::minimize::{minimize, Cover};
 
 const XLEN: usize = 32;
 
 fn main() {
-    println!("hi");
+    let names = [
+        "op[0]", "op[1]", "op[2]", "op[3]", "op[4]", "op[5]", "op[6]",
+    ];
+    for (format, cover) in format_decoders() {
+        println!(
+            "{:?}: {} ({} NAND gates)",
+            format,
+            cover.to_expr(&names),
+            cover.to_netlist(&names).nand_count()
+        );
+    }
+}
+
+/// Generates a minimal NAND-NAND decoder for each instruction format from the opcode table.
+/// Opcodes which aren't used by any format are don't cares
+fn format_decoders() -> Vec<(InstructionFormat, Cover)> {
+    let formats: Vec<Option<InstructionFormat>> = (0..128u8)
+        .map(|opcode| {
+            let opcode = Bus::try_from_num(opcode).unwrap();
+            InstructionFormat::try_from(&opcode).ok()
+        })
+        .collect();
+    let unused: Vec<usize> = (0..formats.len())
+        .filter(|opcode| formats[*opcode].is_none())
+        .collect();
+
+    InstructionFormat::ALL
+        .iter()
+        .map(|format| {
+            let opcodes: Vec<usize> = (0..formats.len())
+                .filter(|opcode| formats[*opcode] == Some(*format))
+                .collect();
+            (*format, minimize(7, &opcodes, &unused))
+        })
+        .collect()
 }
 
 /// Pseudocode notation:
@@ -103,72 +138,74 @@ pub enum BaseInstruction {
 }
 
 impl BaseInstruction {
-    pub fn value(&self) -> [bool; 6] {
-        match self {
+    /// Opcode of the instruction
+    pub fn value(&self) -> Bus<7> {
+        let opcode: u8 = match self {
             // Upper immediate
-            BaseInstruction::LUI => to_bus(0b0110111),
-            BaseInstruction::AUIPC => to_bus(0b0010111),
+            BaseInstruction::LUI => 0b0110111,
+            BaseInstruction::AUIPC => 0b0010111,
 
             // Jump
-            BaseInstruction::JAL => to_bus(0b1101111),
+            BaseInstruction::JAL => 0b1101111,
 
             // Immediate
-            BaseInstruction::JALR => to_bus(0b1100111),
+            BaseInstruction::JALR => 0b1100111,
 
             // Branch
-            BaseInstruction::BEQ => to_bus(0b1100011),
-            BaseInstruction::BNE => to_bus(0b1100011),
-            BaseInstruction::BLT => to_bus(0b1100011),
-            BaseInstruction::BGE => to_bus(0b1100011),
-            BaseInstruction::BLTU => to_bus(0b1100011),
-            BaseInstruction::BGEU => to_bus(0b1100011),
+            BaseInstruction::BEQ => 0b1100011,
+            BaseInstruction::BNE => 0b1100011,
+            BaseInstruction::BLT => 0b1100011,
+            BaseInstruction::BGE => 0b1100011,
+            BaseInstruction::BLTU => 0b1100011,
+            BaseInstruction::BGEU => 0b1100011,
 
             // Immediate
-            BaseInstruction::LB => to_bus(0b0000011),
-            BaseInstruction::LH => to_bus(0b0000011),
-            BaseInstruction::LW => to_bus(0b0000011),
-            BaseInstruction::LBU => to_bus(0b0000011),
-            BaseInstruction::LHU => to_bus(0b0000011),
+            BaseInstruction::LB => 0b0000011,
+            BaseInstruction::LH => 0b0000011,
+            BaseInstruction::LW => 0b0000011,
+            BaseInstruction::LBU => 0b0000011,
+            BaseInstruction::LHU => 0b0000011,
 
             // Store
-            BaseInstruction::SB => to_bus(0b0100011),
-            BaseInstruction::SH => to_bus(0b0100011),
-            BaseInstruction::SW => to_bus(0b0100011),
+            BaseInstruction::SB => 0b0100011,
+            BaseInstruction::SH => 0b0100011,
+            BaseInstruction::SW => 0b0100011,
 
             // Immediate
-            BaseInstruction::ADDI => to_bus(0b0010011),
-            BaseInstruction::SLTI => to_bus(0b0010011),
-            BaseInstruction::SLTIU => to_bus(0b0010011),
-            BaseInstruction::XORI => to_bus(0b0010011),
-            BaseInstruction::ORI => to_bus(0b0010011),
-            BaseInstruction::ANDI => to_bus(0b0010011),
-            BaseInstruction::SLLI => to_bus(0b0010011),
-            BaseInstruction::SRLI => to_bus(0b0010011),
-            BaseInstruction::SRAI => to_bus(0b0010011),
+            BaseInstruction::ADDI => 0b0010011,
+            BaseInstruction::SLTI => 0b0010011,
+            BaseInstruction::SLTIU => 0b0010011,
+            BaseInstruction::XORI => 0b0010011,
+            BaseInstruction::ORI => 0b0010011,
+            BaseInstruction::ANDI => 0b0010011,
+            BaseInstruction::SLLI => 0b0010011,
+            BaseInstruction::SRLI => 0b0010011,
+            BaseInstruction::SRAI => 0b0010011,
 
             // Register/register
-            BaseInstruction::ADD => to_bus(0b0110011),
-            BaseInstruction::SUB => to_bus(0b0110011),
-            BaseInstruction::SLL => to_bus(0b0110011),
-            BaseInstruction::SLT => to_bus(0b0110011),
-            BaseInstruction::SLTU => to_bus(0b0110011),
-            BaseInstruction::XOR => to_bus(0b0110011),
-            BaseInstruction::SRL => to_bus(0b0110011),
-            BaseInstruction::SRA => to_bus(0b0110011),
-            BaseInstruction::OR => to_bus(0b0110011),
-            BaseInstruction::AND => to_bus(0b0110011),
-
-            BaseInstruction::FENCE => to_bus(0b0001111),
-            BaseInstruction::FENCEI => to_bus(0b0001111),
-            // BaseInstruction::SCALL => to_bus(0b1110011),
-            // BaseInstruction::SBREAK => to_bus(0b1110011),
-            // BaseInstruction::RDCYCLE => to_bus(0b1110011),
-            // BaseInstruction::RDCYCLEH => to_bus(0b1110011),
-            // BaseInstruction::RDTIME => to_bus(0b1110011),
-            // BaseInstruction::RDTIMEH => to_bus(0b1110011),
-            // BaseInstruction::RDINSTRET => to_bus(0b1110011),
-            // BaseInstruction::RDINSTRETH => to_bus(0b1110011),
-        }
+            BaseInstruction::ADD => 0b0110011,
+            BaseInstruction::SUB => 0b0110011,
+            BaseInstruction::SLL => 0b0110011,
+            BaseInstruction::SLT => 0b0110011,
+            BaseInstruction::SLTU => 0b0110011,
+            BaseInstruction::XOR => 0b0110011,
+            BaseInstruction::SRL => 0b0110011,
+            BaseInstruction::SRA => 0b0110011,
+            BaseInstruction::OR => 0b0110011,
+            BaseInstruction::AND => 0b0110011,
+
+            BaseInstruction::FENCE => 0b0001111,
+            BaseInstruction::FENCEI => 0b0001111,
+            // BaseInstruction::SCALL => 0b1110011,
+            // BaseInstruction::SBREAK => 0b1110011,
+            // BaseInstruction::RDCYCLE => 0b1110011,
+            // BaseInstruction::RDCYCLEH => 0b1110011,
+            // BaseInstruction::RDTIME => 0b1110011,
+            // BaseInstruction::RDTIMEH => 0b1110011,
+            // BaseInstruction::RDINSTRET => 0b1110011,
+            // BaseInstruction::RDINSTRETH => 0b1110011,
+        };
+        Bus::try_from_num(opcode).expect("opcodes are 7 bits wide")
     }
 
     pub fn instruction_format(&self) -> InstructionFormat {
@@ -240,11 +277,11 @@ impl BaseInstruction {
     }
 }
 
-impl TryFrom<&[bool; XLEN]> for BaseInstruction {
+impl TryFrom<&Bus<XLEN>> for BaseInstruction {
     type Error = ();
 
-    fn try_from(value: &[bool; XLEN]) -> Result<Self, Self::Error> {
-        let opcode: [bool; 7] = value[0..7].try_into().unwrap();
+    fn try_from(value: &Bus<XLEN>) -> Result<Self, Self::Error> {
+        let opcode = value.slice::<0, 7>();
 
         let format = InstructionFormat::try_from(&opcode).unwrap();
 
@@ -259,6 +296,7 @@ impl TryFrom<&[bool; XLEN]> for BaseInstruction {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum InstructionFormat {
     /// Register/register.
     RType,
@@ -274,11 +312,22 @@ pub enum InstructionFormat {
     JType,
 }
 
-impl TryFrom<&[bool; 7]> for InstructionFormat {
+impl InstructionFormat {
+    pub const ALL: [InstructionFormat; 6] = [
+        InstructionFormat::RType,
+        InstructionFormat::IType,
+        InstructionFormat::SType,
+        InstructionFormat::BType,
+        InstructionFormat::UType,
+        InstructionFormat::JType,
+    ];
+}
+
+impl TryFrom<&Bus<7>> for InstructionFormat {
     type Error = ();
 
-    fn try_from(value: &[bool; 7]) -> Result<Self, Self::Error> {
-        let opcode = bus_to_num::<u8>(value);
+    fn try_from(value: &Bus<7>) -> Result<Self, Self::Error> {
+        let opcode = u8::try_from(*value).map_err(|_| ())?;
         match opcode {
             0b0110011 => Ok(InstructionFormat::RType),
 
@@ -299,41 +348,3 @@ impl TryFrom<&[bool; 7]> for InstructionFormat {
         }
     }
 }
-
-pub struct Cpu {
-    /// Register file.
-    rf: RegisterFile,
-    /// ALU.
-    alu: Alu,
-}
-
-pub struct RegisterFile {
-    // Hardwired to contant 0.
-    x0: Register,
-    /// General purpose registers: x0 to xXLEN. Details at https://en.wikichip.org/wiki/risc-v/registers.
-    general: [Register; XLEN],
-    /// Program counter. Cannot be written or read using load/store instructions.
-    pc: Register,
-}
-
-struct Register {}
-
-pub struct Alu {}
-
-impl Alu {
-    pub fn execute_instruction(instruction: &[bool; XLEN]) {
-        // 1. Decode opcode
-        // 2. If required, decode funct3
-        // 3. If required, decode funct7
-        // 4. Execute instruction
-    }
-
-    fn decode_opcode(instruction: &[bool; XLEN]) -> BaseInstruction {
-        let _instruction = BaseInstruction::try_from(instruction).unwrap();
-        todo!()
-    }
-}
-
-pub fn alu(op: &[bool; 6], a: &[bool; XLEN], b: &[bool; XLEN]) -> [bool; XLEN] {
-    todo!()
-}
@@ -0,0 +1,640 @@
+use core::fmt;
+use std::collections::HashMap;
+
+use crate::netlist::{Builder, CellKind, Driver, Net, Netlist, NetlistError};
+
+/// Most inputs read, so a malformed header can't exhaust memory
+const MAX_INPUTS: usize = 1 << 20;
+
+/// Encoding of an AIGER file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AigerFormat {
+    /// The "aag" format, with every literal written as text
+    Ascii,
+    /// The compact "aig" format, with inputs implicit and AND gates delta encoded
+    Binary,
+}
+
+/// Error returned when a netlist can't be written as AIGER, or AIGER can't be read
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AigerError {
+    /// The named flip-flop isn't clocked by the same input port as the others, or that port is
+    /// read by something else. AIGER latches all share one implicit clock
+    Clock(String),
+    MissingHeader,
+    Syntax {
+        line: usize,
+    },
+    /// A literal refers to a variable beyond the maximum given in the header
+    LiteralOutOfRange {
+        line: usize,
+        literal: usize,
+    },
+    /// The AND gate with the given index redefines a variable, reads one which isn't defined or
+    /// is part of a loop, or in the binary format is truncated
+    InvalidAnd(usize),
+    Netlist(NetlistError),
+}
+
+impl fmt::Display for AigerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AigerError::Clock(cell) => {
+                write!(
+                    f,
+                    "flip-flop {} isn't clocked by the shared clock input",
+                    cell
+                )
+            }
+            AigerError::MissingHeader => write!(f, "missing \"aag\" or \"aig\" header"),
+            AigerError::Syntax { line } => write!(f, "syntax error on line {}", line),
+            AigerError::LiteralOutOfRange { line, literal } => {
+                write!(f, "literal {} on line {} is out of range", literal, line)
+            }
+            AigerError::InvalidAnd(index) => write!(f, "AND gate {} is invalid", index),
+            AigerError::Netlist(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for AigerError {}
+
+/// Writes a netlist as an and-inverter graph in the AIGER format, for tools such as ABC
+///
+/// Each NAND gate becomes a chain of AND gates with its output inverted, and each flip-flop a
+/// latch starting low. AIGER latches share an implicit clock, so every flip-flop must be clocked
+/// by the same input port, which nothing else reads and which is left out of the inputs. Ports
+/// and latches are named in the symbol table
+pub fn to_aiger(netlist: &Netlist, format: AigerFormat) -> Result<Vec<u8>, AigerError> {
+    let flipflops = netlist.flipflops();
+    let clock = flipflops.first().map(|id| netlist.cell(*id).inputs()[0]);
+    let mut clock_readers = 0;
+    for id in netlist.cell_ids() {
+        let cell = netlist.cell(id);
+        for (pin, net) in cell.inputs().iter().enumerate() {
+            let clocked = cell.kind() == CellKind::Dff && pin == 0;
+            if clocked && Some(*net) != clock {
+                return Err(AigerError::Clock(netlist.cell_path(id)));
+            }
+            clock_readers += (Some(*net) == clock) as usize;
+        }
+    }
+    if let Some(clock) = clock {
+        let is_input = matches!(netlist.driver(clock), Some(Driver::Input(_)));
+        let read_by_outputs = netlist.outputs().iter().any(|p| p.net() == clock);
+        if !is_input || read_by_outputs || clock_readers != flipflops.len() {
+            return Err(AigerError::Clock(netlist.cell_path(flipflops[0])));
+        }
+    }
+
+    // Variables are numbered inputs first, then latches, then AND gates
+    let inputs: Vec<Net> = netlist
+        .inputs()
+        .iter()
+        .map(|p| p.net())
+        .filter(|net| Some(*net) != clock)
+        .collect();
+    let mut literals: HashMap<Net, usize> = HashMap::new();
+    for (i, net) in inputs.iter().enumerate() {
+        literals.insert(*net, 2 * (i + 1));
+    }
+    for (i, id) in flipflops.iter().enumerate() {
+        literals.insert(netlist.cell(*id).output(), 2 * (inputs.len() + i + 1));
+    }
+    let mut ands: Vec<(usize, usize)> = Vec::new();
+    let first_and = inputs.len() + flipflops.len() + 1;
+    for cell in netlist.cells() {
+        let literal = match cell.kind() {
+            CellKind::Const(value) => value as usize,
+            CellKind::Dff => continue,
+            CellKind::Nand => {
+                let mut inputs = cell.inputs().iter().map(|n| literals[n]);
+                let mut and = inputs.next().unwrap();
+                for input in inputs {
+                    ands.push((and.max(input), and.min(input)));
+                    and = 2 * (first_and + ands.len() - 1);
+                }
+                and ^ 1
+            }
+        };
+        literals.insert(cell.output(), literal);
+    }
+
+    let max = first_and - 1 + ands.len();
+    let mut aiger = format!(
+        "{} {} {} {} {} {}\n",
+        match format {
+            AigerFormat::Ascii => "aag",
+            AigerFormat::Binary => "aig",
+        },
+        max,
+        inputs.len(),
+        flipflops.len(),
+        netlist.outputs().len(),
+        ands.len()
+    )
+    .into_bytes();
+    let mut text = String::new();
+    if format == AigerFormat::Ascii {
+        for net in &inputs {
+            text.push_str(&format!("{}\n", literals[net]));
+        }
+    }
+    for id in &flipflops {
+        let cell = netlist.cell(*id);
+        if format == AigerFormat::Ascii {
+            text.push_str(&format!("{} ", literals[&cell.output()]));
+        }
+        text.push_str(&format!("{}\n", literals[&cell.inputs()[1]]));
+    }
+    for port in netlist.outputs() {
+        text.push_str(&format!("{}\n", literals[&port.net()]));
+    }
+    for (i, (rhs0, rhs1)) in ands.iter().enumerate() {
+        let lhs = 2 * (first_and + i);
+        match format {
+            AigerFormat::Ascii => text.push_str(&format!("{} {} {}\n", lhs, rhs0, rhs1)),
+            AigerFormat::Binary => {
+                aiger.append(&mut core::mem::take(&mut text).into_bytes());
+                encode_delta(&mut aiger, lhs - rhs0);
+                encode_delta(&mut aiger, rhs0 - rhs1);
+            }
+        }
+    }
+
+    let ports = netlist.inputs().iter().filter(|p| Some(p.net()) != clock);
+    for (i, port) in ports.enumerate() {
+        text.push_str(&format!("i{} {}\n", i, port.name()));
+    }
+    for (i, id) in flipflops.iter().enumerate() {
+        let name = netlist.net_name(netlist.cell(*id).output());
+        text.push_str(&format!("l{} {}\n", i, name));
+    }
+    for (i, port) in netlist.outputs().iter().enumerate() {
+        text.push_str(&format!("o{} {}\n", i, port.name()));
+    }
+    aiger.append(&mut text.into_bytes());
+    Ok(aiger)
+}
+
+/// Appends a number as 7 bit groups, least significant first, with the top bit of each byte set
+/// if more follow
+fn encode_delta(bytes: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Reads an ASCII or binary AIGER file into a netlist with the given name
+///
+/// Latches become flip-flops clocked by an input port "clk", added before the other inputs. A
+/// latch starting high is a flip-flop storing the inverse of its next state, and one with an
+/// undefined start starts low. Ports and latches named in the symbol table keep their names,
+/// and other ports are named after their position, e.g. "i0" and "o0"
+pub fn from_aiger(data: &[u8], name: &str) -> Result<Netlist, AigerError> {
+    let mut reader = Reader {
+        data,
+        pos: 0,
+        line: 1,
+    };
+    let header = reader.line().ok_or(AigerError::MissingHeader)?;
+    let mut fields = header.split_whitespace();
+    let binary = match fields.next() {
+        Some("aag") => false,
+        Some("aig") => true,
+        _ => return Err(AigerError::MissingHeader),
+    };
+    let numbers: Result<Vec<usize>, _> = fields.map(|f| f.parse()).collect();
+    let Ok(&[max, num_inputs, num_latches, num_outputs, num_ands]) = numbers.as_deref() else {
+        return Err(AigerError::Syntax { line: 1 });
+    };
+    // Literals go up to 2 * max + 1, which must fit along with every variable
+    let variables = num_inputs
+        .checked_add(num_latches)
+        .and_then(|n| n.checked_add(num_ands));
+    let fits = max.checked_mul(2).and_then(|n| n.checked_add(1)).is_some();
+    if !fits || variables.is_none_or(|n| n > max) || num_inputs > MAX_INPUTS {
+        return Err(AigerError::Syntax { line: 1 });
+    }
+    // Every section must fit in what's left of the file before anything is allocated. Binary AND
+    // gates take at least two bytes each, and everything else a line
+    let lines = if binary {
+        num_latches.checked_add(num_outputs)
+    } else {
+        variables.and_then(|n| n.checked_add(num_outputs))
+    };
+    let and_bytes = if binary {
+        num_ands.checked_mul(2)
+    } else {
+        Some(0)
+    };
+    if lines.is_none_or(|n| n > reader.remaining_lines())
+        || and_bytes.is_none_or(|n| n > reader.data.len().saturating_sub(reader.pos))
+    {
+        return Err(AigerError::Syntax { line: 1 });
+    }
+
+    // Lines of each section, with the binary format's implicit literals filled in
+    let mut inputs = Vec::new();
+    for i in 0..num_inputs {
+        let line = reader.line;
+        let literal = if binary {
+            2 * (i + 1)
+        } else {
+            reader.literals(1..2, max)?[0]
+        };
+        inputs.push((literal, line));
+    }
+    let mut latches = Vec::new();
+    for i in 0..num_latches {
+        let line = reader.line;
+        let mut fields = if binary {
+            [vec![2 * (num_inputs + i + 1)], reader.literals(1..3, max)?].concat()
+        } else {
+            reader.literals(2..4, max)?
+        };
+        fields.resize(3, 0);
+        latches.push((fields, line));
+    }
+    let mut outputs = Vec::new();
+    for _ in 0..num_outputs {
+        let line = reader.line;
+        outputs.push((reader.literals(1..2, max)?[0], line));
+    }
+    let mut ands = Vec::new();
+    for i in 0..num_ands {
+        if binary {
+            let lhs = 2 * (num_inputs + num_latches + i + 1);
+            let rhs0 = reader
+                .delta()
+                .filter(|d| *d > 0)
+                .and_then(|d| lhs.checked_sub(d));
+            let rhs1 = rhs0.and_then(|r| Some(r - reader.delta().filter(|d| *d <= r)?));
+            match (rhs0, rhs1) {
+                (Some(rhs0), Some(rhs1)) => ands.push([lhs, rhs0, rhs1]),
+                _ => return Err(AigerError::InvalidAnd(i)),
+            }
+        } else {
+            let fields = reader.literals(3..4, max)?;
+            ands.push([fields[0], fields[1], fields[2]]);
+        }
+    }
+
+    // The symbol table runs up to the optional comment section
+    let mut symbols: HashMap<(char, usize), String> = HashMap::new();
+    loop {
+        let line = reader.line;
+        let Some(text) = reader.line() else { break };
+        if text == "c" {
+            break;
+        }
+        let symbol = text.split_once(' ').and_then(|(position, name)| {
+            let kind = position.chars().next().filter(|c| "ilo".contains(*c))?;
+            Some(((kind, position[1..].parse().ok()?), name.to_string()))
+        });
+        let Some((key, name)) = symbol else {
+            return Err(AigerError::Syntax { line });
+        };
+        symbols.insert(key, name);
+    }
+
+    // Each variable is the value of a net, or of its inverse
+    let mut builder = Builder::new(name);
+    let clock = (num_latches > 0).then(|| builder.input("clk"));
+    let mut values: HashMap<usize, (Net, bool)> = HashMap::new();
+    for (i, (literal, line)) in inputs.iter().enumerate() {
+        let name = symbols.remove(&('i', i)).unwrap_or(format!("i{}", i));
+        let net = builder.input(&name);
+        if literal % 2 == 1 || *literal == 0 || values.insert(literal / 2, (net, false)).is_some() {
+            return Err(AigerError::Syntax { line: *line });
+        }
+    }
+
+    // A latch starting high is a flip-flop storing its inverse
+    let mut flipflops = Vec::new();
+    for (i, (fields, line)) in latches.iter().enumerate() {
+        let q = builder.net();
+        if let Some(name) = symbols.remove(&('l', i)) {
+            builder.name_net(q, &name);
+        }
+        let high = fields[2] == 1;
+        let literal = fields[0];
+        if literal % 2 == 1 || literal == 0 || values.insert(literal / 2, (q, high)).is_some() {
+            return Err(AigerError::Syntax { line: *line });
+        }
+        flipflops.push((q, fields[1], high, *line));
+    }
+
+    // AND gates are built as NAND cells, whose outputs are the inverse of their variables. The
+    // ASCII format allows them in any order, so they're built in topological order
+    let mut defined: HashMap<usize, usize> = HashMap::new();
+    for (i, [lhs, _, _]) in ands.iter().enumerate() {
+        if lhs % 2 == 1 || values.contains_key(&(lhs / 2)) || defined.insert(lhs / 2, i).is_some() {
+            return Err(AigerError::InvalidAnd(i));
+        }
+    }
+    let mut order = Vec::new();
+    let mut done: HashMap<usize, bool> = HashMap::new();
+    for i in 0..ands.len() {
+        let mut stack = vec![i];
+        while let Some(i) = stack.pop() {
+            match done.get(&i) {
+                Some(true) => continue,
+                Some(false) => {
+                    done.insert(i, true);
+                    order.push(i);
+                    continue;
+                }
+                None => {}
+            }
+            done.insert(i, false);
+            stack.push(i);
+            for rhs in &ands[i][1..] {
+                let Some(j) = defined.get(&(rhs / 2)) else {
+                    continue;
+                };
+                match done.get(j) {
+                    Some(false) => return Err(AigerError::InvalidAnd(*j)),
+                    Some(true) => {}
+                    None => stack.push(*j),
+                }
+            }
+        }
+    }
+
+    let mut inverses: HashMap<Net, Net> = HashMap::new();
+    let mut literal = |builder: &mut Builder, values: &HashMap<usize, (Net, bool)>, literal| {
+        if literal / 2 == 0 {
+            return Some(builder.constant(literal == 1));
+        }
+        let (net, inverted) = values.get(&(literal / 2))?;
+        if inverted ^ (literal % 2 == 1) {
+            Some(*inverses.entry(*net).or_insert_with(|| builder.not(*net)))
+        } else {
+            Some(*net)
+        }
+    };
+    for i in order {
+        let [lhs, rhs0, rhs1] = ands[i];
+        let a = literal(&mut builder, &values, rhs0).ok_or(AigerError::InvalidAnd(i))?;
+        let b = literal(&mut builder, &values, rhs1).ok_or(AigerError::InvalidAnd(i))?;
+        let nand = builder.nand(&[a, b]);
+        values.insert(lhs / 2, (nand, true));
+    }
+    let out_of_range = |literal, line| AigerError::LiteralOutOfRange { line, literal };
+    for (q, next, high, line) in flipflops {
+        let d =
+            literal(&mut builder, &values, next ^ high as usize).ok_or(out_of_range(next, line))?;
+        builder.add_cell(CellKind::Dff, &[clock.unwrap(), d], q);
+    }
+    for (i, (output, line)) in outputs.iter().enumerate() {
+        let net = literal(&mut builder, &values, *output).ok_or(out_of_range(*output, *line))?;
+        let name = symbols.remove(&('o', i)).unwrap_or(format!("o{}", i));
+        builder.output(&name, net);
+    }
+    builder.try_finish().map_err(AigerError::Netlist)
+}
+
+/// Reads lines and binary deltas from an AIGER file
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// Line number of the next line
+    line: usize,
+}
+
+impl Reader<'_> {
+    fn line(&mut self) -> Option<&str> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let rest = &self.data[self.pos..];
+        let end = rest.iter().position(|b| *b == b'\n').unwrap_or(rest.len());
+        self.pos += end + 1;
+        self.line += 1;
+        core::str::from_utf8(&rest[..end]).ok()
+    }
+
+    /// Number of lines left, counting an unterminated last one
+    fn remaining_lines(&self) -> usize {
+        let rest = self.data.get(self.pos..).unwrap_or_default();
+        let newlines = rest.iter().filter(|b| **b == b'\n').count();
+        newlines + (rest.last().is_some_and(|b| *b != b'\n') as usize)
+    }
+
+    /// Reads a line of literals, with a count in the given range
+    fn literals(
+        &mut self,
+        count: impl core::ops::RangeBounds<usize>,
+        max: usize,
+    ) -> Result<Vec<usize>, AigerError> {
+        let line = self.line;
+        let syntax = AigerError::Syntax { line };
+        let text = self.line().ok_or(syntax.clone())?;
+        let literals: Vec<usize> = text
+            .split_whitespace()
+            .map(|f| f.parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| syntax.clone())?;
+        if !count.contains(&literals.len()) {
+            return Err(syntax);
+        }
+        if let Some(literal) = literals.iter().find(|l| **l / 2 > max) {
+            return Err(AigerError::LiteralOutOfRange {
+                line,
+                literal: *literal,
+            });
+        }
+        Ok(literals)
+    }
+
+    fn delta(&mut self) -> Option<usize> {
+        let mut value = 0;
+        for shift in (0..usize::BITS).step_by(7) {
+            let byte = *self.data.get(self.pos)?;
+            self.pos += 1;
+            value |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::{RippleCounter, SynchronousCounter};
+    use crate::math::RippleCarryAdder;
+    use crate::truth_table::TruthTable;
+
+    /// Outputs of a sequential netlist after each step of the inputs
+    fn run(netlist: &Netlist, vectors: &[Vec<bool>]) -> Vec<Vec<bool>> {
+        let mut state = netlist.reset_state();
+        vectors
+            .iter()
+            .map(|inputs| {
+                let values = netlist.step(&mut state, inputs);
+                netlist
+                    .outputs()
+                    .iter()
+                    .map(|p| values[p.net().index()])
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_to_aiger() {
+        let mut builder = Builder::new("nand3");
+        let inputs = builder.input_bus::<3>("a");
+        let nand = builder.nand(&inputs);
+        let one = builder.constant(true);
+        builder.output("y", nand);
+        builder.output("one", one);
+        let netlist = builder.finish();
+        assert_eq!(
+            String::from_utf8(to_aiger(&netlist, AigerFormat::Ascii).unwrap()).unwrap(),
+            "aag 5 3 0 2 2\n2\n4\n6\n11\n1\n8 4 2\n10 8 6\ni0 a[0]\ni1 a[1]\ni2 a[2]\no0 y\no1 one\n"
+        );
+        assert_eq!(
+            to_aiger(&netlist, AigerFormat::Binary).unwrap(),
+            b"aig 5 3 0 2 2\n11\n1\n\x04\x02\x02\x02i0 a[0]\ni1 a[1]\ni2 a[2]\no0 y\no1 one\n"
+        );
+
+        let netlist = SynchronousCounter::<2>::new().netlist();
+        let aiger = String::from_utf8(to_aiger(&netlist, AigerFormat::Ascii).unwrap()).unwrap();
+        assert!(aiger.starts_with("aag "), "{}", aiger);
+        let header: Vec<&str> = aiger.lines().next().unwrap().split(' ').collect();
+        assert_eq!(header[2..5], ["0", "2", "2"]);
+
+        let netlist = RippleCounter::<2>::new().netlist();
+        assert!(matches!(
+            to_aiger(&netlist, AigerFormat::Ascii),
+            Err(AigerError::Clock(_))
+        ));
+    }
+
+    #[test]
+    fn test_from_aiger() {
+        for aiger in [
+            &b"aag 3 2 0 1 1\n2\n4\n6\n6 4 2\n"[..],
+            &b"aig 3 2 0 1 1\n6\n\x02\x02"[..],
+        ] {
+            let netlist = from_aiger(aiger, "and").unwrap();
+            assert_eq!(netlist.nand_count(), 2);
+            assert!(
+                TruthTable::from_netlist(&netlist)
+                    .verify_fn(|[a, b]: &[bool; 2]| a & b)
+                    .is_ok(),
+                "failed for inputs: {:?}",
+                aiger
+            );
+        }
+
+        // The half adder from the AIGER specification
+        let netlist = from_aiger(
+            b"aag 7 2 0 2 3\n2\n4\n6\n12\n6 13 15\n12 2 4\n14 3 5\n\
+              i0 x\ni1 y\no0 s\no1 c\nc\nhalf adder\n",
+            "half_adder",
+        )
+        .unwrap();
+        let inputs: Vec<&str> = netlist.inputs().iter().map(|p| p.name()).collect();
+        let outputs: Vec<&str> = netlist.outputs().iter().map(|p| p.name()).collect();
+        assert_eq!((inputs, outputs), (vec!["x", "y"], vec!["s", "c"]));
+        assert!(TruthTable::from_netlist(&netlist)
+            .verify_fn(|[x, y]: &[bool; 2]| [x ^ y, x & y])
+            .is_ok());
+
+        // A toggle starting high, with an unnamed output
+        let netlist = from_aiger(b"aag 1 0 1 1 0\n2 3 1\n2\n", "toggle").unwrap();
+        let inputs: Vec<&str> = netlist.inputs().iter().map(|p| p.name()).collect();
+        assert_eq!(inputs, ["clk"]);
+        assert_eq!(netlist.outputs()[0].name(), "o0");
+        let vectors: Vec<Vec<bool>> = (0..6).map(|i| vec![i % 2 == 1]).collect();
+        let outputs: Vec<bool> = run(&netlist, &vectors).concat();
+        assert_eq!(outputs, [true, false, false, true, true, false]);
+    }
+
+    #[test]
+    fn test_from_aiger_errors() {
+        let cases: [(&[u8], AigerError); 13] = [
+            (b"", AigerError::MissingHeader),
+            (b"blif 0 0 0 0 0\n", AigerError::MissingHeader),
+            (b"aig x 0 0 0 0\n", AigerError::Syntax { line: 1 }),
+            (
+                b"aag 5 18446744073709551615 1 0 0\n",
+                AigerError::Syntax { line: 1 },
+            ),
+            (
+                b"aig 18446744073709551615 1 0 0 0\n",
+                AigerError::Syntax { line: 1 },
+            ),
+            (b"aag 2 1 1 0 1\n", AigerError::Syntax { line: 1 }),
+            (b"aag 1 1 0 1 0\n2\nx\n", AigerError::Syntax { line: 3 }),
+            (b"aag 1 1 0 1 0\n2\n", AigerError::Syntax { line: 1 }),
+            (
+                b"aig 3000000000 3000000000 0 0 0\n",
+                AigerError::Syntax { line: 1 },
+            ),
+            (
+                b"aig 3000000 0 3000000 0 0\n",
+                AigerError::Syntax { line: 1 },
+            ),
+            (
+                b"aig 3 1 0 0 2\n\x02\x02\x02",
+                AigerError::Syntax { line: 1 },
+            ),
+            (
+                b"aag 1 1 0 1 0\n2\n4\n",
+                AigerError::LiteralOutOfRange {
+                    line: 3,
+                    literal: 4,
+                },
+            ),
+            (
+                b"aag 3 1 0 1 2\n2\n4\n4 6 2\n6 4 2\n",
+                AigerError::InvalidAnd(0),
+            ),
+        ];
+        for (aiger, expected) in cases {
+            assert_eq!(
+                from_aiger(aiger, "broken").unwrap_err(),
+                expected,
+                "failed for inputs: {:?}",
+                String::from_utf8_lossy(aiger)
+            );
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let netlist = RippleCarryAdder::<3>::new().netlist();
+        let table = TruthTable::from_netlist(&netlist);
+        for format in [AigerFormat::Ascii, AigerFormat::Binary] {
+            let imported = from_aiger(&to_aiger(&netlist, format).unwrap(), "adder").unwrap();
+            assert_eq!(
+                TruthTable::from_netlist(&imported),
+                table,
+                "failed for inputs: {:?}",
+                format
+            );
+        }
+
+        let netlist = SynchronousCounter::<3>::new().netlist();
+        let vectors: Vec<Vec<bool>> = (0..20).map(|i| vec![i % 2 == 1]).collect();
+        for format in [AigerFormat::Ascii, AigerFormat::Binary] {
+            let imported = from_aiger(&to_aiger(&netlist, format).unwrap(), "counter").unwrap();
+            assert_eq!(imported.flipflops().len(), 3);
+            assert_eq!(
+                run(&imported, &vectors),
+                run(&netlist, &vectors),
+                "failed for inputs: {:?}",
+                format
+            );
+        }
+    }
+}
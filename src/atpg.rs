@@ -0,0 +1,545 @@
+use crate::fault::{self, Fault, FaultReport};
+use crate::netlist::{nand3, CellId, CellKind, Driver, Net, Netlist};
+use crate::truth_table::{self, Mismatch, Mismatches, TableError};
+
+/// Number of decisions PODEM may reverse before giving up on a fault
+const BACKTRACK_LIMIT: usize = 1000;
+
+/// Result of generating a test for one fault
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Input values detecting the fault, None for inputs which may take either value
+    Test(Vec<Option<bool>>),
+    /// No input vector detects the fault, so the logic it sits in is redundant
+    Untestable,
+    /// The search hit the backtrack limit
+    Aborted,
+}
+
+/// Generates a test for a stuck-at fault in a combinational netlist using PODEM, which only
+/// ever decides the value of an input and implies the rest of the circuit from it
+pub fn podem(netlist: &Netlist, fault: &Fault) -> Outcome {
+    assert!(
+        !netlist.is_sequential(),
+        "netlist {} is sequential and can't be tested with PODEM",
+        netlist.name()
+    );
+    let Fault::StuckAt { net, value } = *fault else {
+        panic!("PODEM only targets stuck-at faults");
+    };
+    Podem::new(netlist, net, value).run()
+}
+
+/// Search state of PODEM. Each net has a value in the fault free and the faulty circuit, which
+/// is None while it depends on an unassigned input
+struct Podem<'a> {
+    netlist: &'a Netlist,
+    fault_net: Net,
+    stuck: bool,
+    fanout: Vec<Vec<CellId>>,
+    is_output: Vec<bool>,
+    inputs: Vec<Option<bool>>,
+    good: Vec<Option<bool>>,
+    faulty: Vec<Option<bool>>,
+}
+
+impl<'a> Podem<'a> {
+    fn new(netlist: &'a Netlist, fault_net: Net, stuck: bool) -> Self {
+        let mut is_output = vec![false; netlist.num_nets()];
+        for port in netlist.outputs() {
+            is_output[port.net().index()] = true;
+        }
+        Podem {
+            netlist,
+            fault_net,
+            stuck,
+            fanout: netlist.fanout(),
+            is_output,
+            inputs: vec![None; netlist.inputs().len()],
+            good: vec![None; netlist.num_nets()],
+            faulty: vec![None; netlist.num_nets()],
+        }
+    }
+
+    fn run(mut self) -> Outcome {
+        // Decisions in the order they were made, and whether each has been reversed
+        let mut decisions: Vec<(usize, bool)> = Vec::new();
+        let mut backtracks = 0;
+        loop {
+            self.imply();
+            if self.detected() {
+                return Outcome::Test(self.inputs);
+            }
+            if let Some((net, value)) = self.objective() {
+                let (input, value) = self.backtrace(net, value);
+                self.inputs[input] = Some(value);
+                decisions.push((input, false));
+                continue;
+            }
+
+            // Reverse the most recent decision which hasn't been tried both ways
+            loop {
+                match decisions.pop() {
+                    None => return Outcome::Untestable,
+                    Some((input, false)) => {
+                        backtracks += 1;
+                        if backtracks > BACKTRACK_LIMIT {
+                            return Outcome::Aborted;
+                        }
+                        self.inputs[input] = self.inputs[input].map(|v| !v);
+                        decisions.push((input, true));
+                        break;
+                    }
+                    Some((input, true)) => self.inputs[input] = None,
+                }
+            }
+        }
+    }
+
+    /// Evaluates both circuits from the assigned inputs
+    fn imply(&mut self) {
+        let fault = self.fault_net.index();
+        for (port, value) in self.netlist.inputs().iter().zip(&self.inputs) {
+            self.good[port.net().index()] = *value;
+            self.faulty[port.net().index()] = *value;
+        }
+        self.faulty[fault] = Some(self.stuck);
+        for cell in self.netlist.cells() {
+            let output = cell.output().index();
+            let (good, faulty) = match cell.kind() {
+                CellKind::Nand => (
+                    nand3(cell.inputs().iter().map(|n| self.good[n.index()])),
+                    nand3(cell.inputs().iter().map(|n| self.faulty[n.index()])),
+                ),
+                CellKind::Const(value) => (Some(value), Some(value)),
+                CellKind::Dff => unreachable!("netlist is combinational"),
+            };
+            self.good[output] = good;
+            self.faulty[output] = if output == fault {
+                Some(self.stuck)
+            } else {
+                faulty
+            };
+        }
+    }
+
+    /// Whether the net carries the fault effect, written D or D' in the D-calculus
+    fn has_effect(&self, net: Net) -> bool {
+        let i = net.index();
+        matches!((self.good[i], self.faulty[i]), (Some(g), Some(f)) if g != f)
+    }
+
+    fn is_unknown(&self, net: Net) -> bool {
+        self.good[net.index()].is_none() || self.faulty[net.index()].is_none()
+    }
+
+    fn detected(&self) -> bool {
+        self.netlist
+            .outputs()
+            .iter()
+            .any(|port| self.has_effect(port.net()))
+    }
+
+    /// Next net value to aim for: first exciting the fault, then driving its effect through a
+    /// gate of the D-frontier. Returns None if the current assignment can't detect the fault
+    fn objective(&self) -> Option<(Net, bool)> {
+        match self.good[self.fault_net.index()] {
+            None => return Some((self.fault_net, !self.stuck)),
+            Some(value) if value == self.stuck => return None,
+            Some(_) => {}
+        }
+
+        // Gates with the fault effect on an input and an unknown output
+        let cell = self.netlist.cells().iter().find(|cell| {
+            cell.kind() == CellKind::Nand
+                && self.is_unknown(cell.output())
+                && cell.inputs().iter().any(|n| self.has_effect(*n))
+                && self.x_path(cell.output())
+        })?;
+        // A NAND passes its other inputs through when they are all high
+        let input = cell.inputs().iter().find(|n| self.is_unknown(**n))?;
+        Some((*input, true))
+    }
+
+    /// Whether a path of unknown nets leads from the net to an output
+    fn x_path(&self, net: Net) -> bool {
+        let mut visited = vec![false; self.netlist.num_nets()];
+        let mut stack = vec![net];
+        while let Some(net) = stack.pop() {
+            if self.is_output[net.index()] {
+                return true;
+            }
+            for id in &self.fanout[net.index()] {
+                let output = self.netlist.cell(*id).output();
+                if self.is_unknown(output) && !visited[output.index()] {
+                    visited[output.index()] = true;
+                    stack.push(output);
+                }
+            }
+        }
+        false
+    }
+
+    /// Follows unknown nets back from an objective to an unassigned input, returning the input
+    /// and the value to try on it
+    fn backtrace(&self, mut net: Net, mut value: bool) -> (usize, bool) {
+        loop {
+            match self.netlist.driver(net) {
+                Some(Driver::Input(i)) => return (i, value),
+                Some(Driver::Cell(id)) => {
+                    let cell = self.netlist.cell(id);
+                    net = *cell
+                        .inputs()
+                        .iter()
+                        .find(|n| self.is_unknown(**n))
+                        .expect("unknown nets depend on an unknown input");
+                    // A NAND is high when any input is low and low when all are high
+                    value = !value;
+                }
+                None => unreachable!("unknown nets are driven"),
+            }
+        }
+    }
+}
+
+/// Test vectors generated for a netlist, and how well they cover its stuck-at faults
+#[derive(Clone, Debug, PartialEq)]
+pub struct Atpg {
+    pub tests: TestSet,
+    /// Fault coverage of the tests over every stuck-at fault
+    pub report: FaultReport,
+    pub untestable: Vec<Fault>,
+    pub aborted: Vec<Fault>,
+}
+
+/// Generates a compact set of test vectors detecting every testable stuck-at fault of a
+/// combinational netlist. Tests which don't conflict are merged as they are generated, unknown
+/// inputs are filled with zeros, and vectors which only detect faults already detected by later
+/// vectors are dropped
+pub fn generate_tests(netlist: &Netlist) -> Atpg {
+    let faults = fault::stuck_at_faults(netlist);
+    let mut cubes: Vec<Vec<Option<bool>>> = Vec::new();
+    let mut testable = Vec::new();
+    let mut untestable = Vec::new();
+    let mut aborted = Vec::new();
+    for fault in &faults {
+        match podem(netlist, fault) {
+            Outcome::Test(test) => {
+                testable.push(*fault);
+                match cubes.iter_mut().find(|cube| compatible(cube, &test)) {
+                    Some(cube) => merge(cube, &test),
+                    None => cubes.push(test),
+                }
+            }
+            Outcome::Untestable => untestable.push(*fault),
+            Outcome::Aborted => aborted.push(*fault),
+        }
+    }
+
+    let vectors: Vec<Vec<bool>> = cubes
+        .iter()
+        .map(|cube| cube.iter().map(|v| v.unwrap_or(false)).collect())
+        .collect();
+    let vectors = compact(netlist, testable, vectors);
+    let tests = TestSet::new(netlist, vectors);
+    let report = tests.fault_coverage(netlist, &faults);
+    Atpg {
+        tests,
+        report,
+        untestable,
+        aborted,
+    }
+}
+
+fn compatible(a: &[Option<bool>], b: &[Option<bool>]) -> bool {
+    a.iter()
+        .zip(b)
+        .all(|(x, y)| x.is_none() || y.is_none() || x == y)
+}
+
+fn merge(cube: &mut [Option<bool>], test: &[Option<bool>]) {
+    for (c, t) in cube.iter_mut().zip(test) {
+        if c.is_none() {
+            *c = *t;
+        }
+    }
+}
+
+/// Reverse order fault simulation: keeps a vector only if it detects a fault no later vector
+/// detects
+fn compact(netlist: &Netlist, mut faults: Vec<Fault>, vectors: Vec<Vec<bool>>) -> Vec<Vec<bool>> {
+    let mut kept = Vec::new();
+    for vector in vectors.into_iter().rev() {
+        let report = fault::simulate_faults(netlist, &faults, std::slice::from_ref(&vector));
+        if report.detected().is_empty() {
+            continue;
+        }
+        faults = report.escaped().iter().map(|r| r.fault).collect();
+        kept.push(vector);
+    }
+    kept.reverse();
+    kept
+}
+
+/// Input vectors with the outputs a fault free circuit produces for them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestSet {
+    input_names: Vec<String>,
+    output_names: Vec<String>,
+    vectors: Vec<Vec<bool>>,
+    expected: Vec<Vec<bool>>,
+}
+
+impl TestSet {
+    /// Records the outputs of a combinational netlist for each vector
+    pub fn new(netlist: &Netlist, vectors: Vec<Vec<bool>>) -> Self {
+        let expected = vectors.iter().map(|v| netlist.evaluate(v)).collect();
+        TestSet {
+            input_names: netlist
+                .inputs()
+                .iter()
+                .map(|p| p.name().to_string())
+                .collect(),
+            output_names: netlist
+                .outputs()
+                .iter()
+                .map(|p| p.name().to_string())
+                .collect(),
+            vectors,
+            expected,
+        }
+    }
+
+    pub fn input_names(&self) -> &[String] {
+        &self.input_names
+    }
+
+    pub fn output_names(&self) -> &[String] {
+        &self.output_names
+    }
+
+    pub fn vectors(&self) -> &[Vec<bool>] {
+        &self.vectors
+    }
+
+    pub fn expected(&self) -> &[Vec<bool>] {
+        &self.expected
+    }
+
+    pub fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vectors.is_empty()
+    }
+
+    /// Applies every vector to a netlist with the same ports and compares its outputs with the
+    /// expected ones
+    pub fn replay(&self, netlist: &Netlist) -> Result<(), Mismatches> {
+        self.check_ports(netlist);
+        self.compare(|inputs| Some(netlist.evaluate(inputs)))
+    }
+
+    /// Applies every vector to a netlist with a fault injected. An error means the vectors
+    /// detect the fault
+    pub fn replay_faulty(&self, netlist: &Netlist, fault: &Fault) -> Result<(), Mismatches> {
+        self.check_ports(netlist);
+        self.compare(|inputs| fault::evaluate_faulty(netlist, fault, inputs))
+    }
+
+    /// Which of the faults the vectors detect
+    pub fn fault_coverage(&self, netlist: &Netlist, faults: &[Fault]) -> FaultReport {
+        self.check_ports(netlist);
+        fault::simulate_faults(netlist, faults, &self.vectors)
+    }
+
+    fn check_ports(&self, netlist: &Netlist) {
+        let names = |ports: &[crate::netlist::Port]| -> Vec<String> {
+            ports.iter().map(|p| p.name().to_string()).collect()
+        };
+        assert_eq!(
+            (names(netlist.inputs()), names(netlist.outputs())),
+            (self.input_names.clone(), self.output_names.clone()),
+            "netlist {} has different ports to the test vectors",
+            netlist.name()
+        );
+    }
+
+    /// Compares the outputs for each vector, treating nets which oscillate as all low
+    fn compare(&self, evaluate: impl Fn(&[bool]) -> Option<Vec<bool>>) -> Result<(), Mismatches> {
+        let mismatches: Vec<Mismatch> = self
+            .vectors
+            .iter()
+            .zip(&self.expected)
+            .enumerate()
+            .filter_map(|(row, (inputs, expected))| {
+                let actual =
+                    evaluate(inputs).unwrap_or_else(|| vec![false; self.output_names.len()]);
+                (actual != *expected).then(|| Mismatch {
+                    row,
+                    inputs: inputs.clone(),
+                    expected: expected.clone(),
+                    actual,
+                })
+            })
+            .collect();
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(Mismatches::new(
+                self.input_names.clone(),
+                self.output_names.clone(),
+                mismatches,
+            ))
+        }
+    }
+
+    /// Writes the vectors as CSV in the layout of a truth table, with an empty column between
+    /// the inputs and outputs
+    pub fn to_csv(&self) -> String {
+        let rows = self
+            .vectors
+            .iter()
+            .cloned()
+            .zip(self.expected.iter().cloned());
+        truth_table::write_csv(&self.input_names, &self.output_names, rows)
+    }
+
+    /// Reads vectors written by `to_csv`
+    pub fn from_csv(csv: &str) -> Result<Self, TableError> {
+        let csv = truth_table::read_csv(csv, usize::MAX)?;
+        let (vectors, expected) = csv
+            .rows
+            .into_iter()
+            .map(|(_, inputs, outputs)| (inputs, outputs))
+            .unzip();
+        Ok(TestSet {
+            input_names: csv.input_names,
+            output_names: csv.output_names,
+            vectors,
+            expected,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{full_adder_netlist, RippleCarryAdder};
+    use crate::netlist::Builder;
+
+    #[test]
+    fn test_podem() {
+        let netlist = full_adder_netlist();
+        for fault in fault::stuck_at_faults(&netlist) {
+            let Outcome::Test(cube) = podem(&netlist, &fault) else {
+                panic!("no test for {}", fault.describe(&netlist));
+            };
+            // Any value of the unassigned inputs detects the fault
+            for fill in [false, true] {
+                let inputs: Vec<bool> = cube.iter().map(|v| v.unwrap_or(fill)).collect();
+                assert_ne!(
+                    fault::evaluate_faulty(&netlist, &fault, &inputs),
+                    Some(netlist.evaluate(&inputs)),
+                    "failed for fault: {}",
+                    fault.describe(&netlist)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_tests() {
+        let netlist = full_adder_netlist();
+        let atpg = generate_tests(&netlist);
+        assert_eq!(atpg.report.coverage(), 1.0, "{}", atpg.report);
+        assert!(atpg.untestable.is_empty() && atpg.aborted.is_empty());
+        assert!(atpg.tests.len() < 8, "{} vectors", atpg.tests.len());
+
+        // The carry into the first bit is tied low, so faults needing it high are untestable
+        let netlist = RippleCarryAdder::<8>::new().netlist();
+        let atpg = generate_tests(&netlist);
+        let escaped: Vec<Fault> = atpg.report.escaped().iter().map(|r| r.fault).collect();
+        assert_eq!(escaped, atpg.untestable, "{}", atpg.report);
+        assert!(atpg.aborted.is_empty());
+        assert!(atpg.tests.len() <= 16, "{} vectors", atpg.tests.len());
+        assert_eq!(atpg.tests.replay(&netlist), Ok(()));
+    }
+
+    #[test]
+    fn test_untestable_faults() {
+        // a | (a & b) is just a, so nothing about b can be observed
+        let mut builder = Builder::new("absorb");
+        let [a, b] = builder.input_bus::<2>("in");
+        let ab = builder.and(&[a, b]);
+        let y = builder.or(&[a, ab]);
+        builder.output("y", y);
+        let netlist = builder.finish();
+
+        let atpg = generate_tests(&netlist);
+        let untestable: Vec<String> = atpg
+            .untestable
+            .iter()
+            .map(|f| f.describe(&netlist))
+            .collect();
+        assert!(untestable.contains(&"in[1] stuck-at-0".to_string()));
+        assert!(untestable.contains(&"in[1] stuck-at-1".to_string()));
+        assert_eq!(atpg.report.escaped().len(), untestable.len());
+
+        // Exhaustive vectors don't detect them either
+        let exhaustive = TestSet::new(
+            &netlist,
+            vec![
+                vec![false, false],
+                vec![true, false],
+                vec![false, true],
+                vec![true, true],
+            ],
+        );
+        let report = exhaustive.fault_coverage(&netlist, &atpg.untestable);
+        assert_eq!(report.coverage(), 0.0, "{}", report);
+    }
+
+    #[test]
+    fn test_replay_csv() {
+        let netlist = full_adder_netlist();
+        let atpg = generate_tests(&netlist);
+        let csv = atpg.tests.to_csv();
+        assert!(csv.starts_with("in[0],in[1],in[2],,sum,cout\n"));
+        let tests = TestSet::from_csv(&csv).unwrap();
+        assert_eq!(tests, atpg.tests);
+        let constant = TestSet::from_csv(",out\n,1\n").unwrap();
+        assert_eq!(constant.to_csv(), ",out\n,1\n");
+        assert_eq!(tests.replay(&netlist), Ok(()));
+
+        // Every fault makes at least one vector fail
+        for fault in fault::stuck_at_faults(&netlist) {
+            assert!(
+                tests.replay_faulty(&netlist, &fault).is_err(),
+                "failed for fault: {}",
+                fault.describe(&netlist)
+            );
+        }
+
+        for (csv, error) in [
+            ("", TableError::MissingHeader),
+            ("a,b,y\n", TableError::MissingHeader),
+            (",,out\n", TableError::Syntax { line: 1 }),
+            ("a,b,,y\n0,1,1\n", TableError::Syntax { line: 2 }),
+            ("a,b,,y\n0,2,,1\n", TableError::Syntax { line: 2 }),
+            (
+                "a,b,,y\n\n0,1,,1\n\n0,2,,1\n",
+                TableError::Syntax { line: 5 },
+            ),
+        ] {
+            assert_eq!(
+                TestSet::from_csv(csv),
+                Err(error),
+                "failed for csv: {:?}",
+                csv
+            );
+        }
+    }
+}
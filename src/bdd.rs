@@ -0,0 +1,737 @@
+use core::fmt;
+use std::collections::HashMap;
+
+use crate::netlist::{CellKind, Netlist, Port};
+use crate::truth_table::{self, Outputs, TableError, TruthTable};
+
+/// Node of a [Bdd], which is also the function it represents
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Node(usize);
+
+impl Node {
+    pub const FALSE: Node = Node(0);
+    pub const TRUE: Node = Node(1);
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+
+    pub fn is_constant(self) -> bool {
+        self.0 < 2
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct NodeData {
+    var: usize,
+    low: Node,
+    high: Node,
+}
+
+/// Reduced ordered binary decision diagram manager
+///
+/// Variables are tested in index order, so variable 0 is at the root. Nodes are shared between
+/// every function built with the same manager, which makes two functions equivalent exactly when
+/// they are the same [Node].
+#[derive(Clone, Debug)]
+pub struct Bdd {
+    num_vars: usize,
+    nodes: Vec<NodeData>,
+    unique: HashMap<NodeData, Node>,
+    ite_cache: HashMap<(Node, Node, Node), Node>,
+}
+
+impl Bdd {
+    pub fn new(num_vars: usize) -> Self {
+        // The constants sit below every variable
+        let terminal = |value| NodeData {
+            var: num_vars,
+            low: Node(value),
+            high: Node(value),
+        };
+        Bdd {
+            num_vars,
+            nodes: vec![terminal(0), terminal(1)],
+            unique: HashMap::new(),
+            ite_cache: HashMap::new(),
+        }
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.num_vars
+    }
+
+    /// Total number of nodes created, including the constants
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn constant(&self, value: bool) -> Node {
+        if value {
+            Node::TRUE
+        } else {
+            Node::FALSE
+        }
+    }
+
+    /// The function which is true when the variable is
+    pub fn var(&mut self, var: usize) -> Node {
+        assert!(var < self.num_vars, "variable {} is out of range", var);
+        self.node(var, Node::FALSE, Node::TRUE)
+    }
+
+    /// Variable tested by a node, or None for the constants
+    pub fn variable(&self, node: Node) -> Option<usize> {
+        (!node.is_constant()).then(|| self.nodes[node.0].var)
+    }
+
+    /// Child followed when the node's variable is false
+    pub fn low(&self, node: Node) -> Node {
+        self.nodes[node.0].low
+    }
+
+    /// Child followed when the node's variable is true
+    pub fn high(&self, node: Node) -> Node {
+        self.nodes[node.0].high
+    }
+
+    /// If-then-else: the function which is g where f is true and h elsewhere
+    pub fn ite(&mut self, f: Node, g: Node, h: Node) -> Node {
+        if f == Node::TRUE || g == h {
+            return g;
+        }
+        if f == Node::FALSE {
+            return h;
+        }
+        if g == Node::TRUE && h == Node::FALSE {
+            return f;
+        }
+        if let Some(node) = self.ite_cache.get(&(f, g, h)) {
+            return *node;
+        }
+
+        let var = [f, g, h].iter().map(|n| self.nodes[n.0].var).min().unwrap();
+        let (f0, f1) = self.cofactors(f, var);
+        let (g0, g1) = self.cofactors(g, var);
+        let (h0, h1) = self.cofactors(h, var);
+        let low = self.ite(f0, g0, h0);
+        let high = self.ite(f1, g1, h1);
+        let node = self.node(var, low, high);
+
+        self.ite_cache.insert((f, g, h), node);
+        node
+    }
+
+    pub fn not(&mut self, f: Node) -> Node {
+        self.ite(f, Node::FALSE, Node::TRUE)
+    }
+
+    pub fn and(&mut self, f: Node, g: Node) -> Node {
+        self.ite(f, g, Node::FALSE)
+    }
+
+    pub fn or(&mut self, f: Node, g: Node) -> Node {
+        self.ite(f, Node::TRUE, g)
+    }
+
+    pub fn nand(&mut self, f: Node, g: Node) -> Node {
+        let and = self.and(f, g);
+        self.not(and)
+    }
+
+    pub fn xor(&mut self, f: Node, g: Node) -> Node {
+        let not_g = self.not(g);
+        self.ite(f, not_g, g)
+    }
+
+    pub fn xnor(&mut self, f: Node, g: Node) -> Node {
+        let not_g = self.not(g);
+        self.ite(f, g, not_g)
+    }
+
+    /// AND of any number of functions, true if there are none
+    pub fn and_all(&mut self, nodes: &[Node]) -> Node {
+        nodes.iter().fold(Node::TRUE, |acc, n| self.and(acc, *n))
+    }
+
+    /// OR of any number of functions, false if there are none
+    pub fn or_all(&mut self, nodes: &[Node]) -> Node {
+        nodes.iter().fold(Node::FALSE, |acc, n| self.or(acc, *n))
+    }
+
+    /// Value of a function for an assignment of every variable
+    pub fn evaluate(&self, node: Node, vars: &[bool]) -> bool {
+        let mut node = node;
+        while !node.is_constant() {
+            let data = self.nodes[node.0];
+            node = if vars[data.var] { data.high } else { data.low };
+        }
+        node == Node::TRUE
+    }
+
+    /// Finds an assignment of the variables making a function true, with the variables it
+    /// doesn't depend on set to false. Returns None if the function is never true
+    pub fn satisfy_one(&self, node: Node) -> Option<Vec<bool>> {
+        if node == Node::FALSE {
+            return None;
+        }
+
+        // Every node other than FALSE has a path to TRUE, so avoiding FALSE is enough
+        let mut vars = vec![false; self.num_vars];
+        let mut node = node;
+        while !node.is_constant() {
+            let data = self.nodes[node.0];
+            if data.low == Node::FALSE {
+                vars[data.var] = true;
+                node = data.high;
+            } else {
+                node = data.low;
+            }
+        }
+        Some(vars)
+    }
+
+    /// Number of assignments of the variables making a function true
+    pub fn sat_count(&self, node: Node) -> u128 {
+        assert!(
+            self.num_vars < 128,
+            "too many variables to count assignments"
+        );
+
+        fn count(bdd: &Bdd, node: Node, cache: &mut HashMap<Node, u128>) -> u128 {
+            // Counts assignments of the variables from the node's variable downwards
+            if node.is_constant() {
+                return (node == Node::TRUE) as u128;
+            }
+            if let Some(n) = cache.get(&node) {
+                return *n;
+            }
+            let data = bdd.nodes[node.0];
+            let below = |child: Node| bdd.nodes[child.0].var - data.var - 1;
+            let n = (count(bdd, data.low, cache) << below(data.low))
+                + (count(bdd, data.high, cache) << below(data.high));
+            cache.insert(node, n);
+            n
+        }
+
+        count(self, node, &mut HashMap::new()) << self.nodes[node.0].var
+    }
+
+    /// Number of nodes reachable from a node, including the constants
+    pub fn size(&self, node: Node) -> usize {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut stack = vec![node];
+        let mut size = 0;
+        while let Some(node) = stack.pop() {
+            if seen[node.0] {
+                continue;
+            }
+            seen[node.0] = true;
+            size += 1;
+            if !node.is_constant() {
+                stack.push(self.nodes[node.0].low);
+                stack.push(self.nodes[node.0].high);
+            }
+        }
+        size
+    }
+
+    /// Builds the outputs of a function by evaluating it for every input, with input i as
+    /// variable i. Every input pattern is enumerated, so functions of more than
+    /// [MAX_INPUTS](truth_table::MAX_INPUTS) inputs are rejected; use
+    /// [Bdd::build_netlist] for wider functions
+    pub fn build_fn<const N: usize, O: Outputs>(
+        &mut self,
+        f: impl Fn(&[bool; N]) -> O,
+    ) -> Result<Vec<Node>, TableError> {
+        assert!(N <= self.num_vars, "the function has too many inputs");
+        if N > truth_table::MAX_INPUTS {
+            return Err(TableError::TooManyInputs(N));
+        }
+        let table = TruthTable::from_fn(f);
+
+        fn build(bdd: &mut Bdd, table: &TruthTable, output: usize, var: usize, row: usize) -> Node {
+            if var == table.num_inputs() {
+                return bdd.constant(table.output(row, output));
+            }
+            let low = build(bdd, table, output, var + 1, row);
+            let high = build(bdd, table, output, var + 1, row | 1 << var);
+            bdd.node(var, low, high)
+        }
+
+        Ok((0..table.num_outputs())
+            .map(|output| build(self, &table, output, 0, 0))
+            .collect())
+    }
+
+    /// Builds the outputs of a combinational netlist given a function for each of its inputs.
+    /// Panics if the netlist is sequential
+    pub fn build_netlist(&mut self, netlist: &Netlist, inputs: &[Node]) -> Vec<Node> {
+        assert!(
+            !netlist.is_sequential(),
+            "netlist {} is sequential",
+            netlist.name()
+        );
+        let values = self.build_nets(netlist, inputs, &[]);
+        netlist
+            .outputs()
+            .iter()
+            .map(|port| values[port.net().index()])
+            .collect()
+    }
+
+    /// Builds every net of a netlist given functions for its inputs and for the Q output of each
+    /// flip-flop, in the order of [Netlist::flipflops]
+    pub fn build_nets(&mut self, netlist: &Netlist, inputs: &[Node], state: &[Node]) -> Vec<Node> {
+        assert_eq!(
+            inputs.len(),
+            netlist.inputs().len(),
+            "wrong number of inputs"
+        );
+
+        let mut values = vec![Node::FALSE; netlist.num_nets()];
+        for (port, node) in netlist.inputs().iter().zip(inputs) {
+            values[port.net().index()] = *node;
+        }
+        let mut state = state.iter();
+        for cell in netlist.cells() {
+            let value = match cell.kind() {
+                CellKind::Nand => {
+                    let inputs: Vec<Node> =
+                        cell.inputs().iter().map(|n| values[n.index()]).collect();
+                    let and = self.and_all(&inputs);
+                    self.not(and)
+                }
+                CellKind::Const(value) => self.constant(value),
+                CellKind::Dff => *state.next().expect("missing flip-flop state"),
+            };
+            values[cell.output().index()] = value;
+        }
+        values
+    }
+
+    fn node(&mut self, var: usize, low: Node, high: Node) -> Node {
+        if low == high {
+            return low;
+        }
+        let data = NodeData { var, low, high };
+        if let Some(node) = self.unique.get(&data) {
+            return *node;
+        }
+        let node = Node(self.nodes.len());
+        self.nodes.push(data);
+        self.unique.insert(data, node);
+        node
+    }
+
+    /// Functions for the variable being false and true
+    fn cofactors(&self, node: Node, var: usize) -> (Node, Node) {
+        let data = self.nodes[node.0];
+        if data.var == var {
+            (data.low, data.high)
+        } else {
+            (node, node)
+        }
+    }
+}
+
+/// Chooses a variable for each input of a netlist, returning the variable of input i at index i
+///
+/// Interleaving the bits of buses of the same width keeps the BDDs of datapaths such as adders
+/// small, and placing narrow buses first puts control inputs such as mux selects above the data
+/// they choose between. Inputs named "name[i]" are treated as bit i of bus "name".
+pub fn variable_order(netlist: &Netlist) -> Vec<usize> {
+    // Buses in order of first appearance, each with the input index of every bit
+    let mut buses: Vec<(String, Vec<(usize, usize)>)> = Vec::new();
+    for (input, port) in netlist.inputs().iter().enumerate() {
+        let (name, bit) = split_bus_name(port.name());
+        match buses.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, bits)) => bits.push((bit, input)),
+            None => buses.push((name.to_string(), vec![(bit, input)])),
+        }
+    }
+    buses.sort_by_key(|(_, bits)| bits.len());
+
+    let mut order = vec![0; netlist.inputs().len()];
+    let mut var = 0;
+    let mut start = 0;
+    while start < buses.len() {
+        let width = buses[start].1.len();
+        let end = start
+            + buses[start..]
+                .iter()
+                .take_while(|(_, b)| b.len() == width)
+                .count();
+        let mut bits: Vec<(usize, usize, usize)> = buses[start..end]
+            .iter()
+            .enumerate()
+            .flat_map(|(bus, (_, bits))| bits.iter().map(move |(bit, input)| (*bit, bus, *input)))
+            .collect();
+        bits.sort();
+        for (_, _, input) in bits {
+            order[input] = var;
+            var += 1;
+        }
+        start = end;
+    }
+    order
+}
+
+/// Splits "name[i]" into the name and bit index, treating other names as bit 0
+fn split_bus_name(name: &str) -> (&str, usize) {
+    name.strip_suffix(']')
+        .and_then(|rest| rest.rsplit_once('['))
+        .and_then(|(base, bit)| Some((base, bit.parse().ok()?)))
+        .unwrap_or((name, 0))
+}
+
+/// Checks that two combinational netlists compute the same outputs. Ports are matched by name,
+/// so they may be declared in different orders
+pub fn check_equivalence(a: &Netlist, b: &Netlist) -> Result<(), EquivalenceError> {
+    let ports = PortMap::new(a, b)?;
+
+    let order = variable_order(a);
+    let mut bdd = Bdd::new(order.len());
+    let vars_a: Vec<Node> = order.iter().map(|v| bdd.var(*v)).collect();
+    let vars_b: Vec<Node> = ports.inputs.iter().map(|i| vars_a[*i]).collect();
+    let outputs_a = bdd.build_netlist(a, &vars_a);
+    let outputs_b = bdd.build_netlist(b, &vars_b);
+
+    let differences: Vec<Node> = ports
+        .outputs
+        .iter()
+        .enumerate()
+        .map(|(j, i)| bdd.xor(outputs_a[*i], outputs_b[j]))
+        .collect();
+    let miter = bdd.or_all(&differences);
+
+    match bdd.satisfy_one(miter) {
+        Some(assignment) => {
+            let inputs = order.iter().map(|v| assignment[*v]).collect();
+            Err(EquivalenceError::NotEquivalent(
+                ports.counterexample(a, b, inputs),
+            ))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Matches the ports of two netlists being compared by name
+pub(crate) struct PortMap {
+    /// Index in the first netlist of each input of the second
+    pub(crate) inputs: Vec<usize>,
+    /// Index in the first netlist of each output of the second
+    pub(crate) outputs: Vec<usize>,
+}
+
+impl PortMap {
+    /// Matches the ports of two combinational netlists, which must have the same port names
+    pub(crate) fn new(a: &Netlist, b: &Netlist) -> Result<Self, EquivalenceError> {
+        for netlist in [a, b] {
+            if netlist.is_sequential() {
+                return Err(EquivalenceError::Sequential(netlist.name().to_string()));
+            }
+        }
+        Ok(PortMap {
+            inputs: Self::match_ports(a.inputs(), b.inputs(), "input", a, b)?,
+            outputs: Self::match_ports(a.outputs(), b.outputs(), "output", a, b)?,
+        })
+    }
+
+    fn match_ports(
+        ports_a: &[Port],
+        ports_b: &[Port],
+        kind: &str,
+        a: &Netlist,
+        b: &Netlist,
+    ) -> Result<Vec<usize>, EquivalenceError> {
+        let missing = |port: &str, from: &Netlist| {
+            EquivalenceError::PortMismatch(format!(
+                "{} {} is missing from {}",
+                kind,
+                port,
+                from.name()
+            ))
+        };
+        let map = ports_b
+            .iter()
+            .map(|port| {
+                ports_a
+                    .iter()
+                    .position(|p| p.name() == port.name())
+                    .ok_or_else(|| missing(port.name(), a))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for port in ports_a {
+            if !ports_b.iter().any(|p| p.name() == port.name()) {
+                return Err(missing(port.name(), b));
+            }
+        }
+        Ok(map)
+    }
+
+    /// Simulates both netlists with inputs given in the first netlist's order
+    pub(crate) fn counterexample(
+        &self,
+        a: &Netlist,
+        b: &Netlist,
+        inputs: Vec<bool>,
+    ) -> Counterexample {
+        let inputs_b: Vec<bool> = self.inputs.iter().map(|i| inputs[*i]).collect();
+        let mut actual = vec![false; self.outputs.len()];
+        for (j, value) in b.evaluate(&inputs_b).into_iter().enumerate() {
+            actual[self.outputs[j]] = value;
+        }
+
+        Counterexample {
+            input_names: a.inputs().iter().map(|p| p.name().to_string()).collect(),
+            output_names: a.outputs().iter().map(|p| p.name().to_string()).collect(),
+            expected: a.evaluate(&inputs),
+            actual,
+            inputs,
+        }
+    }
+}
+
+/// Inputs for which two netlists differ, with the outputs of each in the first netlist's order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Counterexample {
+    pub input_names: Vec<String>,
+    pub inputs: Vec<bool>,
+    pub output_names: Vec<String>,
+    pub expected: Vec<bool>,
+    pub actual: Vec<bool>,
+}
+
+impl Counterexample {
+    /// Names of the outputs which differ
+    pub fn differing_outputs(&self) -> Vec<&str> {
+        self.output_names
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.expected[*i] != self.actual[*i])
+            .map(|(_, name)| name.as_str())
+            .collect()
+    }
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inputs: Vec<String> = self
+            .input_names
+            .iter()
+            .zip(&self.inputs)
+            .map(|(name, value)| format!("{}={}", name, *value as u8))
+            .collect();
+        write!(f, "outputs differ for inputs {}:", inputs.join(" "))?;
+        for (i, name) in self.output_names.iter().enumerate() {
+            if self.expected[i] != self.actual[i] {
+                write!(
+                    f,
+                    " {} expected {} actual {}",
+                    name, self.expected[i] as u8, self.actual[i] as u8
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reason two netlists couldn't be shown to be equivalent
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EquivalenceError {
+    PortMismatch(String),
+    Sequential(String),
+    NotEquivalent(Counterexample),
+}
+
+impl fmt::Display for EquivalenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EquivalenceError::PortMismatch(message) => write!(f, "{}", message),
+            EquivalenceError::Sequential(name) => write!(f, "netlist {} is sequential", name),
+            EquivalenceError::NotEquivalent(counterexample) => write!(f, "{}", counterexample),
+        }
+    }
+}
+
+impl std::error::Error for EquivalenceError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{CarryLookaheadAdder, RippleCarryAdder};
+    use crate::mux::{build_mux16, build_mux2, mux16, mux2};
+    use crate::netlist::{Builder, Net};
+
+    #[test]
+    fn test_operations() {
+        let mut bdd = Bdd::new(3);
+        let x: Vec<Node> = (0..3).map(|i| bdd.var(i)).collect();
+
+        // De Morgan's laws give the same node
+        let and = bdd.and(x[0], x[1]);
+        let not0 = bdd.not(x[0]);
+        let not1 = bdd.not(x[1]);
+        let or = bdd.or(not0, not1);
+        assert_eq!(bdd.not(or), and);
+        assert_eq!(bdd.nand(x[0], x[1]), or);
+
+        let xor = bdd.xor(x[0], x[2]);
+        let xnor = bdd.xnor(x[0], x[2]);
+        assert_eq!(bdd.not(xor), xnor);
+        assert_eq!(bdd.and(xor, xnor), Node::FALSE);
+        assert_eq!(bdd.or(xor, xnor), Node::TRUE);
+
+        for (node, count) in [
+            (and, 2),
+            (xor, 4),
+            (Node::TRUE, 8),
+            (Node::FALSE, 0),
+            (x[2], 4),
+        ] {
+            assert_eq!(bdd.sat_count(node), count, "failed for node: {:?}", node);
+        }
+        assert_eq!(bdd.size(xor), 5);
+
+        let all = bdd.and_all(&x);
+        assert_eq!(bdd.satisfy_one(all), Some(vec![true, true, true]));
+        assert_eq!(bdd.satisfy_one(Node::FALSE), None);
+        assert!(bdd.evaluate(xor, &[true, false, false]));
+        assert!(!bdd.evaluate(xor, &[true, false, true]));
+    }
+
+    #[test]
+    fn test_build_fn() {
+        let mut bdd = Bdd::new(20);
+
+        // mux16 and a tree of mux2 are the same function, so they share a node
+        let mux = bdd
+            .build_fn(|x: &[bool; 20]| {
+                mux16(x[..4].try_into().unwrap(), x[4..].try_into().unwrap())
+            })
+            .unwrap();
+        let tree = bdd
+            .build_fn(|x: &[bool; 20]| {
+                let mut level: Vec<bool> = x[4..].to_vec();
+                for select in &x[..4] {
+                    level = level
+                        .chunks(2)
+                        .map(|pair| mux2(*select, &[pair[0], pair[1]]))
+                        .collect();
+                }
+                level[0]
+            })
+            .unwrap();
+        assert_eq!(mux, tree);
+        assert_eq!(bdd.sat_count(mux[0]), 1 << 19);
+
+        let mut bdd = Bdd::new(40);
+        assert_eq!(
+            bdd.build_fn(|x: &[bool; 40]| x[39]),
+            Err(TableError::TooManyInputs(40))
+        );
+    }
+
+    fn mux2_tree_netlist() -> Netlist {
+        let mut builder = Builder::new("mux2_tree");
+        let select = builder.input_bus::<4>("select");
+        let input = builder.input_bus::<16>("input");
+        let mut level: Vec<Net> = input.to_vec();
+        for s in select {
+            level = level
+                .chunks(2)
+                .map(|pair| build_mux2(&mut builder, s, &[pair[0], pair[1]]))
+                .collect();
+        }
+        builder.output("out", level[0]);
+        builder.finish()
+    }
+
+    #[test]
+    fn test_mux_equivalence() {
+        let mut builder = Builder::new("mux16");
+        // Declared in a different order to the tree, so ports must be matched by name
+        let input = builder.input_bus::<16>("input");
+        let select = builder.input_bus::<4>("select");
+        let out = build_mux16(&mut builder, &select, &input);
+        builder.output("out", out);
+        let mux = builder.finish();
+
+        assert_eq!(check_equivalence(&mux, &mux2_tree_netlist()), Ok(()));
+    }
+
+    #[test]
+    fn test_adder_equivalence() {
+        let ripple = RippleCarryAdder::<32>::new().netlist();
+        let lookahead = CarryLookaheadAdder::<32>::new().netlist();
+        assert_eq!(check_equivalence(&ripple, &lookahead), Ok(()));
+    }
+
+    #[test]
+    fn test_counterexample() {
+        // An adder with a bug in bit 5 that only shows when a[5], b[5] and a[6] are all set
+        let mut builder = Builder::new("buggy_adder");
+        let a = builder.input_bus::<16>("a");
+        let b = builder.input_bus::<16>("b");
+        let mut sum = RippleCarryAdder::<16>::new().build(&mut builder, &a, &b);
+        let bug = builder.and(&[a[5], b[5], a[6]]);
+        sum[5] = builder.xor(&[sum[5], bug]);
+        builder.output_bus("sum", &sum);
+        let buggy = builder.finish();
+
+        let ripple = RippleCarryAdder::<16>::new().netlist();
+        let Err(EquivalenceError::NotEquivalent(counterexample)) =
+            check_equivalence(&ripple, &buggy)
+        else {
+            panic!("the adders should differ");
+        };
+
+        assert_eq!(counterexample.differing_outputs(), ["sum[5]"]);
+        assert_eq!(
+            counterexample.expected,
+            ripple.evaluate(&counterexample.inputs)
+        );
+        assert_eq!(
+            counterexample.actual,
+            buggy.evaluate(&counterexample.inputs)
+        );
+        for input in ["a[5]", "b[5]", "a[6]"] {
+            let i = counterexample
+                .input_names
+                .iter()
+                .position(|n| n == input)
+                .unwrap();
+            assert!(counterexample.inputs[i], "{} should be set", input);
+        }
+        assert!(counterexample
+            .to_string()
+            .starts_with("outputs differ for inputs a[0]=0"));
+    }
+
+    #[test]
+    fn test_port_mismatch() {
+        let ripple = RippleCarryAdder::<4>::new().netlist();
+        let wider = RippleCarryAdder::<5>::new().netlist();
+        assert_eq!(
+            check_equivalence(&ripple, &wider),
+            Err(EquivalenceError::PortMismatch(
+                "input a[4] is missing from ripple_carry_adder".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_variable_order() {
+        let mut builder = Builder::new("order");
+        let a = builder.input_bus::<2>("a");
+        let cin = builder.input("cin");
+        let b = builder.input_bus::<2>("b");
+        let out = builder.nand(&[a[0], a[1], cin, b[0], b[1]]);
+        builder.output("out", out);
+        let netlist = builder.finish();
+
+        // cin, a[0], b[0], a[1], b[1]
+        assert_eq!(variable_order(&netlist), [1, 3, 0, 2, 4]);
+    }
+}
@@ -0,0 +1,608 @@
+use core::fmt;
+use std::collections::{HashMap, HashSet};
+
+use crate::netlist::{Builder, CellKind, Net, Netlist, NetlistError, Port};
+
+/// Error returned when BLIF can't be read into a netlist
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlifError {
+    MissingModel,
+    Syntax {
+        line: usize,
+    },
+    /// A construct outside the subset [from_blif] reads, e.g. ".subckt"
+    Unsupported {
+        line: usize,
+        construct: String,
+    },
+    /// The model doesn't form a valid netlist, e.g. because a signal is never defined
+    Netlist(NetlistError),
+}
+
+impl fmt::Display for BlifError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlifError::MissingModel => write!(f, "missing \".model\""),
+            BlifError::Syntax { line } => write!(f, "syntax error on line {}", line),
+            BlifError::Unsupported { line, construct } => {
+                write!(f, "{} on line {} is not supported", construct, line)
+            }
+            BlifError::Netlist(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for BlifError {}
+
+/// Writes a netlist as a BLIF model named after it, for tools such as ABC and Yosys
+///
+/// Each NAND gate is a ".names" cover, each constant a cover with no inputs and each flip-flop a
+/// rising edge ".latch" clocked by its clock net and starting low. Nets keep their names, with
+/// whitespace replaced, and nets driving an output port take the port's name
+pub fn to_blif(netlist: &Netlist) -> String {
+    // Ports are named first so no other net takes their names
+    let mut used: HashSet<String> = netlist
+        .inputs()
+        .iter()
+        .chain(netlist.outputs())
+        .map(|p| p.name().to_string())
+        .collect();
+    let mut names: Vec<Option<String>> = vec![None; netlist.num_nets()];
+    for port in netlist.inputs() {
+        names[port.net().index()] = Some(port.name().to_string());
+    }
+    let mut buffers = Vec::new();
+    for port in netlist.outputs() {
+        match &names[port.net().index()] {
+            None => names[port.net().index()] = Some(port.name().to_string()),
+            Some(name) if name == port.name() => {}
+            Some(name) => buffers.push((name.clone(), port.name())),
+        }
+    }
+    for net in netlist.nets() {
+        if names[net.index()].is_none() {
+            let mut name: String = netlist
+                .net_name(net)
+                .chars()
+                .map(|c| if c.is_whitespace() { '_' } else { c })
+                .collect();
+            if !used.insert(name.clone()) {
+                name = format!("n{}", net.index());
+                used.insert(name.clone());
+            }
+            names[net.index()] = Some(name);
+        }
+    }
+    let name = |net: Net| names[net.index()].as_deref().unwrap();
+
+    let ports =
+        |ports: &[Port]| -> String { ports.iter().map(|p| format!(" {}", p.name())).collect() };
+    let mut blif = format!(
+        "# Written by nandverse\n.model {}\n.inputs{}\n.outputs{}\n",
+        netlist.name().replace(char::is_whitespace, "_"),
+        ports(netlist.inputs()),
+        ports(netlist.outputs())
+    );
+    for cell in netlist.cells() {
+        // Inputs are listed once, so an inverter made from a NAND has one
+        let mut inputs: Vec<&str> = Vec::new();
+        for net in cell.inputs() {
+            if !inputs.contains(&name(*net)) {
+                inputs.push(name(*net));
+            }
+        }
+        let output = name(cell.output());
+        match cell.kind() {
+            CellKind::Nand => {
+                blif.push_str(&format!(".names {} {}\n", inputs.join(" "), output));
+                for i in 0..inputs.len() {
+                    let mut row = vec!['-'; inputs.len()];
+                    row[i] = '0';
+                    blif.push_str(&format!("{} 1\n", row.into_iter().collect::<String>()));
+                }
+            }
+            CellKind::Const(value) => {
+                blif.push_str(&format!(".names {}\n", output));
+                if value {
+                    blif.push_str("1\n");
+                }
+            }
+            CellKind::Dff => {
+                let [clk, d] = cell.inputs() else {
+                    unreachable!("flip-flops have two inputs")
+                };
+                blif.push_str(&format!(
+                    ".latch {} {} re {} 0\n",
+                    name(*d),
+                    output,
+                    name(*clk)
+                ));
+            }
+        }
+    }
+    for (net, port) in buffers {
+        blif.push_str(&format!(".names {} {}\n1 1\n", net, port));
+    }
+    blif.push_str(".end\n");
+    blif
+}
+
+/// Signal defined by a ".names" cover
+#[derive(Clone, Debug)]
+struct Cover {
+    inputs: Vec<String>,
+    output: String,
+    /// Input plane of each row, with '0', '1' or '-' for each input
+    rows: Vec<Vec<u8>>,
+    /// Whether the rows give where the output is high rather than low
+    on_set: bool,
+}
+
+#[derive(Clone, Debug)]
+struct Latch {
+    input: String,
+    output: String,
+    /// Clock and whether it's rising edge triggered, or None for the model's global clock
+    clock: Option<(String, bool)>,
+    init: bool,
+}
+
+/// Name of the input port added for latches with no clock of their own
+const GLOBAL_CLOCK: &str = "clk";
+
+/// Reads the first model of a BLIF file into a netlist named after it
+///
+/// Covers become gates, with covers of NAND gates, as written by [to_blif], read back as single
+/// NAND cells. Latches become flip-flops: "re" latches clocked by their clock, "fe" latches by
+/// its inverse, and latches without a clock by an input named "clk", added first if the model
+/// has no such input. A latch starting high is a flip-flop storing the inverse of its input.
+/// Hierarchy and library gates aren't supported
+pub fn from_blif(text: &str) -> Result<Netlist, BlifError> {
+    // Statements with their first line, joining lines continued with a backslash
+    let mut statements: Vec<(usize, Vec<&str>)> = Vec::new();
+    let mut continued = false;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap();
+        let (line, next) = match line.trim_end().strip_suffix('\\') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match statements.last_mut() {
+            Some((_, last)) if continued => last.extend(fields),
+            _ if fields.is_empty() => {}
+            _ => statements.push((i + 1, fields)),
+        }
+        continued = next;
+    }
+
+    let mut model = None;
+    let mut inputs: Vec<String> = Vec::new();
+    let mut outputs: Vec<String> = Vec::new();
+    let mut covers: Vec<Cover> = Vec::new();
+    let mut latches: Vec<Latch> = Vec::new();
+    // Whether rows can follow, as they do ".names"
+    let mut in_cover = false;
+    for (line, fields) in statements {
+        let syntax = || BlifError::Syntax { line };
+        let command = fields[0];
+        if model.is_none() && command != ".model" {
+            return Err(BlifError::MissingModel);
+        }
+        let row = !command.starts_with('.');
+        if !row {
+            in_cover = command == ".names";
+        }
+        match command {
+            ".model" => {
+                if model.is_some() {
+                    // The first model ends without ".end"
+                    break;
+                }
+                model = Some(fields.get(1).copied().unwrap_or("blif").to_string());
+            }
+            ".inputs" => inputs.extend(fields[1..].iter().map(|f| f.to_string())),
+            ".outputs" => outputs.extend(fields[1..].iter().map(|f| f.to_string())),
+            ".names" => {
+                let [inputs @ .., output] = &fields[1..] else {
+                    return Err(syntax());
+                };
+                covers.push(Cover {
+                    inputs: inputs.iter().map(|f| f.to_string()).collect(),
+                    output: output.to_string(),
+                    rows: Vec::new(),
+                    on_set: true,
+                });
+            }
+            ".latch" => {
+                let (clock, init) = match fields[1..] {
+                    [_, _] => (None, None),
+                    [_, _, init] => (None, Some(init)),
+                    [_, _, kind, clock] => (Some((kind, clock)), None),
+                    [_, _, kind, clock, init] => (Some((kind, clock)), Some(init)),
+                    _ => return Err(syntax()),
+                };
+                let clock = match clock {
+                    None | Some((_, "NIL")) => None,
+                    Some(("re", clock)) => Some((clock.to_string(), true)),
+                    Some(("fe", clock)) => Some((clock.to_string(), false)),
+                    Some(("ah" | "al" | "as", _)) => {
+                        return Err(BlifError::Unsupported {
+                            line,
+                            construct: "level sensitive latch".to_string(),
+                        })
+                    }
+                    Some(_) => return Err(syntax()),
+                };
+                let init = match init {
+                    None | Some("0" | "2" | "3") => false,
+                    Some("1") => true,
+                    Some(_) => return Err(syntax()),
+                };
+                latches.push(Latch {
+                    input: fields[1].to_string(),
+                    output: fields[2].to_string(),
+                    clock,
+                    init,
+                });
+            }
+            ".end" => break,
+            ".attr"
+            | ".param"
+            | ".cname"
+            | ".area"
+            | ".delay"
+            | ".input_arrival"
+            | ".output_required"
+            | ".default_input_arrival"
+            | ".default_output_required"
+            | ".wire_load_slope"
+            | ".clock" => {}
+            _ if !row => {
+                return Err(BlifError::Unsupported {
+                    line,
+                    construct: command.to_string(),
+                })
+            }
+            _ => {
+                // A row of the last cover
+                let Some(cover) = covers.last_mut().filter(|_| in_cover) else {
+                    return Err(syntax());
+                };
+                let (plane, value) = match (&fields[..], cover.inputs.len()) {
+                    ([value], 0) => ("", *value),
+                    ([plane, value], n) if plane.len() == n => (*plane, *value),
+                    _ => return Err(syntax()),
+                };
+                if !plane.bytes().all(|c| matches!(c, b'0' | b'1' | b'-')) {
+                    return Err(syntax());
+                }
+                let on_set = match value {
+                    "1" => true,
+                    "0" => false,
+                    _ => return Err(syntax()),
+                };
+                if !cover.rows.is_empty() && cover.on_set != on_set {
+                    return Err(syntax());
+                }
+                cover.on_set = on_set;
+                cover.rows.push(plane.as_bytes().to_vec());
+            }
+        }
+    }
+    let Some(model) = model else {
+        return Err(BlifError::MissingModel);
+    };
+
+    let mut builder = Builder::new(&model);
+    let global_clock =
+        latches.iter().any(|l| l.clock.is_none()) && !inputs.iter().any(|i| i == GLOBAL_CLOCK);
+    let mut signals: HashMap<String, Net> = HashMap::new();
+    if global_clock {
+        signals.insert(GLOBAL_CLOCK.to_string(), builder.input(GLOBAL_CLOCK));
+    }
+    for input in &inputs {
+        signals.insert(input.clone(), builder.input(input));
+    }
+
+    // Signals are declared up front so covers and latches can read them before they're defined
+    let mut defined = HashSet::new();
+    let definitions = covers
+        .iter()
+        .map(|c| &c.output)
+        .chain(latches.iter().map(|l| &l.output));
+    for name in definitions {
+        if signals.contains_key(name) || !defined.insert(name) {
+            return Err(BlifError::Netlist(NetlistError::MultipleDrivers(
+                name.clone(),
+            )));
+        }
+    }
+    let mut signal = |builder: &mut Builder, name: &str| {
+        *signals
+            .entry(name.to_string())
+            .or_insert_with(|| builder.placeholder(name))
+    };
+
+    for cover in &covers {
+        let inputs: Vec<Net> = cover
+            .inputs
+            .iter()
+            .map(|name| signal(&mut builder, name))
+            .collect();
+        let value = build_cover(&mut builder, &inputs, &cover.rows, cover.on_set);
+        let output = signal(&mut builder, &cover.output);
+        builder
+            .drive_placeholder(output, value)
+            .map_err(BlifError::Netlist)?;
+    }
+    for latch in &latches {
+        let (clock, rising) = latch
+            .clock
+            .clone()
+            .unwrap_or((GLOBAL_CLOCK.to_string(), true));
+        let clock = signal(&mut builder, &clock);
+        let clock = if rising { clock } else { builder.not(clock) };
+        let input = signal(&mut builder, &latch.input);
+        let value = if latch.init {
+            let inverse = builder.not(input);
+            let q = builder.dff(clock, inverse);
+            builder.not(q)
+        } else {
+            builder.dff(clock, input)
+        };
+        let output = signal(&mut builder, &latch.output);
+        builder
+            .drive_placeholder(output, value)
+            .map_err(BlifError::Netlist)?;
+    }
+    for output in &outputs {
+        let net = signal(&mut builder, output);
+        builder.output(output, net);
+    }
+    builder.try_finish().map_err(BlifError::Netlist)
+}
+
+/// Builds the function of a cover, as a NAND cell if it's the cover of one
+fn build_cover(builder: &mut Builder, inputs: &[Net], rows: &[Vec<u8>], on_set: bool) -> Net {
+    let n = inputs.len();
+    let nand_rows = (0..n).all(|i| {
+        rows.iter().any(|row| {
+            row.iter()
+                .enumerate()
+                .all(|(j, c)| *c == [b'-', b'0'][(i == j) as usize])
+        })
+    });
+    let all_high = |row: &Vec<u8>| row.iter().all(|c| *c == b'1');
+    match (on_set, rows) {
+        (_, []) => return builder.constant(!on_set),
+        (true, _) if n > 0 && rows.len() == n && nand_rows => return builder.nand(inputs),
+        (false, [row]) if n > 0 && all_high(row) => return builder.nand(inputs),
+        _ => {}
+    }
+
+    let mut inverted: Vec<Option<Net>> = vec![None; n];
+    let mut terms = Vec::new();
+    for row in rows {
+        let mut literals = Vec::new();
+        for (i, c) in row.iter().enumerate() {
+            match c {
+                b'1' => literals.push(inputs[i]),
+                b'0' => {
+                    let not = *inverted[i].get_or_insert_with(|| builder.not(inputs[i]));
+                    literals.push(not);
+                }
+                _ => {}
+            }
+        }
+        terms.push(match literals[..] {
+            [] => return builder.constant(on_set),
+            [literal] => literal,
+            _ => builder.and(&literals),
+        });
+    }
+    let sum = match terms[..] {
+        [term] => term,
+        _ => builder.or(&terms),
+    };
+    if on_set {
+        sum
+    } else {
+        builder.not(sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::{RippleCounter, SynchronousCounter};
+    use crate::flipflop::DFlipflop;
+    use crate::math::RippleCarryAdder;
+    use crate::truth_table::TruthTable;
+
+    /// Outputs of a sequential netlist after each step of the inputs
+    fn run(netlist: &Netlist, vectors: &[Vec<bool>]) -> Vec<Vec<bool>> {
+        let mut state = netlist.reset_state();
+        vectors
+            .iter()
+            .map(|inputs| {
+                let values = netlist.step(&mut state, inputs);
+                netlist
+                    .outputs()
+                    .iter()
+                    .map(|p| values[p.net().index()])
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_to_blif() {
+        assert_eq!(
+            to_blif(&DFlipflop::new().netlist()),
+            "# Written by nandverse\n\
+             .model d_flipflop\n\
+             .inputs clk d\n\
+             .outputs q qn\n\
+             .latch d q re clk 0\n\
+             .names q qn\n\
+             0 1\n\
+             .end\n"
+        );
+
+        let mut builder = Builder::new("ports");
+        let a = builder.input("a");
+        let b = builder.input("b");
+        let nand = builder.nand(&[a, b]);
+        let one = builder.constant(true);
+        builder.output("a_copy", a);
+        builder.output("y", nand);
+        builder.output("z", nand);
+        builder.output("one", one);
+        assert_eq!(
+            to_blif(&builder.finish()),
+            "# Written by nandverse\n\
+             .model ports\n\
+             .inputs a b\n\
+             .outputs a_copy y z one\n\
+             .names a b y\n\
+             0- 1\n\
+             -0 1\n\
+             .names one\n\
+             1\n\
+             .names a a_copy\n\
+             1 1\n\
+             .names y z\n\
+             1 1\n\
+             .end\n"
+        );
+    }
+
+    #[test]
+    fn test_from_blif() {
+        let netlist = from_blif(
+            "# Majority vote, and a toggle starting high\n\
+             .model vote\n\
+             .inputs a b \\\n    c\n\
+             .outputs maj none t\n\
+             .names a b c maj\n\
+             11- 1\n\
+             1-1 1\n\
+             -11 1\n\
+             .names a b c none  # low when any input is high\n\
+             1-- 0\n\
+             -1- 0\n\
+             --1 0\n\
+             .names t tn\n\
+             0 1\n\
+             .latch tn t 1\n\
+             .end\n\
+             .model ignored\n",
+        )
+        .unwrap();
+        assert_eq!(netlist.name(), "vote");
+        let inputs: Vec<&str> = netlist.inputs().iter().map(|p| p.name()).collect();
+        assert_eq!(inputs, ["clk", "a", "b", "c"]);
+        assert_eq!(netlist.flipflops().len(), 1);
+
+        let vectors: Vec<Vec<bool>> = (0..16)
+            .map(|i| (0..4).map(|bit| i >> bit & 1 == 1).collect())
+            .collect();
+        let outputs = run(&netlist, &vectors);
+        let mut t = true;
+        for (inputs, outputs) in vectors.iter().zip(&outputs) {
+            let [clk, a, b, c] = inputs[..] else {
+                unreachable!()
+            };
+            t ^= clk;
+            let high = a as u8 + b as u8 + c as u8;
+            assert_eq!(
+                outputs,
+                &[high >= 2, high == 0, t],
+                "failed for inputs: {:?}",
+                inputs
+            );
+        }
+
+        // A falling edge latch with an explicit clock
+        let netlist =
+            from_blif(".model fe\n.inputs ck d\n.outputs q\n.latch d q fe ck 0\n.end\n").unwrap();
+        let vectors = [[true, true], [false, true], [true, false]].map(|v| v.to_vec());
+        assert_eq!(
+            run(&netlist, &vectors),
+            [[false], [true], [true]].map(|v| v.to_vec())
+        );
+    }
+
+    #[test]
+    fn test_from_blif_errors() {
+        let cases = [
+            (".inputs a\n.model m\n", BlifError::MissingModel),
+            (
+                ".model m\n.inputs a\n.outputs y\n.names a y\n1 1 1\n",
+                BlifError::Syntax { line: 5 },
+            ),
+            (
+                ".model m\n.inputs a\n.outputs y\n.names a y\n1 1\n0 0\n",
+                BlifError::Syntax { line: 6 },
+            ),
+            (
+                ".model m\n.inputs a\n.outputs y\n.subckt inv x=a y=y\n",
+                BlifError::Unsupported {
+                    line: 4,
+                    construct: ".subckt".to_string(),
+                },
+            ),
+            (
+                ".model m\n.inputs a\n.outputs y\n.names b y\n1 1\n",
+                BlifError::Netlist(NetlistError::UndrivenNet("b".to_string())),
+            ),
+            (
+                ".model m\n.inputs a\n.outputs y\n.names a y\n1 1\n.names a y\n0 1\n",
+                BlifError::Netlist(NetlistError::MultipleDrivers("y".to_string())),
+            ),
+            (
+                ".model m\n.outputs y\n.names y y\n1 1\n",
+                BlifError::Netlist(NetlistError::CombinationalLoop("y".to_string())),
+            ),
+            (
+                ".model m\n.outputs y\n.names b y\n1 1\n.names y b\n1 1\n",
+                BlifError::Netlist(NetlistError::CombinationalLoop("b".to_string())),
+            ),
+        ];
+        for (blif, expected) in cases {
+            assert_eq!(
+                from_blif(blif).unwrap_err(),
+                expected,
+                "failed for inputs: {:?}",
+                blif
+            );
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let netlist = RippleCarryAdder::<3>::new().netlist();
+        let imported = from_blif(&to_blif(&netlist)).unwrap();
+        assert_eq!(imported.nand_count(), netlist.nand_count());
+        assert_eq!(
+            TruthTable::from_netlist(&imported),
+            TruthTable::from_netlist(&netlist)
+        );
+
+        let vectors: Vec<Vec<bool>> = (0..20).map(|i| vec![i % 2 == 1]).collect();
+        for netlist in [
+            RippleCounter::<3>::new().netlist(),
+            SynchronousCounter::<3>::new().netlist(),
+        ] {
+            let imported = from_blif(&to_blif(&netlist)).unwrap();
+            assert_eq!(imported.nand_count(), netlist.nand_count());
+            assert_eq!(
+                run(&imported, &vectors),
+                run(&netlist, &vectors),
+                "failed for inputs: {:?}",
+                netlist.name()
+            );
+        }
+    }
+}
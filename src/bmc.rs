@@ -0,0 +1,501 @@
+use core::fmt;
+
+use crate::bdd::{variable_order, Bdd, Node};
+use crate::expr::Expr;
+use crate::netlist::{Net, Netlist};
+use crate::sat::{Cnf, Lit};
+use crate::waveform::Waveform;
+
+/// Engine used to search for counterexamples
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Engine {
+    Sat,
+    Bdd,
+}
+
+/// Checks that a property holds after each of the first `steps` steps of a sequential netlist,
+/// starting from its reset state
+///
+/// The property is an [Expr] over the names of inputs, outputs and nets, such as
+/// `!(q[3] & (q[2] | q[1]))` for "the counter never exceeds 9". Each step applies a new set of
+/// inputs chosen by the engine, as with [Netlist::step]. The shortest trace breaking the property
+/// is returned if there is one.
+pub fn check_safety(
+    netlist: &Netlist,
+    property: &Expr,
+    steps: usize,
+    engine: Engine,
+) -> Result<(), BmcError> {
+    let signals = property
+        .variables()
+        .into_iter()
+        .map(|name| match resolve(netlist, &name) {
+            Some(net) => Ok((name, net)),
+            None => Err(BmcError::UnknownSignal(name)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let rounds = netlist.clock_depth().ok_or(BmcError::ClockLoop)?;
+
+    let inputs = match engine {
+        Engine::Sat => {
+            let mut cnf = Cnf::new();
+            unroll(&mut cnf, netlist, property, &signals, steps, rounds)
+        }
+        Engine::Bdd => {
+            let mut bdd = BddUnroll {
+                bdd: Bdd::new(steps * netlist.inputs().len()),
+                next_var: 0,
+            };
+            unroll(&mut bdd, netlist, property, &signals, steps, rounds)
+        }
+    };
+    match inputs {
+        Some(inputs) => Err(BmcError::Violated(Trace::simulate(netlist, &inputs))),
+        None => Ok(()),
+    }
+}
+
+/// Looks up a signal by output, input or net name
+fn resolve(netlist: &Netlist, name: &str) -> Option<Net> {
+    netlist
+        .find_output(name)
+        .or_else(|| netlist.find_input(name))
+        .or_else(|| netlist.find_net(name))
+}
+
+/// Formulas which a sequential netlist can be unrolled into
+trait Unroll {
+    type Signal: Copy;
+
+    /// Creates the inputs for the next step, in input order
+    fn new_inputs(&mut self, netlist: &Netlist) -> Vec<Self::Signal>;
+    fn constant(&mut self, value: bool) -> Self::Signal;
+    fn not(&mut self, a: Self::Signal) -> Self::Signal;
+    fn and(&mut self, a: Self::Signal, b: Self::Signal) -> Self::Signal;
+    fn or(&mut self, a: Self::Signal, b: Self::Signal) -> Self::Signal;
+    fn xor(&mut self, a: Self::Signal, b: Self::Signal) -> Self::Signal;
+    fn mux(&mut self, select: Self::Signal, low: Self::Signal, high: Self::Signal) -> Self::Signal;
+    fn nets(
+        &mut self,
+        netlist: &Netlist,
+        inputs: &[Self::Signal],
+        state: &[Self::Signal],
+    ) -> Vec<Self::Signal>;
+    /// Finds values of the inputs making a signal true
+    fn satisfy(&self, signal: Self::Signal, inputs: &[Self::Signal]) -> Option<Vec<bool>>;
+}
+
+impl Unroll for Cnf {
+    type Signal = Lit;
+
+    fn new_inputs(&mut self, netlist: &Netlist) -> Vec<Lit> {
+        netlist.inputs().iter().map(|_| self.new_var()).collect()
+    }
+
+    fn constant(&mut self, value: bool) -> Lit {
+        Cnf::constant(self, value)
+    }
+
+    fn not(&mut self, a: Lit) -> Lit {
+        !a
+    }
+
+    fn and(&mut self, a: Lit, b: Lit) -> Lit {
+        Cnf::and(self, &[a, b])
+    }
+
+    fn or(&mut self, a: Lit, b: Lit) -> Lit {
+        Cnf::or(self, &[a, b])
+    }
+
+    fn xor(&mut self, a: Lit, b: Lit) -> Lit {
+        Cnf::xor(self, a, b)
+    }
+
+    fn mux(&mut self, select: Lit, low: Lit, high: Lit) -> Lit {
+        Cnf::mux(self, select, low, high)
+    }
+
+    fn nets(&mut self, netlist: &Netlist, inputs: &[Lit], state: &[Lit]) -> Vec<Lit> {
+        self.encode_netlist(netlist, inputs, state)
+    }
+
+    fn satisfy(&self, signal: Lit, inputs: &[Lit]) -> Option<Vec<bool>> {
+        let mut cnf = self.clone();
+        cnf.assert(signal);
+        let model = cnf.solve()?;
+        Some(inputs.iter().map(|lit| lit.evaluate(&model)).collect())
+    }
+}
+
+/// BDDs of an unrolled netlist, with a block of variables for each step's inputs
+struct BddUnroll {
+    bdd: Bdd,
+    next_var: usize,
+}
+
+impl Unroll for BddUnroll {
+    type Signal = Node;
+
+    fn new_inputs(&mut self, netlist: &Netlist) -> Vec<Node> {
+        // Within each block the inputs are ordered as for a combinational check
+        let first = self.next_var;
+        self.next_var += netlist.inputs().len();
+        variable_order(netlist)
+            .iter()
+            .map(|v| self.bdd.var(first + v))
+            .collect()
+    }
+
+    fn constant(&mut self, value: bool) -> Node {
+        self.bdd.constant(value)
+    }
+
+    fn not(&mut self, a: Node) -> Node {
+        self.bdd.not(a)
+    }
+
+    fn and(&mut self, a: Node, b: Node) -> Node {
+        self.bdd.and(a, b)
+    }
+
+    fn or(&mut self, a: Node, b: Node) -> Node {
+        self.bdd.or(a, b)
+    }
+
+    fn xor(&mut self, a: Node, b: Node) -> Node {
+        self.bdd.xor(a, b)
+    }
+
+    fn mux(&mut self, select: Node, low: Node, high: Node) -> Node {
+        self.bdd.ite(select, high, low)
+    }
+
+    fn nets(&mut self, netlist: &Netlist, inputs: &[Node], state: &[Node]) -> Vec<Node> {
+        self.bdd.build_nets(netlist, inputs, state)
+    }
+
+    fn satisfy(&self, signal: Node, inputs: &[Node]) -> Option<Vec<bool>> {
+        let vars = self.bdd.satisfy_one(signal)?;
+        Some(
+            inputs
+                .iter()
+                .map(|node| vars[self.bdd.variable(*node).expect("inputs are variables")])
+                .collect(),
+        )
+    }
+}
+
+/// Unrolls the netlist one step at a time, returning the inputs for each step of the shortest
+/// trace which breaks the property
+fn unroll<U: Unroll>(
+    u: &mut U,
+    netlist: &Netlist,
+    property: &Expr,
+    signals: &[(String, Net)],
+    steps: usize,
+    rounds: usize,
+) -> Option<Vec<Vec<bool>>> {
+    let flipflops = netlist.flipflops();
+    let reset = netlist.reset_state();
+    let mut q: Vec<U::Signal> = reset.q.iter().map(|v| u.constant(*v)).collect();
+    let mut clk: Vec<U::Signal> = reset.clk.iter().map(|v| u.constant(*v)).collect();
+    let mut inputs: Vec<U::Signal> = Vec::new();
+
+    for step in 0..steps {
+        let step_inputs = u.new_inputs(netlist);
+        inputs.extend(&step_inputs);
+
+        // Each round loads the flip-flops whose clock rises, as in Netlist::step
+        for _ in 0..rounds {
+            let nets = u.nets(netlist, &step_inputs, &q);
+            for (i, id) in flipflops.iter().enumerate() {
+                let cell = netlist.cell(*id);
+                let (c, d) = (
+                    nets[cell.inputs()[0].index()],
+                    nets[cell.inputs()[1].index()],
+                );
+                let was_low = u.not(clk[i]);
+                let rising = u.and(was_low, c);
+                q[i] = u.mux(rising, q[i], d);
+                clk[i] = c;
+            }
+        }
+
+        let nets = u.nets(netlist, &step_inputs, &q);
+        let holds = encode_expr(u, property, &|name| {
+            let (_, net) = signals.iter().find(|(n, _)| n == name).unwrap();
+            nets[net.index()]
+        });
+        let broken = u.not(holds);
+        if let Some(values) = u.satisfy(broken, &inputs) {
+            let n = netlist.inputs().len();
+            return Some(
+                (0..=step)
+                    .map(|s| values[s * n..(s + 1) * n].to_vec())
+                    .collect(),
+            );
+        }
+    }
+    None
+}
+
+fn encode_expr<U: Unroll>(u: &mut U, expr: &Expr, var: &impl Fn(&str) -> U::Signal) -> U::Signal {
+    match expr {
+        Expr::Const(c) => u.constant(*c),
+        Expr::Var(name) => var(name),
+        Expr::Not(e) => {
+            let e = encode_expr(u, e, var);
+            u.not(e)
+        }
+        Expr::And(terms) | Expr::Or(terms) => {
+            let is_and = matches!(expr, Expr::And(_));
+            let mut acc = u.constant(is_and);
+            for term in terms {
+                let t = encode_expr(u, term, var);
+                acc = if is_and { u.and(acc, t) } else { u.or(acc, t) };
+            }
+            acc
+        }
+        Expr::Nand(a, b) | Expr::Nor(a, b) | Expr::Xor(a, b) | Expr::Xnor(a, b) => {
+            let a = encode_expr(u, a, var);
+            let b = encode_expr(u, b, var);
+            match expr {
+                Expr::Nand(..) => {
+                    let and = u.and(a, b);
+                    u.not(and)
+                }
+                Expr::Nor(..) => {
+                    let or = u.or(a, b);
+                    u.not(or)
+                }
+                Expr::Xor(..) => u.xor(a, b),
+                _ => {
+                    let xor = u.xor(a, b);
+                    u.not(xor)
+                }
+            }
+        }
+    }
+}
+
+/// Values of a sequential netlist's inputs, flip-flops and outputs after each step
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trace {
+    pub input_names: Vec<String>,
+    /// Name of the Q output of each flip-flop, in the order of [Netlist::flipflops]
+    pub state_names: Vec<String>,
+    pub output_names: Vec<String>,
+    pub steps: Vec<TraceStep>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceStep {
+    pub inputs: Vec<bool>,
+    pub state: Vec<bool>,
+    pub outputs: Vec<bool>,
+}
+
+impl Trace {
+    /// Simulates a netlist from its reset state with the inputs for each step
+    pub fn simulate(netlist: &Netlist, inputs: &[Vec<bool>]) -> Self {
+        let mut state = netlist.reset_state();
+        let steps = inputs
+            .iter()
+            .map(|inputs| {
+                let nets = netlist.step(&mut state, inputs);
+                TraceStep {
+                    inputs: inputs.clone(),
+                    state: state.q.clone(),
+                    outputs: netlist
+                        .outputs()
+                        .iter()
+                        .map(|p| nets[p.net().index()])
+                        .collect(),
+                }
+            })
+            .collect();
+
+        Trace {
+            input_names: netlist
+                .inputs()
+                .iter()
+                .map(|p| p.name().to_string())
+                .collect(),
+            state_names: netlist
+                .flipflops()
+                .iter()
+                .map(|id| netlist.net_name(netlist.cell(*id).output()))
+                .collect(),
+            output_names: netlist
+                .outputs()
+                .iter()
+                .map(|p| p.name().to_string())
+                .collect(),
+            steps,
+        }
+    }
+
+    /// Waveform of every input, flip-flop and output, with one sample per step
+    pub fn to_waveform(&self) -> Waveform {
+        let mut names = self.input_names.clone();
+        names.extend(self.state_names.iter().cloned());
+        names.extend(self.output_names.iter().cloned());
+
+        let mut waveform = Waveform::new(names);
+        for (i, step) in self.steps.iter().enumerate() {
+            let mut values = step.inputs.clone();
+            values.extend(&step.state);
+            values.extend(&step.outputs);
+            waveform.push(i as u64, &values);
+        }
+        waveform
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bits =
+            |bits: &[bool]| -> String { bits.iter().map(|b| if *b { '1' } else { '0' }).collect() };
+
+        writeln!(
+            f,
+            "inputs {} | state {} | outputs {}",
+            self.input_names.join(","),
+            self.state_names.join(","),
+            self.output_names.join(",")
+        )?;
+        for (i, step) in self.steps.iter().enumerate() {
+            writeln!(
+                f,
+                "  step {}: {} | {} | {}",
+                i,
+                bits(&step.inputs),
+                bits(&step.state),
+                bits(&step.outputs)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Reason a property couldn't be shown to hold
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BmcError {
+    UnknownSignal(String),
+    ClockLoop,
+    Violated(Trace),
+}
+
+impl fmt::Display for BmcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BmcError::UnknownSignal(name) => write!(f, "unknown signal {}", name),
+            BmcError::ClockLoop => write!(f, "a flip-flop is clocked by its own output"),
+            BmcError::Violated(trace) => write!(f, "property violated:\n{}", trace),
+        }
+    }
+}
+
+impl std::error::Error for BmcError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::RippleCounter;
+    use crate::netlist::{Builder, CellKind};
+
+    const BELOW_TEN: &str = "!(q[3] & (q[2] | q[1]))";
+
+    #[test]
+    fn test_counter_never_exceeds_nine() {
+        let netlist = RippleCounter::<4>::new().netlist();
+        let property: Expr = BELOW_TEN.parse().unwrap();
+
+        for engine in [Engine::Sat, Engine::Bdd] {
+            // Reaching 10 takes 10 rising edges of the clock, with a falling edge between each
+            assert_eq!(
+                check_safety(&netlist, &property, 18, engine),
+                Ok(()),
+                "failed for engine: {:?}",
+                engine
+            );
+
+            let Err(BmcError::Violated(trace)) = check_safety(&netlist, &property, 25, engine)
+            else {
+                panic!("the counter should reach 10 with {:?}", engine);
+            };
+            assert_eq!(trace.steps.len(), 19, "failed for engine: {:?}", engine);
+            let last = trace.steps.last().unwrap();
+            assert_eq!(last.outputs, [false, true, false, true]);
+            let clk: Vec<bool> = trace.steps.iter().map(|s| s.inputs[0]).collect();
+            assert_eq!(clk, (0..19).map(|i| i % 2 == 0).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_trace_waveform() {
+        let netlist = RippleCounter::<2>::new().netlist();
+        let property: Expr = "!(q[0] & q[1])".parse().unwrap();
+        let Err(BmcError::Violated(trace)) = check_safety(&netlist, &property, 10, Engine::Sat)
+        else {
+            panic!("the counter should reach 3");
+        };
+
+        assert_eq!(
+            trace.to_string(),
+            "inputs clk | state bit[0]/dff0,bit[1]/dff0 | outputs q[0],q[1]\n  \
+             step 0: 1 | 10 | 10\n  \
+             step 1: 0 | 10 | 10\n  \
+             step 2: 1 | 01 | 01\n  \
+             step 3: 0 | 01 | 01\n  \
+             step 4: 1 | 11 | 11\n"
+        );
+        let waveform = trace.to_waveform();
+        assert_eq!(
+            waveform.signal("clk"),
+            Some(vec![true, false, true, false, true])
+        );
+        assert!(waveform
+            .to_vcd("counter", "1ns")
+            .contains("$var wire 1 $ q[0] $end"));
+    }
+
+    #[test]
+    fn test_synchronous_state_machine() {
+        // Two flip-flops on a shared clock, loaded with each other's inverted outputs, cycle
+        // through 00, 11, 00, ... so they are never different
+        let mut b = Builder::new("pair");
+        let clk = b.input("clk");
+        let d: [Net; 2] = [b.net(), b.net()];
+        let q0 = b.dff(clk, d[0]);
+        let q1 = b.dff(clk, d[1]);
+        b.add_cell(CellKind::Nand, &[q1], d[0]);
+        b.add_cell(CellKind::Nand, &[q0], d[1]);
+        b.name_net(q0, "a");
+        b.name_net(q1, "b");
+        let netlist = b.finish();
+
+        let property: Expr = "a ~^ b".parse().unwrap();
+        for engine in [Engine::Sat, Engine::Bdd] {
+            assert_eq!(check_safety(&netlist, &property, 12, engine), Ok(()));
+        }
+
+        let property: Expr = "!(a & b)".parse().unwrap();
+        let Err(BmcError::Violated(trace)) = check_safety(&netlist, &property, 12, Engine::Bdd)
+        else {
+            panic!("both flip-flops should be set after the first edge");
+        };
+        assert_eq!(trace.steps.len(), 1);
+    }
+
+    #[test]
+    fn test_errors() {
+        let netlist = RippleCounter::<2>::new().netlist();
+        let property: Expr = "q[7]".parse().unwrap();
+        assert_eq!(
+            check_safety(&netlist, &property, 4, Engine::Sat),
+            Err(BmcError::UnknownSignal("q[7]".to_string()))
+        );
+    }
+}
@@ -1,6 +1,501 @@
-use core::ops::BitOrAssign;
+use core::fmt;
+use core::ops::{BitAnd, BitOr, BitOrAssign, BitXor, Deref, DerefMut, Not};
+use core::str::FromStr;
 use num::PrimInt;
 
+use crate::logic::{and_n, not_n, or_n, xor_n};
+use crate::swap;
+
+/// A fixed width bus of N bits. Index 0 is the least significant bit
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Bus<const N: usize>([bool; N]);
+
+impl<const N: usize> Bus<N> {
+    /// Creates a bus from its bits (little-endian)
+    pub const fn new(bits: [bool; N]) -> Self {
+        Bus(bits)
+    }
+
+    /// Creates a bus with every bit set to false
+    pub const fn zero() -> Self {
+        Bus([false; N])
+    }
+
+    /// Number of bits in the bus
+    pub const fn width(&self) -> usize {
+        N
+    }
+
+    pub const fn bits(&self) -> &[bool; N] {
+        &self.0
+    }
+
+    pub const fn into_bits(self) -> [bool; N] {
+        self.0
+    }
+
+    /// Returns the least significant bit
+    pub const fn lsb(&self) -> bool {
+        const { assert!(N > 0, "bus has no bits") };
+        self.0[0]
+    }
+
+    /// Returns the most significant bit
+    pub const fn msb(&self) -> bool {
+        const { assert!(N > 0, "bus has no bits") };
+        self.0[N - 1]
+    }
+
+    /// Returns the M bit sub-bus starting at bit START. The range is checked at compile time
+    pub const fn slice<const START: usize, const M: usize>(&self) -> Bus<M> {
+        const { assert!(START + M <= N, "slice out of range of bus") };
+        let mut bits = [false; M];
+        let mut i = 0;
+        while i < M {
+            bits[i] = self.0[START + i];
+            i += 1;
+        }
+        Bus(bits)
+    }
+
+    /// Joins two buses. The bits of self are the low bits and the bits of high are the high bits
+    /// of the result
+    pub const fn concat<const M: usize, const O: usize>(&self, high: &Bus<M>) -> Bus<O> {
+        const {
+            assert!(
+                N + M == O,
+                "concatenated bus width must be the sum of both widths"
+            )
+        };
+        let mut bits = [false; O];
+        let mut i = 0;
+        while i < O {
+            bits[i] = if i < N { self.0[i] } else { high.0[i - N] };
+            i += 1;
+        }
+        Bus(bits)
+    }
+
+    /// Widens the bus to M bits, filling the new high bits with false
+    pub const fn zero_extend<const M: usize>(&self) -> Bus<M> {
+        self.extend_with(false)
+    }
+
+    /// Widens the bus to M bits, filling the new high bits with the most significant bit
+    pub const fn sign_extend<const M: usize>(&self) -> Bus<M> {
+        self.extend_with(self.msb())
+    }
+
+    const fn extend_with<const M: usize>(&self, fill: bool) -> Bus<M> {
+        const {
+            assert!(
+                M >= N,
+                "extended bus must not be narrower than the original"
+            )
+        };
+        let mut bits = [fill; M];
+        let mut i = 0;
+        while i < N {
+            bits[i] = self.0[i];
+            i += 1;
+        }
+        Bus(bits)
+    }
+
+    /// Converts a value into a bus, checking that no significant bits are lost
+    pub fn try_from_num<T: PrimInt>(value: T) -> Result<Self, ConversionError> {
+        try_to_bus(value).map(Bus)
+    }
+
+    /// Converts a value into a bus, discarding any bits which don't fit
+    pub fn from_num_truncated<T: PrimInt>(value: T) -> Self {
+        Bus(to_bus_truncated(value))
+    }
+
+    /// Converts a value into a bus at least as wide as its type, filling the extra bits with the
+    /// most significant bit of the value
+    pub fn from_num_sign_extended<T: PrimInt>(value: T) -> Self {
+        Bus(to_bus_sign_extended(value))
+    }
+
+    /// Creates a bus from bits listed in the given order
+    pub const fn from_ordered_bits(bits: [bool; N], order: BitOrder) -> Self {
+        match order {
+            BitOrder::LsbFirst => Bus(bits),
+            BitOrder::MsbFirst => Bus(swap::reverse_bits(&bits)),
+        }
+    }
+
+    /// Lists the bits of the bus in the given order
+    pub const fn to_ordered_bits(&self, order: BitOrder) -> [bool; N] {
+        match order {
+            BitOrder::LsbFirst => self.0,
+            BitOrder::MsbFirst => swap::reverse_bits(&self.0),
+        }
+    }
+
+    /// Returns the bus with the order of its bits reversed
+    pub const fn reverse_bits(&self) -> Self {
+        Bus(swap::reverse_bits(&self.0))
+    }
+
+    /// Returns the bus with the order of its bytes reversed
+    pub const fn swap_bytes(&self) -> Self {
+        Bus(swap::byte_swap(&self.0))
+    }
+
+    /// Creates a bus from bytes stored in the given byte order. Panics unless there are exactly
+    /// N / 8 bytes
+    pub fn from_bytes(bytes: &[u8], order: ByteOrder) -> Self {
+        const { assert!(N.is_multiple_of(8), "bus must hold a whole number of bytes") };
+        assert_eq!(bytes.len() * 8, N, "wrong number of bytes for bus");
+
+        let mut bits = [false; N];
+        for (i, byte) in bytes.iter().enumerate() {
+            let position = match order {
+                ByteOrder::LittleEndian => i,
+                ByteOrder::BigEndian => bytes.len() - 1 - i,
+            };
+            bits[position * 8..(position + 1) * 8].copy_from_slice(&u8_to_bus(*byte));
+        }
+        Bus(bits)
+    }
+
+    /// Splits the bus into bytes stored in the given byte order
+    pub fn to_bytes(&self, order: ByteOrder) -> Vec<u8> {
+        const { assert!(N.is_multiple_of(8), "bus must hold a whole number of bytes") };
+
+        let mut bytes: Vec<u8> = self.0.chunks(8).map(bus_to_num).collect();
+        if order == ByteOrder::BigEndian {
+            bytes.reverse();
+        }
+        bytes
+    }
+
+    /// Writes the bits most significant first, separating every group of bits with an
+    /// underscore, e.g. "1010_0011"
+    pub fn to_grouped_string(&self, group: usize) -> String {
+        format_bits(&self.0, BitOrder::MsbFirst, group)
+    }
+
+    /// Iterates over the bits, starting with the least significant
+    pub fn iter(&self) -> core::slice::Iter<'_, bool> {
+        self.0.iter()
+    }
+
+    /// Writes the bus most significant digit first, with each digit covering bits_per_digit bits
+    fn fmt_radix(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        bits_per_digit: usize,
+        upper: bool,
+    ) -> fmt::Result {
+        let digits = N.div_ceil(bits_per_digit).max(1);
+        let mut out = String::with_capacity(digits);
+        for d in (0..digits).rev() {
+            let mut digit = 0u32;
+            for b in 0..bits_per_digit {
+                let i = d * bits_per_digit + b;
+                if i < N && self.0[i] {
+                    digit |= 1 << b;
+                }
+            }
+            let c = char::from_digit(digit, 1 << bits_per_digit).unwrap();
+            out.push(if upper { c.to_ascii_uppercase() } else { c });
+        }
+
+        let prefix = match bits_per_digit {
+            1 => "0b",
+            _ => "0x",
+        };
+        f.pad_integral(true, prefix, &out)
+    }
+}
+
+impl<const N: usize> Default for Bus<N> {
+    fn default() -> Self {
+        Self::zero()
+    }
+}
+
+impl<const N: usize> Deref for Bus<N> {
+    type Target = [bool; N];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<const N: usize> DerefMut for Bus<N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<const N: usize> From<[bool; N]> for Bus<N> {
+    fn from(bits: [bool; N]) -> Self {
+        Bus(bits)
+    }
+}
+
+impl<const N: usize> From<Bus<N>> for [bool; N] {
+    fn from(bus: Bus<N>) -> Self {
+        bus.0
+    }
+}
+
+impl<const N: usize> IntoIterator for Bus<N> {
+    type Item = bool;
+    type IntoIter = core::array::IntoIter<bool, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, const N: usize> IntoIterator for &'a Bus<N> {
+    type Item = &'a bool;
+    type IntoIter = core::slice::Iter<'a, bool>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<const N: usize> BitAnd for Bus<N> {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Bus(and_n(&self.0, &rhs.0))
+    }
+}
+
+impl<const N: usize> BitOr for Bus<N> {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Bus(or_n(&self.0, &rhs.0))
+    }
+}
+
+impl<const N: usize> BitXor for Bus<N> {
+    type Output = Self;
+
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        Bus(xor_n(&self.0, &rhs.0))
+    }
+}
+
+impl<const N: usize> Not for Bus<N> {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        Bus(not_n(&self.0))
+    }
+}
+
+/// Prints the bits most significant first
+impl<const N: usize> fmt::Display for Bus<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Binary::fmt(self, f)
+    }
+}
+
+impl<const N: usize> fmt::Binary for Bus<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_radix(f, 1, false)
+    }
+}
+
+impl<const N: usize> fmt::LowerHex for Bus<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_radix(f, 4, false)
+    }
+}
+
+impl<const N: usize> fmt::UpperHex for Bus<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_radix(f, 4, true)
+    }
+}
+
+/// Parses a bus written most significant bit first, e.g. "1010_0011" or "0b1010_0011"
+impl<const N: usize> FromStr for Bus<N> {
+    type Err = ParseBusError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_bits(s, BitOrder::MsbFirst).map(Bus)
+    }
+}
+
+/// Order in which the bits of a bus are listed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitOrder {
+    /// Bit 0 comes first. This is the order used by every bus in the crate
+    LsbFirst,
+    /// The most significant bit comes first, as buses are usually written by hand
+    MsbFirst,
+}
+
+/// Order in which the bytes of a multi-byte value are stored
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+/// Parses a string of 0s and 1s listed in the given order into a bus. Underscores and whitespace
+/// are ignored and an optional "0b" prefix is accepted
+pub fn parse_bits<const N: usize>(s: &str, order: BitOrder) -> Result<[bool; N], ParseBusError> {
+    let trimmed = s.trim();
+    let digits = trimmed.strip_prefix("0b").unwrap_or(trimmed);
+    // Positions are reported as byte offsets into s
+    let start = s.len() - s.trim_start().len() + trimmed.len() - digits.len();
+
+    let mut listed = Vec::with_capacity(N);
+    for (i, c) in digits.char_indices() {
+        let position = start + i;
+        match c {
+            '0' => listed.push(false),
+            '1' => listed.push(true),
+            '_' => {}
+            c if c.is_whitespace() => {}
+            c => return Err(ParseBusError::InvalidDigit { digit: c, position }),
+        }
+    }
+
+    let bits: [bool; N] =
+        listed
+            .try_into()
+            .map_err(|listed: Vec<bool>| ParseBusError::WrongWidth {
+                found: listed.len(),
+                expected: N,
+            })?;
+
+    Ok(Bus::from_ordered_bits(bits, order).into_bits())
+}
+
+/// Writes bits in the given order, separating every group of bits with an underscore. A group
+/// size of zero disables the separators
+pub fn format_bits(bits: &[bool], order: BitOrder, group: usize) -> String {
+    let mut listed = bits.to_vec();
+    if order == BitOrder::MsbFirst {
+        listed.reverse();
+    }
+
+    let mut s = String::with_capacity(bits.len() * 2);
+    for (i, bit) in listed.iter().enumerate() {
+        // Count groups from the least significant end so that partial groups come first
+        let remaining = listed.len() - i;
+        if i > 0 && group > 0 && remaining.is_multiple_of(group) {
+            s.push('_');
+        }
+        s.push(if *bit { '1' } else { '0' });
+    }
+    s
+}
+
+/// Error returned when a string of bits can't be parsed into a bus
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseBusError {
+    /// A character other than 0, 1, underscore or whitespace was found at the given byte offset
+    InvalidDigit { digit: char, position: usize },
+    /// The number of bits doesn't match the width of the bus
+    WrongWidth { found: usize, expected: usize },
+}
+
+impl fmt::Display for ParseBusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseBusError::InvalidDigit { digit, position } => {
+                write!(f, "invalid bit {:?} at position {}", digit, position)
+            }
+            ParseBusError::WrongWidth { found, expected } => {
+                write!(f, "found {} bits but expected {}", found, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseBusError {}
+
+/// Error returned when the value on a bus cannot be represented by the target integer type
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TryFromBusError {
+    width: usize,
+    target: &'static str,
+}
+
+impl fmt::Display for TryFromBusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "value on {} bit bus is out of range for {}",
+            self.width, self.target
+        )
+    }
+}
+
+impl std::error::Error for TryFromBusError {}
+
+macro_rules! impl_unsigned_conversions {
+    ($($t:ty),*) => {$(
+        impl From<$t> for Bus<{ <$t>::BITS as usize }> {
+            fn from(value: $t) -> Self {
+                Bus(to_bus(value))
+            }
+        }
+
+        impl<const N: usize> TryFrom<Bus<N>> for $t {
+            type Error = TryFromBusError;
+
+            fn try_from(bus: Bus<N>) -> Result<Self, Self::Error> {
+                if bus.0.iter().skip(<$t>::BITS as usize).any(|bit| *bit) {
+                    return Err(TryFromBusError { width: N, target: stringify!($t) });
+                }
+                Ok(bus_to_num(&bus.0[..N.min(<$t>::BITS as usize)]))
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_signed_conversions {
+    ($($t:ty),*) => {$(
+        impl From<$t> for Bus<{ <$t>::BITS as usize }> {
+            fn from(value: $t) -> Self {
+                Bus(to_bus(value))
+            }
+        }
+
+        /// Interprets the bus as a two's complement number
+        impl<const N: usize> TryFrom<Bus<N>> for $t {
+            type Error = TryFromBusError;
+
+            fn try_from(bus: Bus<N>) -> Result<Self, Self::Error> {
+                let bits = <$t>::BITS as usize;
+                let sign = N > 0 && bus.0[N - 1];
+                // Every bit from the sign bit of the target upwards must match the bus sign
+                if N > bits && bus.0[bits - 1..].iter().any(|bit| *bit != sign) {
+                    return Err(TryFromBusError { width: N, target: stringify!($t) });
+                }
+
+                let mut value: $t = if sign { -1 } else { 0 };
+                for (i, bit) in bus.0.iter().take(bits).enumerate() {
+                    if *bit {
+                        value |= 1 << i;
+                    } else {
+                        value &= !(1 << i);
+                    }
+                }
+                Ok(value)
+            }
+        }
+    )*};
+}
+
+impl_unsigned_conversions!(u8, u16, u32, u64, u128, usize);
+impl_signed_conversions!(i8, i16, i32, i64, i128, isize);
+
 pub fn bus_to_u8(bits: [bool; 8]) -> u8 {
     bus_to_num(&bits)
 }
@@ -50,22 +545,133 @@ pub fn u64_to_bus(value: u64) -> [bool; 64] {
     to_bus(value)
 }
 
+/// Converts a value into a bus, panicking if the value has more significant bits than the bus
 pub fn to_bus<const N: usize, T>(value: T) -> [bool; N]
 where
     T: PrimInt,
 {
-    if std::mem::size_of::<T>() > N {
-        unreachable!("value doesn't fit into bus");
+    try_to_bus(value).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Converts a value into a bus, checking that no significant bits are lost. Non-negative values
+/// are zero extended and negative values are sign extended (two's complement)
+pub fn try_to_bus<const N: usize, T>(value: T) -> Result<[bool; N], ConversionError>
+where
+    T: PrimInt,
+{
+    let required = significant_bits(value);
+    if required > N {
+        return Err(ConversionError::new(value, required, N));
     }
 
+    Ok(to_bus_extended(value, value < T::zero()))
+}
+
+/// Converts a value into a bus, discarding any bits which don't fit. Bits beyond the width of
+/// the value are filled with false
+pub fn to_bus_truncated<const N: usize, T>(value: T) -> [bool; N]
+where
+    T: PrimInt,
+{
     let mut bits = [false; N];
-    (0..N).for_each(|i| {
-        bits[i] = ((value >> i) & T::from(1).unwrap()) == T::from(1).unwrap();
+    (0..N.min(bit_width::<T>())).for_each(|i| {
+        bits[i] = bit(value, i);
+    });
+
+    bits
+}
+
+/// Converts a value into a bus at least as wide as its type, filling the extra bits with the
+/// most significant bit of the value
+pub fn to_bus_sign_extended<const N: usize, T>(value: T) -> [bool; N]
+where
+    T: PrimInt,
+{
+    assert!(
+        N >= bit_width::<T>(),
+        "{} bit bus is narrower than the {} bit value",
+        N,
+        bit_width::<T>()
+    );
+    to_bus_extended(value, bit(value, bit_width::<T>() - 1))
+}
+
+/// Converts a value into a bus, filling any bits beyond the width of the value with fill
+fn to_bus_extended<const N: usize, T>(value: T, fill: bool) -> [bool; N]
+where
+    T: PrimInt,
+{
+    let width = bit_width::<T>();
+    let mut bits = [fill; N];
+    (0..N.min(width)).for_each(|i| {
+        bits[i] = bit(value, i);
     });
 
     bits
 }
 
+/// Number of bits in the integer type T
+fn bit_width<T: PrimInt>() -> usize {
+    T::zero().count_zeros() as usize
+}
+
+fn bit<T: PrimInt>(value: T, i: usize) -> bool {
+    (value >> i) & T::one() == T::one()
+}
+
+/// Number of bits needed to hold the value. Negative values include a sign bit
+fn significant_bits<T: PrimInt>(value: T) -> usize {
+    if value < T::zero() {
+        bit_width::<T>() - (!value).leading_zeros() as usize + 1
+    } else {
+        bit_width::<T>() - value.leading_zeros() as usize
+    }
+}
+
+/// Error returned when a value has more significant bits than the bus it is converted into
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConversionError {
+    value: String,
+    required: usize,
+    width: usize,
+}
+
+impl ConversionError {
+    fn new<T: PrimInt>(value: T, required: usize, width: usize) -> Self {
+        let value = match value.to_i128() {
+            Some(v) => v.to_string(),
+            None => value.to_u128().unwrap_or_default().to_string(),
+        };
+        ConversionError {
+            value,
+            required,
+            width,
+        }
+    }
+
+    /// Number of bits needed to hold the value without losing information
+    pub fn required_bits(&self) -> usize {
+        self.required
+    }
+
+    /// Width of the bus the value was converted into
+    pub fn bus_width(&self) -> usize {
+        self.width
+    }
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "value {} needs {} bits but the bus is only {} bits wide",
+            self.value, self.required, self.width
+        )
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -83,5 +689,244 @@ mod tests {
     fn test_to_bus() {
         let n = 64;
         assert_eq!(bus_to_u8(u8_to_bus(n)), n);
+        assert_eq!(bus_to_u16(to_bus::<16, u8>(200)), 0x00c8);
+        assert_eq!(bus_to_u16(to_bus::<16, i8>(-56)), 0xffc8);
+    }
+
+    #[test]
+    fn test_bus_slice_and_concat() {
+        let bus = Bus::from(0b1011_0110u8);
+        assert_eq!(u8::try_from(bus.slice::<0, 4>()).unwrap(), 0b0110);
+        assert_eq!(u8::try_from(bus.slice::<4, 4>()).unwrap(), 0b1011);
+        assert_eq!(u8::try_from(bus.slice::<1, 3>()).unwrap(), 0b011);
+
+        let joined: Bus<8> = bus.slice::<0, 4>().concat(&bus.slice::<4, 4>());
+        assert_eq!(joined, bus);
+
+        let wide: Bus<16> = bus.concat(&Bus::from(0xffu8));
+        assert_eq!(u16::try_from(wide).unwrap(), 0xffb6);
+    }
+
+    #[test]
+    fn test_bus_extend() {
+        for (value, zero_extended, sign_extended) in [
+            (0b0000_0000u8, 0x0000u16, 0x0000u16),
+            (0b0111_1111u8, 0x007f, 0x007f),
+            (0b1000_0000u8, 0x0080, 0xff80),
+            (0b1111_1111u8, 0x00ff, 0xffff),
+        ] {
+            let bus = Bus::from(value);
+            assert_eq!(
+                u16::try_from(bus.zero_extend::<16>()).unwrap(),
+                zero_extended,
+                "failed for input: {:?}",
+                value
+            );
+            assert_eq!(
+                u16::try_from(bus.sign_extend::<16>()).unwrap(),
+                sign_extended,
+                "failed for input: {:?}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_bus_operators() {
+        let a = Bus::from(0b0101_0110u8);
+        let b = Bus::from(0b0011_0010u8);
+        assert_eq!(a & b, Bus::from(0b0001_0010u8));
+        assert_eq!(a | b, Bus::from(0b0111_0110u8));
+        assert_eq!(a ^ b, Bus::from(0b0110_0100u8));
+        assert_eq!(!a, Bus::from(0b1010_1001u8));
+    }
+
+    #[test]
+    fn test_bus_indexing_and_iteration() {
+        let mut bus = Bus::<4>::zero();
+        bus[2] = true;
+        assert!(bus[2]);
+        assert!(!bus.lsb());
+        assert!(!bus.msb());
+        assert_eq!(bus.iter().filter(|bit| **bit).count(), 1);
+        assert_eq!(
+            bus.into_iter().collect::<Vec<_>>(),
+            [false, false, true, false]
+        );
+    }
+
+    #[test]
+    fn test_bus_format() {
+        let bus = Bus::<6>::new([true, false, true, true, false, true]);
+        assert_eq!(bus.to_string(), "101101");
+        assert_eq!(format!("{:b}", bus), "101101");
+        assert_eq!(format!("{:#b}", bus), "0b101101");
+        assert_eq!(format!("{:x}", bus), "2d");
+        assert_eq!(format!("{:#X}", bus), "0x2D");
+        assert_eq!(format!("{:08b}", bus), "00101101");
+    }
+
+    #[test]
+    fn test_bus_integer_conversions() {
+        assert_eq!(u32::try_from(Bus::from(u32::MAX)).unwrap(), u32::MAX);
+        assert_eq!(u128::try_from(Bus::from(u128::MAX)).unwrap(), u128::MAX);
+        assert_eq!(i8::try_from(Bus::from(-5i8)).unwrap(), -5);
+        assert_eq!(i64::try_from(Bus::from(i64::MIN)).unwrap(), i64::MIN);
+
+        // Narrowing succeeds only when the value fits
+        assert_eq!(u8::try_from(Bus::from(200u16)).unwrap(), 200);
+        assert!(u8::try_from(Bus::from(256u16)).is_err());
+        assert_eq!(i8::try_from(Bus::from(-128i16)).unwrap(), -128);
+        assert!(i8::try_from(Bus::from(-129i16)).is_err());
+        assert!(i8::try_from(Bus::from(128i16)).is_err());
+
+        // Widening a signed value interprets the bus as two's complement
+        assert_eq!(i32::try_from(Bus::from(-1i8)).unwrap(), -1);
+        assert_eq!(u32::try_from(Bus::from(-1i8)).unwrap(), 0xff);
+    }
+
+    #[test]
+    fn test_try_to_bus() {
+        assert_eq!(
+            try_to_bus::<7, u8>(0b0110111),
+            Ok(to_bus_truncated(0b0110111))
+        );
+        assert_eq!(try_to_bus::<4, u64>(0b1010), Ok([false, true, false, true]));
+        assert_eq!(try_to_bus::<3, i8>(-4), Ok([false, false, true]));
+        assert_eq!(try_to_bus::<8, i32>(-1), Ok([true; 8]));
+        assert_eq!(try_to_bus::<128, u128>(u128::MAX), Ok([true; 128]));
+        assert_eq!(try_to_bus::<16, i8>(-2).map(bus_to_u16), Ok(0xfffe));
+        assert_eq!(try_to_bus::<16, i8>(100).map(bus_to_u16), Ok(0x0064));
+        assert_eq!(try_to_bus::<16, u8>(200).map(bus_to_u16), Ok(0x00c8));
+        assert_eq!(
+            try_to_bus::<64, u32>(u32::MAX).map(bus_to_u64),
+            Ok(0xffff_ffff)
+        );
+        assert_eq!(
+            try_to_bus::<64, i32>(i32::MIN).map(bus_to_u64),
+            Ok(0xffff_ffff_8000_0000)
+        );
+
+        for (result, required, width) in [
+            (try_to_bus::<6, u8>(0b1101111).map(|_| ()), 7, 6),
+            (try_to_bus::<4, u64>(u64::MAX).map(|_| ()), 64, 4),
+            (try_to_bus::<3, i8>(-5).map(|_| ()), 4, 3),
+            (try_to_bus::<100, u128>(1 << 100).map(|_| ()), 101, 100),
+        ] {
+            let err = result.unwrap_err();
+            assert_eq!(err.required_bits(), required, "failed for error: {}", err);
+            assert_eq!(err.bus_width(), width, "failed for error: {}", err);
+        }
+
+        assert_eq!(
+            try_to_bus::<6, u8>(111).unwrap_err().to_string(),
+            "value 111 needs 7 bits but the bus is only 6 bits wide"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "value 300 needs 9 bits but the bus is only 8 bits wide")]
+    fn test_to_bus_overflow() {
+        to_bus::<8, u16>(300);
+    }
+
+    #[test]
+    fn test_to_bus_truncated() {
+        assert_eq!(bus_to_u8(to_bus_truncated(0x1234u16)), 0x34);
+        assert_eq!(bus_to_u8(to_bus_truncated(-1i64)), 0xff);
+        assert_eq!(bus_to_u16(to_bus_truncated(0x80u8)), 0x0080);
+        assert_eq!(bus_to_u16(to_bus_truncated(-1i8)), 0x00ff);
+    }
+
+    #[test]
+    fn test_to_bus_sign_extended() {
+        assert_eq!(bus_to_u16(to_bus_sign_extended(0x80u8)), 0xff80);
+        assert_eq!(bus_to_u16(to_bus_sign_extended(0x7fu8)), 0x007f);
+        assert_eq!(bus_to_u32(to_bus_sign_extended(-3i16)), 0xffff_fffd);
+        assert_eq!(
+            Bus::<64>::from_num_sign_extended(i32::MIN),
+            Bus::from(i64::from(i32::MIN))
+        );
+    }
+
+    #[test]
+    fn test_parse_bits() {
+        assert_eq!("1010_0011".parse(), Ok(Bus::from(0b1010_0011u8)));
+        assert_eq!("0b1010_0011".parse(), Ok(Bus::from(0b1010_0011u8)));
+        assert_eq!("1010 0011".parse(), Ok(Bus::from(0b1010_0011u8)));
+        assert_eq!(
+            parse_bits("1100_0101", BitOrder::LsbFirst),
+            Ok(u8_to_bus(0b1010_0011))
+        );
+
+        assert_eq!(
+            "1010_0012".parse::<Bus<8>>(),
+            Err(ParseBusError::InvalidDigit {
+                digit: '2',
+                position: 8
+            })
+        );
+        for (s, position) in [("0b1010_0012", 10), ("  0b10x0", 6), (" 1\t1 é", 5)] {
+            assert_eq!(
+                s.parse::<Bus<4>>(),
+                Err(ParseBusError::InvalidDigit {
+                    digit: s[position..].chars().next().unwrap(),
+                    position
+                }),
+                "failed for inputs: {:?}",
+                s
+            );
+        }
+        assert_eq!(
+            "1010".parse::<Bus<8>>(),
+            Err(ParseBusError::WrongWidth {
+                found: 4,
+                expected: 8
+            })
+        );
+    }
+
+    #[test]
+    fn test_format_bits() {
+        let bus = Bus::from(0b1010_0011u8);
+        assert_eq!(bus.to_grouped_string(4), "1010_0011");
+        assert_eq!(bus.to_grouped_string(0), "10100011");
+        assert_eq!(bus.zero_extend::<10>().to_grouped_string(4), "00_1010_0011");
+        assert_eq!(format_bits(&*bus, BitOrder::LsbFirst, 4), "1100_0101");
+
+        // Printing and parsing round trip
+        assert_eq!(bus.to_grouped_string(3).parse(), Ok(bus));
+    }
+
+    #[test]
+    fn test_bit_order() {
+        let bits = [true, true, false, false];
+        let bus = Bus::from_ordered_bits(bits, BitOrder::MsbFirst);
+        assert_eq!(u8::try_from(bus).unwrap(), 0b1100);
+        assert_eq!(bus.to_ordered_bits(BitOrder::MsbFirst), bits);
+        assert_eq!(
+            bus.to_ordered_bits(BitOrder::LsbFirst),
+            [false, false, true, true]
+        );
+        assert_eq!(bus.reverse_bits(), Bus::new(bits));
+    }
+
+    #[test]
+    fn test_byte_order() {
+        let bus = Bus::from(0x1234_5678u32);
+        assert_eq!(
+            bus.to_bytes(ByteOrder::LittleEndian),
+            [0x78, 0x56, 0x34, 0x12]
+        );
+        assert_eq!(bus.to_bytes(ByteOrder::BigEndian), [0x12, 0x34, 0x56, 0x78]);
+        assert_eq!(
+            Bus::<32>::from_bytes(&[0x12, 0x34, 0x56, 0x78], ByteOrder::BigEndian),
+            bus
+        );
+        assert_eq!(
+            Bus::<32>::from_bytes(&[0x12, 0x34, 0x56, 0x78], ByteOrder::LittleEndian),
+            bus.swap_bytes()
+        );
+        assert_eq!(u32::try_from(bus.swap_bytes()).unwrap(), 0x7856_3412);
     }
 }
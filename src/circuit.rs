@@ -0,0 +1,490 @@
+use crate::gate::{nand, not};
+use crate::netlist::{Builder, Net};
+
+/// Gates the circuits described with [circuit!](crate::circuit!) are wired from, either as
+/// booleans with [Eval] or as cells added to a [Builder]
+pub trait Logic {
+    type Bit: Copy;
+
+    fn constant(&mut self, value: bool) -> Self::Bit;
+    fn nand(&mut self, inputs: &[Self::Bit]) -> Self::Bit;
+    fn not(&mut self, input: Self::Bit) -> Self::Bit;
+    fn and(&mut self, inputs: &[Self::Bit]) -> Self::Bit;
+    fn or(&mut self, inputs: &[Self::Bit]) -> Self::Bit;
+    fn nor(&mut self, inputs: &[Self::Bit]) -> Self::Bit;
+    fn xor(&mut self, inputs: &[Self::Bit]) -> Self::Bit;
+    fn xnor(&mut self, inputs: &[Self::Bit]) -> Self::Bit;
+
+    /// Wires a circuit used inside another, which a netlist places in a scope named after the
+    /// base name and a count
+    fn instance<T>(&mut self, base: &str, f: impl FnOnce(&mut Self) -> T) -> T;
+}
+
+/// Evaluates circuits on booleans, with the gate functions of [crate::gate]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Eval;
+
+impl Logic for Eval {
+    type Bit = bool;
+
+    fn constant(&mut self, value: bool) -> bool {
+        value
+    }
+
+    fn nand(&mut self, inputs: &[bool]) -> bool {
+        nand(inputs)
+    }
+
+    fn not(&mut self, input: bool) -> bool {
+        not(input)
+    }
+
+    fn and(&mut self, inputs: &[bool]) -> bool {
+        not(nand(inputs))
+    }
+
+    fn or(&mut self, inputs: &[bool]) -> bool {
+        let inverted: Vec<bool> = inputs.iter().map(|i| not(*i)).collect();
+        nand(&inverted)
+    }
+
+    fn nor(&mut self, inputs: &[bool]) -> bool {
+        not(self.or(inputs))
+    }
+
+    fn xor(&mut self, inputs: &[bool]) -> bool {
+        not(self.xnor(inputs))
+    }
+
+    fn xnor(&mut self, inputs: &[bool]) -> bool {
+        let inverted: Vec<bool> = inputs.iter().map(|i| not(*i)).collect();
+        nand(&[nand(&inverted), nand(inputs)])
+    }
+
+    fn instance<T>(&mut self, _base: &str, f: impl FnOnce(&mut Self) -> T) -> T {
+        f(self)
+    }
+}
+
+impl Logic for Builder {
+    type Bit = Net;
+
+    fn constant(&mut self, value: bool) -> Net {
+        Builder::constant(self, value)
+    }
+
+    fn nand(&mut self, inputs: &[Net]) -> Net {
+        Builder::nand(self, inputs)
+    }
+
+    fn not(&mut self, input: Net) -> Net {
+        Builder::not(self, input)
+    }
+
+    fn and(&mut self, inputs: &[Net]) -> Net {
+        Builder::and(self, inputs)
+    }
+
+    fn or(&mut self, inputs: &[Net]) -> Net {
+        Builder::or(self, inputs)
+    }
+
+    fn nor(&mut self, inputs: &[Net]) -> Net {
+        Builder::nor(self, inputs)
+    }
+
+    fn xor(&mut self, inputs: &[Net]) -> Net {
+        Builder::xor(self, inputs)
+    }
+
+    fn xnor(&mut self, inputs: &[Net]) -> Net {
+        Builder::xnor(self, inputs)
+    }
+
+    fn instance<T>(&mut self, base: &str, f: impl FnOnce(&mut Self) -> T) -> T {
+        Builder::instance(self, base, f)
+    }
+}
+
+/// Bit I of a bus, checked when compiling. Used by [circuit!](crate::circuit!) for indices
+pub fn bit<T: Copy, const N: usize, const I: usize>(bus: &[T; N]) -> T {
+    const { assert!(I < N, "bus index out of range") };
+    bus[I]
+}
+
+/// Describes circuits by their ports and wiring, in the style of an HDL. Each becomes a unit
+/// struct with:
+/// - `eval`, which computes the outputs from booleans
+/// - `build`, which adds the circuit's gates to a [Builder] in a scope named after it and a
+///   count, e.g. "HalfAdder0"
+/// - `netlist`, which builds a netlist with a port for each input and output, named like
+///   [Builder::input_bus] for buses
+/// - `wire`, the body shared by the others, generic over [Logic](crate::circuit::Logic)
+///
+/// Ports are bits, or buses such as `select[3]` whose bits are numbered from 0. A single output
+/// is returned as it is, and several as a tuple. The body assigns each output once, and can
+/// name intermediate signals with `let`. Expressions are:
+/// - inputs, outputs and `let` names, and bits of buses such as `select[0]`
+/// - `true` and `false`
+/// - the gates `nand`, `and`, `or`, `nor`, `xor`, `xnor` and `not`, taking bits
+/// - buses of bits such as `[a, b[1], false]`
+/// - other circuits, such as `HalfAdder(a, b)`, which return their outputs as a tuple and can be
+///   taken apart with `let (sum, carry) = ...`
+///
+/// Widths are checked when compiling, since buses are arrays and bits aren't, and an index
+/// beyond the end of a bus fails to compile too.
+///
+/// ```
+/// use nandverse::circuit;
+///
+/// circuit! {
+///     /// Adds two bits, returning the sum and carry
+///     pub HalfAdder(a, b) -> (sum, carry) {
+///         sum = xor(a, b);
+///         carry = and(a, b);
+///     }
+///
+///     pub Mux2(select, input[2]) -> out {
+///         let low = and(not(select), input[0]);
+///         out = or(low, and(select, input[1]));
+///     }
+/// }
+///
+/// assert_eq!(HalfAdder::eval(true, true), (false, true));
+/// assert!(Mux2::eval(true, [false, true]));
+/// assert_eq!(Mux2::netlist().inputs().len(), 3);
+/// ```
+///
+/// ```compile_fail
+/// use nandverse::circuit;
+///
+/// circuit! {
+///     Swap(input[2]) -> out[2] {
+///         out = [input[1], input[0], false];
+///     }
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use nandverse::circuit;
+///
+/// circuit! {
+///     High(input[2]) -> out {
+///         out = input[2];
+///     }
+/// }
+///
+/// High::eval([true, false]);
+/// ```
+#[macro_export]
+macro_rules! circuit {
+    // Type of a port with bits of the given type
+    (@ty $bit:ty, $name:ident) => { $bit };
+    (@ty $bit:ty, $name:ident [$width:literal]) => { [$bit; $width] };
+    (@outputs single $bit:ty, ($($port:tt)*)) => { $crate::circuit!(@ty $bit, $($port)*) };
+    (@outputs tuple $bit:ty, $(($($port:tt)*))*) => {
+        ($($crate::circuit!(@ty $bit, $($port)*),)*)
+    };
+    (@unwrap single $outputs:expr) => {{
+        let (output,) = $outputs;
+        output
+    }};
+    (@unwrap tuple $outputs:expr) => { $outputs };
+    (@input $builder:ident $name:ident) => { $builder.input(stringify!($name)) };
+    (@input $builder:ident $name:ident [$width:literal]) => {
+        $builder.input_bus::<$width>(stringify!($name))
+    };
+    (@output $builder:ident $name:ident) => { $builder.output(stringify!($name), $name) };
+    (@output $builder:ident $name:ident [$width:literal]) => {
+        $builder.output_bus(stringify!($name), &$name)
+    };
+
+    // Splits arguments at their commas, one argument of one or two token trees at a time, then
+    // wires them into an array or a tuple
+    (@args $logic:ident $kind:ident [$($done:tt)*]) => {
+        $crate::circuit!(@join $logic $kind [$($done)*])
+    };
+    (@args $logic:ident $kind:ident [$($done:tt)*] $a:tt $(, $($rest:tt)*)?) => {
+        $crate::circuit!(@args $logic $kind [$($done)* ($a)] $($($rest)*)?)
+    };
+    (@args $logic:ident $kind:ident [$($done:tt)*] $a:tt $b:tt $(, $($rest:tt)*)?) => {
+        $crate::circuit!(@args $logic $kind [$($done)* ($a $b)] $($($rest)*)?)
+    };
+    (@join $logic:ident array [$(($($arg:tt)*))*]) => {
+        [$($crate::circuit!(@expr $logic $($arg)*)),*]
+    };
+    (@join $logic:ident tuple [$(($($arg:tt)*))*]) => {
+        ($($crate::circuit!(@expr $logic $($arg)*),)*)
+    };
+
+    (@expr $logic:ident true) => { $crate::circuit::Logic::constant($logic, true) };
+    (@expr $logic:ident false) => { $crate::circuit::Logic::constant($logic, false) };
+    (@expr $logic:ident not ($($args:tt)*)) => {{
+        let [input] = $crate::circuit!(@args $logic array [] $($args)*);
+        $crate::circuit::Logic::not($logic, input)
+    }};
+    (@expr $logic:ident nand ($($args:tt)*)) => { $crate::circuit!(@gate $logic nand $($args)*) };
+    (@expr $logic:ident and ($($args:tt)*)) => { $crate::circuit!(@gate $logic and $($args)*) };
+    (@expr $logic:ident or ($($args:tt)*)) => { $crate::circuit!(@gate $logic or $($args)*) };
+    (@expr $logic:ident nor ($($args:tt)*)) => { $crate::circuit!(@gate $logic nor $($args)*) };
+    (@expr $logic:ident xor ($($args:tt)*)) => { $crate::circuit!(@gate $logic xor $($args)*) };
+    (@expr $logic:ident xnor ($($args:tt)*)) => { $crate::circuit!(@gate $logic xnor $($args)*) };
+    (@expr $logic:ident $circuit:ident ($($args:tt)*)) => {{
+        let inputs = $crate::circuit!(@args $logic tuple [] $($args)*);
+        $crate::circuit::Logic::instance($logic, stringify!($circuit), |logic| {
+            $circuit::wire(logic, inputs)
+        })
+    }};
+    (@expr $logic:ident $bus:ident [$index:literal]) => {
+        $crate::circuit::bit::<_, _, $index>(&$bus)
+    };
+    (@expr $logic:ident [$($args:tt)*]) => { $crate::circuit!(@args $logic array [] $($args)*) };
+    (@expr $logic:ident ($($inner:tt)*)) => { $crate::circuit!(@expr $logic $($inner)*) };
+    (@expr $logic:ident $name:ident) => { $name };
+    (@expr $logic:ident $($other:tt)*) => {
+        compile_error!(concat!("unsupported expression: ", stringify!($($other)*)))
+    };
+    (@gate $logic:ident $gate:ident $($args:tt)*) => {{
+        let inputs = $crate::circuit!(@args $logic array [] $($args)*);
+        $crate::circuit::Logic::$gate($logic, &inputs)
+    }};
+
+    // Statements, one at a time, each with an expression of one or two token trees
+    (@body $logic:ident) => {};
+    (@body $logic:ident let $name:ident = $a:tt; $($rest:tt)*) => {
+        let $name = $crate::circuit!(@expr $logic $a);
+        $crate::circuit!(@body $logic $($rest)*);
+    };
+    (@body $logic:ident let $name:ident = $a:tt $b:tt; $($rest:tt)*) => {
+        let $name = $crate::circuit!(@expr $logic $a $b);
+        $crate::circuit!(@body $logic $($rest)*);
+    };
+    (@body $logic:ident let ($($name:ident),* $(,)?) = $a:tt $b:tt; $($rest:tt)*) => {
+        let ($($name,)*) = $crate::circuit!(@expr $logic $a $b);
+        $crate::circuit!(@body $logic $($rest)*);
+    };
+    (@body $logic:ident $name:ident = $a:tt; $($rest:tt)*) => {
+        $name = $crate::circuit!(@expr $logic $a);
+        $crate::circuit!(@body $logic $($rest)*);
+    };
+    (@body $logic:ident $name:ident = $a:tt $b:tt; $($rest:tt)*) => {
+        $name = $crate::circuit!(@expr $logic $a $b);
+        $crate::circuit!(@body $logic $($rest)*);
+    };
+    (@body $logic:ident $($other:tt)*) => {
+        compile_error!(concat!("unsupported statement: ", stringify!($($other)*)))
+    };
+
+    (
+        @circuit $(#[$attr:meta])* $vis:vis $name:ident
+        ($($input:ident $([$input_width:literal])?),*)
+        $mode:ident ($($output:ident $([$output_width:literal])?),*)
+        {$($body:tt)*}
+    ) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, Default)]
+        $vis struct $name;
+
+        #[allow(dead_code)]
+        impl $name {
+            /// Evaluates the circuit's outputs from its inputs
+            #[allow(clippy::unused_unit)]
+            $vis fn eval(
+                $($input: $crate::circuit!(@ty bool, $input $([$input_width])?)),*
+            ) -> $crate::circuit!(@outputs $mode bool, $(($output $([$output_width])?))*) {
+                let outputs = Self::wire(&mut $crate::circuit::Eval, ($($input,)*));
+                $crate::circuit!(@unwrap $mode outputs)
+            }
+
+            /// Builds the circuit's gates in a scope named after it, returning its outputs
+            $vis fn build(
+                builder: &mut $crate::netlist::Builder,
+                $($input: $crate::circuit!(@ty $crate::netlist::Net, $input $([$input_width])?)),*
+            ) -> $crate::circuit!(
+                @outputs $mode $crate::netlist::Net, $(($output $([$output_width])?))*
+            ) {
+                let outputs = builder.instance(stringify!($name), |builder| {
+                    Self::wire(builder, ($($input,)*))
+                });
+                $crate::circuit!(@unwrap $mode outputs)
+            }
+
+            /// Builds a netlist of the circuit with a port for each bit of its inputs and
+            /// outputs
+            $vis fn netlist() -> $crate::netlist::Netlist {
+                let mut builder = $crate::netlist::Builder::new(stringify!($name));
+                let inputs = ($($crate::circuit!(@input builder $input $([$input_width])?),)*);
+                let ($($output,)*) = Self::wire(&mut builder, inputs);
+                $($crate::circuit!(@output builder $output $([$output_width])?);)*
+                builder.finish()
+            }
+
+            /// Wires the circuit from the gates of L, returning its outputs as a tuple
+            #[allow(clippy::unused_unit)]
+            $vis fn wire<L: $crate::circuit::Logic>(
+                logic: &mut L,
+                ($($input,)*): ($($crate::circuit!(@ty L::Bit, $input $([$input_width])?),)*),
+            ) -> ($($crate::circuit!(@ty L::Bit, $output $([$output_width])?),)*) {
+                $(let $output;)*
+                $crate::circuit!(@body logic $($body)*);
+                ($($output,)*)
+            }
+        }
+    };
+
+    () => {};
+    (
+        $(#[$attr:meta])* $vis:vis $name:ident ($($inputs:tt)*)
+        -> ($($outputs:tt)*) {$($body:tt)*} $($rest:tt)*
+    ) => {
+        $crate::circuit!(
+            @circuit $(#[$attr])* $vis $name ($($inputs)*) tuple ($($outputs)*) {$($body)*}
+        );
+        $crate::circuit!($($rest)*);
+    };
+    (
+        $(#[$attr:meta])* $vis:vis $name:ident ($($inputs:tt)*)
+        -> $output:ident $([$width:literal])? {$($body:tt)*} $($rest:tt)*
+    ) => {
+        $crate::circuit!(
+            @circuit $(#[$attr])* $vis $name ($($inputs)*) single ($output $([$width])?)
+            {$($body)*}
+        );
+        $crate::circuit!($($rest)*);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::full_add;
+    use crate::mux::{build_mux4, mux4};
+    use crate::truth_table::TruthTable;
+
+    crate::circuit! {
+        HalfAdder(a, b) -> (sum, carry) {
+            sum = xor(a, b);
+            carry = and(a, b);
+        }
+
+        FullAdder(a, b, cin) -> (sum, cout) {
+            let (partial, low) = HalfAdder(a, b);
+            let (total, high) = HalfAdder(partial, cin);
+            sum = total;
+            cout = or(low, high);
+        }
+
+        Mux4(select[2], input[4]) -> out {
+            let low = not(select[0]);
+            let high = not(select[1]);
+            out = or(
+                and(input[0], low, high),
+                and(input[1], select[0], high),
+                and(input[2], low, select[1]),
+                and(input[3], select[0], select[1]),
+            );
+        }
+
+        Swap(input[2], enable) -> (out[2], any) {
+            out = [and(input[1], enable), (and(input[0], enable))];
+            any = nor(nand(true, input[0]), not(input[1]), false);
+        }
+    }
+
+    #[test]
+    fn test_eval() {
+        for i in 0..8 {
+            let [a, b, cin] = core::array::from_fn(|bit| i >> bit & 1 == 1);
+            assert_eq!(
+                FullAdder::eval(a, b, cin),
+                full_add(a, b, cin),
+                "failed for inputs: {:?}",
+                (a, b, cin)
+            );
+        }
+
+        let table =
+            TruthTable::from_fn(|x: &[bool; 6]| Mux4::eval([x[0], x[1]], [x[2], x[3], x[4], x[5]]));
+        let reference =
+            TruthTable::from_fn(|x: &[bool; 6]| mux4(&[x[0], x[1]], &[x[2], x[3], x[4], x[5]]));
+        assert_eq!(table, reference);
+
+        for (inputs, expected) in [
+            (([false, true], true), ([true, false], false)),
+            (([true, true], true), ([true, true], true)),
+            (([true, false], false), ([false, false], false)),
+        ] {
+            assert_eq!(
+                Swap::eval(inputs.0, inputs.1),
+                expected,
+                "failed for inputs: {:?}",
+                inputs
+            );
+        }
+    }
+
+    #[test]
+    fn test_netlist() {
+        let netlist = Mux4::netlist();
+        let inputs: Vec<&str> = netlist.inputs().iter().map(|p| p.name()).collect();
+        assert_eq!(
+            inputs,
+            [
+                "select[0]",
+                "select[1]",
+                "input[0]",
+                "input[1]",
+                "input[2]",
+                "input[3]"
+            ]
+        );
+        assert_eq!(netlist.outputs()[0].name(), "out");
+        let reference =
+            TruthTable::from_fn(|x: &[bool; 6]| mux4(&[x[0], x[1]], &[x[2], x[3], x[4], x[5]]));
+        assert!(TruthTable::from_netlist(&netlist)
+            .verify(&reference)
+            .is_ok());
+
+        let mut builder = Builder::new("mux4");
+        let select = builder.input_bus::<2>("select");
+        let input = builder.input_bus::<4>("input");
+        let out = build_mux4(&mut builder, &select, &input);
+        builder.output("out", out);
+        assert_eq!(netlist.nand_count(), builder.finish().nand_count());
+
+        let netlist = FullAdder::netlist();
+        let table = TruthTable::from_netlist(&netlist);
+        let reference = TruthTable::from_fn(|[a, b, cin]: &[bool; 3]| {
+            let (sum, cout) = full_add(*a, *b, *cin);
+            [sum, cout]
+        });
+        assert!(table.verify(&reference).is_ok());
+        assert!(netlist.find_cell("HalfAdder1/and0/nand0").is_some());
+
+        let outputs: Vec<String> = Swap::netlist()
+            .outputs()
+            .iter()
+            .map(|p| p.name().to_string())
+            .collect();
+        assert_eq!(outputs, ["out[0]", "out[1]", "any"]);
+    }
+
+    #[test]
+    fn test_build() {
+        let mut builder = Builder::new("swaps");
+        let input = builder.input_bus::<2>("input");
+        let enable = builder.input("enable");
+        let (first, _) = Swap::build(&mut builder, input, enable);
+        let (second, any) = Swap::build(&mut builder, first, enable);
+        builder.output_bus("out", &second);
+        builder.output("any", any);
+        let netlist = builder.finish();
+        assert!(netlist.find_cell("Swap1/not0/nand0").is_some());
+        let reference = TruthTable::from_fn(|[a, b, enable]: &[bool; 3]| {
+            ([*a && *enable, *b && *enable], [*a && *b && *enable])
+        });
+        assert!(TruthTable::from_netlist(&netlist)
+            .verify(&reference)
+            .is_ok());
+    }
+}
@@ -1,4 +1,6 @@
 use crate::flipflop::DFlipflop;
+use crate::math::{build_half_add, half_add};
+use crate::netlist::{Builder, CellKind, Netlist};
 
 /// Asynchronous counter of N bits in width
 pub struct RippleCounter<const N: usize> {
@@ -49,6 +51,26 @@ impl<const N: usize> RippleCounter<N> {
 
         T::try_from(val)
     }
+
+    /// Builds a netlist of the counter with input "clk" and outputs "q[i]". Each bit is a
+    /// flip-flop in a scope named "bit[i]", clocked by the inverted output of the bit below
+    pub fn netlist(&self) -> Netlist {
+        let mut builder = Builder::new("ripple_counter");
+        let mut clk = builder.input("clk");
+        let q: [_; N] = core::array::from_fn(|i| {
+            builder.scoped(&format!("bit[{}]", i), |builder| {
+                let qn = builder.net();
+                let q = builder.dff(clk, qn);
+                builder.instance("not", |builder| {
+                    builder.add_cell(CellKind::Nand, &[q, q], qn)
+                });
+                clk = qn;
+                q
+            })
+        });
+        builder.output_bus("q", &q);
+        builder.finish()
+    }
 }
 
 impl<const N: usize> Default for RippleCounter<N> {
@@ -57,9 +79,82 @@ impl<const N: usize> Default for RippleCounter<N> {
     }
 }
 
+/// Synchronous counter of N bits in width, with every flip-flop sharing the clock
+pub struct SynchronousCounter<const N: usize> {
+    flipflops: [DFlipflop; N],
+}
+
+impl<const N: usize> SynchronousCounter<N> {
+    pub fn new() -> Self {
+        let mut counter = SynchronousCounter {
+            flipflops: core::array::from_fn(|_| DFlipflop::new()),
+        };
+
+        counter.update(false);
+        counter
+    }
+
+    /// Update the counter with a new input
+    pub fn update(&mut self, clk: bool) {
+        // Each bit toggles when every bit below it is set, found with a chain of half adders
+        let mut carry = true;
+        let d: [bool; N] = core::array::from_fn(|i| {
+            let (sum, cout) = half_add(self.flipflops[i].q(), carry);
+            carry = cout;
+            sum
+        });
+        for (ff, d) in self.flipflops.iter_mut().zip(d) {
+            ff.update(clk, d);
+        }
+    }
+
+    /// Reset the counter to zero
+    pub fn clear(&mut self) {
+        for ff in self.flipflops.iter_mut() {
+            ff.clear();
+        }
+        self.update(false);
+    }
+
+    /// Get the value of the counter
+    pub fn value<T: TryFrom<u64>>(&self) -> Result<T, T::Error> {
+        let mut val = 0u64;
+        for (i, ff) in self.flipflops.iter().enumerate() {
+            val |= if ff.q() { 1 << i } else { 0 };
+        }
+
+        T::try_from(val)
+    }
+
+    /// Builds a netlist of the counter with input "clk" and outputs "q[i]". Each bit is a
+    /// flip-flop and a half adder in a scope named "bit[i]"
+    pub fn netlist(&self) -> Netlist {
+        let mut builder = Builder::new("synchronous_counter");
+        let clk = builder.input("clk");
+        let q: [_; N] = core::array::from_fn(|_| builder.net());
+        let mut carry = builder.constant(true);
+        for (i, q) in q.iter().enumerate() {
+            builder.scoped(&format!("bit[{}]", i), |builder| {
+                let (d, cout) = build_half_add(builder, *q, carry);
+                builder.add_cell(CellKind::Dff, &[clk, d], *q);
+                carry = cout;
+            });
+        }
+        builder.output_bus("q", &q);
+        builder.finish()
+    }
+}
+
+impl<const N: usize> Default for SynchronousCounter<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bus;
 
     #[test]
     fn test_ripple_counter() {
@@ -88,4 +183,53 @@ mod tests {
         let max_count: u64 = 2u64.pow(WIDTH as u32);
         assert_eq!(counter.value::<u64>().unwrap(), num_toggles % max_count);
     }
+
+    #[test]
+    fn test_ripple_counter_netlist() {
+        let mut counter = RippleCounter::<4>::new();
+        let netlist = counter.netlist();
+        assert_eq!(netlist.clock_depth(), Some(4));
+
+        let mut state = netlist.reset_state();
+        for i in 0..40 {
+            let clk = i % 2 == 0;
+            counter.update(clk);
+            netlist.step(&mut state, &[clk]);
+            assert_eq!(
+                bus::bus_to_num::<u64>(&state.q),
+                counter.value::<u64>().unwrap(),
+                "failed for step: {}",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn test_synchronous_counter() {
+        let mut counter = SynchronousCounter::<4>::new();
+        let netlist = counter.netlist();
+        assert_eq!(netlist.clock_depth(), Some(1));
+
+        let mut state = netlist.reset_state();
+        for i in 0..40 {
+            let clk = i % 2 == 0;
+            counter.update(clk);
+            netlist.step(&mut state, &[clk]);
+            assert_eq!(
+                counter.value::<u64>().unwrap(),
+                (i as u64 / 2 + 1) % 16,
+                "failed for step: {}",
+                i
+            );
+            assert_eq!(
+                bus::bus_to_num::<u64>(&state.q),
+                counter.value::<u64>().unwrap(),
+                "failed for step: {}",
+                i
+            );
+        }
+
+        counter.clear();
+        assert_eq!(counter.value::<u64>().unwrap(), 0);
+    }
 }
@@ -0,0 +1,639 @@
+use core::fmt;
+use core::str::FromStr;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::netlist::{Builder, Net, Netlist};
+
+/// Deepest nesting of parentheses and NOT operators the parser accepts, so it can't overflow the
+/// stack
+const MAX_DEPTH: usize = 64;
+
+/// A boolean expression over named variables
+///
+/// Operators from lowest to highest precedence:
+///
+/// | Operator | Symbols |
+/// | - | - |
+/// | OR, NOR | `\|`, `or`, `~\|`, `!\|`, `nor` |
+/// | XOR, XNOR | `^`, `xor`, `~^`, `!^`, `xnor` |
+/// | AND, NAND | `&`, `and`, `~&`, `!&`, `nand` |
+/// | NOT | `!`, `~`, `not` |
+///
+/// Binary operators are left associative. Constants are written `0`, `1`, `false` or `true`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Const(bool),
+    Var(String),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Nand(Box<Expr>, Box<Expr>),
+    Nor(Box<Expr>, Box<Expr>),
+    Xor(Box<Expr>, Box<Expr>),
+    Xnor(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Names of the variables in the expression, in alphabetical order
+    pub fn variables(&self) -> Vec<String> {
+        let mut vars = BTreeSet::new();
+        self.collect_variables(&mut vars);
+        vars.into_iter().map(str::to_string).collect()
+    }
+
+    fn collect_variables<'a>(&'a self, vars: &mut BTreeSet<&'a str>) {
+        match self {
+            Expr::Const(_) => {}
+            Expr::Var(name) => {
+                vars.insert(name);
+            }
+            Expr::Not(e) => e.collect_variables(vars),
+            Expr::And(terms) | Expr::Or(terms) => {
+                terms.iter().for_each(|t| t.collect_variables(vars));
+            }
+            Expr::Nand(a, b) | Expr::Nor(a, b) | Expr::Xor(a, b) | Expr::Xnor(a, b) => {
+                a.collect_variables(vars);
+                b.collect_variables(vars);
+            }
+        }
+    }
+
+    /// Evaluates the expression, looking up the value of each variable by name
+    pub fn evaluate(&self, value: &impl Fn(&str) -> bool) -> bool {
+        match self {
+            Expr::Const(c) => *c,
+            Expr::Var(name) => value(name),
+            Expr::Not(e) => !e.evaluate(value),
+            Expr::And(terms) => terms.iter().all(|t| t.evaluate(value)),
+            Expr::Or(terms) => terms.iter().any(|t| t.evaluate(value)),
+            Expr::Nand(a, b) => !(a.evaluate(value) && b.evaluate(value)),
+            Expr::Nor(a, b) => !(a.evaluate(value) || b.evaluate(value)),
+            Expr::Xor(a, b) => a.evaluate(value) != b.evaluate(value),
+            Expr::Xnor(a, b) => a.evaluate(value) == b.evaluate(value),
+        }
+    }
+
+    /// Builds the expression from NAND gates, reading each variable from the given net
+    pub fn build(&self, b: &mut Builder, vars: &HashMap<String, Net>) -> Net {
+        match self {
+            Expr::Const(c) => b.constant(*c),
+            Expr::Var(name) => *vars
+                .get(name)
+                .unwrap_or_else(|| panic!("no net for variable {}", name)),
+            Expr::Not(e) => {
+                let e = e.build(b, vars);
+                b.not(e)
+            }
+            Expr::And(terms) => {
+                let terms: Vec<Net> = terms.iter().map(|t| t.build(b, vars)).collect();
+                b.and(&terms)
+            }
+            Expr::Or(terms) => {
+                let terms: Vec<Net> = terms.iter().map(|t| t.build(b, vars)).collect();
+                b.or(&terms)
+            }
+            Expr::Nand(x, y) => {
+                let inputs = [x.build(b, vars), y.build(b, vars)];
+                b.nand(&inputs)
+            }
+            Expr::Nor(x, y) => {
+                let inputs = [x.build(b, vars), y.build(b, vars)];
+                b.nor(&inputs)
+            }
+            Expr::Xor(x, y) => {
+                let inputs = [x.build(b, vars), y.build(b, vars)];
+                b.xor(&inputs)
+            }
+            Expr::Xnor(x, y) => {
+                let inputs = [x.build(b, vars), y.build(b, vars)];
+                b.xnor(&inputs)
+            }
+        }
+    }
+
+    /// Synthesizes a netlist with an input for each variable, in alphabetical order, and a
+    /// single output named "out"
+    pub fn to_netlist(&self) -> Netlist {
+        let mut b = Builder::new("expr");
+        let vars: HashMap<String, Net> = self
+            .variables()
+            .into_iter()
+            .map(|name| {
+                let net = b.input(&name);
+                (name, net)
+            })
+            .collect();
+        let out = self.build(&mut b, &vars);
+        b.output("out", out);
+        b.finish()
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Or(_) | Expr::Nor(..) => 1,
+            Expr::Xor(..) | Expr::Xnor(..) => 2,
+            Expr::And(_) | Expr::Nand(..) => 3,
+            Expr::Not(_) => 4,
+            Expr::Const(_) | Expr::Var(_) => 5,
+        }
+    }
+}
+
+/// Parses an expression and synthesizes it into a netlist of NAND gates
+pub fn synthesize(expr: &str) -> Result<Netlist, ParseError> {
+    Ok(expr.parse::<Expr>()?.to_netlist())
+}
+
+impl FromStr for Expr {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+            end: s.len(),
+            depth: 0,
+        };
+        let expr = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some((token, position)) => Err(ParseError::UnexpectedToken {
+                token: token.to_string(),
+                position: *position,
+            }),
+        }
+    }
+}
+
+/// Writes the expression with the fewest parentheses needed to parse it back
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Operands of left associative operators need parentheses on the right at equal
+        // precedence, since a op (b op c) differs from (a op b) op c
+        let operand = |f: &mut fmt::Formatter<'_>, e: &Expr, right: bool| -> fmt::Result {
+            let p = self.precedence();
+            if e.precedence() < p || (right && e.precedence() == p) {
+                write!(f, "({})", e)
+            } else {
+                write!(f, "{}", e)
+            }
+        };
+        let binary = |f: &mut fmt::Formatter<'_>, a: &Expr, op: &str, b: &Expr| -> fmt::Result {
+            operand(f, a, false)?;
+            write!(f, " {} ", op)?;
+            operand(f, b, true)
+        };
+
+        match self {
+            Expr::Const(c) => write!(f, "{}", *c as u8),
+            Expr::Var(name) => write!(f, "{}", name),
+            Expr::Not(e) => {
+                write!(f, "!")?;
+                operand(f, e, false)
+            }
+            Expr::And(terms) | Expr::Or(terms) => {
+                let op = if matches!(self, Expr::And(_)) {
+                    "&"
+                } else {
+                    "|"
+                };
+                for (i, term) in terms.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " {} ", op)?;
+                    }
+                    operand(f, term, i > 0)?;
+                }
+                Ok(())
+            }
+            Expr::Nand(a, b) => binary(f, a, "~&", b),
+            Expr::Nor(a, b) => binary(f, a, "~|", b),
+            Expr::Xor(a, b) => binary(f, a, "^", b),
+            Expr::Xnor(a, b) => binary(f, a, "~^", b),
+        }
+    }
+}
+
+/// Error returned when an expression can't be parsed. Positions are byte offsets
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    UnexpectedChar {
+        c: char,
+        position: usize,
+    },
+    UnexpectedToken {
+        token: String,
+        position: usize,
+    },
+    UnexpectedEnd {
+        position: usize,
+    },
+    /// Parentheses or NOT operators are nested too deeply to parse
+    TooDeep {
+        position: usize,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedChar { c, position } => {
+                write!(f, "unexpected character {:?} at position {}", c, position)
+            }
+            ParseError::UnexpectedToken { token, position } => {
+                write!(f, "unexpected {:?} at position {}", token, position)
+            }
+            ParseError::UnexpectedEnd { position } => {
+                write!(f, "unexpected end of expression at position {}", position)
+            }
+            ParseError::TooDeep { position } => {
+                write!(f, "expression nested too deeply at position {}", position)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Const(bool),
+    Not,
+    And,
+    Or,
+    Xor,
+    Nand,
+    Nor,
+    Xnor,
+    LParen,
+    RParen,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Const(c) => write!(f, "{}", *c as u8),
+            Token::Not => write!(f, "!"),
+            Token::And => write!(f, "&"),
+            Token::Or => write!(f, "|"),
+            Token::Xor => write!(f, "^"),
+            Token::Nand => write!(f, "~&"),
+            Token::Nor => write!(f, "~|"),
+            Token::Xnor => write!(f, "~^"),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '&' => Token::And,
+            '|' => Token::Or,
+            '^' => Token::Xor,
+            '!' | '~' => match chars.peek().map(|(_, c)| *c) {
+                Some('&') => {
+                    chars.next();
+                    Token::Nand
+                }
+                Some('|') => {
+                    chars.next();
+                    Token::Nor
+                }
+                Some('^') => {
+                    chars.next();
+                    Token::Xnor
+                }
+                _ => Token::Not,
+            },
+            c if c.is_ascii_alphanumeric() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some((_, c)) = chars.peek().filter(|(_, c)| is_word_char(*c)) {
+                    word.push(*c);
+                    chars.next();
+                }
+                match word.as_str() {
+                    "0" | "false" => Token::Const(false),
+                    "1" | "true" => Token::Const(true),
+                    "not" => Token::Not,
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "xor" => Token::Xor,
+                    "nand" => Token::Nand,
+                    "nor" => Token::Nor,
+                    "xnor" => Token::Xnor,
+                    _ if c.is_ascii_digit() => {
+                        return Err(ParseError::UnexpectedToken {
+                            token: word,
+                            position,
+                        })
+                    }
+                    _ => Token::Ident(word),
+                }
+            }
+            c => return Err(ParseError::UnexpectedChar { c, position }),
+        };
+        tokens.push((token, position));
+    }
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '[' || c == ']'
+}
+
+/// Recursive descent parser with one function per precedence level
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    end: usize,
+    /// Number of parentheses and NOT operators being parsed, each inside the last
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn next(&mut self) -> Result<(Token, usize), ParseError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or(ParseError::UnexpectedEnd { position: self.end })?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.xor()?;
+        while let Some(op) = self.peek().filter(|t| matches!(t, Token::Or | Token::Nor)) {
+            let op = op.clone();
+            self.pos += 1;
+            let rhs = self.xor()?;
+            expr = match (op, expr) {
+                (Token::Or, Expr::Or(mut terms)) => {
+                    terms.push(rhs);
+                    Expr::Or(terms)
+                }
+                (Token::Or, lhs) => Expr::Or(vec![lhs, rhs]),
+                (_, lhs) => Expr::Nor(Box::new(lhs), Box::new(rhs)),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn xor(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.and()?;
+        while let Some(op) = self
+            .peek()
+            .filter(|t| matches!(t, Token::Xor | Token::Xnor))
+        {
+            let op = op.clone();
+            self.pos += 1;
+            let rhs = Box::new(self.and()?);
+            expr = match op {
+                Token::Xor => Expr::Xor(Box::new(expr), rhs),
+                _ => Expr::Xnor(Box::new(expr), rhs),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.not()?;
+        while let Some(op) = self
+            .peek()
+            .filter(|t| matches!(t, Token::And | Token::Nand))
+        {
+            let op = op.clone();
+            self.pos += 1;
+            let rhs = self.not()?;
+            expr = match (op, expr) {
+                (Token::And, Expr::And(mut terms)) => {
+                    terms.push(rhs);
+                    Expr::And(terms)
+                }
+                (Token::And, lhs) => Expr::And(vec![lhs, rhs]),
+                (_, lhs) => Expr::Nand(Box::new(lhs), Box::new(rhs)),
+            };
+        }
+        Ok(expr)
+    }
+
+    /// Runs a parser one level deeper, failing past [MAX_DEPTH]
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Expr, ParseError>,
+    ) -> Result<Expr, ParseError> {
+        if self.depth == MAX_DEPTH {
+            // The position of the operator or parenthesis just read
+            let position = self.tokens[self.pos - 1].1;
+            return Err(ParseError::TooDeep { position });
+        }
+        self.depth += 1;
+        let expr = parse(self);
+        self.depth -= 1;
+        expr
+    }
+
+    fn not(&mut self) -> Result<Expr, ParseError> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.nested(Self::not)?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        match self.next()? {
+            (Token::Ident(name), _) => Ok(Expr::Var(name)),
+            (Token::Const(c), _) => Ok(Expr::Const(c)),
+            (Token::LParen, _) => {
+                let expr = self.nested(Self::or)?;
+                match self.next()? {
+                    (Token::RParen, _) => Ok(expr),
+                    (token, position) => Err(ParseError::UnexpectedToken {
+                        token: token.to_string(),
+                        position,
+                    }),
+                }
+            }
+            (token, position) => Err(ParseError::UnexpectedToken {
+                token: token.to_string(),
+                position,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::truth_table::TruthTable;
+
+    fn var(name: &str) -> Box<Expr> {
+        Box::new(Expr::Var(name.to_string()))
+    }
+
+    #[test]
+    fn test_parse() {
+        for (input, expected) in [
+            ("a", Expr::Var("a".to_string())),
+            ("!a", Expr::Not(var("a"))),
+            ("not ~a", Expr::Not(Box::new(Expr::Not(var("a"))))),
+            (
+                "a & b & c",
+                Expr::And(vec![*var("a"), *var("b"), *var("c")]),
+            ),
+            (
+                "a | b & c",
+                Expr::Or(vec![*var("a"), Expr::And(vec![*var("b"), *var("c")])]),
+            ),
+            (
+                "(a | b) & c",
+                Expr::And(vec![Expr::Or(vec![*var("a"), *var("b")]), *var("c")]),
+            ),
+            (
+                "a ^ b | c",
+                Expr::Or(vec![Expr::Xor(var("a"), var("b")), *var("c")]),
+            ),
+            (
+                "a nand b nand c",
+                Expr::Nand(Box::new(Expr::Nand(var("a"), var("b"))), var("c")),
+            ),
+            ("a ~| b", Expr::Nor(var("a"), var("b"))),
+            ("x[0] !^ x[1]", Expr::Xnor(var("x[0]"), var("x[1]"))),
+            ("a & 1", Expr::And(vec![*var("a"), Expr::Const(true)])),
+        ] {
+            assert_eq!(input.parse(), Ok(expected), "failed for input: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (input, expected) in [
+            ("a & ", ParseError::UnexpectedEnd { position: 4 }),
+            (
+                "a $ b",
+                ParseError::UnexpectedChar {
+                    c: '$',
+                    position: 2,
+                },
+            ),
+            ("(a | b", ParseError::UnexpectedEnd { position: 6 }),
+            (
+                "a b",
+                ParseError::UnexpectedToken {
+                    token: "b".to_string(),
+                    position: 2,
+                },
+            ),
+            (
+                "a & )",
+                ParseError::UnexpectedToken {
+                    token: ")".to_string(),
+                    position: 4,
+                },
+            ),
+            (
+                "2a",
+                ParseError::UnexpectedToken {
+                    token: "2a".to_string(),
+                    position: 0,
+                },
+            ),
+        ] {
+            assert_eq!(
+                input.parse::<Expr>(),
+                Err(expected),
+                "failed for input: {:?}",
+                input
+            );
+        }
+
+        let deepest = format!("{}a{}", "(".repeat(64), ")".repeat(64));
+        assert_eq!(deepest.parse::<Expr>(), Ok(Expr::Var("a".to_string())));
+        let nots = "!".repeat(200_000) + "a";
+        assert_eq!(
+            nots.parse::<Expr>(),
+            Err(ParseError::TooDeep { position: 64 })
+        );
+        let parens = "(".repeat(200_000) + "a";
+        assert_eq!(
+            parens.parse::<Expr>(),
+            Err(ParseError::TooDeep { position: 64 })
+        );
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for input in [
+            "(a & !b) | (c ^ d)",
+            "a ^ (b ^ c)",
+            "!(a | b) & c",
+            "a ~& (b ~& c)",
+            "(a ~| b) ~^ 0",
+        ] {
+            let expr: Expr = input.parse().unwrap();
+            assert_eq!(
+                expr.to_string().parse(),
+                Ok(expr.clone()),
+                "failed for input: {:?}",
+                input
+            );
+        }
+        assert_eq!(
+            "(a & !b) | (c ^ d)".parse::<Expr>().unwrap().to_string(),
+            "a & !b | c ^ d"
+        );
+    }
+
+    #[test]
+    fn test_synthesize() {
+        let netlist = synthesize("(a & !b) | (c ^ d)").unwrap();
+        let names: Vec<&str> = netlist.inputs().iter().map(|p| p.name()).collect();
+        assert_eq!(names, ["a", "b", "c", "d"]);
+
+        let result = TruthTable::from_netlist(&netlist)
+            .verify_fn(|x: &[bool; 4]| (x[0] && !x[1]) || (x[2] != x[3]));
+        if let Err(mismatches) = result {
+            panic!("{}", mismatches);
+        }
+
+        // NOT = 1, AND = 2, XOR = 6 and OR = 3 NAND gates
+        assert_eq!(netlist.nand_count(), 1 + 2 + 6 + 3);
+    }
+
+    #[test]
+    fn test_netlist_matches_evaluate() {
+        for input in [
+            "a nand b",
+            "a nor b nor c",
+            "!(a xnor b) or c and true",
+            "a & (b | !a) ^ 0",
+        ] {
+            let expr: Expr = input.parse().unwrap();
+            let netlist = expr.to_netlist();
+            let vars = expr.variables();
+            for row in 0..1 << vars.len() {
+                let inputs: Vec<bool> = (0..vars.len()).map(|i| (row >> i) & 1 == 1).collect();
+                let lookup = |name: &str| inputs[vars.iter().position(|v| v == name).unwrap()];
+                assert_eq!(
+                    netlist.evaluate(&inputs),
+                    [expr.evaluate(&lookup)],
+                    "failed for inputs: {:?}",
+                    (input, &inputs)
+                );
+            }
+        }
+    }
+}
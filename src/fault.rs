@@ -0,0 +1,419 @@
+use core::fmt;
+
+use crate::netlist::{CellKind, Driver, Net, Netlist};
+
+/// Number of times a bridging fault is re-evaluated before giving up on it settling
+const BRIDGE_ITERATIONS: usize = 8;
+
+/// How two bridged nets combine their values
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BridgeKind {
+    /// Both nets read low if either driver is low
+    WiredAnd,
+    /// Both nets read high if either driver is high
+    WiredOr,
+}
+
+/// Manufacturing defect modelled on the nets of a netlist
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Fault {
+    /// The net always reads the given value
+    StuckAt { net: Net, value: bool },
+    /// Two nets are shorted together
+    Bridge { a: Net, b: Net, kind: BridgeKind },
+}
+
+impl Fault {
+    pub fn stuck_at(net: Net, value: bool) -> Self {
+        Fault::StuckAt { net, value }
+    }
+
+    pub fn bridge(a: Net, b: Net, kind: BridgeKind) -> Self {
+        Fault::Bridge { a, b, kind }
+    }
+
+    /// Describes the fault using the names of its nets, e.g. "a[0] stuck-at-1"
+    pub fn describe(&self, netlist: &Netlist) -> String {
+        match self {
+            Fault::StuckAt { net, value } => {
+                format!("{} stuck-at-{}", netlist.net_name(*net), *value as u8)
+            }
+            Fault::Bridge { a, b, kind } => format!(
+                "{} and {} bridged ({})",
+                netlist.net_name(*a),
+                netlist.net_name(*b),
+                match kind {
+                    BridgeKind::WiredAnd => "wired-AND",
+                    BridgeKind::WiredOr => "wired-OR",
+                }
+            ),
+        }
+    }
+}
+
+/// Stuck-at-0 and stuck-at-1 faults on every driven net of a netlist, in net order. Constant
+/// nets only get the fault opposite to their value, as the other one can never be detected
+pub fn stuck_at_faults(netlist: &Netlist) -> Vec<Fault> {
+    netlist
+        .nets()
+        .flat_map(|net| match netlist.driver(net) {
+            Some(Driver::Cell(id)) => match netlist.cell(id).kind() {
+                CellKind::Const(value) => vec![Fault::stuck_at(net, !value)],
+                _ => vec![Fault::stuck_at(net, false), Fault::stuck_at(net, true)],
+            },
+            Some(Driver::Input(_)) => vec![Fault::stuck_at(net, false), Fault::stuck_at(net, true)],
+            None => Vec::new(),
+        })
+        .collect()
+}
+
+/// Evaluates every net of a netlist with a fault injected, from the inputs and the output of each
+/// flip-flop. Returns None if a bridging fault makes the nets oscillate
+pub fn evaluate_nets_faulty(
+    netlist: &Netlist,
+    fault: &Fault,
+    inputs: &[bool],
+    state: &[bool],
+) -> Option<Vec<bool>> {
+    match *fault {
+        Fault::StuckAt { net, value } => {
+            Some(evaluate_forced(netlist, inputs, state, &[(net, value)]).0)
+        }
+        Fault::Bridge { a, b, kind } => {
+            // Drive both nets with the combined value until the drivers agree with it
+            let mut forced = Vec::new();
+            for _ in 0..BRIDGE_ITERATIONS {
+                let (values, driven) = evaluate_forced(netlist, inputs, state, &forced);
+                let (x, y) = (driven[a.index()], driven[b.index()]);
+                let wired = match kind {
+                    BridgeKind::WiredAnd => x && y,
+                    BridgeKind::WiredOr => x || y,
+                };
+                if forced == [(a, wired), (b, wired)] {
+                    return Some(values);
+                }
+                forced = vec![(a, wired), (b, wired)];
+            }
+            None
+        }
+    }
+}
+
+/// Evaluates a combinational netlist with a fault injected, returning the value of each output.
+/// Returns None if a bridging fault makes the nets oscillate
+pub fn evaluate_faulty(netlist: &Netlist, fault: &Fault, inputs: &[bool]) -> Option<Vec<bool>> {
+    assert!(
+        !netlist.is_sequential(),
+        "netlist {} is sequential and needs a state to evaluate",
+        netlist.name()
+    );
+    let values = evaluate_nets_faulty(netlist, fault, inputs, &[])?;
+    Some(
+        netlist
+            .outputs()
+            .iter()
+            .map(|p| values[p.net().index()])
+            .collect(),
+    )
+}
+
+/// Evaluates every net with some nets forced to fixed values. Returns the values seen by the
+/// readers of each net, and the values their drivers produce
+fn evaluate_forced(
+    netlist: &Netlist,
+    inputs: &[bool],
+    state: &[bool],
+    forced: &[(Net, bool)],
+) -> (Vec<bool>, Vec<bool>) {
+    let mut values = vec![false; netlist.num_nets()];
+    let mut driven = vec![false; netlist.num_nets()];
+    let mut set = |net: Net, value: bool, values: &mut Vec<bool>| {
+        driven[net.index()] = value;
+        values[net.index()] = forced
+            .iter()
+            .find(|(n, _)| *n == net)
+            .map_or(value, |(_, v)| *v);
+    };
+
+    for (port, value) in netlist.inputs().iter().zip(inputs) {
+        set(port.net(), *value, &mut values);
+    }
+    let mut state = state.iter();
+    for cell in netlist.cells() {
+        let value = match cell.kind() {
+            CellKind::Nand => !cell.inputs().iter().all(|n| values[n.index()]),
+            CellKind::Const(value) => value,
+            CellKind::Dff => *state.next().expect("missing flip-flop state"),
+        };
+        set(cell.output(), value, &mut values);
+    }
+    (values, driven)
+}
+
+/// Outputs of a netlist for each test vector, with an optional fault injected. Sequential
+/// netlists are stepped from reset, one vector per step. Stops with None at the first vector
+/// for which the nets oscillate, or the fault makes a flip-flop clock itself
+fn run(netlist: &Netlist, fault: Option<&Fault>, vectors: &[Vec<bool>]) -> Vec<Option<Vec<bool>>> {
+    let evaluate = |inputs: &[bool], q: &[bool]| match fault {
+        Some(fault) => evaluate_nets_faulty(netlist, fault, inputs, q),
+        None => Some(netlist.evaluate_nets(inputs, q)),
+    };
+    let outputs = |values: &[bool]| -> Vec<bool> {
+        netlist
+            .outputs()
+            .iter()
+            .map(|p| values[p.net().index()])
+            .collect()
+    };
+
+    let mut state = netlist.reset_state();
+    let mut results = Vec::new();
+    for inputs in vectors {
+        let values = if netlist.is_sequential() {
+            netlist.step_with(&mut state, |q| evaluate(inputs, q))
+        } else {
+            evaluate(inputs, &[])
+        };
+        results.push(values.as_deref().map(outputs));
+        if values.is_none() {
+            break;
+        }
+    }
+    results
+}
+
+/// Simulates every fault against a set of test vectors, recording the first vector whose outputs
+/// differ from the fault free netlist. A bridging fault which makes the nets oscillate, or a
+/// flip-flop clock itself, is detected by the vector causing it
+pub fn simulate_faults(netlist: &Netlist, faults: &[Fault], vectors: &[Vec<bool>]) -> FaultReport {
+    let good: Vec<Vec<bool>> = run(netlist, None, vectors)
+        .into_iter()
+        .map(|outputs| outputs.expect("the fault free netlist settles"))
+        .collect();
+    let results = faults
+        .iter()
+        .map(|fault| FaultResult {
+            fault: *fault,
+            name: fault.describe(netlist),
+            detected_by: run(netlist, Some(fault), vectors)
+                .iter()
+                .zip(&good)
+                .position(|(faulty, good)| faulty.as_ref() != Some(good)),
+        })
+        .collect();
+    FaultReport { results }
+}
+
+/// Outcome of simulating one fault
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FaultResult {
+    pub fault: Fault,
+    pub name: String,
+    /// Index of the first test vector detecting the fault
+    pub detected_by: Option<usize>,
+}
+
+/// Which faults a set of test vectors detects
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FaultReport {
+    results: Vec<FaultResult>,
+}
+
+impl FaultReport {
+    pub fn results(&self) -> &[FaultResult] {
+        &self.results
+    }
+
+    pub fn detected(&self) -> Vec<&FaultResult> {
+        self.results
+            .iter()
+            .filter(|r| r.detected_by.is_some())
+            .collect()
+    }
+
+    pub fn escaped(&self) -> Vec<&FaultResult> {
+        self.results
+            .iter()
+            .filter(|r| r.detected_by.is_none())
+            .collect()
+    }
+
+    /// Fraction of the faults detected, or 1 if there are no faults
+    pub fn coverage(&self) -> f64 {
+        if self.results.is_empty() {
+            1.0
+        } else {
+            self.detected().len() as f64 / self.results.len() as f64
+        }
+    }
+}
+
+impl fmt::Display for FaultReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "fault coverage {}/{} ({:.1}%)",
+            self.detected().len(),
+            self.results.len(),
+            self.coverage() * 100.0
+        )?;
+        let escaped = self.escaped();
+        if !escaped.is_empty() {
+            writeln!(f, "escaped:")?;
+            for result in escaped {
+                writeln!(f, "  {}", result.name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::RippleCounter;
+    use crate::math::{build_full_add, full_add, full_adder_netlist};
+    use crate::netlist::Builder;
+    use crate::truth_table::TruthTable;
+
+    fn exhaustive(n: usize) -> Vec<Vec<bool>> {
+        (0..1usize << n)
+            .map(|row| (0..n).map(|i| row >> i & 1 == 1).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_inject_stuck_at() {
+        let netlist = full_adder_netlist();
+        // The NAND combining the two halves of the first XOR inside the full adder
+        let cell = netlist.find_cell("full_add0/xor0/xnor0/nand2").unwrap();
+        let fault = Fault::stuck_at(netlist.cell(cell).output(), true);
+        assert_eq!(
+            fault.describe(&netlist),
+            "full_add0/xor0/xnor0/nand2 stuck-at-1"
+        );
+
+        // a ^ b now reads 0, so the adder behaves as if both inputs were equal
+        for inputs in exhaustive(3) {
+            let (sum, _) = full_add(inputs[0], inputs[1], inputs[2]);
+            let faulty = evaluate_faulty(&netlist, &fault, &inputs).unwrap();
+            let expected = if inputs[0] != inputs[1] { !sum } else { sum };
+            assert_eq!(faulty[0], expected, "failed for inputs: {:?}", inputs);
+        }
+    }
+
+    #[test]
+    fn test_full_adder_coverage() {
+        let netlist = full_adder_netlist();
+        let faults = stuck_at_faults(&netlist);
+        assert_eq!(faults.len(), 2 * (3 + netlist.cells().len()));
+
+        let report = simulate_faults(&netlist, &faults, &exhaustive(3));
+        assert_eq!(report.coverage(), 1.0, "{}", report);
+
+        // Only ever adding zeros leaves the faults which need a high input undetected
+        let report = simulate_faults(&netlist, &faults, &[vec![false, false, false]]);
+        assert!(report.coverage() < 0.6, "{}", report);
+        for result in report.detected() {
+            assert_eq!(result.detected_by, Some(0));
+        }
+        let text = report.to_string();
+        assert!(text.starts_with(&format!(
+            "fault coverage {}/{} (",
+            report.detected().len(),
+            faults.len()
+        )));
+        assert!(text.contains("\n  in[0] stuck-at-0\n"));
+    }
+
+    #[test]
+    fn test_constant_faults() {
+        let mut builder = Builder::new("half_add");
+        let [a, b] = builder.input_bus::<2>("in");
+        let cin = builder.constant(false);
+        let (sum, cout) = build_full_add(&mut builder, a, b, cin);
+        builder.output("sum", sum);
+        builder.output("cout", cout);
+        let netlist = builder.finish();
+
+        // The carry in can only be stuck at the value it isn't tied to
+        let faults = stuck_at_faults(&netlist);
+        assert!(faults.contains(&Fault::stuck_at(cin, true)));
+        assert!(!faults.contains(&Fault::stuck_at(cin, false)));
+        assert_eq!(faults.len(), 2 * (2 + netlist.cells().len() - 1) + 1);
+
+        let report = simulate_faults(&netlist, &faults, &exhaustive(2));
+        let detected: Vec<&str> = report.detected().iter().map(|r| r.name.as_str()).collect();
+        assert!(detected.contains(&"tie00 stuck-at-1"), "{}", report);
+    }
+
+    #[test]
+    fn test_bridging_faults() {
+        let mut builder = Builder::new("pair");
+        let [a, b] = builder.input_bus::<2>("in");
+        let x = builder.not(a);
+        let y = builder.not(b);
+        builder.output("x", x);
+        builder.output("y", y);
+        let netlist = builder.finish();
+
+        let table = TruthTable::from_fn(|inputs: &[bool; 2]| {
+            let (x, y) = (!inputs[0], !inputs[1]);
+            [x && y, x && y]
+        });
+        let fault = Fault::bridge(x, y, BridgeKind::WiredAnd);
+        assert_eq!(
+            fault.describe(&netlist),
+            "not0/nand0 and not1/nand0 bridged (wired-AND)"
+        );
+        for (row, inputs) in exhaustive(2).iter().enumerate() {
+            assert_eq!(
+                evaluate_faulty(&netlist, &fault, inputs),
+                Some(table.outputs(row).to_vec()),
+                "failed for inputs: {:?}",
+                inputs
+            );
+        }
+
+        // Equal inputs can't tell the bridged nets apart
+        let faults = [fault, Fault::bridge(x, y, BridgeKind::WiredOr)];
+        let report = simulate_faults(&netlist, &faults, &[vec![false, false], vec![true, true]]);
+        assert_eq!(report.coverage(), 0.0);
+        let report = simulate_faults(&netlist, &faults, &[vec![true, false]]);
+        assert_eq!(report.coverage(), 1.0);
+    }
+
+    #[test]
+    fn test_sequential_faults() {
+        let netlist = RippleCounter::<2>::new().netlist();
+        let clock: Vec<Vec<bool>> = (0..8).map(|i| vec![i % 2 == 0]).collect();
+        let report = simulate_faults(&netlist, &stuck_at_faults(&netlist), &clock);
+        assert_eq!(report.coverage(), 1.0, "{}", report);
+
+        // A single clock edge never exercises the second bit
+        let report = simulate_faults(&netlist, &stuck_at_faults(&netlist), &clock[..1]);
+        let escaped: Vec<&str> = report.escaped().iter().map(|r| r.name.as_str()).collect();
+        assert!(escaped.contains(&"bit[1]/dff0 stuck-at-0"), "{}", report);
+    }
+
+    #[test]
+    fn test_self_clocking_bridge() {
+        // Bridging the clock to x makes the first flip-flop clock itself through the second
+        let mut builder = Builder::new("toggles");
+        let clk = builder.input("clk");
+        let [qa, qb] = [builder.net(), builder.net()];
+        let [na, nb] = [builder.not(qa), builder.not(qb)];
+        builder.add_cell(CellKind::Dff, &[clk, na], qa);
+        builder.add_cell(CellKind::Dff, &[qa, nb], qb);
+        let x = builder.xnor(&[qa, qb]);
+        builder.output("a", qa);
+        builder.output("b", qb);
+        let netlist = builder.finish();
+
+        let fault = Fault::bridge(clk, x, BridgeKind::WiredAnd);
+        let vectors = [vec![false], vec![true]];
+        assert_eq!(run(&netlist, Some(&fault), &vectors)[1], None);
+        let report = simulate_faults(&netlist, &[fault], &vectors);
+        assert_eq!(report.results()[0].detected_by, Some(1), "{}", report);
+    }
+}
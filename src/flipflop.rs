@@ -1,5 +1,6 @@
 use crate::gate::{and, not};
 use crate::latch;
+use crate::netlist::{Builder, Netlist};
 
 /// Rising edge triggered D flip-flop
 #[derive(Debug)]
@@ -20,7 +21,8 @@ impl DFlipflop {
     /// Updates the flip-flop based on new inputs. The flip-flop triggers on the rising edge of the
     /// clock.
     ///
-    /// Note: D must be set to true before the CLK signal changes.
+    /// Note: D must be set to true before the CLK signal changes. This model doesn't check it, but
+    /// [crate::metastability::simulate] reports setup and hold violations of netlist flip-flops.
     pub fn update(&mut self, clk: bool, d: bool) {
         self.master.set(not(clk), d);
         self.slave.set(clk, self.master.q());
@@ -38,6 +40,35 @@ impl DFlipflop {
     pub fn qn(&self) -> bool {
         self.slave.qn()
     }
+
+    /// Builds a netlist of the flip-flop with inputs "clk" and "d" and outputs "q" and "qn". The
+    /// flip-flop is a single cell rather than two latches, since netlists can't hold the
+    /// combinational loops inside a latch. [DFlipflop::latch_builder] builds the latches
+    pub fn netlist(&self) -> Netlist {
+        let mut builder = Builder::new("d_flipflop");
+        let clk = builder.input("clk");
+        let d = builder.input("d");
+        let q = builder.dff(clk, d);
+        let qn = builder.not(q);
+        builder.output("q", q);
+        builder.output("qn", qn);
+        builder.finish()
+    }
+
+    /// Builds the flip-flop as its master and slave latches, with the same ports as
+    /// [DFlipflop::netlist]. The latches' loops mean the builder can't be finished, but it can be
+    /// drawn with [builder_to_dot](crate::schematic::builder_to_dot)
+    pub fn latch_builder(&self) -> Builder {
+        let mut builder = Builder::new("d_flipflop");
+        let clk = builder.input("clk");
+        let d = builder.input("d");
+        let not_clk = builder.not(clk);
+        let (master, _) = latch::build_d_latch(&mut builder, not_clk, d);
+        let (q, qn) = latch::build_d_latch(&mut builder, clk, master);
+        builder.output("q", q);
+        builder.output("qn", qn);
+        builder
+    }
 }
 
 impl Default for DFlipflop {
@@ -49,6 +49,7 @@ pub const fn xnor<const N: usize>(inputs: &[bool; N]) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::truth_table::TruthTable;
 
     #[test]
     fn test_nand() {
@@ -144,4 +145,31 @@ mod tests {
             assert_eq!(xnor(inputs), expected, "failed for inputs: {:?}", inputs)
         }
     }
+
+    #[test]
+    fn test_gates_exhaustive() {
+        for (table, reference) in [
+            (
+                TruthTable::from_fn(|x: &[bool; 4]| nand(x)),
+                TruthTable::from_fn(|x: &[bool; 4]| !x.iter().all(|b| *b)),
+            ),
+            (
+                TruthTable::from_fn(|x: &[bool; 4]| or(x)),
+                TruthTable::from_fn(|x: &[bool; 4]| x.iter().any(|b| *b)),
+            ),
+            (
+                TruthTable::from_fn(|x: &[bool; 4]| nor(x)),
+                TruthTable::from_fn(|x: &[bool; 4]| !x.iter().any(|b| *b)),
+            ),
+            (
+                TruthTable::from_fn(|x: &[bool; 4]| xor(x)),
+                // Multiple input XOR is true when the inputs are not all equal
+                TruthTable::from_fn(|x: &[bool; 4]| x.iter().any(|b| *b != x[0])),
+            ),
+        ] {
+            if let Err(mismatches) = table.verify(&reference) {
+                panic!("{}", mismatches);
+            }
+        }
+    }
 }
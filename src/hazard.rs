@@ -0,0 +1,388 @@
+use core::fmt;
+
+use crate::expr::Expr;
+use crate::minimize::{Cover, Cube};
+use crate::netlist::{CellKind, Driver, Net, Netlist};
+use crate::timing::{Delays, EventQueue};
+
+/// Largest number of inputs for which every single input transition is checked
+pub const MAX_INPUTS: usize = 12;
+
+/// Changes of every net of a combinational netlist after its inputs switch at time zero, with
+/// each NAND gate delaying its output by its delay
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transition {
+    from: Vec<bool>,
+    to: Vec<bool>,
+    initial: Vec<bool>,
+    /// Time and new value of each change of each net
+    events: Vec<Vec<(f64, bool)>>,
+}
+
+impl Transition {
+    /// Inputs before and after the transition
+    pub fn inputs(&self) -> (&[bool], &[bool]) {
+        (&self.from, &self.to)
+    }
+
+    /// Value of the net before the transition
+    pub fn initial(&self, net: Net) -> bool {
+        self.initial[net.index()]
+    }
+
+    /// Value of the net once every change has settled
+    pub fn settled(&self, net: Net) -> bool {
+        self.events[net.index()]
+            .last()
+            .map_or(self.initial(net), |(_, v)| *v)
+    }
+
+    /// Time and new value of each change of the net
+    pub fn events(&self, net: Net) -> &[(f64, bool)] {
+        &self.events[net.index()]
+    }
+}
+
+/// Simulates a change of the inputs of a combinational netlist with transport delays, so every
+/// pulse a gate produces is seen however short
+pub fn simulate_transition(
+    netlist: &Netlist,
+    delays: &Delays,
+    from: &[bool],
+    to: &[bool],
+) -> Transition {
+    assert!(
+        !netlist.is_sequential(),
+        "netlist {} is sequential and can't be simulated without a clock",
+        netlist.name()
+    );
+    assert_eq!(to.len(), from.len(), "wrong number of inputs");
+    let initial = netlist.evaluate_nets(from, &[]);
+    let cell_delays: Vec<f64> = netlist
+        .cell_ids()
+        .map(|id| match netlist.cell(id).kind() {
+            CellKind::Nand => delays.nand_delay(netlist, id),
+            _ => 0.0,
+        })
+        .collect();
+
+    let mut queue = EventQueue::new(netlist, initial.clone());
+    for ((port, old), new) in netlist.inputs().iter().zip(from).zip(to) {
+        if old != new {
+            queue.schedule(0.0, port.net(), *new);
+        }
+    }
+    while let Some(step) = queue.step() {
+        for id in step.cells {
+            let cell = netlist.cell(id);
+            let value = !cell.inputs().iter().all(|n| queue.values[n.index()]);
+            queue.schedule(step.time + cell_delays[id.index()], cell.output(), value);
+        }
+    }
+
+    Transition {
+        from: from.to_vec(),
+        to: to.to_vec(),
+        initial,
+        events: queue.events,
+    }
+}
+
+/// How an output misbehaves during a transition
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HazardKind {
+    /// The output should stay high but pulses low
+    Static1,
+    /// The output should stay low but pulses high
+    Static0,
+    /// The output should change once but changes three or more times
+    Dynamic,
+}
+
+/// An output pulse during an input transition
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hazard {
+    pub output: String,
+    pub kind: HazardKind,
+    pub from: Vec<bool>,
+    pub to: Vec<bool>,
+    /// Time and new value of each change of the output
+    pub events: Vec<(f64, bool)>,
+    /// Path of the gate where the pulse starts, where paths of different delays from the
+    /// changing inputs reconverge
+    pub reconvergence: String,
+    /// Nets along each path from a changing input to an input of the reconverging gate, with
+    /// the time each one changes last
+    pub paths: Vec<Vec<(String, f64)>>,
+    /// For a static-1 hazard, a product term of the inputs which holds the output high across
+    /// the transition. None if the output is allowed to change, which is a function hazard
+    pub consensus: Option<Expr>,
+}
+
+/// Finds the outputs which pulse when the inputs change from one vector to another
+pub fn find_hazards(netlist: &Netlist, delays: &Delays, from: &[bool], to: &[bool]) -> Vec<Hazard> {
+    let transition = simulate_transition(netlist, delays, from, to);
+    netlist
+        .outputs()
+        .iter()
+        .filter_map(|port| {
+            let events = transition.events(port.net());
+            let kind = match (events.len(), transition.initial(port.net())) {
+                (0 | 1, _) => return None,
+                (n, _) if n % 2 == 1 => HazardKind::Dynamic,
+                (_, true) => HazardKind::Static1,
+                (_, false) => HazardKind::Static0,
+            };
+            let (reconvergence, paths) = reconvergence(netlist, &transition, port.net());
+            let consensus = (kind == HazardKind::Static1)
+                .then(|| consensus(netlist, port.net(), from, to))
+                .flatten();
+            Some(Hazard {
+                output: port.name().to_string(),
+                kind,
+                from: from.to_vec(),
+                to: to.to_vec(),
+                events: events.to_vec(),
+                reconvergence,
+                paths,
+                consensus,
+            })
+        })
+        .collect()
+}
+
+/// Checks every transition in which a single input changes
+pub fn check_hazards(netlist: &Netlist, delays: &Delays) -> Vec<Hazard> {
+    let n = netlist.inputs().len();
+    assert!(
+        n <= MAX_INPUTS,
+        "netlist {} has too many inputs to check every transition",
+        netlist.name()
+    );
+    let mut hazards = Vec::new();
+    for row in 0..1usize << n {
+        let from: Vec<bool> = (0..n).map(|i| row >> i & 1 == 1).collect();
+        for i in 0..n {
+            let mut to = from.clone();
+            to[i] = !to[i];
+            hazards.extend(find_hazards(netlist, delays, &from, &to));
+        }
+    }
+    hazards
+}
+
+/// Follows the pulse back from an output to the first gate which pulses without a pulse on its
+/// inputs, returning its path and the paths leading to its changing inputs
+fn reconvergence(
+    netlist: &Netlist,
+    transition: &Transition,
+    output: Net,
+) -> (String, Vec<Vec<(String, f64)>>) {
+    let pulses = |net: &Net| transition.events(*net).len() > 1;
+    let mut net = output;
+    let id = loop {
+        match netlist.driver(net) {
+            Some(Driver::Cell(id)) => match netlist.cell(id).inputs().iter().find(|n| pulses(n)) {
+                Some(input) => net = *input,
+                None => break id,
+            },
+            _ => return (netlist.net_name(net), Vec::new()),
+        }
+    };
+
+    let last_change = |net: Net| transition.events(net).last().map(|(t, _)| *t);
+    let paths = netlist
+        .cell(id)
+        .inputs()
+        .iter()
+        .filter(|n| last_change(**n).is_some())
+        .map(|input| {
+            let mut path = Vec::new();
+            let mut net = *input;
+            while let Some(time) = last_change(net) {
+                path.push((netlist.net_name(net), time));
+                let Some(Driver::Cell(id)) = netlist.driver(net) else {
+                    break;
+                };
+                // Follow the input whose change caused this one
+                let cause = netlist
+                    .cell(id)
+                    .inputs()
+                    .iter()
+                    .filter(|n| last_change(**n).is_some_and(|t| t <= time))
+                    .max_by(|a, b| {
+                        last_change(**a)
+                            .unwrap()
+                            .total_cmp(&last_change(**b).unwrap())
+                    });
+                match cause {
+                    Some(cause) => net = *cause,
+                    None => break,
+                }
+            }
+            path.reverse();
+            path
+        })
+        .collect();
+    (netlist.cell_path(id), paths)
+}
+
+/// The prime implicant containing both input vectors, found by dropping literals from the
+/// smallest cube containing them while the output stays high
+fn consensus(netlist: &Netlist, output: Net, from: &[bool], to: &[bool]) -> Option<Expr> {
+    let n = from.len();
+    let index = netlist.outputs().iter().position(|p| p.net() == output)?;
+    let high = |cube: &Cube| {
+        cube.minterms(n).into_iter().all(|m| {
+            let inputs: Vec<bool> = (0..n).map(|i| m >> i & 1 == 1).collect();
+            netlist.evaluate(&inputs)[index]
+        })
+    };
+
+    let literals: Vec<Option<bool>> = from
+        .iter()
+        .zip(to)
+        .map(|(a, b)| (a == b).then_some(*a))
+        .collect();
+    let mut cube = Cube::from_literals(&literals);
+    if !high(&cube) {
+        return None;
+    }
+    for var in 0..n {
+        if cube.literal(var).is_some() {
+            let mut wider = literals_of(&cube, n);
+            wider[var] = None;
+            let wider = Cube::from_literals(&wider);
+            if high(&wider) {
+                cube = wider;
+            }
+        }
+    }
+
+    let names: Vec<&str> = netlist.inputs().iter().map(|p| p.name()).collect();
+    Some(Cover::new(n, vec![cube]).to_expr(&names))
+}
+
+fn literals_of(cube: &Cube, n: usize) -> Vec<Option<bool>> {
+    (0..n).map(|i| cube.literal(i)).collect()
+}
+
+impl fmt::Display for Hazard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bits =
+            |bits: &[bool]| -> String { bits.iter().map(|b| if *b { '1' } else { '0' }).collect() };
+        let kind = match self.kind {
+            HazardKind::Static1 => "static-1",
+            HazardKind::Static0 => "static-0",
+            HazardKind::Dynamic => "dynamic",
+        };
+        let events: Vec<String> = self
+            .events
+            .iter()
+            .map(|(t, v)| format!("{}@{}", *v as u8, t))
+            .collect();
+        writeln!(
+            f,
+            "{} hazard on {} for inputs {} -> {}: {}",
+            kind,
+            self.output,
+            bits(&self.from),
+            bits(&self.to),
+            events.join(" ")
+        )?;
+        writeln!(f, "  paths reconverge at {}", self.reconvergence)?;
+        for path in &self.paths {
+            let nets: Vec<String> = path
+                .iter()
+                .map(|(name, t)| format!("{}@{}", name, t))
+                .collect();
+            writeln!(f, "    {}", nets.join(" -> "))?;
+        }
+        if let Some(term) = &self.consensus {
+            writeln!(f, "  add consensus term {}", term)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mux::build_mux2;
+    use crate::netlist::Builder;
+
+    fn mux2_netlist() -> Netlist {
+        let mut b = Builder::new("mux");
+        let select = b.input("select");
+        let input = b.input_bus::<2>("input");
+        let out = build_mux2(&mut b, select, &input);
+        b.output("out", out);
+        b.finish()
+    }
+
+    #[test]
+    fn test_mux2_hazard() {
+        let netlist = mux2_netlist();
+        let delays = Delays::new(1.0);
+
+        // Deselecting input 1 drops the output before the inverted select raises it again
+        let hazards = find_hazards(&netlist, &delays, &[true, true, true], &[false, true, true]);
+        assert_eq!(hazards.len(), 1);
+        let hazard = &hazards[0];
+        assert_eq!(hazard.kind, HazardKind::Static1);
+        assert_eq!(hazard.events, [(4.0, false), (5.0, true)]);
+        assert_eq!(hazard.reconvergence, "mux20/or0/nand0");
+        assert_eq!(hazard.paths.len(), 2);
+        for path in &hazard.paths {
+            assert_eq!(path[0], ("select".to_string(), 0.0));
+        }
+        assert_eq!(
+            hazard.consensus.as_ref().unwrap().to_string(),
+            "input[0] & input[1]"
+        );
+        assert!(hazard.to_string().starts_with(
+            "static-1 hazard on out for inputs 111 -> 011: 0@4 1@5\n  \
+             paths reconverge at mux20/or0/nand0\n"
+        ));
+
+        // The other direction and transitions of the data inputs are clean
+        assert!(
+            find_hazards(&netlist, &delays, &[false, true, true], &[true, true, true]).is_empty()
+        );
+        let hazards = check_hazards(&netlist, &delays);
+        assert_eq!(hazards.len(), 1, "{:?}", hazards);
+    }
+
+    #[test]
+    fn test_consensus_removes_hazard() {
+        // The mux as a sum of products with the consensus term added
+        let cover = Cover::from_patterns(3, &["01-", "1-1", "-11"]).unwrap();
+        let netlist = cover.to_netlist(&["select", "input[0]", "input[1]"]);
+        assert!(check_hazards(&netlist, &Delays::new(1.0)).is_empty());
+    }
+
+    #[test]
+    fn test_static0_and_dynamic_hazards() {
+        // a & !a pulses high when a rises, as the inverter lags behind
+        let mut b = Builder::new("pulse");
+        let a = b.input("a");
+        let na = b.not(a);
+        let y = b.and(&[a, na]);
+        b.output("y", y);
+        let z = b.xor(&[y, a]);
+        b.output("z", z);
+        let netlist = b.finish();
+
+        let hazards = find_hazards(&netlist, &Delays::new(1.0), &[false], &[true]);
+        let kinds: Vec<(&str, HazardKind)> = hazards
+            .iter()
+            .map(|h| (h.output.as_str(), h.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [("y", HazardKind::Static0), ("z", HazardKind::Dynamic)]
+        );
+        assert_eq!(hazards[0].reconvergence, "and0/nand0");
+        assert_eq!(hazards[0].consensus, None);
+    }
+}
@@ -0,0 +1,1743 @@
+use core::fmt;
+use core::ops::Range;
+use std::collections::{HashMap, HashSet};
+
+use crate::netlist::{Builder, Net, Netlist, NetlistError, State};
+
+/// Widest pin read, so malformed chips can't exhaust memory
+const MAX_WIDTH: usize = 1 << 16;
+
+/// Error returned when nand2tetris HDL can't be read into a netlist
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HdlError {
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    /// A construct outside what [HdlLibrary] reads, such as a `BUILTIN` chip other than the
+    /// primitives
+    Unsupported {
+        line: usize,
+        construct: String,
+    },
+    UnknownChip {
+        line: usize,
+        name: String,
+    },
+    /// A pin which the part's chip, or the chip using the part, doesn't have
+    UnknownPin {
+        line: usize,
+        name: String,
+    },
+    /// A connection between pins of different widths, or against the direction of a pin
+    Connection {
+        line: usize,
+        message: String,
+    },
+    /// The chip to build isn't in the library
+    NoChip(String),
+    /// The chip doesn't form a valid netlist, e.g. because an output pin is never driven
+    Netlist(NetlistError),
+}
+
+impl fmt::Display for HdlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HdlError::Syntax { line, message } => {
+                write!(f, "syntax error on line {}: {}", line, message)
+            }
+            HdlError::Unsupported { line, construct } => {
+                write!(f, "{} on line {} is not supported", construct, line)
+            }
+            HdlError::UnknownChip { line, name } => {
+                write!(f, "chip {} on line {} is not defined", name, line)
+            }
+            HdlError::UnknownPin { line, name } => {
+                write!(f, "pin {} on line {} is not defined", name, line)
+            }
+            HdlError::Connection { line, message } => {
+                write!(f, "bad connection on line {}: {}", line, message)
+            }
+            HdlError::NoChip(name) => write!(f, "chip {} is not in the library", name),
+            HdlError::Netlist(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for HdlError {}
+
+/// Error returned when a nand2tetris test script fails to run or its output doesn't match
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TestError {
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    /// A command outside what [HdlLibrary::run_test] runs, such as `while`
+    Unsupported {
+        line: usize,
+        command: String,
+    },
+    UnknownPin {
+        line: usize,
+        name: String,
+    },
+    /// The chip given to `load` couldn't be built
+    Hdl(HdlError),
+    /// A line of output, counting the header as line 1, differs from the compare file
+    Comparison {
+        line: usize,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl fmt::Display for TestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestError::Syntax { line, message } => {
+                write!(f, "syntax error on line {}: {}", line, message)
+            }
+            TestError::Unsupported { line, command } => {
+                write!(f, "{} on line {} is not supported", command, line)
+            }
+            TestError::UnknownPin { line, name } => {
+                write!(f, "pin {} on line {} is not defined", name, line)
+            }
+            TestError::Hdl(e) => write!(f, "{}", e),
+            TestError::Comparison {
+                line,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "comparison failure at line {}: expected {:?}, got {:?}",
+                line, expected, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TestError {}
+
+/// Chips written in the nand2tetris hardware description language, built from the primitives
+/// `Nand`, a [Builder::nand] gate, and `DFF`, a flip-flop like [crate::flipflop::DFlipflop]
+#[derive(Clone, Debug, Default)]
+pub struct HdlLibrary {
+    chips: HashMap<String, Chip>,
+}
+
+impl HdlLibrary {
+    /// Creates a library holding only the primitives
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the chips defined in HDL source into the library, replacing any of the same name,
+    /// and returns their names. `BUILTIN` definitions of the primitives are skipped, and other
+    /// built-in chips are unsupported
+    pub fn add(&mut self, source: &str) -> Result<Vec<String>, HdlError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        let mut names = Vec::new();
+        while parser.peek().is_some() {
+            if let Some(chip) = parser.chip()? {
+                names.push(chip.name.clone());
+                self.chips.insert(chip.name.clone(), chip);
+            }
+        }
+        Ok(names)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.chips.contains_key(name) || primitive(name).is_some()
+    }
+
+    /// Builds a netlist of a chip, with each part flattened into a scope named after its chip
+    /// and a count, e.g. "Xor0". Pins become ports named "a" or, for buses, "a[0]", "a[1]", ...
+    /// A chip containing a `DFF` gets an input "clk" before its other inputs, which clocks every
+    /// flip-flop on its rising edge
+    pub fn netlist(&self, name: &str) -> Result<Netlist, HdlError> {
+        let chip = self
+            .chips
+            .get(name)
+            .ok_or_else(|| HdlError::NoChip(name.to_string()))?;
+        let mut builder = Builder::new(name);
+        let clock = self
+            .is_clocked(name, &mut HashSet::new())
+            .then(|| builder.input("clk"));
+        let mut elaborator = Elaborator {
+            chips: &self.chips,
+            clock,
+            stack: Vec::new(),
+        };
+        let outputs = elaborator.instantiate(&mut builder, chip, "", None)?;
+        for pin in &chip.outputs {
+            for (k, net) in outputs[&pin.name].iter().enumerate() {
+                builder.output(&pin.bit_name(k), *net);
+            }
+        }
+        builder.try_finish().map_err(HdlError::Netlist)
+    }
+
+    /// Whether a chip contains a `DFF`, directly or through its parts
+    fn is_clocked(&self, name: &str, visited: &mut HashSet<String>) -> bool {
+        if name == "DFF" {
+            return true;
+        }
+        if !visited.insert(name.to_string()) {
+            return false;
+        }
+        let Some(chip) = self.chips.get(name) else {
+            return false;
+        };
+        chip.parts
+            .iter()
+            .any(|part| self.is_clocked(&part.chip, visited))
+    }
+
+    /// Runs a nand2tetris test script against the chips in the library and returns its output,
+    /// the contents of the ".out" file. With a compare file, each line of output must match the
+    /// same line of it
+    ///
+    /// Scripts can use `load`, `output-list`, `set`, `eval`, `tick`, `tock`, `output` and
+    /// `repeat` with a count. The files named by `output-file` and `compare-to` are left to the
+    /// caller, and `echo` does nothing. `tick` samples the inputs of every `DFF` and `tock`
+    /// shows its new state, as in the nand2tetris hardware simulator. Values are written in
+    /// decimal or with the prefixes "%B", "%X" and "%D", and decimal output of 16 bit pins is
+    /// signed
+    pub fn run_test(&self, script: &str, compare: Option<&str>) -> Result<String, TestError> {
+        let tokens = tokenize_script(script)?;
+        let mut pos = 0;
+        let commands = script_commands(&tokens, &mut pos, false)?;
+        let mut simulation = Simulation {
+            library: self,
+            compare: compare.map(|text| text.lines().collect()),
+            chip: None,
+            time: 0,
+            ticked: false,
+            columns: Vec::new(),
+            output: String::new(),
+            lines: 0,
+        };
+        simulation.run(&commands)?;
+        Ok(simulation.output)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Pin {
+    name: String,
+    width: usize,
+}
+
+impl Pin {
+    /// Name of a bit of the pin as a netlist port
+    fn bit_name(&self, offset: usize) -> String {
+        if self.width == 1 {
+            self.name.clone()
+        } else {
+            format!("{}[{}]", self.name, offset)
+        }
+    }
+}
+
+/// A pin or some of its bits, e.g. "a", "a[3]" or "a[0..7]"
+#[derive(Clone, Debug)]
+struct PinRef {
+    name: String,
+    /// Lowest and highest bit
+    bits: Option<(usize, usize)>,
+}
+
+impl PinRef {
+    /// Offsets of the bits referred to in a pin of the given width
+    fn offsets(&self, width: usize, line: usize) -> Result<Range<usize>, HdlError> {
+        match self.bits {
+            None => Ok(0..width),
+            Some((low, high)) if low <= high && high < width => Ok(low..high + 1),
+            Some(_) => Err(HdlError::Connection {
+                line,
+                message: format!("{} is outside the pin's {} bits", self, width),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for PinRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.bits {
+            None => write!(f, "{}", self.name),
+            Some((low, high)) if low == high => write!(f, "{}[{}]", self.name, low),
+            Some((low, high)) => write!(f, "{}[{}..{}]", self.name, low, high),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Pin(PinRef),
+    Constant(bool),
+}
+
+/// Use of a chip inside another, with each of the part's pins connected to a value
+#[derive(Clone, Debug)]
+struct Part {
+    chip: String,
+    connections: Vec<(PinRef, Value)>,
+    line: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Chip {
+    name: String,
+    inputs: Vec<Pin>,
+    outputs: Vec<Pin>,
+    parts: Vec<Part>,
+    line: usize,
+}
+
+/// Input and output pins of a chip
+type Interface = (Vec<Pin>, Vec<Pin>);
+
+fn primitive(name: &str) -> Option<Interface> {
+    let pins = |names: &[&str]| -> Vec<Pin> {
+        names
+            .iter()
+            .map(|name| Pin {
+                name: name.to_string(),
+                width: 1,
+            })
+            .collect()
+    };
+    match name {
+        "Nand" => Some((pins(&["a", "b"]), pins(&["out"]))),
+        "DFF" => Some((pins(&["in"]), pins(&["out"]))),
+        _ => None,
+    }
+}
+
+/// Symbols in the order they're matched, longest first
+const SYMBOLS: &[&str] = &["..", "{", "}", "(", ")", "[", "]", ",", ";", ":", "="];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Number(usize),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(s) => write!(f, "{}", s),
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Symbol(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// Splits HDL into tokens and the line of each, skipping comments
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, HdlError> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            line += (c == '\n') as usize;
+            rest = &rest[c.len_utf8()..];
+            continue;
+        } else if rest.starts_with("//") {
+            rest = &rest[rest.find('\n').unwrap_or(rest.len())..];
+            continue;
+        } else if rest.starts_with("/*") {
+            let end = rest[2..].find("*/").ok_or_else(|| HdlError::Syntax {
+                line,
+                message: "unterminated comment".to_string(),
+            })? + 4;
+            line += rest[..end].matches('\n').count();
+            rest = &rest[end..];
+            continue;
+        }
+
+        let word_end = |f: fn(char) -> bool| rest.find(|c| !f(c)).unwrap_or(rest.len());
+        let (token, len) = if c.is_ascii_alphabetic() || c == '_' {
+            let end = word_end(|c| c.is_ascii_alphanumeric() || c == '_');
+            (Token::Ident(rest[..end].to_string()), end)
+        } else if c.is_ascii_digit() {
+            let end = word_end(|c| c.is_ascii_digit());
+            let number = rest[..end].parse().map_err(|_| HdlError::Syntax {
+                line,
+                message: format!("number {} is too large", &rest[..end]),
+            })?;
+            (Token::Number(number), end)
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            (Token::Symbol(symbol), symbol.len())
+        } else {
+            return Err(HdlError::Syntax {
+                line,
+                message: format!("unexpected character {:?}", c),
+            });
+        };
+        tokens.push((token, line));
+        rest = &rest[len..];
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn unexpected(&self) -> HdlError {
+        let message = match self.peek() {
+            Some(token) => format!("unexpected {}", token),
+            None => "unexpected end of file".to_string(),
+        };
+        HdlError::Syntax {
+            line: self.line(),
+            message,
+        }
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol);
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), HdlError> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// Consumes an identifier with the given text, such as the keyword "PARTS"
+    fn eat_word(&mut self, word: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Ident(w)) if w == word);
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect_word(&mut self, word: &str) -> Result<(), HdlError> {
+        if self.eat_word(word) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, HdlError> {
+        match self.peek() {
+            Some(Token::Ident(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn number(&mut self) -> Result<usize, HdlError> {
+        match self.peek() {
+            Some(Token::Number(n)) => {
+                let n = *n;
+                self.pos += 1;
+                Ok(n)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// A chip definition, or None for a built-in primitive
+    fn chip(&mut self) -> Result<Option<Chip>, HdlError> {
+        self.expect_word("CHIP")?;
+        let line = self.line();
+        let name = self.ident()?;
+        self.expect_symbol("{")?;
+        let inputs = if self.eat_word("IN") {
+            self.pins(&[])?
+        } else {
+            Vec::new()
+        };
+        let outputs = if self.eat_word("OUT") {
+            self.pins(&inputs)?
+        } else {
+            Vec::new()
+        };
+
+        if self.eat_word("BUILTIN") {
+            let builtin = self.ident()?;
+            self.expect_symbol(";")?;
+            if self.eat_word("CLOCKED") {
+                self.pins(&[])?;
+            }
+            self.expect_symbol("}")?;
+            if builtin == name && primitive(&name).is_some() {
+                return Ok(None);
+            }
+            return Err(HdlError::Unsupported {
+                line,
+                construct: format!("BUILTIN chip {}", name),
+            });
+        }
+
+        self.expect_word("PARTS")?;
+        self.expect_symbol(":")?;
+        let mut parts = Vec::new();
+        while !self.eat_symbol("}") {
+            parts.push(self.part()?);
+        }
+        Ok(Some(Chip {
+            name,
+            inputs,
+            outputs,
+            parts,
+            line,
+        }))
+    }
+
+    /// Pins declared after "IN" or "OUT", up to the semicolon
+    /// Pin declarations up to the semicolon, whose names must differ from each other and from
+    /// those already declared
+    fn pins(&mut self, declared: &[Pin]) -> Result<Vec<Pin>, HdlError> {
+        let mut pins: Vec<Pin> = Vec::new();
+        if self.eat_symbol(";") {
+            return Ok(pins);
+        }
+        loop {
+            let line = self.line();
+            let name = self.ident()?;
+            if declared.iter().chain(&pins).any(|p| p.name == name) {
+                return Err(HdlError::Syntax {
+                    line,
+                    message: format!("pin {} is declared twice", name),
+                });
+            }
+            let width = if self.eat_symbol("[") {
+                let line = self.line();
+                let width = self.number()?;
+                self.expect_symbol("]")?;
+                if width == 0 {
+                    return Err(HdlError::Syntax {
+                        line,
+                        message: format!("pin {} has no bits", name),
+                    });
+                }
+                if width > MAX_WIDTH {
+                    return Err(HdlError::Syntax {
+                        line,
+                        message: format!("pin {} is wider than {} bits", name, MAX_WIDTH),
+                    });
+                }
+                width
+            } else {
+                1
+            };
+            pins.push(Pin { name, width });
+            if self.eat_symbol(";") {
+                return Ok(pins);
+            }
+            self.expect_symbol(",")?;
+        }
+    }
+
+    fn pin_ref(&mut self) -> Result<PinRef, HdlError> {
+        let name = self.ident()?;
+        let bits = if self.eat_symbol("[") {
+            let low = self.number()?;
+            let high = if self.eat_symbol("..") {
+                self.number()?
+            } else {
+                low
+            };
+            self.expect_symbol("]")?;
+            Some((low, high))
+        } else {
+            None
+        };
+        Ok(PinRef { name, bits })
+    }
+
+    fn part(&mut self) -> Result<Part, HdlError> {
+        let line = self.line();
+        let chip = self.ident()?;
+        self.expect_symbol("(")?;
+        let mut connections = Vec::new();
+        loop {
+            let pin = self.pin_ref()?;
+            self.expect_symbol("=")?;
+            let value = if self.eat_word("true") {
+                Value::Constant(true)
+            } else if self.eat_word("false") {
+                Value::Constant(false)
+            } else {
+                Value::Pin(self.pin_ref()?)
+            };
+            connections.push((pin, value));
+            if self.eat_symbol(")") {
+                break;
+            }
+            self.expect_symbol(",")?;
+        }
+        self.expect_symbol(";")?;
+        Ok(Part {
+            chip,
+            connections,
+            line,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PinKind {
+    Input,
+    Output,
+    Internal,
+}
+
+/// Nets of a pin of a chip instance, least significant first
+#[derive(Clone, Debug)]
+struct Signal {
+    nets: Vec<Net>,
+    kind: PinKind,
+}
+
+fn signal<'s>(
+    signals: &'s HashMap<String, Signal>,
+    name: &str,
+    line: usize,
+) -> Result<&'s Signal, HdlError> {
+    signals.get(name).ok_or_else(|| HdlError::UnknownPin {
+        line,
+        name: name.to_string(),
+    })
+}
+
+fn width_mismatch(line: usize, pin: &PinRef, width: usize, value: &PinRef) -> HdlError {
+    HdlError::Connection {
+        line,
+        message: format!("{} is {} bits wide but {} is not", pin, width, value),
+    }
+}
+
+struct Elaborator<'a> {
+    chips: &'a HashMap<String, Chip>,
+    clock: Option<Net>,
+    /// Chips being instantiated, to catch a chip using itself
+    stack: Vec<String>,
+}
+
+impl<'a> Elaborator<'a> {
+    fn interface(&self, name: &str, line: usize) -> Result<Interface, HdlError> {
+        if let Some(interface) = primitive(name) {
+            return Ok(interface);
+        }
+        match self.chips.get(name) {
+            Some(chip) => Ok((chip.inputs.clone(), chip.outputs.clone())),
+            None => Err(HdlError::UnknownChip {
+                line,
+                name: name.to_string(),
+            }),
+        }
+    }
+
+    /// Nets of an output or internal pin, which are replaced by their drivers
+    fn placeholders(&mut self, builder: &mut Builder, prefix: &str, pin: &Pin) -> Vec<Net> {
+        (0..pin.width)
+            .map(|k| builder.placeholder(&format!("{}{}", prefix, pin.bit_name(k))))
+            .collect()
+    }
+
+    /// Builds an instance of a chip, with the nets connected to its inputs or with new input
+    /// ports at the top level, and returns the nets of its outputs
+    fn instantiate(
+        &mut self,
+        builder: &mut Builder,
+        chip: &'a Chip,
+        prefix: &str,
+        mut inputs: Option<HashMap<String, Vec<Net>>>,
+    ) -> Result<HashMap<String, Vec<Net>>, HdlError> {
+        if self.stack.contains(&chip.name) {
+            return Err(HdlError::Syntax {
+                line: chip.line,
+                message: format!("chip {} uses itself", chip.name),
+            });
+        }
+        self.stack.push(chip.name.clone());
+
+        let mut signals = HashMap::new();
+        for pin in &chip.inputs {
+            let nets = match &mut inputs {
+                Some(inputs) => inputs.remove(&pin.name).unwrap(),
+                None => (0..pin.width)
+                    .map(|k| builder.input(&pin.bit_name(k)))
+                    .collect(),
+            };
+            let kind = PinKind::Input;
+            signals.insert(pin.name.clone(), Signal { nets, kind });
+        }
+        for pin in &chip.outputs {
+            let nets = self.placeholders(builder, prefix, pin);
+            let kind = PinKind::Output;
+            signals.insert(pin.name.clone(), Signal { nets, kind });
+        }
+
+        // Internal pins take their width from the part outputs driving them
+        for part in &chip.parts {
+            let (_, outputs) = self.interface(&part.chip, part.line)?;
+            for (pin, value) in &part.connections {
+                let (Some(output), Value::Pin(target)) =
+                    (outputs.iter().find(|p| p.name == pin.name), value)
+                else {
+                    continue;
+                };
+                let width = pin.offsets(output.width, part.line)?.len();
+                match signals.get(&target.name) {
+                    Some(signal) if signal.kind != PinKind::Internal => {}
+                    _ if target.bits.is_some() => {
+                        return Err(HdlError::Connection {
+                            line: part.line,
+                            message: format!("internal pin {} can't be subscripted", target),
+                        });
+                    }
+                    Some(signal) if signal.nets.len() != width => {
+                        return Err(width_mismatch(part.line, pin, width, target));
+                    }
+                    Some(_) => {}
+                    None => {
+                        let internal = Pin {
+                            name: target.name.clone(),
+                            width,
+                        };
+                        let nets = self.placeholders(builder, prefix, &internal);
+                        let kind = PinKind::Internal;
+                        signals.insert(target.name.clone(), Signal { nets, kind });
+                    }
+                }
+            }
+        }
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for part in &chip.parts {
+            let count = counts.entry(&part.chip).or_insert(0);
+            let index = *count;
+            *count += 1;
+            self.part(builder, &signals, prefix, part, index)?;
+        }
+        self.stack.pop();
+        Ok(chip
+            .outputs
+            .iter()
+            .map(|pin| (pin.name.clone(), signals[&pin.name].nets.clone()))
+            .collect())
+    }
+
+    fn part(
+        &mut self,
+        builder: &mut Builder,
+        signals: &HashMap<String, Signal>,
+        prefix: &str,
+        part: &Part,
+        index: usize,
+    ) -> Result<(), HdlError> {
+        let line = part.line;
+        let (inputs, outputs) = self.interface(&part.chip, line)?;
+
+        // Nets connected to each bit of each input, which are low if left unconnected
+        let mut connected: HashMap<&str, Vec<Option<Net>>> = inputs
+            .iter()
+            .map(|p| (p.name.as_str(), vec![None; p.width]))
+            .collect();
+        for (pin, value) in &part.connections {
+            if outputs.iter().any(|p| p.name == pin.name) {
+                continue;
+            }
+            let Some(bits) = connected.get_mut(pin.name.as_str()) else {
+                return Err(HdlError::UnknownPin {
+                    line,
+                    name: pin.name.clone(),
+                });
+            };
+            let range = pin.offsets(bits.len(), line)?;
+            let nets = match value {
+                Value::Constant(value) => vec![builder.constant(*value); range.len()],
+                Value::Pin(source) => {
+                    let signal = signal(signals, &source.name, line)?;
+                    if signal.kind == PinKind::Output {
+                        return Err(HdlError::Connection {
+                            line,
+                            message: format!("output pin {} can't be read", source.name),
+                        });
+                    }
+                    let nets = &signal.nets[source.offsets(signal.nets.len(), line)?];
+                    if nets.len() != range.len() {
+                        return Err(width_mismatch(line, pin, range.len(), source));
+                    }
+                    nets.to_vec()
+                }
+            };
+            for (bit, net) in bits[range].iter_mut().zip(nets) {
+                *bit = Some(net);
+            }
+        }
+        let mut values: HashMap<String, Vec<Net>> = HashMap::new();
+        for pin in &inputs {
+            let nets = connected[pin.name.as_str()]
+                .iter()
+                .map(|net| net.unwrap_or_else(|| builder.constant(false)))
+                .collect();
+            values.insert(pin.name.clone(), nets);
+        }
+
+        let results = match part.chip.as_str() {
+            "Nand" => {
+                let out = builder.nand(&[values["a"][0], values["b"][0]]);
+                HashMap::from([("out".to_string(), vec![out])])
+            }
+            "DFF" => {
+                let clock = self.clock.expect("clocked chips have a clock input");
+                let out = builder.dff(clock, values["in"][0]);
+                HashMap::from([("out".to_string(), vec![out])])
+            }
+            name => {
+                let chip = &self.chips[name];
+                let instance = format!("{}{}", name, index);
+                let child_prefix = format!("{}{}/", prefix, instance);
+                builder.scoped(&instance, |builder| {
+                    self.instantiate(builder, chip, &child_prefix, Some(values))
+                })?
+            }
+        };
+
+        for (pin, value) in &part.connections {
+            let Some(nets) = results.get(&pin.name) else {
+                continue;
+            };
+            let nets = &nets[pin.offsets(nets.len(), line)?];
+            let Value::Pin(target) = value else {
+                return Err(HdlError::Connection {
+                    line,
+                    message: format!("output {} can't drive a constant", pin),
+                });
+            };
+            let signal = signal(signals, &target.name, line)?;
+            if signal.kind == PinKind::Input {
+                return Err(HdlError::Connection {
+                    line,
+                    message: format!("input pin {} can't be driven", target.name),
+                });
+            }
+            let targets = &signal.nets[target.offsets(signal.nets.len(), line)?];
+            if targets.len() != nets.len() {
+                return Err(width_mismatch(line, pin, nets.len(), target));
+            }
+            for (target, value) in targets.iter().zip(nets) {
+                builder
+                    .drive_placeholder(*target, *value)
+                    .map_err(HdlError::Netlist)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits a test script into words, quoted strings and the symbols ",;!{}", with the line of
+/// each, skipping comments
+fn tokenize_script(script: &str) -> Result<Vec<(String, usize)>, TestError> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut rest = script;
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_whitespace() {
+            line += (c == '\n') as usize;
+            rest = &rest[c.len_utf8()..];
+            continue;
+        } else if rest.starts_with("//") {
+            rest = &rest[rest.find('\n').unwrap_or(rest.len())..];
+            continue;
+        } else if rest.starts_with("/*") || c == '"' {
+            let (open, close) = if c == '"' { (1, "\"") } else { (2, "*/") };
+            let end = rest[open..].find(close).ok_or_else(|| TestError::Syntax {
+                line,
+                message: "unterminated comment or string".to_string(),
+            })? + open
+                + close.len();
+            line += rest[..end].matches('\n').count();
+            if c != '"' {
+                rest = &rest[end..];
+                continue;
+            }
+            end
+        } else if ",;!{}".contains(c) {
+            1
+        } else {
+            rest.find(|c: char| c.is_whitespace() || ",;!{}\"".contains(c))
+                .unwrap_or(rest.len())
+        };
+        tokens.push((rest[..len].to_string(), line));
+        rest = &rest[len..];
+    }
+    Ok(tokens)
+}
+
+#[derive(Clone, Debug)]
+enum Command {
+    Load(String),
+    OutputList(Vec<String>),
+    Set(String, String),
+    Eval,
+    Tick,
+    Tock,
+    Output,
+    Repeat(usize, Vec<(Command, usize)>),
+    /// A command with no effect here, such as `echo` or `output-file`
+    Ignored,
+}
+
+/// Commands of a script, or of the body of a loop up to its closing brace, with their lines
+fn script_commands(
+    tokens: &[(String, usize)],
+    pos: &mut usize,
+    nested: bool,
+) -> Result<Vec<(Command, usize)>, TestError> {
+    let mut commands = Vec::new();
+    loop {
+        let Some((token, line)) = tokens.get(*pos) else {
+            if nested {
+                let line = tokens.last().map_or(1, |(_, line)| *line);
+                return Err(TestError::Syntax {
+                    line,
+                    message: "missing }".to_string(),
+                });
+            }
+            return Ok(commands);
+        };
+        let line = *line;
+        *pos += 1;
+        match token.as_str() {
+            "," | ";" | "!" => continue,
+            "}" if nested => return Ok(commands),
+            "{" | "}" => {
+                return Err(TestError::Syntax {
+                    line,
+                    message: format!("unexpected {}", token),
+                })
+            }
+            _ => {}
+        }
+
+        let mut args = Vec::new();
+        while let Some((arg, _)) = tokens.get(*pos).filter(|(t, _)| !",;!{}".contains(t)) {
+            args.push(arg.as_str());
+            *pos += 1;
+        }
+        let next = tokens.get(*pos).map(|(t, _)| t.as_str());
+        let syntax = |message: &str| TestError::Syntax {
+            line,
+            message: message.to_string(),
+        };
+
+        if token == "repeat" {
+            let [count] = args[..] else {
+                return Err(TestError::Unsupported {
+                    line,
+                    command: "repeat without a count".to_string(),
+                });
+            };
+            let count = count
+                .parse()
+                .map_err(|_| syntax("malformed repeat count"))?;
+            if next != Some("{") {
+                return Err(syntax("missing {"));
+            }
+            *pos += 1;
+            let body = script_commands(tokens, pos, true)?;
+            commands.push((Command::Repeat(count, body), line));
+            continue;
+        }
+        let command = match (token.as_str(), &args[..]) {
+            ("load", [file]) => Command::Load(file.to_string()),
+            ("output-list", columns) => {
+                Command::OutputList(columns.iter().map(|c| c.to_string()).collect())
+            }
+            ("set", [pin, value]) => Command::Set(pin.to_string(), value.to_string()),
+            ("eval", []) => Command::Eval,
+            ("tick", []) => Command::Tick,
+            ("tock", []) => Command::Tock,
+            ("output", []) => Command::Output,
+            ("output-file" | "compare-to", [_]) => Command::Ignored,
+            ("echo", [_]) | ("clear-echo", []) => Command::Ignored,
+            (
+                "load" | "set" | "eval" | "tick" | "tock" | "output" | "output-file" | "compare-to"
+                | "echo" | "clear-echo",
+                _,
+            ) => {
+                return Err(syntax(&format!("wrong arguments for {}", token)));
+            }
+            _ => {
+                return Err(TestError::Unsupported {
+                    line,
+                    command: token.clone(),
+                })
+            }
+        };
+        if !matches!(next, Some(",") | Some(";") | Some("!")) {
+            return Err(syntax(&format!("missing ; after {}", token)));
+        }
+        commands.push((command, line));
+    }
+}
+
+/// Parses a value given to `set`, e.g. "-1", "%B0101", "%XFF" or "%D12"
+fn parse_value(text: &str) -> Option<i64> {
+    match text.get(..2) {
+        Some("%B") => i64::from_str_radix(&text[2..], 2).ok(),
+        Some("%X") => i64::from_str_radix(&text[2..], 16).ok(),
+        Some("%D") => text[2..].parse().ok(),
+        _ => text.parse().ok(),
+    }
+}
+
+/// Column of a test's output, e.g. "out%B3.16.3" for a pin written in binary, 16 digits wide
+/// with 3 spaces either side
+#[derive(Clone, Debug)]
+struct Column {
+    name: String,
+    format: char,
+    left: usize,
+    width: usize,
+    right: usize,
+}
+
+impl Column {
+    /// Parses a column of an output list. Without a format, pins are written in binary and the
+    /// time as text, each with a space either side
+    fn parse(spec: &str, pin_width: usize) -> Option<Self> {
+        let Some((name, format)) = spec.split_once('%') else {
+            let (format, width) = if spec == "time" {
+                ('S', 4)
+            } else {
+                ('B', pin_width)
+            };
+            return Some(Column {
+                name: spec.to_string(),
+                format,
+                left: 1,
+                width,
+                right: 1,
+            });
+        };
+        let mut chars = format.chars();
+        let format = chars.next().filter(|c| "BXDS".contains(*c))?;
+        let numbers: Vec<usize> = chars
+            .as_str()
+            .split('.')
+            .map(|n| n.parse().ok())
+            .collect::<Option<_>>()?;
+        let [left, width, right] = numbers[..] else {
+            return None;
+        };
+        Some(Column {
+            name: name.to_string(),
+            format,
+            left,
+            width,
+            right,
+        })
+    }
+
+    /// Name centred in the column, cut to fit
+    fn header(&self) -> String {
+        let total = self.left + self.width + self.right;
+        let name: String = self.name.chars().take(total).collect();
+        let padding = total - name.chars().count();
+        format!(
+            "{}{}{}",
+            " ".repeat(padding / 2),
+            name,
+            " ".repeat(padding - padding / 2)
+        )
+    }
+
+    fn cell(&self, text: &str) -> String {
+        format!(
+            "{}{}{}",
+            " ".repeat(self.left),
+            text,
+            " ".repeat(self.right)
+        )
+    }
+
+    /// Value of a pin, given least significant bit first, in the column's format
+    fn value(&self, bits: &[bool]) -> String {
+        let value: u64 = bits
+            .iter()
+            .take(64)
+            .enumerate()
+            .map(|(i, bit)| (*bit as u64) << i)
+            .sum();
+        let width = self.width;
+        let text = match self.format {
+            'B' => format!("{:0width$b}", value),
+            'X' => format!("{:0width$X}", value),
+            _ if bits.len() == 16 => format!("{:>width$}", value as u16 as i16),
+            _ => format!("{:>width$}", value),
+        };
+        text[text.len() - width..].to_string()
+    }
+}
+
+/// Chip loaded by a test script, with the values of its pins
+struct Loaded {
+    netlist: Netlist,
+    /// Whether each pin is an input, and the ports of its bits, least significant first
+    pins: HashMap<String, (bool, Vec<usize>)>,
+    inputs: Vec<bool>,
+    values: Vec<bool>,
+    state: State,
+    /// Inputs when the clock last ticked, which flip-flops load when it tocks
+    sampled: Option<Vec<bool>>,
+}
+
+impl Loaded {
+    fn read(&self, pin: &str) -> Vec<bool> {
+        let (input, ports) = &self.pins[pin];
+        ports
+            .iter()
+            .map(|i| match input {
+                true => self.inputs[*i],
+                false => self.values[self.netlist.outputs()[*i].net().index()],
+            })
+            .collect()
+    }
+
+    /// Settles the chip with its current inputs and the clock low. A clocked chip's clock is
+    /// its first input
+    fn eval(&mut self) {
+        if !self.netlist.flipflops().is_empty() {
+            self.inputs[0] = false;
+        }
+        self.values = self.netlist.step(&mut self.state, &self.inputs);
+    }
+}
+
+struct Simulation<'a> {
+    library: &'a HdlLibrary,
+    compare: Option<Vec<&'a str>>,
+    chip: Option<Loaded>,
+    time: usize,
+    /// Whether the clock has ticked but not yet tocked, shown as a "+" after the time
+    ticked: bool,
+    columns: Vec<Column>,
+    output: String,
+    lines: usize,
+}
+
+impl Simulation<'_> {
+    fn loaded(&mut self, line: usize) -> Result<&mut Loaded, TestError> {
+        self.chip.as_mut().ok_or_else(|| TestError::Syntax {
+            line,
+            message: "no chip is loaded".to_string(),
+        })
+    }
+
+    fn run(&mut self, commands: &[(Command, usize)]) -> Result<(), TestError> {
+        for (command, line) in commands {
+            let line = *line;
+            match command {
+                Command::Load(file) => self.load(file)?,
+                Command::OutputList(specs) => {
+                    let chip = self.loaded(line)?;
+                    let mut columns = Vec::new();
+                    for spec in specs {
+                        let name = spec.split('%').next().unwrap();
+                        let width = match chip.pins.get(name) {
+                            Some((_, ports)) => ports.len(),
+                            None if name == "time" => 4,
+                            None => {
+                                return Err(TestError::UnknownPin {
+                                    line,
+                                    name: name.to_string(),
+                                })
+                            }
+                        };
+                        let column = Column::parse(spec, width)
+                            .filter(|c| (c.format == 'S') == (name == "time"))
+                            .ok_or_else(|| TestError::Syntax {
+                                line,
+                                message: format!("malformed output format {}", spec),
+                            })?;
+                        columns.push(column);
+                    }
+                    self.columns = columns;
+                    let headers: Vec<String> = self.columns.iter().map(|c| c.header()).collect();
+                    self.write(format!("|{}|", headers.join("|")))?;
+                }
+                Command::Set(pin, value) => {
+                    let chip = self.loaded(line)?;
+                    let Some((true, ports)) = chip.pins.get(pin) else {
+                        return Err(TestError::UnknownPin {
+                            line,
+                            name: pin.clone(),
+                        });
+                    };
+                    let value = parse_value(value).ok_or_else(|| TestError::Syntax {
+                        line,
+                        message: format!("malformed value {}", value),
+                    })?;
+                    for (k, port) in ports.iter().enumerate() {
+                        chip.inputs[*port] = k < 64 && value >> k & 1 == 1;
+                    }
+                }
+                Command::Eval => self.loaded(line)?.eval(),
+                Command::Tick => {
+                    let chip = self.loaded(line)?;
+                    chip.sampled = Some(chip.inputs.clone());
+                    chip.eval();
+                    self.ticked = true;
+                }
+                Command::Tock => {
+                    let chip = self.loaded(line)?;
+                    let sampled = chip.sampled.take();
+                    if !chip.netlist.flipflops().is_empty() {
+                        let mut inputs = sampled.unwrap_or_else(|| chip.inputs.clone());
+                        inputs[0] = true;
+                        chip.netlist.step(&mut chip.state, &inputs);
+                    }
+                    chip.eval();
+                    self.time += 1;
+                    self.ticked = false;
+                }
+                Command::Output => {
+                    self.loaded(line)?;
+                    let chip = self.chip.as_ref().unwrap();
+                    let time = format!("{}{}", self.time, if self.ticked { "+" } else { "" });
+                    let cells: Vec<String> = self
+                        .columns
+                        .iter()
+                        .map(|column| {
+                            let text = match column.format {
+                                'S' => {
+                                    let time: String = time.chars().take(column.width).collect();
+                                    format!("{:<width$}", time, width = column.width)
+                                }
+                                _ => column.value(&chip.read(&column.name)),
+                            };
+                            column.cell(&text)
+                        })
+                        .collect();
+                    self.write(format!("|{}|", cells.join("|")))?;
+                }
+                Command::Repeat(count, body) => {
+                    for _ in 0..*count {
+                        self.run(body)?;
+                    }
+                }
+                Command::Ignored => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, file: &str) -> Result<(), TestError> {
+        let name = file.strip_suffix(".hdl").unwrap_or(file);
+        let netlist = self.library.netlist(name).map_err(TestError::Hdl)?;
+        let chip = &self.library.chips[name];
+        let mut pins = HashMap::new();
+        for (input, pin) in chip
+            .inputs
+            .iter()
+            .map(|p| (true, p))
+            .chain(chip.outputs.iter().map(|p| (false, p)))
+        {
+            let ports = if input {
+                netlist.inputs()
+            } else {
+                netlist.outputs()
+            };
+            let indices = (0..pin.width)
+                .map(|k| {
+                    let name = pin.bit_name(k);
+                    ports.iter().position(|p| p.name() == name).unwrap()
+                })
+                .collect();
+            pins.insert(pin.name.clone(), (input, indices));
+        }
+
+        let mut loaded = Loaded {
+            inputs: vec![false; netlist.inputs().len()],
+            values: Vec::new(),
+            state: netlist.reset_state(),
+            sampled: None,
+            netlist,
+            pins,
+        };
+        loaded.eval();
+        self.chip = Some(loaded);
+        self.time = 0;
+        self.ticked = false;
+        Ok(())
+    }
+
+    /// Writes a line of output, checking it against the compare file
+    fn write(&mut self, line: String) -> Result<(), TestError> {
+        self.lines += 1;
+        if let Some(compare) = &self.compare {
+            let expected = compare.get(self.lines - 1).map_or("", |l| l.trim_end());
+            if expected != line {
+                return Err(TestError::Comparison {
+                    line: self.lines,
+                    expected: expected.to_string(),
+                    actual: line,
+                });
+            }
+        }
+        self.output.push_str(&line);
+        self.output.push('\n');
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::truth_table::TruthTable;
+
+    const GATES: &str = "
+// Gates built from Nand
+CHIP Not {
+    IN in;
+    OUT out;
+
+    PARTS:
+    Nand(a=in, b=in, out=out);
+}
+
+CHIP And {
+    IN a, b;
+    OUT out;
+
+    PARTS:
+    Nand(a=a, b=b, out=n);
+    Not(in=n, out=out);
+}
+
+/** Or, by De Morgan's law */
+CHIP Or {
+    IN a, b;
+    OUT out;
+
+    PARTS:
+    Not(in=a, out=na);
+    Not(in=b, out=nb);
+    Nand(a=na, b=nb, out=out);
+}
+
+CHIP Xor {
+    IN a, b;
+    OUT out;
+
+    PARTS:
+    And(a=a, b=nb, out=x);
+    And(a=na, b=b, out=y);
+    Not(in=a, out=na);
+    Not(in=b, out=nb);
+    Or(a=x, b=y, out=out);
+}
+
+CHIP Mux {
+    IN a, b, sel;
+    OUT out;
+
+    PARTS:
+    Not(in=sel, out=nsel);
+    And(a=a, b=nsel, out=x);
+    And(a=b, b=sel, out=y);
+    Or(a=x, b=y, out=out);
+}
+
+CHIP Not2 {
+    IN in[2];
+    OUT out[2];
+
+    PARTS:
+    Not(in=in[0], out=out[0]);
+    Not(in=in[1], out=out[1]);
+}
+
+CHIP Bus {
+    IN in[4], en;
+    OUT out[4], all, odd;
+
+    PARTS:
+    Not2(in=in[0..1], out=out[2..3]);
+    Not2(in=w, out=out[0..1]);
+    Not2(in=in[2..3], out=w);
+    Xor(a=in[0], b=in[3], out=odd);
+    And(a=en, b=true, out=all);
+}
+
+CHIP Bit {
+    IN in, load;
+    OUT out;
+
+    PARTS:
+    Mux(a=dffout, b=in, sel=load, out=muxout);
+    DFF(in=muxout, out=dffout, out=out);
+}
+
+CHIP Nand {
+    IN a, b;
+    OUT out;
+
+    BUILTIN Nand;
+}
+";
+
+    fn library() -> HdlLibrary {
+        let mut library = HdlLibrary::new();
+        library.add(GATES).unwrap();
+        library
+    }
+
+    #[test]
+    fn test_add() {
+        let mut library = HdlLibrary::new();
+        assert_eq!(
+            library.add(GATES).unwrap(),
+            ["Not", "And", "Or", "Xor", "Mux", "Not2", "Bus", "Bit"]
+        );
+        assert!(library.contains("Xor"));
+        assert!(library.contains("DFF"));
+        assert!(!library.contains("Register"));
+    }
+
+    #[test]
+    fn test_netlist() {
+        let library = library();
+        let cases: [(&str, usize, TruthTable); 4] = [
+            ("Not", 1, TruthTable::from_fn(|[a]: &[bool; 1]| !a)),
+            ("Xor", 9, TruthTable::from_fn(|[a, b]: &[bool; 2]| a ^ b)),
+            (
+                "Mux",
+                8,
+                TruthTable::from_fn(|[a, b, sel]: &[bool; 3]| if *sel { *b } else { *a }),
+            ),
+            (
+                "Bus",
+                17,
+                TruthTable::from_fn(|i: &[bool; 5]| {
+                    ([i[2], i[3], !i[0], !i[1]], [i[4], i[0] ^ i[3]])
+                }),
+            ),
+        ];
+        for (name, nands, table) in cases {
+            let netlist = library.netlist(name).unwrap();
+            assert_eq!(netlist.nand_count(), nands, "failed for inputs: {:?}", name);
+            assert!(
+                TruthTable::from_netlist(&netlist).verify(&table).is_ok(),
+                "failed for inputs: {:?}",
+                name
+            );
+        }
+
+        let netlist = library.netlist("Bus").unwrap();
+        let inputs: Vec<&str> = netlist.inputs().iter().map(|p| p.name()).collect();
+        assert_eq!(inputs, ["in[0]", "in[1]", "in[2]", "in[3]", "en"]);
+        assert!(netlist.find_cell("Xor0/Or0/Not1/nand0").is_some());
+
+        let netlist = library.netlist("Bit").unwrap();
+        let inputs: Vec<&str> = netlist.inputs().iter().map(|p| p.name()).collect();
+        assert_eq!(inputs, ["clk", "in", "load"]);
+        assert_eq!(netlist.flipflops().len(), 1);
+    }
+
+    #[test]
+    fn test_netlist_errors() {
+        let cases = [
+            (
+                "CHIP A {\n    IN a;\n    OUT out;\n    PARTS:\n    Not(in=a, out=out)\n}",
+                HdlError::Syntax {
+                    line: 6,
+                    message: "unexpected }".to_string(),
+                },
+            ),
+            (
+                "CHIP A {\n    IN a;\n    OUT out;\n    BUILTIN And;\n}",
+                HdlError::Unsupported {
+                    line: 1,
+                    construct: "BUILTIN chip A".to_string(),
+                },
+            ),
+            (
+                "CHIP A {\n    IN a;\n    OUT out;\n    PARTS:\n    Inc(in=a, out=out);\n}",
+                HdlError::UnknownChip {
+                    line: 5,
+                    name: "Inc".to_string(),
+                },
+            ),
+            (
+                "CHIP A {\n    IN a;\n    OUT out;\n    PARTS:\n    Not(x=a, out=out);\n}",
+                HdlError::UnknownPin {
+                    line: 5,
+                    name: "x".to_string(),
+                },
+            ),
+            (
+                "CHIP A {\n    IN a;\n    OUT out;\n    PARTS:\n    Not(in=b, out=out);\n}",
+                HdlError::UnknownPin {
+                    line: 5,
+                    name: "b".to_string(),
+                },
+            ),
+            (
+                "CHIP A {\n    IN a[2];\n    OUT out;\n    PARTS:\n    Not(in=a, out=out);\n}",
+                HdlError::Connection {
+                    line: 5,
+                    message: "in is 1 bits wide but a is not".to_string(),
+                },
+            ),
+            (
+                "CHIP A {\n    IN a;\n    OUT out;\n    PARTS:\n    Not(in=a, out=x[0]);\n}",
+                HdlError::Connection {
+                    line: 5,
+                    message: "internal pin x[0] can't be subscripted".to_string(),
+                },
+            ),
+            (
+                "CHIP A {\n    IN a;\n    OUT out[2];\n    PARTS:\n    Not(in=a, out=out[2]);\n}",
+                HdlError::Connection {
+                    line: 5,
+                    message: "out[2] is outside the pin's 2 bits".to_string(),
+                },
+            ),
+            (
+                "CHIP A {\n    IN a;\n    OUT out;\n    PARTS:\n    Not(in=a, out=a);\n}",
+                HdlError::Connection {
+                    line: 5,
+                    message: "input pin a can't be driven".to_string(),
+                },
+            ),
+            (
+                "CHIP A {\n    IN a;\n    OUT out;\n    PARTS:\n    A(a=a, out=out);\n}",
+                HdlError::Syntax {
+                    line: 1,
+                    message: "chip A uses itself".to_string(),
+                },
+            ),
+            (
+                "CHIP A {\n    IN a;\n    OUT out;\n    PARTS:\n    Not(in=a, out=out);\n    \
+                 Not(in=a, out=out);\n}",
+                HdlError::Netlist(NetlistError::MultipleDrivers("out".to_string())),
+            ),
+            (
+                "CHIP A {\n    IN a;\n    OUT out, y;\n    PARTS:\n    Not(in=a, out=out);\n}",
+                HdlError::Netlist(NetlistError::UndrivenNet("y".to_string())),
+            ),
+            (
+                "CHIP A {\n    IN a, a;\n    OUT out;\n    PARTS:\n    Not(in=a, out=out);\n}",
+                HdlError::Syntax {
+                    line: 2,
+                    message: "pin a is declared twice".to_string(),
+                },
+            ),
+            (
+                "CHIP A {\n    IN a;\n    OUT out,\n    out;\n    PARTS:\n    Not(in=a, out=out);\n}",
+                HdlError::Syntax {
+                    line: 4,
+                    message: "pin out is declared twice".to_string(),
+                },
+            ),
+            (
+                "CHIP A {\n    IN a;\n    OUT a;\n    PARTS:\n    Not(in=a, out=a);\n}",
+                HdlError::Syntax {
+                    line: 3,
+                    message: "pin a is declared twice".to_string(),
+                },
+            ),
+            (
+                "CHIP A {\n    IN a[4000000000];\n    OUT out;\n    PARTS:\n    Not(in=a[0], out=out);\n}",
+                HdlError::Syntax {
+                    line: 2,
+                    message: "pin a is wider than 65536 bits".to_string(),
+                },
+            ),
+        ];
+        for (source, expected) in cases {
+            let mut library = library();
+            let result = library.add(source).and_then(|_| library.netlist("A"));
+            assert_eq!(
+                result.unwrap_err(),
+                expected,
+                "failed for inputs: {:?}",
+                source
+            );
+        }
+        assert_eq!(
+            library().netlist("Register").unwrap_err(),
+            HdlError::NoChip("Register".to_string())
+        );
+    }
+
+    #[test]
+    fn test_run_test() {
+        let library = library();
+        let script = "// Xor.tst
+load Xor.hdl,
+output-file Xor.out,
+compare-to Xor.cmp,
+output-list a%B3.1.3 b%B3.1.3 out%B3.1.3;
+
+set a 0,
+set b 0,
+eval,
+output;
+
+set a 0, set b 1, eval, output;
+set a 1, set b 0, eval, output;
+set a 1, set b 1, eval, output;
+";
+        let compare = "|   a   |   b   |  out  |\r
+|   0   |   0   |   0   |\r
+|   0   |   1   |   1   |\r
+|   1   |   0   |   1   |\r
+|   1   |   1   |   0   |\r
+";
+        assert_eq!(
+            library.run_test(script, Some(compare)).unwrap(),
+            compare.replace('\r', "")
+        );
+
+        let script = "load Bit.hdl,
+output-list time%S1.4.1 in%B2.1.2 load%B2.1.2 out%B2.1.2;
+
+set in 0, set load 0, tick, output; tock, output;
+set in 0, set load 1, tick, output; tock, output;
+set in 1, set load 0, tick, output; tock, output;
+set in 1, set load 1, tick, output;
+set in 0, tock, output;
+repeat 2 {
+    tick, tock;
+}
+output;
+";
+        assert_eq!(
+            library.run_test(script, None).unwrap(),
+            "| time | in  |load | out |
+| 0+   |  0  |  0  |  0  |
+| 1    |  0  |  0  |  0  |
+| 1+   |  0  |  1  |  0  |
+| 2    |  0  |  1  |  0  |
+| 2+   |  1  |  0  |  0  |
+| 3    |  1  |  0  |  0  |
+| 3+   |  1  |  1  |  0  |
+| 4    |  0  |  1  |  1  |
+| 6    |  0  |  1  |  0  |
+"
+        );
+
+        let script = "load Bus,
+output-list in%D1.3.1 in%X1.1.1 in in%B0.8.0 en out%D1.3.1 odd;
+set in -1, set en 1, eval, output;
+set in %B0110, output;
+eval, output;
+set in %X9, set en %D0, eval, output;
+";
+        assert_eq!(
+            library.run_test(script, None).unwrap(),
+            "| in  |in |  in  |   in   |en | out |odd|
+|  15 | F | 1111 |00001111| 1 |   3 | 0 |
+|   6 | 6 | 0110 |00000110| 1 |   3 | 0 |
+|   6 | 6 | 0110 |00000110| 1 |   5 | 0 |
+|   9 | 9 | 1001 |00001001| 0 |  10 | 0 |
+"
+        );
+    }
+
+    #[test]
+    fn test_run_test_errors() {
+        let cases = [
+            (
+                "load Xor.hdl,\noutput-list a b out;\nset a 1, eval, output;\n",
+                Some("| a | b |out|\n| 1 | 0 | 0 |\n"),
+                TestError::Comparison {
+                    line: 2,
+                    expected: "| 1 | 0 | 0 |".to_string(),
+                    actual: "| 1 | 0 | 1 |".to_string(),
+                },
+            ),
+            (
+                "load Xor.hdl,\noutput-list a b c;\n",
+                None,
+                TestError::UnknownPin {
+                    line: 2,
+                    name: "c".to_string(),
+                },
+            ),
+            (
+                "load Xor.hdl,\nset out 1;\n",
+                None,
+                TestError::UnknownPin {
+                    line: 2,
+                    name: "out".to_string(),
+                },
+            ),
+            (
+                "load Xor.hdl,\nwhile a = 0 {\n    eval;\n}\n",
+                None,
+                TestError::Unsupported {
+                    line: 2,
+                    command: "while".to_string(),
+                },
+            ),
+            (
+                "set a 1;\n",
+                None,
+                TestError::Syntax {
+                    line: 1,
+                    message: "no chip is loaded".to_string(),
+                },
+            ),
+            (
+                "load Xor.hdl,\nset a 1\neval;\n",
+                None,
+                TestError::Syntax {
+                    line: 2,
+                    message: "wrong arguments for set".to_string(),
+                },
+            ),
+            (
+                "load Inc16.hdl;\n",
+                None,
+                TestError::Hdl(HdlError::NoChip("Inc16".to_string())),
+            ),
+        ];
+        let library = library();
+        for (script, compare, expected) in cases {
+            assert_eq!(
+                library.run_test(script, compare).unwrap_err(),
+                expected,
+                "failed for inputs: {:?}",
+                script
+            );
+        }
+    }
+}
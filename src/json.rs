@@ -0,0 +1,1088 @@
+use core::fmt;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::netlist::{Builder, CellKind, Driver, Net, Netlist, NetlistError, Port, ScopeId};
+use crate::timing::Delays;
+
+/// Value of the "format" field of every netlist document
+pub const FORMAT: &str = "nandverse-netlist";
+
+/// Version of the format written by [to_json]. Documents with other versions are rejected
+pub const VERSION: u64 = 1;
+
+/// Deepest nesting of arrays and objects the parser accepts
+const MAX_DEPTH: usize = 64;
+
+/// Error returned when a JSON document can't be read into a netlist
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JsonError {
+    /// The file couldn't be read or written
+    Io(String),
+    /// The text isn't valid JSON
+    Syntax {
+        line: usize,
+        column: usize,
+    },
+    UnsupportedVersion(u64),
+    /// An element of the document is missing, has the wrong type or refers to something which
+    /// doesn't exist. The path locates the element, e.g. "cells[3].inputs[1]"
+    Invalid {
+        path: String,
+        message: String,
+    },
+    /// The document doesn't form a valid netlist, e.g. because of a combinational loop
+    Netlist(NetlistError),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Io(e) => write!(f, "{}", e),
+            JsonError::Syntax { line, column } => {
+                write!(f, "syntax error on line {} column {}", line, column)
+            }
+            JsonError::UnsupportedVersion(version) => {
+                write!(f, "version {} is not supported", version)
+            }
+            JsonError::Invalid { path, message } if path.is_empty() => write!(f, "{}", message),
+            JsonError::Invalid { path, message } => write!(f, "{}: {}", path, message),
+            JsonError::Netlist(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+/// Writes a netlist as a JSON document, with the delays as attributes if given
+///
+/// The document lists the ports, nets, scopes and cells, which refer to nets and scopes by their
+/// index. The first scope is the top level, named after the netlist, and every other scope comes
+/// after its parent. Cells keep their instance names, so paths are the same after reading it back
+pub fn to_json(netlist: &Netlist, delays: Option<&Delays>) -> String {
+    let ports = |ports: &[Port]| -> Vec<String> {
+        ports
+            .iter()
+            .map(|p| {
+                format!(
+                    "{{\"name\": {}, \"net\": {}}}",
+                    string(p.name()),
+                    p.net().index()
+                )
+            })
+            .collect()
+    };
+    let nets = netlist
+        .nets()
+        .map(|net| match netlist.explicit_net_name(net) {
+            Some(name) => format!("{{\"name\": {}}}", string(name)),
+            None => "{}".to_string(),
+        })
+        .collect();
+    let scopes = netlist
+        .scopes()
+        .iter()
+        .map(|s| {
+            let parent = s
+                .parent()
+                .map_or("null".to_string(), |p| p.index().to_string());
+            format!("{{\"name\": {}, \"parent\": {}}}", string(s.name()), parent)
+        })
+        .collect();
+    let cells = netlist
+        .cell_ids()
+        .map(|id| {
+            let cell = netlist.cell(id);
+            let inputs: Vec<String> = cell
+                .inputs()
+                .iter()
+                .map(|n| n.index().to_string())
+                .collect();
+            let mut json = format!(
+                "{{\"kind\": {}, \"name\": {}, \"scope\": {}, \"inputs\": [{}], \"output\": {}",
+                string(cell.kind().name()),
+                string(cell.name()),
+                cell.scope().index(),
+                inputs.join(", "),
+                cell.output().index()
+            );
+            let path = netlist.cell_path(id);
+            let delay = delays.and_then(|d| d.cells.iter().find(|(p, _)| *p == path));
+            if let Some((_, delay)) = delay {
+                json.push_str(&format!(
+                    ", \"attributes\": {{\"delay\": {}}}",
+                    number(*delay)
+                ));
+            }
+            json.push('}');
+            json
+        })
+        .collect();
+
+    let mut fields = vec![
+        format!("\"format\": {}", string(FORMAT)),
+        format!("\"version\": {}", VERSION),
+        format!("\"name\": {}", string(netlist.name())),
+        format!("\"inputs\": {}", array(ports(netlist.inputs()))),
+        format!("\"outputs\": {}", array(ports(netlist.outputs()))),
+        format!("\"nets\": {}", array(nets)),
+        format!("\"scopes\": {}", array(scopes)),
+        format!("\"cells\": {}", array(cells)),
+    ];
+    if let Some(delays) = delays {
+        // The first delay given for a gate is the one used
+        let mut gates: Vec<(&str, f64)> = Vec::new();
+        for (base, delay) in &delays.gates {
+            if gates.iter().all(|(b, _)| b != base) {
+                gates.push((base, *delay));
+            }
+        }
+        let gates: Vec<String> = gates
+            .iter()
+            .map(|(base, delay)| format!("{}: {}", string(base), number(*delay)))
+            .collect();
+        fields.push(format!(
+            "\"attributes\": {{\n    \"delays\": {{\"nand\": {}, \"per_input\": {}, \"clk_to_q\": {}, \
+             \"setup\": {}, \"hold\": {}, \"gates\": {{{}}}}}\n  }}",
+            number(delays.nand),
+            number(delays.per_input),
+            number(delays.clk_to_q),
+            number(delays.setup),
+            number(delays.hold),
+            gates.join(", ")
+        ));
+    }
+
+    let mut json = String::from("{\n");
+    json.push_str(
+        &fields
+            .iter()
+            .map(|f| format!("  {}", f))
+            .collect::<Vec<_>>()
+            .join(",\n"),
+    );
+    json.push_str("\n}\n");
+    json
+}
+
+/// Reads a netlist written by [to_json], with the delays stored in its attributes if there are
+/// any. Cells may be listed in any order, and attributes other than delays are ignored so other
+/// tools can add their own
+pub fn from_json(text: &str) -> Result<(Netlist, Option<Delays>), JsonError> {
+    let value = Parser::new(text).document()?;
+    let root = Element {
+        value: &value,
+        path: String::new(),
+    };
+
+    let format = root.field("format")?;
+    if format.str()? != FORMAT {
+        return Err(format.invalid(&format!("expected \"{}\"", FORMAT)));
+    }
+    let version = root.field("version")?;
+    let v = version.index()?;
+    if v != VERSION as usize {
+        return Err(JsonError::UnsupportedVersion(v as u64));
+    }
+
+    let netlist_name = root.field("name")?.str()?;
+    let mut builder = Builder::new(netlist_name);
+
+    let mut nets = Vec::new();
+    for element in root.field("nets")?.items()? {
+        let net = builder.net();
+        if let Some(name) = element.optional_field("name")? {
+            builder.name_net(net, name.str()?);
+        }
+        nets.push(net);
+    }
+    // Path of the element driving each net
+    let mut drivers: Vec<Option<String>> = vec![None; nets.len()];
+    let mut drive = |element: &Element, driver: &Element| -> Result<Net, JsonError> {
+        let net = element.reference(nets.len(), "net")?;
+        if let Some(other) = &drivers[net] {
+            return Err(element.invalid(&format!("net {} is already driven by {}", net, other)));
+        }
+        drivers[net] = Some(driver.path.clone());
+        Ok(nets[net])
+    };
+
+    let scopes = root.field("scopes")?;
+    let mut scope_ids = Vec::new();
+    // Path of the scope using each name in each parent
+    let mut scope_names: HashMap<(usize, &str), String> = HashMap::new();
+    for (i, element) in scopes.items()?.iter().enumerate() {
+        let name_element = element.field("name")?;
+        let name = name_element.str()?;
+        let parent = element.field("parent")?;
+        if i == 0 {
+            if *parent.value != Value::Null {
+                return Err(parent.invalid("the top scope has no parent"));
+            }
+            if name != netlist_name {
+                return Err(name_element.invalid("the top scope must be named after the netlist"));
+            }
+            scope_ids.push(ScopeId::TOP);
+        } else {
+            let p = parent.index()?;
+            if p >= i {
+                return Err(parent.invalid("the parent must come before the scope"));
+            }
+            if let Some(other) = scope_names.insert((p, name), element.path.clone()) {
+                return Err(
+                    name_element.invalid(&format!("name {} is already used by {}", name, other))
+                );
+            }
+            scope_ids.push(builder.add_scope(name, scope_ids[p]));
+        }
+    }
+    if scope_ids.is_empty() {
+        return Err(scopes.invalid("missing the top scope"));
+    }
+
+    for element in root.field("inputs")?.items()? {
+        let name = element.field("name")?.str()?;
+        let net = drive(&element.field("net")?, &element)?;
+        builder.input_net(name, net);
+    }
+
+    // Nets read by cells and output ports, which must all be driven
+    let mut reads = Vec::new();
+    let mut cell_delays = Vec::new();
+    // Path of the cell using each name in each scope
+    let mut cell_names: HashMap<(usize, &str), String> = HashMap::new();
+    for element in root.field("cells")?.items()? {
+        let kind_element = element.field("kind")?;
+        let kind = match kind_element.str()? {
+            "nand" => CellKind::Nand,
+            "tie0" => CellKind::Const(false),
+            "tie1" => CellKind::Const(true),
+            "dff" => CellKind::Dff,
+            other => return Err(kind_element.invalid(&format!("unknown cell kind \"{}\"", other))),
+        };
+        let name_element = element.field("name")?;
+        let name = name_element.str()?;
+        let scope = element
+            .field("scope")?
+            .reference(scope_ids.len(), "scope")?;
+        if let Some(other) = cell_names.insert((scope, name), element.path.clone()) {
+            return Err(
+                name_element.invalid(&format!("name {} is already used by {}", name, other))
+            );
+        }
+
+        let inputs_element = element.field("inputs")?;
+        let mut inputs = Vec::new();
+        for input in inputs_element.items()? {
+            inputs.push(nets[input.reference(nets.len(), "net")?]);
+            reads.push(input);
+        }
+        let expected = match kind {
+            CellKind::Nand if inputs.is_empty() => Some("expected at least one input"),
+            CellKind::Const(_) if !inputs.is_empty() => Some("expected no inputs"),
+            CellKind::Dff if inputs.len() != 2 => Some("expected the inputs [clk, d]"),
+            _ => None,
+        };
+        if let Some(message) = expected {
+            return Err(inputs_element.invalid(message));
+        }
+
+        let output = drive(&element.field("output")?, &element)?;
+        builder.add_named_cell(kind, &inputs, output, name, scope_ids[scope]);
+        if let Some(attributes) = element.optional_field("attributes")? {
+            if let Some(delay) = attributes.optional_field("delay")? {
+                cell_delays.push((output, delay.number()?));
+            }
+        }
+    }
+
+    for element in root.field("outputs")?.items()? {
+        let name = element.field("name")?.str()?;
+        let net = element.field("net")?;
+        builder.output(name, nets[net.reference(nets.len(), "net")?]);
+        reads.push(net);
+    }
+    for element in &reads {
+        let net = element.index()?;
+        if drivers[net].is_none() {
+            return Err(element.invalid(&format!("net {} has no driver", net)));
+        }
+    }
+
+    let mut delays = None;
+    if let Some(attributes) = root.optional_field("attributes")? {
+        if let Some(element) = attributes.optional_field("delays")? {
+            let mut d = Delays::new(element.field("nand")?.number()?);
+            d.per_input = element.field("per_input")?.number()?;
+            d.clk_to_q = element.field("clk_to_q")?.number()?;
+            d.setup = element.field("setup")?.number()?;
+            d.hold = element.field("hold")?.number()?;
+            if let Some(gates) = element.optional_field("gates")? {
+                for (base, delay) in gates.entries()? {
+                    d = d.with_gate(base, delay.number()?);
+                }
+            }
+            delays = Some(d);
+        }
+    }
+
+    let netlist = builder.try_finish().map_err(JsonError::Netlist)?;
+    if !cell_delays.is_empty() {
+        let mut d = delays.unwrap_or_default();
+        for (net, delay) in cell_delays {
+            if let Some(Driver::Cell(id)) = netlist.driver(net) {
+                d = d.with_cell(&netlist.cell_path(id), delay);
+            }
+        }
+        delays = Some(d);
+    }
+    Ok((netlist, delays))
+}
+
+/// Writes a netlist to a JSON file with [to_json]
+pub fn save(
+    path: impl AsRef<Path>,
+    netlist: &Netlist,
+    delays: Option<&Delays>,
+) -> Result<(), JsonError> {
+    fs::write(path, to_json(netlist, delays)).map_err(|e| JsonError::Io(e.to_string()))
+}
+
+/// Reads a netlist from a JSON file with [from_json]
+pub fn load(path: impl AsRef<Path>) -> Result<(Netlist, Option<Delays>), JsonError> {
+    let text = fs::read_to_string(path).map_err(|e| JsonError::Io(e.to_string()))?;
+    from_json(&text)
+}
+
+/// Quotes and escapes a string
+fn string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// JSON has no infinities or NaN, so they're written as null, which is read back as infinity.
+/// Delays are never negative, so NaN and negative infinity aren't worth telling apart
+fn number(x: f64) -> String {
+    if x.is_finite() {
+        x.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+/// Array with one element per line, indented to sit in the top level object
+fn array(items: Vec<String>) -> String {
+    if items.is_empty() {
+        return "[]".to_string();
+    }
+    format!("[\n    {}\n  ]", items.join(",\n    "))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Members in the order they appear
+    Object(Vec<(String, Value)>),
+}
+
+/// A value in a document and its path, for error messages
+struct Element<'a> {
+    value: &'a Value,
+    path: String,
+}
+
+impl<'a> Element<'a> {
+    fn invalid(&self, message: &str) -> JsonError {
+        JsonError::Invalid {
+            path: self.path.clone(),
+            message: message.to_string(),
+        }
+    }
+
+    fn optional_field(&self, key: &str) -> Result<Option<Element<'a>>, JsonError> {
+        let Value::Object(members) = self.value else {
+            return Err(self.invalid("expected an object"));
+        };
+        let path = if self.path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", self.path, key)
+        };
+        Ok(members
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| Element { value, path }))
+    }
+
+    fn field(&self, key: &str) -> Result<Element<'a>, JsonError> {
+        self.optional_field(key)?.ok_or_else(|| JsonError::Invalid {
+            path: self.path.clone(),
+            message: format!("missing \"{}\"", key),
+        })
+    }
+
+    fn items(&self) -> Result<Vec<Element<'a>>, JsonError> {
+        let Value::Array(items) = self.value else {
+            return Err(self.invalid("expected an array"));
+        };
+        Ok(items
+            .iter()
+            .enumerate()
+            .map(|(i, value)| Element {
+                value,
+                path: format!("{}[{}]", self.path, i),
+            })
+            .collect())
+    }
+
+    fn entries(&self) -> Result<Vec<(&'a str, Element<'a>)>, JsonError> {
+        let Value::Object(members) = self.value else {
+            return Err(self.invalid("expected an object"));
+        };
+        Ok(members
+            .iter()
+            .map(|(key, value)| {
+                let path = format!("{}.{}", self.path, key);
+                (key.as_str(), Element { value, path })
+            })
+            .collect())
+    }
+
+    fn str(&self) -> Result<&'a str, JsonError> {
+        match self.value {
+            Value::String(s) => Ok(s),
+            _ => Err(self.invalid("expected a string")),
+        }
+    }
+
+    /// A delay, where null stands for one that never ends
+    fn number(&self) -> Result<f64, JsonError> {
+        match self.value {
+            Value::Number(x) => Ok(*x),
+            Value::Null => Ok(f64::INFINITY),
+            _ => Err(self.invalid("expected a number")),
+        }
+    }
+
+    fn index(&self) -> Result<usize, JsonError> {
+        match self.value {
+            Value::Number(x) if *x >= 0.0 && x.fract() == 0.0 && *x < usize::MAX as f64 => {
+                Ok(*x as usize)
+            }
+            _ => Err(self.invalid("expected an index")),
+        }
+    }
+
+    /// Index of one of n nets or scopes
+    fn reference(&self, n: usize, what: &str) -> Result<usize, JsonError> {
+        let index = self.index()?;
+        if index >= n {
+            return Err(self.invalid(&format!("{} {} doesn't exist", what, index)));
+        }
+        Ok(index)
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Parser {
+            text,
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    fn document(&mut self) -> Result<Value, JsonError> {
+        let value = self.value()?;
+        self.skip_whitespace();
+        if self.pos < self.text.len() {
+            return Err(self.error());
+        }
+        Ok(value)
+    }
+
+    /// Syntax error at the current position
+    fn error(&self) -> JsonError {
+        let before = &self.text[..self.pos];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        JsonError::Syntax {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), JsonError> {
+        self.skip_whitespace();
+        if self.peek() != Some(byte) {
+            return Err(self.error());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value, JsonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self
+                .nested(b'}', |p| {
+                    let key = p.string()?;
+                    p.expect(b':')?;
+                    Ok((key, p.value()?))
+                })
+                .map(Value::Object),
+            Some(b'[') => self.nested(b']', Parser::value).map(Value::Array),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => {
+                for (word, value) in [
+                    ("null", Value::Null),
+                    ("true", Value::Bool(true)),
+                    ("false", Value::Bool(false)),
+                ] {
+                    if self.text[self.pos..].starts_with(word) {
+                        self.pos += word.len();
+                        return Ok(value);
+                    }
+                }
+                Err(self.error())
+            }
+        }
+    }
+
+    /// Comma separated elements between the current opening bracket and the closing one
+    fn nested<T>(
+        &mut self,
+        close: u8,
+        mut element: impl FnMut(&mut Self) -> Result<T, JsonError>,
+    ) -> Result<Vec<T>, JsonError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error());
+        }
+        self.depth += 1;
+        self.pos += 1;
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(close) {
+            self.pos += 1;
+        } else {
+            loop {
+                elements.push(element(self)?);
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => self.pos += 1,
+                    Some(c) if c == close => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => return Err(self.error()),
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(elements)
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect(b'"')?;
+        let mut s = String::new();
+        loop {
+            let Some(c) = self.text[self.pos..].chars().next() else {
+                return Err(self.error());
+            };
+            match c {
+                '"' => {
+                    self.pos += 1;
+                    return Ok(s);
+                }
+                '\\' => {
+                    self.pos += 1;
+                    let escaped = match self.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.pos += 1;
+                            s.push(self.unicode_escape()?);
+                            continue;
+                        }
+                        _ => return Err(self.error()),
+                    };
+                    self.pos += 1;
+                    s.push(escaped);
+                }
+                c if (c as u32) < 0x20 => return Err(self.error()),
+                c => {
+                    self.pos += c.len_utf8();
+                    s.push(c);
+                }
+            }
+        }
+    }
+
+    /// Character given by the hex digits after "\u", which may be the first of a surrogate pair
+    fn unicode_escape(&mut self) -> Result<char, JsonError> {
+        let start = self.pos;
+        let mut code = self.hex4()?;
+        if (0xd800..0xdc00).contains(&code) && self.text[self.pos..].starts_with("\\u") {
+            self.pos += 2;
+            let low = self.hex4()?;
+            if (0xdc00..0xe000).contains(&low) {
+                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+            }
+        }
+        match char::from_u32(code) {
+            Some(c) => Ok(c),
+            None => {
+                self.pos = start;
+                Err(self.error())
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, JsonError> {
+        let Some(hex) = self.text.get(self.pos..self.pos + 4) else {
+            return Err(self.error());
+        };
+        let code = u32::from_str_radix(hex, 16).map_err(|_| self.error())?;
+        self.pos += 4;
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<Value, JsonError> {
+        let start = self.pos;
+        let digits = |p: &mut Self| {
+            let from = p.pos;
+            while matches!(p.peek(), Some(b'0'..=b'9')) {
+                p.pos += 1;
+            }
+            p.pos > from
+        };
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        if self.peek() == Some(b'0') {
+            self.pos += 1;
+        } else if !digits(self) {
+            return Err(self.error());
+        }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            if !digits(self) {
+                return Err(self.error());
+            }
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if !digits(self) {
+                return Err(self.error());
+            }
+        }
+        let x = self.text[start..self.pos]
+            .parse()
+            .map_err(|_| self.error())?;
+        Ok(Value::Number(x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::SynchronousCounter;
+    use crate::flipflop::DFlipflop;
+    use crate::math::RippleCarryAdder;
+
+    fn example() -> Netlist {
+        let mut builder = Builder::new("example");
+        let clk = builder.input("clk");
+        let a = builder.input("a");
+        let b = builder.input("b");
+        let y = builder.and(&[a, b]);
+        builder.name_net(y, "y");
+        let q = builder.dff(clk, y);
+        let one = builder.constant(true);
+        builder.output("q", q);
+        builder.output("one", one);
+        builder.finish()
+    }
+
+    #[test]
+    fn test_to_json() {
+        assert_eq!(
+            to_json(&example(), None),
+            r#"{
+  "format": "nandverse-netlist",
+  "version": 1,
+  "name": "example",
+  "inputs": [
+    {"name": "clk", "net": 0},
+    {"name": "a", "net": 1},
+    {"name": "b", "net": 2}
+  ],
+  "outputs": [
+    {"name": "q", "net": 5},
+    {"name": "one", "net": 6}
+  ],
+  "nets": [
+    {},
+    {},
+    {},
+    {},
+    {"name": "y"},
+    {},
+    {}
+  ],
+  "scopes": [
+    {"name": "example", "parent": null},
+    {"name": "and0", "parent": 0},
+    {"name": "not0", "parent": 1}
+  ],
+  "cells": [
+    {"kind": "nand", "name": "nand0", "scope": 1, "inputs": [1, 2], "output": 3},
+    {"kind": "nand", "name": "nand0", "scope": 2, "inputs": [3, 3], "output": 4},
+    {"kind": "dff", "name": "dff0", "scope": 0, "inputs": [0, 4], "output": 5},
+    {"kind": "tie1", "name": "tie10", "scope": 0, "inputs": [], "output": 6}
+  ]
+}
+"#
+        );
+
+        let delays = Delays::new(1.0)
+            .with_gate("and", 0.5)
+            .with_gate("and", 3.0)
+            .with_cell("and0/not0/nand0", 2.0)
+            .with_cell("missing/nand0", 4.0);
+        let json = to_json(&example(), Some(&delays));
+        assert!(json.contains(
+            r#"{"kind": "nand", "name": "nand0", "scope": 2, "inputs": [3, 3], "output": 4, "attributes": {"delay": 2}}"#
+        ));
+        assert!(json.ends_with(
+            r#"  "attributes": {
+    "delays": {"nand": 1, "per_input": 0, "clk_to_q": 2, "setup": 1, "hold": 0, "gates": {"and": 0.5}}
+  }
+}
+"#
+        ));
+
+        let mut builder = Builder::new("say \"hi\"\n");
+        let a = builder.input("a\\b\u{1}");
+        builder.output("é", a);
+        let json = to_json(&builder.finish(), None);
+        assert!(json.contains(r#""name": "say \"hi\"\n","#));
+        assert!(json.contains(r#"{"name": "a\\b\u0001", "net": 0}"#));
+        assert!(json.contains(r#"{"name": "é", "net": 0}"#));
+    }
+
+    #[test]
+    fn test_from_json() {
+        for netlist in [
+            example(),
+            RippleCarryAdder::<3>::new().netlist(),
+            SynchronousCounter::<3>::new().netlist(),
+            DFlipflop::new().netlist(),
+        ] {
+            let (imported, delays) = from_json(&to_json(&netlist, None)).unwrap();
+            assert_eq!(imported, netlist, "failed for inputs: {:?}", netlist.name());
+            assert_eq!(delays, None);
+        }
+
+        // Cells out of order, attributes of other tools and escaped names
+        let text = r#"{
+            "version": 1, "format": "nandverse-netlist", "name": "n\u00e9t",
+            "inputs": [{"name": "a", "net": 1}],
+            "outputs": [{"name": "y\ud83d\ude00", "net": 0}],
+            "nets": [{"name": "y"}, {}, {"color": "red"}],
+            "scopes": [{"name": "n\u00e9t", "parent": null}, {"name": "buf", "parent": 0}],
+            "cells": [
+                {"kind": "nand", "name": "second", "scope": 1, "inputs": [2], "output": 0},
+                {"kind": "nand", "name": "first", "scope": 1, "inputs": [1], "output": 2,
+                 "attributes": {"x": 1.5e1, "label": [true, false, null]}}
+            ],
+            "attributes": {"author": "someone"}
+        }"#;
+        let (netlist, delays) = from_json(text).unwrap();
+        assert_eq!(netlist.name(), "nét");
+        assert_eq!(netlist.outputs()[0].name(), "y\u{1f600}");
+        assert_eq!(
+            netlist.cell_path(netlist.cell_ids().next().unwrap()),
+            "buf/first"
+        );
+        assert_eq!(netlist.find_net("y"), Some(netlist.outputs()[0].net()));
+        assert_eq!(netlist.evaluate(&[false]), vec![false]);
+        assert_eq!(netlist.evaluate(&[true]), vec![true]);
+        assert_eq!(delays, None);
+    }
+
+    #[test]
+    fn test_delays() {
+        let netlist = RippleCarryAdder::<2>::new().netlist();
+        let path = netlist.cell_path(netlist.cell_ids().nth(3).unwrap());
+        let delays = Delays {
+            per_input: 0.25,
+            clk_to_q: 3.0,
+            setup: 0.5,
+            hold: 0.125,
+            ..Delays::new(2.0)
+        }
+        .with_gate("xor", 1.5)
+        .with_cell(&path, 4.0);
+        let (imported, imported_delays) = from_json(&to_json(&netlist, Some(&delays))).unwrap();
+        let imported_delays = imported_delays.unwrap();
+        assert_eq!(
+            (
+                imported_delays.clk_to_q,
+                imported_delays.setup,
+                imported_delays.hold
+            ),
+            (3.0, 0.5, 0.125)
+        );
+        for id in netlist.cell_ids() {
+            assert_eq!(
+                imported_delays.nand_delay(&imported, id),
+                delays.nand_delay(&netlist, id),
+                "failed for inputs: {:?}",
+                netlist.cell_path(id)
+            );
+        }
+
+        // A cell delay alone uses the default delays for everything else
+        let text = to_json(&netlist, None).replacen(
+            r#""output": 4}"#,
+            r#""output": 4, "attributes": {"delay": 9}}"#,
+            1,
+        );
+        let (imported, delays) = from_json(&text).unwrap();
+        let delays = delays.unwrap();
+        let id = imported
+            .cell_ids()
+            .find(|id| imported.cell(*id).output().index() == 4);
+        assert_eq!(delays.nand_delay(&imported, id.unwrap()), 9.0);
+        assert_eq!(delays.nand, Delays::default().nand);
+
+        // Infinite delays are written as null and read back
+        let delays = Delays {
+            hold: f64::INFINITY,
+            ..Delays::new(2.0)
+        }
+        .with_cell(&path, f64::INFINITY);
+        let text = to_json(&netlist, Some(&delays));
+        assert!(text.contains("\"hold\": null"));
+        let (imported, imported_delays) = from_json(&text).unwrap();
+        let imported_delays = imported_delays.unwrap();
+        assert_eq!(imported_delays.hold, f64::INFINITY);
+        for id in netlist.cell_ids() {
+            assert_eq!(
+                imported_delays.nand_delay(&imported, id),
+                delays.nand_delay(&netlist, id),
+                "failed for inputs: {:?}",
+                netlist.cell_path(id)
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_json_errors() {
+        let valid = to_json(&example(), None);
+        let invalid = |path: &str, message: &str| JsonError::Invalid {
+            path: path.to_string(),
+            message: message.to_string(),
+        };
+        let tests = [
+            ("".to_string(), JsonError::Syntax { line: 1, column: 1 }),
+            (
+                "{\n  \"a\": [1, 2,]\n}".to_string(),
+                JsonError::Syntax {
+                    line: 2,
+                    column: 14,
+                },
+            ),
+            ("[01]".to_string(), JsonError::Syntax { line: 1, column: 3 }),
+            (
+                "\"\\x\"".to_string(),
+                JsonError::Syntax { line: 1, column: 3 },
+            ),
+            (
+                "\"é\t\"".to_string(),
+                JsonError::Syntax { line: 1, column: 3 },
+            ),
+            (
+                "\"\\udc00\"".to_string(),
+                JsonError::Syntax { line: 1, column: 4 },
+            ),
+            (
+                "{} {}".to_string(),
+                JsonError::Syntax { line: 1, column: 4 },
+            ),
+            (
+                "[".repeat(100) + &"]".repeat(100),
+                JsonError::Syntax {
+                    line: 1,
+                    column: 65,
+                },
+            ),
+            ("[]".to_string(), invalid("", "expected an object")),
+            ("{}".to_string(), invalid("", "missing \"format\"")),
+            (
+                valid.replace("nandverse-netlist", "other"),
+                invalid("format", "expected \"nandverse-netlist\""),
+            ),
+            (
+                valid.replace("\"version\": 1", "\"version\": 2"),
+                JsonError::UnsupportedVersion(2),
+            ),
+            (
+                valid.replace("\"version\": 1", "\"version\": 1.5"),
+                invalid("version", "expected an index"),
+            ),
+            (
+                valid.replace("\"name\": \"y\"", "\"name\": 7"),
+                invalid("nets[4].name", "expected a string"),
+            ),
+            (
+                valid.replace("\"inputs\": [3, 3]", "\"inputs\": [3, 30]"),
+                invalid("cells[1].inputs[1]", "net 30 doesn't exist"),
+            ),
+            (
+                valid.replace("\"inputs\": [3, 3]", "\"inputs\": []"),
+                invalid("cells[1].inputs", "expected at least one input"),
+            ),
+            (
+                valid.replace("\"inputs\": [0, 4]", "\"inputs\": [4]"),
+                invalid("cells[2].inputs", "expected the inputs [clk, d]"),
+            ),
+            (
+                valid.replace("\"inputs\": [], ", "\"inputs\": [0], "),
+                invalid("cells[3].inputs", "expected no inputs"),
+            ),
+            (
+                valid.replace("\"tie1\"", "\"tie2\""),
+                invalid("cells[3].kind", "unknown cell kind \"tie2\""),
+            ),
+            (
+                valid.replace("\"scope\": 2", "\"scope\": 1"),
+                invalid("cells[1].name", "name nand0 is already used by cells[0]"),
+            ),
+            (
+                valid.replace("\"scope\": 2", "\"scope\": 3"),
+                invalid("cells[1].scope", "scope 3 doesn't exist"),
+            ),
+            (
+                valid.replace(", \"output\": 6}", "}"),
+                invalid("cells[3]", "missing \"output\""),
+            ),
+            (
+                valid.replace("\"output\": 6", "\"output\": 5"),
+                invalid("cells[3].output", "net 5 is already driven by cells[2]"),
+            ),
+            (
+                valid.replace("\"output\": 3", "\"output\": 1"),
+                invalid("cells[0].output", "net 1 is already driven by inputs[1]"),
+            ),
+            (
+                valid.replace("\"output\": 6", "\"output\": -1"),
+                invalid("cells[3].output", "expected an index"),
+            ),
+            (
+                valid.replace(
+                    ",\n    {\"kind\": \"tie1\", \"name\": \"tie10\", \"scope\": 0, \"inputs\": [], \"output\": 6}",
+                    "",
+                ),
+                invalid("outputs[1].net", "net 6 has no driver"),
+            ),
+            (
+                valid.replace("\"parent\": null", "\"parent\": 0"),
+                invalid("scopes[0].parent", "the top scope has no parent"),
+            ),
+            (
+                valid.replace(
+                    "{\"name\": \"example\", \"parent\"",
+                    "{\"name\": \"top\", \"parent\"",
+                ),
+                invalid(
+                    "scopes[0].name",
+                    "the top scope must be named after the netlist",
+                ),
+            ),
+            (
+                valid.replace("\"not0\", \"parent\": 1", "\"and0\", \"parent\": 0"),
+                invalid("scopes[2].name", "name and0 is already used by scopes[1]"),
+            ),
+            (
+                valid.replace("\"parent\": 1", "\"parent\": 2"),
+                invalid("scopes[2].parent", "the parent must come before the scope"),
+            ),
+            (
+                valid.replace(
+                    "\"inputs\": [0, 4]",
+                    "\"inputs\": [0, 4], \"attributes\": {\"delay\": \"1\"}",
+                ),
+                invalid("cells[2].attributes.delay", "expected a number"),
+            ),
+            (
+                valid.replace("\"inputs\": [1, 2]", "\"inputs\": [1, 4]"),
+                JsonError::Netlist(NetlistError::CombinationalLoop("and0/nand0".to_string())),
+            ),
+        ];
+        for (text, error) in tests {
+            assert_eq!(
+                from_json(&text),
+                Err(error),
+                "failed for inputs: {:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_save_load() {
+        let netlist = SynchronousCounter::<2>::new().netlist();
+        let delays = Delays::new(0.5);
+        let path = std::env::temp_dir().join(format!("nandverse-{}.json", std::process::id()));
+        save(&path, &netlist, Some(&delays)).unwrap();
+        let loaded = load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, Ok((netlist, Some(delays))));
+
+        assert!(matches!(load(&path), Err(JsonError::Io(_))));
+    }
+}
@@ -0,0 +1,503 @@
+use core::fmt;
+
+use crate::minimize::{minimize, Cover, Cube};
+use crate::schematic::escape_xml;
+use crate::truth_table::TruthTable;
+
+pub const MIN_VARS: usize = 2;
+pub const MAX_VARS: usize = 6;
+
+/// Size of a cell in SVG output, in pixels
+const SVG_CELL: usize = 40;
+/// Outline colours used for successive groups in SVG output
+const SVG_COLOURS: [&str; 8] = [
+    "#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4", "#42d4f4", "#f032e6", "#9a6324",
+];
+
+/// Value of a cell in a Karnaugh map
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Value {
+    Zero,
+    One,
+    DontCare,
+}
+
+impl Value {
+    fn symbol(&self) -> char {
+        match self {
+            Value::Zero => '0',
+            Value::One => '1',
+            Value::DontCare => 'X',
+        }
+    }
+}
+
+/// Karnaugh map of a function of 2 to 6 variables
+///
+/// Variables 0 and 1 label the columns and variables 2 and 3 label the rows, with the axes in
+/// Gray code order. Functions of 5 or 6 variables are split into one 4 variable map per value of
+/// variables 4 and 5.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KMap {
+    names: Vec<String>,
+    /// Value of each cell, indexed by minterm
+    cells: Vec<Value>,
+    /// Highlighted groups of cells, such as prime implicants
+    groups: Vec<Cube>,
+}
+
+impl KMap {
+    /// Creates a map by evaluating a function for every input
+    pub fn from_fn<const N: usize>(f: impl Fn(&[bool; N]) -> bool) -> Self {
+        Self::from_table(&TruthTable::from_fn(f), 0)
+    }
+
+    /// Creates a map of one output of a truth table, naming the variables after its inputs
+    pub fn from_table(table: &TruthTable, output: usize) -> Self {
+        let n = table.num_inputs();
+        assert!(
+            (MIN_VARS..=MAX_VARS).contains(&n),
+            "Karnaugh maps need {} to {} variables",
+            MIN_VARS,
+            MAX_VARS
+        );
+
+        KMap {
+            names: table.input_names().to_vec(),
+            cells: (0..table.num_rows())
+                .map(|row| {
+                    if table.output(row, output) {
+                        Value::One
+                    } else {
+                        Value::Zero
+                    }
+                })
+                .collect(),
+            groups: Vec::new(),
+        }
+    }
+
+    /// Marks the given minterms as don't cares
+    pub fn with_dont_cares(mut self, minterms: &[usize]) -> Self {
+        for m in minterms {
+            self.cells[*m] = Value::DontCare;
+        }
+        self
+    }
+
+    /// Names the variables. Panics if the number of names doesn't match
+    pub fn with_names(mut self, names: &[&str]) -> Self {
+        assert_eq!(names.len(), self.num_vars(), "wrong number of names");
+        self.names = names.iter().map(|n| n.to_string()).collect();
+        self
+    }
+
+    /// Highlights the terms of a cover
+    pub fn with_groups(mut self, cover: &Cover) -> Self {
+        assert_eq!(
+            cover.num_vars(),
+            self.num_vars(),
+            "wrong number of variables"
+        );
+        self.groups = cover.cubes().to_vec();
+        self
+    }
+
+    /// Highlights the prime implicants of a minimal cover of the map
+    pub fn with_minimized_groups(self) -> Self {
+        let cover = self.minimize();
+        self.with_groups(&cover)
+    }
+
+    /// Finds a minimal cover of the map, making use of the don't cares
+    pub fn minimize(&self) -> Cover {
+        minimize(
+            self.num_vars(),
+            &self.minterms(Value::One),
+            &self.minterms(Value::DontCare),
+        )
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn value(&self, minterm: usize) -> Value {
+        self.cells[minterm]
+    }
+
+    pub fn groups(&self) -> &[Cube] {
+        &self.groups
+    }
+
+    fn minterms(&self, value: Value) -> Vec<usize> {
+        (0..self.cells.len())
+            .filter(|m| self.cells[*m] == value)
+            .collect()
+    }
+
+    /// Number of column and row variables of each 4 variable map
+    fn axis_vars(&self) -> (usize, usize) {
+        let vars = self.num_vars().min(4);
+        let cols = vars.div_ceil(2);
+        (cols, vars - cols)
+    }
+
+    /// Values of the variables above 4 selecting each map, in display order
+    fn submaps(&self) -> Vec<usize> {
+        (0..1 << (self.num_vars() - self.num_vars().min(4))).collect()
+    }
+
+    /// Minterm shown in a cell of the given map
+    fn minterm(&self, submap: usize, row: usize, col: usize) -> usize {
+        let (cols, rows) = self.axis_vars();
+        gray(col) | gray(row) << cols | submap << (cols + rows)
+    }
+
+    /// Axis label listing variables from the most significant, e.g. "x1x0"
+    fn axis_label(&self, first: usize, count: usize) -> String {
+        (first..first + count)
+            .rev()
+            .map(|i| self.names[i].as_str())
+            .collect::<Vec<_>>()
+            .join("")
+    }
+
+    /// Label of a map of 5 or 6 variables, e.g. "x5x4 = 01"
+    fn submap_label(&self, submap: usize) -> String {
+        let extra = self.num_vars() - 4;
+        format!(
+            "{} = {}",
+            self.axis_label(4, extra),
+            bit_string(submap, extra)
+        )
+    }
+
+    fn group_letter(group: usize) -> char {
+        (b'a' + (group % 26) as u8) as char
+    }
+
+    /// Renders the map as plain text. Each cell shows its value followed by the letter of every
+    /// group it belongs to, and a legend lists the terms of the groups
+    pub fn to_text(&self) -> String {
+        let (cols, rows) = self.axis_vars();
+        let col_label = self.axis_label(0, cols);
+        let row_label = self.axis_label(cols, rows);
+
+        let cell_text = |m: usize| -> String {
+            let mut text = self.cells[m].symbol().to_string();
+            for (i, group) in self.groups.iter().enumerate() {
+                if group.contains(m) {
+                    text.push(Self::group_letter(i));
+                }
+            }
+            text
+        };
+        let cell_width = (0..self.cells.len())
+            .map(|m| cell_text(m).len())
+            .max()
+            .unwrap_or(1)
+            .max(cols);
+        let margin = row_label.len().max(rows);
+
+        let mut text = String::new();
+        for submap in self.submaps() {
+            if self.num_vars() > 4 {
+                text.push_str(&self.submap_label(submap));
+                text.push('\n');
+            }
+
+            text.push_str(&format!("{:margin$} | {}\n", row_label, col_label));
+            let headings: Vec<String> = (0..1 << cols)
+                .map(|c| format!("{:>cell_width$}", bit_string(gray(c), cols)))
+                .collect();
+            text.push_str(&format!("{:margin$} | {}\n", "", headings.join(" ")));
+            text.push_str(&format!(
+                "{}-+-{}\n",
+                "-".repeat(margin),
+                "-".repeat(headings.join(" ").len())
+            ));
+            for row in 0..1 << rows {
+                let cells: Vec<String> = (0..1 << cols)
+                    .map(|col| {
+                        format!("{:>cell_width$}", cell_text(self.minterm(submap, row, col)))
+                    })
+                    .collect();
+                text.push_str(&format!(
+                    "{:>margin$} | {}\n",
+                    bit_string(gray(row), rows),
+                    cells.join(" ")
+                ));
+            }
+            text.push('\n');
+        }
+
+        let names: Vec<&str> = self.names.iter().map(String::as_str).collect();
+        for (i, group) in self.groups.iter().enumerate() {
+            let cover = Cover::new(self.num_vars(), vec![*group]);
+            text.push_str(&format!(
+                "{}: {}\n",
+                Self::group_letter(i),
+                cover.to_expr(&names)
+            ));
+        }
+        text.trim_end().to_string() + "\n"
+    }
+
+    /// Renders the map as a self contained SVG image, outlining each group in its own colour
+    pub fn to_svg(&self) -> String {
+        let (cols, rows) = self.axis_vars();
+        let extra = self.num_vars() - self.num_vars().min(4);
+        // Maps of 6 variables are arranged in a 2x2 grid, otherwise side by side
+        let (grid_cols, grid_rows) = match extra {
+            0 => (1, 1),
+            1 => (2, 1),
+            _ => (2, 2),
+        };
+
+        let margin = SVG_CELL * 2;
+        let map_width = margin + (SVG_CELL << cols);
+        let map_height = margin + (SVG_CELL << rows);
+        let gap = SVG_CELL / 2;
+        let width = grid_cols * map_width + (grid_cols + 1) * gap;
+        let height = grid_rows * map_height + (grid_rows + 1) * gap;
+
+        let mut svg = String::new();
+        svg.push_str(&format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+             viewBox=\"0 0 {w} {h}\" font-family=\"monospace\" font-size=\"14\">\n",
+            w = width,
+            h = height
+        ));
+        svg.push_str(&format!(
+            "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>\n",
+            width, height
+        ));
+
+        for submap in self.submaps() {
+            let x0 = gap + (submap % grid_cols) * (map_width + gap);
+            let y0 = gap + (submap / grid_cols) * (map_height + gap);
+            let gx = x0 + margin;
+            let gy = y0 + margin;
+
+            if extra > 0 {
+                svg.push_str(&svg_text(x0, y0 + 14, "start", &self.submap_label(submap)));
+            }
+            svg.push_str(&svg_text(
+                gx + (SVG_CELL << cols) / 2,
+                gy - SVG_CELL + 4,
+                "middle",
+                &self.axis_label(0, cols),
+            ));
+            svg.push_str(&svg_text(
+                x0 + SVG_CELL / 2,
+                gy - 8,
+                "middle",
+                &self.axis_label(cols, rows),
+            ));
+
+            for col in 0..1 << cols {
+                svg.push_str(&svg_text(
+                    gx + col * SVG_CELL + SVG_CELL / 2,
+                    gy - 8,
+                    "middle",
+                    &bit_string(gray(col), cols),
+                ));
+            }
+            for row in 0..1 << rows {
+                svg.push_str(&svg_text(
+                    gx - 8,
+                    gy + row * SVG_CELL + SVG_CELL / 2 + 5,
+                    "end",
+                    &bit_string(gray(row), rows),
+                ));
+                for col in 0..1 << cols {
+                    let x = gx + col * SVG_CELL;
+                    let y = gy + row * SVG_CELL;
+                    svg.push_str(&format!(
+                        "<rect x=\"{}\" y=\"{}\" width=\"{s}\" height=\"{s}\" fill=\"none\" \
+                         stroke=\"black\"/>\n",
+                        x,
+                        y,
+                        s = SVG_CELL
+                    ));
+                    let value = self.cells[self.minterm(submap, row, col)];
+                    svg.push_str(&svg_text(
+                        x + SVG_CELL / 2,
+                        y + SVG_CELL / 2 + 5,
+                        "middle",
+                        &value.symbol().to_string(),
+                    ));
+                }
+            }
+
+            for (i, group) in self.groups.iter().enumerate() {
+                for (row_span, col_span) in self.group_rects(group, submap) {
+                    // Inset each group by a different amount so overlapping outlines stay visible
+                    let inset = 3 + (i % 4) * 3;
+                    svg.push_str(&format!(
+                        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"8\" fill=\"none\" \
+                         stroke=\"{}\" stroke-width=\"2\"/>\n",
+                        gx + col_span.0 * SVG_CELL + inset,
+                        gy + row_span.0 * SVG_CELL + inset,
+                        col_span.1 * SVG_CELL - 2 * inset,
+                        row_span.1 * SVG_CELL - 2 * inset,
+                        SVG_COLOURS[i % SVG_COLOURS.len()]
+                    ));
+                }
+            }
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Rectangles of cells covered by a group in one map, as (start, length) spans of rows and
+    /// columns. Groups wrapping around an edge of the map are split into several rectangles
+    fn group_rects(&self, group: &Cube, submap: usize) -> Vec<((usize, usize), (usize, usize))> {
+        let (cols, rows) = self.axis_vars();
+        let in_group = |row: usize, col: usize| group.contains(self.minterm(submap, row, col));
+
+        let col_spans = spans((0..1 << cols).filter(|c| (0..1 << rows).any(|r| in_group(r, *c))));
+        let row_spans = spans((0..1 << rows).filter(|r| (0..1 << cols).any(|c| in_group(*r, c))));
+
+        row_spans
+            .iter()
+            .flat_map(|r| col_spans.iter().map(move |c| (*r, *c)))
+            .collect()
+    }
+}
+
+impl fmt::Display for KMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_text())
+    }
+}
+
+fn gray(i: usize) -> usize {
+    i ^ (i >> 1)
+}
+
+/// Writes the low bits of a value, most significant first
+fn bit_string(value: usize, bits: usize) -> String {
+    (0..bits)
+        .rev()
+        .map(|i| if value >> i & 1 == 1 { '1' } else { '0' })
+        .collect()
+}
+
+/// Splits ascending indices into runs of consecutive indices, as (start, length)
+fn spans(indices: impl Iterator<Item = usize>) -> Vec<(usize, usize)> {
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    for i in indices {
+        match spans.last_mut() {
+            Some((start, len)) if *start + *len == i => *len += 1,
+            _ => spans.push((i, 1)),
+        }
+    }
+    spans
+}
+
+fn svg_text(x: usize, y: usize, anchor: &str, text: &str) -> String {
+    format!(
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"{}\">{}</text>\n",
+        x,
+        y,
+        anchor,
+        escape_xml(text)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gate::{and, xor};
+    use crate::mux::mux4;
+
+    #[test]
+    fn test_text_two_vars() {
+        let kmap = KMap::from_fn(|x: &[bool; 2]| and(x)).with_names(&["a", "b"]);
+        assert_eq!(
+            kmap.to_text(),
+            "b | a\n  | 0 1\n--+----\n0 | 0 0\n1 | 0 1\n"
+        );
+    }
+
+    #[test]
+    fn test_gray_code_axes() {
+        let kmap = KMap::from_fn(|x: &[bool; 4]| x[0] && !x[1] && x[2] && x[3])
+            .with_names(&["a", "b", "c", "d"]);
+        let text = kmap.to_text();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "dc | ba");
+        assert_eq!(lines[1], "   | 00 01 11 10");
+        // Minterm 0b1101 is in row dc = 11 (the third row) and column ba = 01
+        assert_eq!(lines[5], "11 |  0  1  0  0");
+    }
+
+    #[test]
+    fn test_dont_cares_and_groups() {
+        let kmap = KMap::from_fn(|x: &[bool; 3]| x[0] && x[1] && !x[2])
+            .with_dont_cares(&[1])
+            .with_minimized_groups();
+
+        // The don't care lets the group grow to cover two cells
+        assert_eq!(kmap.groups().len(), 1);
+        assert_eq!(kmap.groups()[0].to_pattern(3), "1-0");
+        assert_eq!(kmap.value(1), Value::DontCare);
+        assert_eq!(
+            kmap.to_text(),
+            "in[2] | in[1]in[0]\n      | 00 01 11 10\n------+------------\n    0 |  0 Xa 1a  0\n    1 |  0  0  0  0\n\na: in[0] & !in[2]\n"
+        );
+    }
+
+    #[test]
+    fn test_five_and_six_vars() {
+        let kmap = KMap::from_fn(|x: &[bool; 5]| x[4]);
+        let text = kmap.to_text();
+        assert!(text.contains("in[4] = 0\n"));
+        assert!(text.contains("in[4] = 1\n"));
+
+        let kmap = KMap::from_fn(|x: &[bool; 6]| {
+            let select = [x[0], x[1]];
+            mux4(&select, &[x[2], x[3], x[4], x[5]])
+        })
+        .with_minimized_groups();
+        let text = kmap.to_text();
+        for label in ["in[5]in[4] = 00", "in[5]in[4] = 01", "in[5]in[4] = 10"] {
+            assert!(text.contains(label), "missing label {}", label);
+        }
+        assert_eq!(kmap.groups().len(), 4);
+    }
+
+    #[test]
+    fn test_group_rects_wrap() {
+        // The four corners of a 4 variable map form one group
+        let kmap = KMap::from_fn(|x: &[bool; 4]| !x[0] && !x[2]).with_minimized_groups();
+        assert_eq!(kmap.groups().len(), 1);
+        assert_eq!(
+            kmap.group_rects(&kmap.groups()[0], 0),
+            [
+                ((0, 1), (0, 1)),
+                ((0, 1), (3, 1)),
+                ((3, 1), (0, 1)),
+                ((3, 1), (3, 1))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_svg() {
+        let kmap = KMap::from_fn(|x: &[bool; 3]| xor(&[x[0], x[2]]))
+            .with_names(&["a", "b<", "c"])
+            .with_minimized_groups();
+        let svg = kmap.to_svg();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains("b&lt;a"));
+
+        // The background, eight cells and the outlines of two groups, one of which wraps around
+        let rects = svg.matches("<rect").count();
+        assert_eq!(rects, 1 + 8 + 3);
+    }
+}
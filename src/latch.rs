@@ -1,4 +1,6 @@
 use crate::gate::{and, nand, not};
+use crate::netlist::{Builder, CellKind, Net};
+use crate::sat::{Cnf, Lit};
 
 /// Active high SR latch with the following truth table:
 ///
@@ -65,6 +67,16 @@ impl SRLatchActiveLow {
         SRLatchActiveLow { q: false, qn: true }
     }
 
+    /// Encodes the stable states of the latch's cross-coupled NAND gates for the given inputs,
+    /// returning literals for Q and Q'. S and R both low is the only input giving Q = Q'
+    pub fn encode_stable_state(cnf: &mut Cnf, s: Lit, r: Lit) -> (Lit, Lit) {
+        let q = cnf.new_var();
+        let qn = cnf.new_var();
+        cnf.define_nand(q, &[s, qn]);
+        cnf.define_nand(qn, &[q, r]);
+        (q, qn)
+    }
+
     /// Set the set and reset inputs
     pub fn set(&mut self, s: bool, r: bool) {
         assert!(s | r, "restricted combination");
@@ -178,6 +190,26 @@ impl Default for DLatch {
     }
 }
 
+/// Builds a D latch from gates in its own scope, mirroring [DLatch]. Returns the Q and Q' nets
+///
+/// The latch's cross-coupled NAND gates form a combinational loop, so the builder can't be
+/// finished, only drawn with [builder_to_dot](crate::schematic::builder_to_dot)
+pub fn build_d_latch(builder: &mut Builder, e: Net, d: Net) -> (Net, Net) {
+    builder.instance("d_latch", |builder| {
+        let s = builder.and(&[d, e]);
+        let not_d = builder.not(d);
+        let r = builder.and(&[not_d, e]);
+        builder.instance("sr_latch", |builder| {
+            let s = builder.not(s);
+            let r = builder.not(r);
+            let (q, qn) = (builder.net(), builder.net());
+            builder.add_cell(CellKind::Nand, &[s, qn], q);
+            builder.add_cell(CellKind::Nand, &[q, r], qn);
+            (q, qn)
+        })
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
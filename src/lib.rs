@@ -1,9 +1,34 @@
+pub mod aiger;
+pub mod atpg;
+pub mod bdd;
+pub mod blif;
+pub mod bmc;
 pub mod bus;
+pub mod circuit;
 pub mod counter;
+pub mod expr;
+pub mod fault;
 pub mod flipflop;
 pub mod gate;
+pub mod hazard;
+pub mod hdl;
+pub mod json;
+pub mod kmap;
 pub mod latch;
 pub mod logic;
 pub mod math;
+pub mod metastability;
+pub mod minimize;
 pub mod mux;
+pub mod netlist;
+pub mod power;
+pub mod sat;
+pub mod scan;
+pub mod schematic;
 pub mod shift;
+pub mod structure;
+pub mod swap;
+pub mod timing;
+pub mod truth_table;
+pub mod verilog;
+pub mod waveform;
@@ -1,4 +1,15 @@
-use crate::gate::{and, or, xor};
+use crate::gate::{and, not, or, xor};
+
+/// Performs a NOT operation on an N bit word.
+pub const fn not_n<const N: usize>(a: &[bool; N]) -> [bool; N] {
+    let mut result = [false; N];
+    let mut i = 0;
+    while i < a.len() {
+        result[i] = not(a[i]);
+        i += 1;
+    }
+    result
+}
 
 /// Performs an XOR operation on two N bit words.
 pub const fn xor_n<const N: usize>(a: &[bool; N], b: &[bool; N]) -> [bool; N] {
@@ -39,6 +50,11 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_not_n() {
+        assert_eq!(not_n(&u8_to_bus(0b0101_0110)), u8_to_bus(0b1010_1001))
+    }
+
     #[test]
     fn test_xor_n() {
         assert_eq!(
@@ -1,4 +1,5 @@
-use crate::gate::{and, or, xor};
+use crate::gate::{and, nand, not, or, xor};
+use crate::netlist::{Builder, Net, Netlist};
 
 /// Perform a half add operation. Returns the sum and carry bits
 pub fn half_add(a: bool, b: bool) -> (bool, bool) {
@@ -13,6 +14,52 @@ pub fn full_add(a: bool, b: bool, cin: bool) -> (bool, bool) {
     )
 }
 
+/// Builds a half adder in its own scope. Returns the sum and carry nets
+pub fn build_half_add(builder: &mut Builder, a: Net, b: Net) -> (Net, Net) {
+    builder.instance("half_add", |builder| {
+        (builder.xor(&[a, b]), builder.and(&[a, b]))
+    })
+}
+
+/// Builds a full adder in its own scope. Returns the sum and carry nets
+pub fn build_full_add(builder: &mut Builder, a: Net, b: Net, cin: Net) -> (Net, Net) {
+    builder.instance("full_add", |builder| full_add_cells(builder, a, b, cin))
+}
+
+/// Full adder netlist with inputs "in[0..3]" and outputs "sum" and "cout", for tests
+#[cfg(test)]
+pub(crate) fn full_adder_netlist() -> Netlist {
+    let mut builder = Builder::new("full_add");
+    let [a, b, cin] = builder.input_bus::<3>("in");
+    let (sum, cout) = build_full_add(&mut builder, a, b, cin);
+    builder.output("sum", sum);
+    builder.output("cout", cout);
+    builder.finish()
+}
+
+/// Builds the gates of a full adder in the current scope, mirroring [full_add]
+fn full_add_cells(builder: &mut Builder, a: Net, b: Net, cin: Net) -> (Net, Net) {
+    let a_xor_b = builder.xor(&[a, b]);
+    let sum = builder.xor(&[a_xor_b, cin]);
+    let propagate = builder.and(&[a_xor_b, cin]);
+    let a_and_b = builder.and(&[a, b]);
+    let generate = builder.and(&[a, a_and_b]);
+    (sum, builder.or(&[propagate, generate]))
+}
+
+/// Builds an N bit adder netlist with inputs "a[i]" and "b[i]" and outputs "sum[i]"
+fn adder_netlist<const N: usize>(
+    name: &str,
+    build: impl FnOnce(&mut Builder, &[Net; N], &[Net; N]) -> [Net; N],
+) -> Netlist {
+    let mut builder = Builder::new(name);
+    let a = builder.input_bus::<N>("a");
+    let b = builder.input_bus::<N>("b");
+    let sum = build(&mut builder, &a, &b);
+    builder.output_bus("sum", &sum);
+    builder.finish()
+}
+
 pub struct RippleCarryAdder<const N: usize> {}
 
 impl<const N: usize> RippleCarryAdder<N> {
@@ -30,6 +77,26 @@ impl<const N: usize> RippleCarryAdder<N> {
         }
         sum
     }
+
+    /// Builds the adder's gates, with full adder i in a scope named "full_add[i]". Returns the
+    /// sum nets, discarding the final carry like [RippleCarryAdder::add]
+    pub fn build(&self, builder: &mut Builder, a: &[Net; N], b: &[Net; N]) -> [Net; N] {
+        let mut carry = builder.constant(false);
+        core::array::from_fn(|i| {
+            let (sum, cout) = builder.scoped(&format!("full_add[{}]", i), |builder| {
+                full_add_cells(builder, a[i], b[i], carry)
+            });
+            carry = cout;
+            sum
+        })
+    }
+
+    /// Builds a netlist of the adder with inputs "a[i]" and "b[i]" and outputs "sum[i]"
+    pub fn netlist(&self) -> Netlist {
+        adder_netlist::<N>("ripple_carry_adder", |builder, a, b| {
+            self.build(builder, a, b)
+        })
+    }
 }
 
 impl<const N: usize> Default for RippleCarryAdder<N> {
@@ -38,9 +105,87 @@ impl<const N: usize> Default for RippleCarryAdder<N> {
     }
 }
 
+/// Adder which computes every carry directly from the generate and propagate signals of the
+/// lower bits, rather than waiting for it to ripple through them
+pub struct CarryLookaheadAdder<const N: usize> {}
+
+impl<const N: usize> CarryLookaheadAdder<N> {
+    pub fn new() -> Self {
+        CarryLookaheadAdder {}
+    }
+
+    pub fn add(&self, a: u64, b: u64) -> u64 {
+        let bit = |x: u64, i: usize| (x >> i) & 1 == 1;
+        let generate: Vec<bool> = (0..N).map(|i| and(&[bit(a, i), bit(b, i)])).collect();
+        let propagate: Vec<bool> = (0..N).map(|i| xor(&[bit(a, i), bit(b, i)])).collect();
+
+        let mut sum = 0u64;
+        for i in 0..N {
+            // Carry into bit i is generated by some bit j below it and propagated by the rest
+            let terms: Vec<bool> = (0..i)
+                .map(|j| {
+                    let mut inputs = vec![generate[j]];
+                    inputs.extend(&propagate[j + 1..i]);
+                    and(&inputs)
+                })
+                .collect();
+            let s = if terms.is_empty() {
+                propagate[i]
+            } else {
+                // An OR of any width, built the same way as gate::or
+                let inverted: Vec<bool> = terms.iter().map(|t| not(*t)).collect();
+                xor(&[propagate[i], nand(&inverted)])
+            };
+            sum |= if s { 1 << i } else { 0 };
+        }
+        sum
+    }
+
+    /// Builds the adder's gates, with the logic for each bit in a scope named "bit[i]"
+    pub fn build(&self, builder: &mut Builder, a: &[Net; N], b: &[Net; N]) -> [Net; N] {
+        let mut generate = Vec::new();
+        let mut propagate = Vec::new();
+        core::array::from_fn(|i| {
+            builder.scoped(&format!("bit[{}]", i), |builder| {
+                generate.push(builder.and(&[a[i], b[i]]));
+                propagate.push(builder.xor(&[a[i], b[i]]));
+
+                let terms: Vec<Net> = (0..i)
+                    .map(|j| {
+                        let mut inputs = vec![generate[j]];
+                        inputs.extend(&propagate[j + 1..i]);
+                        builder.and(&inputs)
+                    })
+                    .collect();
+                if terms.is_empty() {
+                    propagate[i]
+                } else {
+                    let carry = builder.or(&terms);
+                    builder.xor(&[propagate[i], carry])
+                }
+            })
+        })
+    }
+
+    /// Builds a netlist of the adder with inputs "a[i]" and "b[i]" and outputs "sum[i]"
+    pub fn netlist(&self) -> Netlist {
+        adder_netlist::<N>("carry_lookahead_adder", |builder, a, b| {
+            self.build(builder, a, b)
+        })
+    }
+}
+
+impl<const N: usize> Default for CarryLookaheadAdder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bus::{self, Bus};
+    use crate::truth_table::TruthTable;
 
     #[test]
     fn test_half_add() {
@@ -71,4 +216,50 @@ mod tests {
             assert_eq!(adder.add(a, b), expect, "failed for inputs: {:?}", (a, b));
         }
     }
+
+    #[test]
+    fn test_carry_lookahead_add() {
+        let ripple = RippleCarryAdder::<8>::new();
+        let lookahead = CarryLookaheadAdder::<8>::new();
+        for a in 0..256 {
+            for b in 0..256 {
+                assert_eq!(
+                    lookahead.add(a, b),
+                    ripple.add(a, b),
+                    "failed for inputs: {:?}",
+                    (a, b)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_adder_netlists() {
+        let ripple = RippleCarryAdder::<4>::new();
+        let table = TruthTable::from_fn(|x: &[bool; 8]| {
+            let a = bus::bus_to_num(&x[..4]);
+            let b = bus::bus_to_num(&x[4..]);
+            Bus::<4>::from_num_truncated(ripple.add(a, b))
+        });
+
+        for netlist in [ripple.netlist(), CarryLookaheadAdder::<4>::new().netlist()] {
+            let result = TruthTable::from_netlist(&netlist).verify(&table);
+            assert!(
+                result.is_ok(),
+                "{}: {}",
+                netlist.name(),
+                result.unwrap_err()
+            );
+        }
+        assert!(ripple
+            .netlist()
+            .find_cell("full_add[3]/xor1/not0/nand0")
+            .is_some());
+
+        let table = TruthTable::from_fn(|x: &[bool; 3]| full_add(x[0], x[1], x[2]));
+        assert_eq!(
+            TruthTable::from_netlist(&full_adder_netlist()).verify(&table),
+            Ok(())
+        );
+    }
 }
@@ -0,0 +1,361 @@
+use core::fmt;
+use std::collections::HashMap;
+
+use crate::netlist::{nand3, CellKind, Net, Netlist};
+use crate::timing::{Delays, EventQueue};
+use crate::waveform::Waveform;
+
+/// What a flip-flop's output does after it samples D inside its setup and hold window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metastability {
+    /// The violation is reported and D is loaded as usual
+    Ignore,
+    /// The output is unknown until the flip-flop next loads a stable D
+    Unknown,
+    /// The output is unknown for the resolution time, then settles to a pseudo random value
+    /// drawn from the seed
+    Random { resolution: f64, seed: u64 },
+}
+
+/// Which side of the clock edge D changed on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ViolationKind {
+    Setup,
+    Hold,
+}
+
+/// A change of a flip-flop's D input too close to a rising edge of its clock
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    /// Path of the flip-flop
+    pub flipflop: String,
+    pub kind: ViolationKind,
+    /// Time of the clock edge
+    pub edge: f64,
+    /// Time D changed
+    pub change: f64,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (kind, side) = match self.kind {
+            ViolationKind::Setup => ("setup", "before"),
+            ViolationKind::Hold => ("hold", "after"),
+        };
+        write!(
+            f,
+            "{} violation at {}: d changed at {:.2}, {:.2} {} the clock edge at {:.2}",
+            kind,
+            self.flipflop,
+            self.change,
+            (self.edge - self.change).abs(),
+            side,
+            self.edge
+        )
+    }
+}
+
+/// Changes of every net of a sequential netlist driven by a timed stimulus, where None is an
+/// unknown value
+#[derive(Clone, Debug, PartialEq)]
+pub struct Simulation {
+    initial: Vec<bool>,
+    /// Time and new value of each change of each net
+    events: Vec<Vec<(f64, Option<bool>)>>,
+    violations: Vec<Violation>,
+}
+
+impl Simulation {
+    /// Value of the net before the first stimulus
+    pub fn initial(&self, net: Net) -> bool {
+        self.initial[net.index()]
+    }
+
+    /// Time and new value of each change of the net
+    pub fn events(&self, net: Net) -> &[(f64, Option<bool>)] {
+        &self.events[net.index()]
+    }
+
+    /// Value of the net once every change at or before the time has happened
+    pub fn value(&self, net: Net, time: f64) -> Option<bool> {
+        self.events[net.index()]
+            .iter()
+            .take_while(|(t, _)| *t <= time)
+            .last()
+            .map_or(Some(self.initial(net)), |(_, v)| *v)
+    }
+
+    /// Setup and hold violations in time order
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+}
+
+/// Timing of a flip-flop's inputs, for checking its setup and hold window
+#[derive(Clone, Copy, Debug)]
+struct Flipflop {
+    last_change: f64,
+    last_edge: f64,
+}
+
+/// Simulates a sequential netlist from its reset state with the delays of each NAND gate and
+/// flip-flop, applying the input values of the stimulus at its sample times. Inputs missing from
+/// the stimulus are held low. Every change of a D input within the setup time before or the hold
+/// time after a rising edge of its clock is reported, and the model decides what the flip-flop
+/// loads
+pub fn simulate(
+    netlist: &Netlist,
+    delays: &Delays,
+    model: Metastability,
+    stimulus: &Waveform,
+) -> Simulation {
+    assert!(
+        netlist.clock_depth().is_some(),
+        "netlist {} has a flip-flop clocked by itself",
+        netlist.name()
+    );
+    let ports: Vec<Net> = stimulus
+        .names()
+        .iter()
+        .map(|name| {
+            netlist
+                .find_input(name)
+                .unwrap_or_else(|| panic!("netlist {} has no input named {}", netlist.name(), name))
+        })
+        .collect();
+    let state = netlist.reset_state();
+    let initial = netlist.evaluate_nets(&vec![false; netlist.inputs().len()], &state.q);
+
+    let flipflop_ids = netlist.flipflops();
+    let index: HashMap<_, _> = flipflop_ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| (*id, i))
+        .collect();
+    let mut flipflops = vec![
+        Flipflop {
+            last_change: f64::NEG_INFINITY,
+            last_edge: f64::NEG_INFINITY,
+        };
+        flipflop_ids.len()
+    ];
+    let mut random = match model {
+        Metastability::Random { seed, .. } => seed.max(1),
+        _ => 1,
+    };
+
+    let initial_values = initial.iter().map(|v| Some(*v)).collect();
+    let mut queue = EventQueue::new(netlist, initial_values);
+    for (time, values) in stimulus.times().iter().zip(stimulus.samples()) {
+        for (net, value) in ports.iter().zip(values) {
+            queue.schedule(*time as f64, *net, Some(*value));
+        }
+    }
+
+    let mut violations = Vec::new();
+    while let Some(step) = queue.step() {
+        let (time, previous) = (step.time, &step.previous);
+        for id in step.cells {
+            let cell = netlist.cell(id);
+            if cell.kind() == CellKind::Nand {
+                let value = nand3(cell.inputs().iter().map(|n| queue.values[n.index()]));
+                queue.schedule(time + delays.nand_delay(netlist, id), cell.output(), value);
+                continue;
+            }
+
+            let [clk, d] = [cell.inputs()[0].index(), cell.inputs()[1].index()];
+            let ff = &mut flipflops[index[&id]];
+            let path = || netlist.cell_path(id);
+            let mut violated = false;
+            let mut sampled = None;
+            if let Some(old) = previous.get(&clk) {
+                if queue.values[clk] == Some(true) && *old != Some(true) {
+                    // The flip-flop sees D as it was just before the edge
+                    let before = previous.get(&d).copied().unwrap_or(queue.values[d]);
+                    sampled = if *old == Some(false) { before } else { None };
+                    ff.last_edge = time;
+                    let change = if previous.contains_key(&d) {
+                        time
+                    } else {
+                        ff.last_change
+                    };
+                    if time - change < delays.setup {
+                        violations.push(Violation {
+                            flipflop: path(),
+                            kind: ViolationKind::Setup,
+                            edge: time,
+                            change,
+                        });
+                        violated = true;
+                    }
+                }
+            }
+            if previous.contains_key(&d) {
+                ff.last_change = time;
+                if !violated && time - ff.last_edge < delays.hold {
+                    violations.push(Violation {
+                        flipflop: path(),
+                        kind: ViolationKind::Hold,
+                        edge: ff.last_edge,
+                        change: time,
+                    });
+                    violated = true;
+                }
+            }
+
+            // A hold violation found after the edge overrides the value it loaded
+            let load = time.max(ff.last_edge + delays.clk_to_q);
+            let clocked = ff.last_edge == time;
+            match (violated, model) {
+                (false, _) | (true, Metastability::Ignore) => {
+                    if clocked {
+                        queue.schedule(load, cell.output(), sampled);
+                    }
+                }
+                (true, Metastability::Unknown) => {
+                    queue.schedule(load, cell.output(), None);
+                }
+                (true, Metastability::Random { resolution, .. }) => {
+                    queue.schedule(load, cell.output(), None);
+                    let value = next_random(&mut random) & 1 == 1;
+                    queue.schedule(load + resolution, cell.output(), Some(value));
+                }
+            }
+        }
+    }
+
+    violations.sort_by(|a, b| a.edge.max(a.change).total_cmp(&b.edge.max(b.change)));
+    Simulation {
+        initial,
+        events: queue.events,
+        violations,
+    }
+}
+
+/// Advances a xorshift generator, which must not be zero
+fn next_random(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netlist::Builder;
+
+    /// Samples "data" on "clk" and passes it through a second flip-flop on the same clock
+    fn synchronizer() -> Netlist {
+        let mut b = Builder::new("synchronizer");
+        let clk = b.input("clk");
+        let data = b.input("data");
+        let meta = b.scoped("meta", |b| b.dff(clk, data));
+        let sync = b.scoped("sync", |b| b.dff(clk, meta));
+        b.output("meta", meta);
+        b.output("sync", sync);
+        b.finish()
+    }
+
+    /// Clock with a period of 10 rising at 10, 20, ..., with data rising at the given time
+    fn stimulus(data_rise: u64) -> Waveform {
+        let mut waveform = Waveform::new(vec!["clk".to_string(), "data".to_string()]);
+        let mut times: Vec<u64> = (1..10).map(|i| i * 5).collect();
+        times.push(data_rise);
+        times.sort_unstable();
+        times.dedup();
+        for time in times {
+            waveform.push(time, &[time % 10 == 0 && time > 0, time >= data_rise]);
+        }
+        waveform
+    }
+
+    fn delays() -> Delays {
+        let mut delays = Delays::new(1.0);
+        delays.setup = 2.0;
+        delays.hold = 2.0;
+        delays
+    }
+
+    #[test]
+    fn test_clean_capture() {
+        let netlist = synchronizer();
+        let sim = simulate(&netlist, &delays(), Metastability::Unknown, &stimulus(15));
+        assert!(sim.violations().is_empty(), "{:?}", sim.violations());
+
+        // Each flip-flop's output follows its clock by the clock to Q delay
+        let meta = netlist.find_output("meta").unwrap();
+        let sync = netlist.find_output("sync").unwrap();
+        assert_eq!(sim.events(meta), [(22.0, Some(true))]);
+        assert_eq!(sim.events(sync), [(32.0, Some(true))]);
+    }
+
+    #[test]
+    fn test_violations() {
+        let netlist = synchronizer();
+        for (data_rise, kind, edge) in [
+            (19, ViolationKind::Setup, 20.0),
+            (20, ViolationKind::Setup, 20.0),
+            (21, ViolationKind::Hold, 20.0),
+        ] {
+            let sim = simulate(
+                &netlist,
+                &delays(),
+                Metastability::Ignore,
+                &stimulus(data_rise),
+            );
+            let violation = Violation {
+                flipflop: "meta/dff0".to_string(),
+                kind,
+                edge,
+                change: data_rise as f64,
+            };
+            assert_eq!(
+                sim.violations(),
+                [violation],
+                "failed for inputs: {:?}",
+                data_rise
+            );
+        }
+
+        let sim = simulate(&netlist, &delays(), Metastability::Ignore, &stimulus(19));
+        assert_eq!(
+            sim.violations()[0].to_string(),
+            "setup violation at meta/dff0: d changed at 19.00, 1.00 before the clock edge at 20.00"
+        );
+    }
+
+    #[test]
+    fn test_metastability() {
+        let netlist = synchronizer();
+        let meta = netlist.find_output("meta").unwrap();
+        let sync = netlist.find_output("sync").unwrap();
+
+        // An unknown sample reaches the second flip-flop, then the next edge loads a clean value
+        let sim = simulate(&netlist, &delays(), Metastability::Unknown, &stimulus(19));
+        assert_eq!(sim.events(meta), [(22.0, None), (32.0, Some(true))]);
+        assert_eq!(sim.events(sync), [(32.0, None), (42.0, Some(true))]);
+
+        // Resolving well within a clock period keeps the second flip-flop clean
+        for seed in 1..20 {
+            let model = Metastability::Random {
+                resolution: 3.0,
+                seed,
+            };
+            let sim = simulate(&netlist, &delays(), model, &stimulus(19));
+            assert_eq!(sim.value(meta, 24.0), None, "failed for seed: {}", seed);
+            assert!(sim.value(meta, 25.0).is_some(), "failed for seed: {}", seed);
+            assert_eq!(
+                sim.value(meta, 40.0),
+                Some(true),
+                "failed for seed: {}",
+                seed
+            );
+            assert!(
+                sim.events(sync).iter().all(|(_, v)| v.is_some()),
+                "failed for seed: {}",
+                seed
+            );
+        }
+    }
+}
@@ -0,0 +1,830 @@
+use core::fmt;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::expr::Expr;
+use crate::netlist::{Builder, Net, Netlist};
+use crate::truth_table::TruthTable;
+
+/// Largest number of variables a cube can have
+pub const MAX_VARS: usize = 64;
+
+/// Functions with up to this many variables are minimized exactly with Quine-McCluskey
+pub const QUINE_MCCLUSKEY_MAX_VARS: usize = 12;
+
+/// Largest number of candidate primes searched exhaustively when choosing a minimal cover
+const EXACT_COVER_MAX_PRIMES: usize = 40;
+
+/// A product term. Variable i appears in the term when bit i of mask is set, and is inverted
+/// when bit i of value is clear
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Cube {
+    mask: u64,
+    value: u64,
+}
+
+impl Cube {
+    /// The cube containing every input
+    pub const UNIVERSE: Cube = Cube { mask: 0, value: 0 };
+
+    /// The cube containing a single minterm of num_vars variables
+    pub fn minterm(minterm: usize, num_vars: usize) -> Self {
+        let mask = low_bits(num_vars);
+        Cube {
+            mask,
+            value: minterm as u64 & mask,
+        }
+    }
+
+    /// Creates a cube from the polarity of each variable, with None for absent variables
+    pub fn from_literals(literals: &[Option<bool>]) -> Self {
+        assert!(literals.len() <= MAX_VARS, "too many variables for a cube");
+        let mut cube = Cube::UNIVERSE;
+        for (i, literal) in literals.iter().enumerate() {
+            if let Some(polarity) = literal {
+                cube.mask |= 1 << i;
+                cube.value |= (*polarity as u64) << i;
+            }
+        }
+        cube
+    }
+
+    /// Polarity of a variable in the term, or None if it doesn't appear
+    pub fn literal(&self, var: usize) -> Option<bool> {
+        (self.mask >> var & 1 == 1).then_some(self.value >> var & 1 == 1)
+    }
+
+    /// Number of literals in the term
+    pub fn num_literals(&self) -> usize {
+        self.mask.count_ones() as usize
+    }
+
+    pub fn contains(&self, minterm: usize) -> bool {
+        (minterm as u64 ^ self.value) & self.mask == 0
+    }
+
+    /// Whether every input in other is also in self
+    pub fn covers(&self, other: &Cube) -> bool {
+        self.mask & !other.mask == 0 && (self.value ^ other.value) & self.mask == 0
+    }
+
+    pub fn intersect(&self, other: &Cube) -> Option<Cube> {
+        if (self.value ^ other.value) & self.mask & other.mask != 0 {
+            return None;
+        }
+        Some(Cube {
+            mask: self.mask | other.mask,
+            value: self.value | other.value,
+        })
+    }
+
+    /// Evaluates the term for the given inputs
+    pub fn evaluate(&self, inputs: &[bool]) -> bool {
+        (0..inputs.len()).all(|i| self.literal(i).is_none_or(|p| inputs[i] == p))
+    }
+
+    /// Minterms contained in the cube
+    pub fn minterms(&self, num_vars: usize) -> Vec<usize> {
+        let free: Vec<usize> = (0..num_vars).filter(|i| self.mask >> i & 1 == 0).collect();
+        (0..1usize << free.len())
+            .map(|combination| {
+                let mut minterm = self.value as usize;
+                for (bit, var) in free.iter().enumerate() {
+                    minterm |= (combination >> bit & 1) << var;
+                }
+                minterm
+            })
+            .collect()
+    }
+
+    /// Writes the cube as a string of 0, 1 and - with variable 0 first
+    pub fn to_pattern(&self, num_vars: usize) -> String {
+        (0..num_vars)
+            .map(|i| match self.literal(i) {
+                None => '-',
+                Some(true) => '1',
+                Some(false) => '0',
+            })
+            .collect()
+    }
+
+    /// Removes the given variables from the cube
+    fn without(&self, mask: u64) -> Cube {
+        Cube {
+            mask: self.mask & !mask,
+            value: self.value & !mask,
+        }
+    }
+
+    /// The cube restricted to the inputs where var has the given polarity
+    fn with_literal(&self, var: usize, polarity: bool) -> Cube {
+        Cube {
+            mask: self.mask | 1 << var,
+            value: (self.value & !(1 << var)) | (polarity as u64) << var,
+        }
+    }
+
+    fn cofactor(&self, p: &Cube) -> Option<Cube> {
+        self.intersect(p).map(|_| self.without(p.mask))
+    }
+}
+
+/// A sum of products over a number of variables
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cover {
+    num_vars: usize,
+    cubes: Vec<Cube>,
+}
+
+impl Cover {
+    pub fn new(num_vars: usize, cubes: Vec<Cube>) -> Self {
+        assert!(num_vars <= MAX_VARS, "too many variables for a cover");
+        Cover { num_vars, cubes }
+    }
+
+    /// Creates a cover from patterns of 0, 1 and - with variable 0 first, e.g. "1-0"
+    pub fn from_patterns(num_vars: usize, patterns: &[&str]) -> Result<Self, PatternError> {
+        let cubes = patterns
+            .iter()
+            .map(|pattern| {
+                let literals = pattern
+                    .chars()
+                    .map(|c| match c {
+                        '0' => Ok(Some(false)),
+                        '1' => Ok(Some(true)),
+                        '-' => Ok(None),
+                        _ => Err(PatternError(pattern.to_string())),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if literals.len() != num_vars {
+                    return Err(PatternError(pattern.to_string()));
+                }
+                Ok(Cube::from_literals(&literals))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Cover::new(num_vars, cubes))
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.num_vars
+    }
+
+    pub fn cubes(&self) -> &[Cube] {
+        &self.cubes
+    }
+
+    /// Total number of literals in every term
+    pub fn num_literals(&self) -> usize {
+        self.cubes.iter().map(Cube::num_literals).sum()
+    }
+
+    /// Cost used to compare covers: fewest terms first, then fewest literals
+    pub fn cost(&self) -> (usize, usize) {
+        (self.cubes.len(), self.num_literals())
+    }
+
+    pub fn evaluate(&self, inputs: &[bool]) -> bool {
+        self.cubes.iter().any(|c| c.evaluate(inputs))
+    }
+
+    /// Minterms contained in at least one term, in ascending order
+    pub fn minterms(&self) -> Vec<usize> {
+        let minterms: BTreeSet<usize> = self
+            .cubes
+            .iter()
+            .flat_map(|c| c.minterms(self.num_vars))
+            .collect();
+        minterms.into_iter().collect()
+    }
+
+    /// Converts the cover into an expression using the given variable names
+    pub fn to_expr(&self, names: &[&str]) -> Expr {
+        assert_eq!(names.len(), self.num_vars, "wrong number of variable names");
+        let product = |cube: &Cube| -> Expr {
+            let literals: Vec<Expr> = (0..self.num_vars)
+                .filter_map(|i| {
+                    cube.literal(i).map(|p| {
+                        let var = Expr::Var(names[i].to_string());
+                        if p {
+                            var
+                        } else {
+                            Expr::Not(Box::new(var))
+                        }
+                    })
+                })
+                .collect();
+            match literals.len() {
+                0 => Expr::Const(true),
+                1 => literals.into_iter().next().unwrap(),
+                _ => Expr::And(literals),
+            }
+        };
+
+        match self.cubes.len() {
+            0 => Expr::Const(false),
+            1 => product(&self.cubes[0]),
+            _ => Expr::Or(self.cubes.iter().map(product).collect()),
+        }
+    }
+
+    /// Builds the cover as a two level NAND-NAND circuit. Each term is a NAND of its literals and
+    /// the output is a NAND of the terms
+    pub fn build(&self, b: &mut Builder, inputs: &[Net]) -> Net {
+        assert_eq!(inputs.len(), self.num_vars, "wrong number of inputs");
+        if self.cubes.is_empty() {
+            return b.constant(false);
+        }
+        if self.cubes.contains(&Cube::UNIVERSE) {
+            return b.constant(true);
+        }
+
+        let mut inverted = HashMap::new();
+        let terms: Vec<Net> = self
+            .cubes
+            .iter()
+            .map(|cube| {
+                let literals: Vec<Net> = (0..self.num_vars)
+                    .filter_map(|i| {
+                        cube.literal(i).map(|p| {
+                            if p {
+                                inputs[i]
+                            } else {
+                                *inverted.entry(i).or_insert_with(|| b.not(inputs[i]))
+                            }
+                        })
+                    })
+                    .collect();
+                b.nand(&literals)
+            })
+            .collect();
+        b.nand(&terms)
+    }
+
+    /// Synthesizes the cover into a NAND-NAND netlist with one input per variable
+    pub fn to_netlist(&self, names: &[&str]) -> Netlist {
+        assert_eq!(names.len(), self.num_vars, "wrong number of variable names");
+        let mut b = Builder::new("sop");
+        let inputs: Vec<Net> = names.iter().map(|name| b.input(name)).collect();
+        let out = self.build(&mut b, &inputs);
+        b.output("out", out);
+        b.finish()
+    }
+}
+
+impl fmt::Display for Cover {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<String> = (0..self.num_vars).map(|i| format!("x{}", i)).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        write!(f, "{}", self.to_expr(&names))
+    }
+}
+
+/// Error returned for a cube pattern which isn't made of 0, 1 and - or has the wrong length
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PatternError(String);
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid cube pattern {:?}", self.0)
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+/// Finds a minimal cover of a function, using Quine-McCluskey for small functions and the
+/// Espresso heuristic for larger ones
+pub fn minimize(num_vars: usize, minterms: &[usize], dont_cares: &[usize]) -> Cover {
+    if num_vars <= QUINE_MCCLUSKEY_MAX_VARS {
+        quine_mccluskey(num_vars, minterms, dont_cares)
+    } else {
+        let on = Cover::new(
+            num_vars,
+            minterms
+                .iter()
+                .map(|m| Cube::minterm(*m, num_vars))
+                .collect(),
+        );
+        let dc = Cover::new(
+            num_vars,
+            dont_cares
+                .iter()
+                .map(|m| Cube::minterm(*m, num_vars))
+                .collect(),
+        );
+        espresso(&on, &dc)
+    }
+}
+
+/// Finds a minimal cover of one output of a truth table
+pub fn minimize_table(table: &TruthTable, output: usize) -> Cover {
+    minimize(table.num_inputs(), &table.minterms(output), &[])
+}
+
+/// Finds a cover with the fewest terms, then the fewest literals, by generating every prime
+/// implicant and choosing among them. Large covering problems fall back to a greedy choice
+pub fn quine_mccluskey(num_vars: usize, minterms: &[usize], dont_cares: &[usize]) -> Cover {
+    assert!(num_vars <= MAX_VARS, "too many variables for a cover");
+    let primes = prime_implicants(num_vars, minterms, dont_cares);
+
+    let minterms: BTreeSet<usize> = minterms
+        .iter()
+        .copied()
+        .filter(|m| !dont_cares.contains(m))
+        .collect();
+    let mut uncovered = minterms.clone();
+    let mut chosen = Vec::new();
+
+    // Essential primes are the only ones covering some minterm
+    for m in &minterms {
+        let covering: Vec<&Cube> = primes.iter().filter(|p| p.contains(*m)).collect();
+        if covering.len() == 1 && !chosen.contains(covering[0]) {
+            chosen.push(*covering[0]);
+        }
+    }
+    uncovered.retain(|m| !chosen.iter().any(|c| c.contains(*m)));
+
+    let candidates: Vec<Cube> = primes
+        .iter()
+        .filter(|p| !chosen.contains(p) && uncovered.iter().any(|m| p.contains(*m)))
+        .copied()
+        .collect();
+    let uncovered: Vec<usize> = uncovered.into_iter().collect();
+    if candidates.len() <= EXACT_COVER_MAX_PRIMES {
+        let mut best = None;
+        exact_cover(&candidates, &uncovered, &mut Vec::new(), &mut best);
+        chosen.extend(best.unwrap_or_default());
+    } else {
+        chosen.extend(greedy_cover(&candidates, &uncovered));
+    }
+
+    chosen.sort();
+    Cover::new(num_vars, chosen)
+}
+
+/// Generates every prime implicant by repeatedly merging cubes which differ in one variable
+fn prime_implicants(num_vars: usize, minterms: &[usize], dont_cares: &[usize]) -> Vec<Cube> {
+    let mut current: BTreeSet<Cube> = minterms
+        .iter()
+        .chain(dont_cares)
+        .map(|m| Cube::minterm(*m, num_vars))
+        .collect();
+    let mut primes = Vec::new();
+
+    while !current.is_empty() {
+        let cubes: Vec<Cube> = current.iter().copied().collect();
+        let mut merged = vec![false; cubes.len()];
+        let mut next = BTreeSet::new();
+
+        // Only cubes with the same variables and one more true literal can merge
+        let mut groups: HashMap<(u64, u32), Vec<usize>> = HashMap::new();
+        for (i, c) in cubes.iter().enumerate() {
+            groups
+                .entry((c.mask, c.value.count_ones()))
+                .or_default()
+                .push(i);
+        }
+        for (i, a) in cubes.iter().enumerate() {
+            let Some(group) = groups.get(&(a.mask, a.value.count_ones() + 1)) else {
+                continue;
+            };
+            for j in group {
+                let b = &cubes[*j];
+                let diff = a.value ^ b.value;
+                if diff.count_ones() == 1 {
+                    merged[i] = true;
+                    merged[*j] = true;
+                    next.insert(a.without(diff));
+                }
+            }
+        }
+
+        primes.extend(
+            cubes
+                .iter()
+                .zip(&merged)
+                .filter(|(_, m)| !**m)
+                .map(|(c, _)| *c),
+        );
+        current = next;
+    }
+    primes
+}
+
+/// Branch and bound search for the cheapest set of cubes covering every minterm
+fn exact_cover(
+    candidates: &[Cube],
+    uncovered: &[usize],
+    chosen: &mut Vec<Cube>,
+    best: &mut Option<Vec<Cube>>,
+) {
+    let cost = |cubes: &[Cube]| {
+        (
+            cubes.len(),
+            cubes.iter().map(Cube::num_literals).sum::<usize>(),
+        )
+    };
+    if best.as_ref().is_some_and(|best| cost(chosen) >= cost(best)) {
+        return;
+    }
+
+    let Some(m) = uncovered.first() else {
+        *best = Some(chosen.clone());
+        return;
+    };
+
+    // Branch on each cube covering the first uncovered minterm, largest cubes first
+    let mut covering: Vec<&Cube> = candidates.iter().filter(|c| c.contains(*m)).collect();
+    covering.sort_by_key(|c| c.num_literals());
+    for cube in covering {
+        let remaining: Vec<usize> = uncovered
+            .iter()
+            .copied()
+            .filter(|m| !cube.contains(*m))
+            .collect();
+        chosen.push(*cube);
+        exact_cover(candidates, &remaining, chosen, best);
+        chosen.pop();
+    }
+}
+
+/// Repeatedly chooses the cube covering the most uncovered minterms
+fn greedy_cover(candidates: &[Cube], uncovered: &[usize]) -> Vec<Cube> {
+    let mut uncovered: BTreeSet<usize> = uncovered.iter().copied().collect();
+    let mut chosen = Vec::new();
+    while !uncovered.is_empty() {
+        let best = candidates
+            .iter()
+            .max_by_key(|c| {
+                let count = uncovered.iter().filter(|m| c.contains(**m)).count();
+                (count, usize::MAX - c.num_literals())
+            })
+            .unwrap();
+        uncovered.retain(|m| !best.contains(*m));
+        chosen.push(*best);
+    }
+    chosen
+}
+
+/// Heuristically minimizes a cover with the Espresso loop of expanding terms into primes,
+/// removing redundant terms and reducing terms to give the next expansion a different start.
+/// Works on cubes directly, so it scales to functions too large to list the minterms of
+pub fn espresso(on: &Cover, dont_care: &Cover) -> Cover {
+    assert_eq!(
+        on.num_vars, dont_care.num_vars,
+        "covers have different variables"
+    );
+    let n = on.num_vars;
+    let off = complement(&[on.cubes.clone(), dont_care.cubes.clone()].concat(), n);
+
+    let mut f = expand(&on.cubes, &off, n);
+    f = irredundant(&f, &dont_care.cubes);
+    let mut best = Cover::new(n, f.clone());
+    loop {
+        f = reduce(&f, &dont_care.cubes, n);
+        f = expand(&f, &off, n);
+        f = irredundant(&f, &dont_care.cubes);
+
+        let cover = Cover::new(n, f.clone());
+        if cover.cost() >= best.cost() {
+            break;
+        }
+        best = cover;
+    }
+
+    best.cubes.sort();
+    best
+}
+
+/// Expands each cube into a prime by removing literals while it stays clear of the off-set,
+/// dropping cubes covered by an expanded cube
+fn expand(cubes: &[Cube], off: &[Cube], num_vars: usize) -> Vec<Cube> {
+    // Expand the largest cubes first since they are most likely to cover others
+    let mut pending: Vec<Cube> = cubes.to_vec();
+    pending.sort_by_key(|c| c.num_literals());
+
+    let mut expanded: Vec<Cube> = Vec::new();
+    for cube in pending {
+        if expanded.iter().any(|e| e.covers(&cube)) {
+            continue;
+        }
+
+        let mut cube = cube;
+        for var in 0..num_vars {
+            if cube.literal(var).is_none() {
+                continue;
+            }
+            let raised = cube.without(1 << var);
+            if off.iter().all(|o| raised.intersect(o).is_none()) {
+                cube = raised;
+            }
+        }
+        expanded.retain(|e| !cube.covers(e));
+        expanded.push(cube);
+    }
+    expanded
+}
+
+/// Removes cubes covered by the rest of the cover and the don't cares
+fn irredundant(cubes: &[Cube], dont_care: &[Cube]) -> Vec<Cube> {
+    // Try to remove the smallest cubes first, since they contribute the fewest minterms
+    let mut order: Vec<usize> = (0..cubes.len()).collect();
+    order.sort_by_key(|i| core::cmp::Reverse(cubes[*i].num_literals()));
+
+    let mut keep = vec![true; cubes.len()];
+    for i in order {
+        let rest: Vec<Cube> = cubes
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i && keep[*j])
+            .map(|(_, c)| *c)
+            .chain(dont_care.iter().copied())
+            .collect();
+        if cube_covered_by(&cubes[i], &rest) {
+            keep[i] = false;
+        }
+    }
+    cubes
+        .iter()
+        .zip(keep)
+        .filter(|(_, k)| *k)
+        .map(|(c, _)| *c)
+        .collect()
+}
+
+/// Shrinks each cube to the smallest cube containing the minterms only it covers
+fn reduce(cubes: &[Cube], dont_care: &[Cube], num_vars: usize) -> Vec<Cube> {
+    let mut reduced = cubes.to_vec();
+    for i in 0..reduced.len() {
+        let rest: Vec<Cube> = reduced
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, c)| *c)
+            .chain(dont_care.iter().copied())
+            .collect();
+
+        // Minterms of the cube not covered elsewhere, as cubes within the cube
+        let cofactors: Vec<Cube> = rest
+            .iter()
+            .filter_map(|c| c.cofactor(&reduced[i]))
+            .collect();
+        let unique = complement(&cofactors, num_vars);
+        if let Some(supercube) = supercube(&unique) {
+            if let Some(cube) = reduced[i].intersect(&supercube) {
+                reduced[i] = cube;
+            }
+        }
+    }
+    reduced
+}
+
+/// Smallest cube containing every cube
+fn supercube(cubes: &[Cube]) -> Option<Cube> {
+    let first = cubes.first()?;
+    Some(cubes.iter().fold(*first, |acc, c| {
+        let mask = acc.mask & c.mask & !(acc.value ^ c.value);
+        Cube {
+            mask,
+            value: acc.value & mask,
+        }
+    }))
+}
+
+fn cube_covered_by(cube: &Cube, cover: &[Cube]) -> bool {
+    let cofactors: Vec<Cube> = cover.iter().filter_map(|c| c.cofactor(cube)).collect();
+    tautology(&cofactors)
+}
+
+/// Whether the cover contains every input
+fn tautology(cubes: &[Cube]) -> bool {
+    if cubes.contains(&Cube::UNIVERSE) {
+        return true;
+    }
+    match binate_variable(cubes) {
+        // A unate cover is a tautology only if it has the universal cube
+        None => false,
+        Some(var) => [false, true].iter().all(|p| {
+            let literal = Cube::UNIVERSE.with_literal(var, *p);
+            let cofactors: Vec<Cube> = cubes.iter().filter_map(|c| c.cofactor(&literal)).collect();
+            tautology(&cofactors)
+        }),
+    }
+}
+
+/// Cover of every input not in the given cubes
+fn complement(cubes: &[Cube], num_vars: usize) -> Vec<Cube> {
+    if cubes.is_empty() {
+        return vec![Cube::UNIVERSE];
+    }
+    if cubes.contains(&Cube::UNIVERSE) {
+        return Vec::new();
+    }
+    if let [cube] = cubes {
+        // De Morgan: one cube per inverted literal
+        return (0..num_vars)
+            .filter_map(|i| cube.literal(i).map(|p| Cube::UNIVERSE.with_literal(i, !p)))
+            .collect();
+    }
+
+    let var = binate_variable(cubes).unwrap_or_else(|| most_common_variable(cubes));
+    let mut result = Vec::new();
+    for polarity in [false, true] {
+        let literal = Cube::UNIVERSE.with_literal(var, polarity);
+        let cofactors: Vec<Cube> = cubes.iter().filter_map(|c| c.cofactor(&literal)).collect();
+        for c in complement(&cofactors, num_vars) {
+            result.push(c.with_literal(var, polarity));
+        }
+    }
+
+    // Merge the two halves where a cube appears with both polarities of the split variable
+    let mut merged: Vec<Cube> = Vec::new();
+    for c in result {
+        let partner = c.without(1 << var);
+        if let Some(existing) = merged
+            .iter_mut()
+            .find(|m| m.without(1 << var) == partner && m.mask == c.mask && *m != &c)
+        {
+            *existing = partner;
+        } else if !merged.iter().any(|m| m.covers(&c)) {
+            merged.push(c);
+        }
+    }
+    merged
+}
+
+/// The variable appearing in both polarities in the most cubes
+fn binate_variable(cubes: &[Cube]) -> Option<usize> {
+    (0..MAX_VARS)
+        .filter(|v| {
+            let positive = cubes.iter().any(|c| c.literal(*v) == Some(true));
+            let negative = cubes.iter().any(|c| c.literal(*v) == Some(false));
+            positive && negative
+        })
+        .max_by_key(|v| cubes.iter().filter(|c| c.literal(*v).is_some()).count())
+}
+
+fn most_common_variable(cubes: &[Cube]) -> usize {
+    (0..MAX_VARS)
+        .max_by_key(|v| cubes.iter().filter(|c| c.literal(*v).is_some()).count())
+        .unwrap()
+}
+
+fn low_bits(n: usize) -> u64 {
+    if n >= 64 {
+        u64::MAX
+    } else {
+        (1 << n) - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::Bus;
+    use crate::mux::{mux4, mux8};
+
+    /// Checks that a cover matches the function on every minterm which isn't a don't care
+    fn check_cover(cover: &Cover, minterms: &[usize], dont_cares: &[usize]) {
+        for m in 0..1 << cover.num_vars() {
+            if dont_cares.contains(&m) {
+                continue;
+            }
+            let inputs: Vec<bool> = (0..cover.num_vars()).map(|i| m >> i & 1 == 1).collect();
+            assert_eq!(
+                cover.evaluate(&inputs),
+                minterms.contains(&m),
+                "failed for minterm {} of cover {}",
+                m,
+                cover
+            );
+        }
+    }
+
+    #[test]
+    fn test_quine_mccluskey() {
+        // Classic example: f = sum of minterms 4, 8, 10, 11, 12, 15 with don't cares 9, 14
+        let minterms = [4, 8, 10, 11, 12, 15];
+        let dont_cares = [9, 14];
+        let cover = quine_mccluskey(4, &minterms, &dont_cares);
+        check_cover(&cover, &minterms, &dont_cares);
+        assert_eq!(cover.cost(), (3, 7));
+
+        for (num_vars, minterms, expected) in [
+            (2, vec![], (0, 0)),
+            (2, vec![0, 1, 2, 3], (1, 0)),
+            (3, vec![1, 3, 5, 7], (1, 1)),
+            (3, vec![0, 3, 5, 6], (4, 12)),
+        ] {
+            let cover = quine_mccluskey(num_vars, &minterms, &[]);
+            check_cover(&cover, &minterms, &[]);
+            assert_eq!(cover.cost(), expected, "failed for inputs: {:?}", minterms);
+        }
+    }
+
+    #[test]
+    fn test_cyclic_cover() {
+        // Every minterm is covered by two primes, so there are no essential primes
+        let minterms = [0, 1, 2, 5, 6, 7];
+        let cover = quine_mccluskey(3, &minterms, &[]);
+        check_cover(&cover, &minterms, &[]);
+        assert_eq!(cover.cost(), (3, 6));
+    }
+
+    #[test]
+    fn test_espresso() {
+        for (num_vars, patterns, expected) in [
+            (4, vec!["0000", "0001", "0011", "0010"], (1, 2)),
+            (3, vec!["1-0", "110", "11-", "0-1"], (3, 6)),
+            (4, vec!["--11", "11--", "1111"], (2, 4)),
+        ] {
+            let on = Cover::from_patterns(num_vars, &patterns).unwrap();
+            let cover = espresso(&on, &Cover::new(num_vars, vec![]));
+            check_cover(&cover, &on.minterms(), &[]);
+            assert_eq!(cover.cost(), expected, "failed for inputs: {:?}", patterns);
+        }
+
+        // Don't cares let the terms grow
+        let on = Cover::from_patterns(3, &["110"]).unwrap();
+        let dc = Cover::from_patterns(3, &["-10", "1-0"]).unwrap();
+        let cover = espresso(&on, &dc);
+        assert!(cover.cost() <= (1, 1), "failed for cover {}", cover);
+    }
+
+    #[test]
+    fn test_espresso_large() {
+        // out = x0 & x19 | !x0 & x5 over 20 inputs, given with a redundant consensus term
+        let on = Cover::from_patterns(
+            20,
+            &[
+                "1------------------1",
+                "0----1--------------",
+                "-----1-------------1",
+            ],
+        )
+        .unwrap();
+        let cover = espresso(&on, &Cover::new(20, vec![]));
+        assert_eq!(cover.cost(), (2, 4), "failed for cover {}", cover);
+        for cube in &on.cubes()[..2] {
+            assert!(cover.cubes().contains(cube), "failed for cover {}", cover);
+        }
+    }
+
+    #[test]
+    fn test_complement() {
+        let cubes = Cover::from_patterns(3, &["1-0", "01-"]).unwrap();
+        let off = complement(cubes.cubes(), 3);
+        for m in 0..8 {
+            assert_ne!(
+                cubes.cubes().iter().any(|c| c.contains(m)),
+                off.iter().any(|c| c.contains(m)),
+                "failed for minterm {}",
+                m
+            );
+        }
+        assert!(tautology(&[cubes.cubes(), &off[..]].concat()));
+        assert!(!tautology(cubes.cubes()));
+    }
+
+    #[test]
+    fn test_muxes_are_minimal() {
+        // The sum of products in mux4 and mux8 has one term per input, which is minimal
+        let table = TruthTable::from_fn(|x: &[bool; 6]| {
+            let x = Bus::new(*x);
+            mux4(&x.slice::<0, 2>(), &x.slice::<2, 4>())
+        });
+        assert_eq!(minimize_table(&table, 0).cost(), (4, 12));
+
+        let table = TruthTable::from_fn(|x: &[bool; 11]| {
+            let x = Bus::new(*x);
+            mux8(&x.slice::<0, 3>(), &x.slice::<3, 8>())
+        });
+        assert_eq!(minimize_table(&table, 0).cost(), (8, 32));
+    }
+
+    #[test]
+    fn test_nand_nand_netlist() {
+        let minterms = [1, 3, 4, 5, 7];
+        let cover = quine_mccluskey(3, &minterms, &[]);
+        let netlist = cover.to_netlist(&["a", "b", "c"]);
+
+        let table = TruthTable::from_netlist(&netlist);
+        assert_eq!(table.minterms(0), minterms);
+
+        // One inverter, a NAND per term and the output NAND
+        assert_eq!(cover.to_expr(&["a", "b", "c"]).to_string(), "a | !b & c");
+        assert_eq!(netlist.nand_count(), 1 + 2 + 1);
+    }
+
+    #[test]
+    fn test_cube() {
+        let cube = Cube::from_literals(&[Some(true), None, Some(false)]);
+        assert_eq!(cube.to_pattern(3), "1-0");
+        assert_eq!(cube.minterms(3), [1, 3]);
+        assert!(cube.covers(&Cube::minterm(3, 3)));
+        assert!(!cube.covers(&Cube::minterm(5, 3)));
+        assert_eq!(cube.num_literals(), 2);
+        assert_eq!(
+            Cover::from_patterns(3, &["1-2"]),
+            Err(PatternError("1-2".to_string()))
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::gate::{and, not, or};
+use crate::netlist::{Builder, Net};
 
 /// Returns the input bit corresponding to the select value
 pub fn mux2(select: bool, input: &[bool; 2]) -> bool {
@@ -63,10 +64,96 @@ pub fn mux32(select: &[bool; 5], input: &[bool; 32]) -> bool {
     )
 }
 
+/// Builds a mux2 in its own scope, mirroring [mux2]
+pub fn build_mux2(builder: &mut Builder, select: Net, input: &[Net; 2]) -> Net {
+    builder.instance("mux2", |builder| {
+        let not_select = builder.not(select);
+        let low = builder.and(&[not_select, input[0]]);
+        let high = builder.and(&[select, input[1]]);
+        builder.or(&[low, high])
+    })
+}
+
+/// Builds a mux4 in its own scope, mirroring [mux4]
+pub fn build_mux4(builder: &mut Builder, select: &[Net; 2], input: &[Net; 4]) -> Net {
+    builder.instance("mux4", |builder| decoded_mux_cells(builder, select, input))
+}
+
+/// Builds a mux8 in its own scope, mirroring [mux8]
+pub fn build_mux8(builder: &mut Builder, select: &[Net; 3], input: &[Net; 8]) -> Net {
+    builder.instance("mux8", |builder| decoded_mux_cells(builder, select, input))
+}
+
+/// Builds a mux16 in its own scope, mirroring [mux16]
+pub fn build_mux16(builder: &mut Builder, select: &[Net; 4], input: &[Net; 16]) -> Net {
+    builder.instance("mux16", |builder| {
+        let low = build_mux8(
+            builder,
+            select[..3].try_into().unwrap(),
+            input[0..8].try_into().unwrap(),
+        );
+        let high = build_mux8(
+            builder,
+            select[..3].try_into().unwrap(),
+            input[8..16].try_into().unwrap(),
+        );
+        build_mux2(builder, select[3], &[low, high])
+    })
+}
+
+/// Builds a mux32 in its own scope, mirroring [mux32]
+pub fn build_mux32(builder: &mut Builder, select: &[Net; 5], input: &[Net; 32]) -> Net {
+    builder.instance("mux32", |builder| {
+        let low = build_mux16(
+            builder,
+            select[..4].try_into().unwrap(),
+            input[0..16].try_into().unwrap(),
+        );
+        let high = build_mux16(
+            builder,
+            select[..4].try_into().unwrap(),
+            input[16..].try_into().unwrap(),
+        );
+        build_mux2(builder, select[4], &[low, high])
+    })
+}
+
+/// Builds an OR of one AND term per input, each selecting the input when the select value
+/// matches its index
+fn decoded_mux_cells(builder: &mut Builder, select: &[Net], input: &[Net]) -> Net {
+    let inverted: Vec<Net> = select.iter().map(|s| builder.not(*s)).collect();
+    let terms: Vec<Net> = input
+        .iter()
+        .enumerate()
+        .map(|(i, input)| {
+            let mut literals = vec![*input];
+            for (bit, s) in select.iter().enumerate() {
+                literals.push(if i >> bit & 1 == 1 { *s } else { inverted[bit] });
+            }
+            builder.and(&literals)
+        })
+        .collect();
+    builder.or(&terms)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::bus;
+    use crate::bus::{self, Bus};
+    use crate::netlist::Netlist;
+    use crate::truth_table::TruthTable;
+
+    /// Builds a netlist around a mux builder, with inputs "select[i]" and "input[i]"
+    fn mux_netlist<const S: usize, const N: usize>(
+        build: impl FnOnce(&mut Builder, &[Net; S], &[Net; N]) -> Net,
+    ) -> Netlist {
+        let mut builder = Builder::new("mux");
+        let select = builder.input_bus::<S>("select");
+        let input = builder.input_bus::<N>("input");
+        let out = build(&mut builder, &select, &input);
+        builder.output("out", out);
+        builder.finish()
+    }
 
     #[test]
     fn test_mux2() {
@@ -204,4 +291,62 @@ mod test {
             )
         }
     }
+
+    #[test]
+    fn test_mux8_exhaustive() {
+        let table = TruthTable::from_fn(|x: &[bool; 11]| {
+            let x = Bus::new(*x);
+            mux8(&x.slice::<0, 3>(), &x.slice::<3, 8>())
+        });
+
+        let reference = |x: &[bool; 11]| {
+            let x = Bus::new(*x);
+            let select = u8::try_from(x.slice::<0, 3>()).unwrap() as usize;
+            x.slice::<3, 8>()[select]
+        };
+
+        if let Err(mismatches) = table.verify_fn(reference) {
+            panic!("{}", mismatches);
+        }
+    }
+
+    #[test]
+    fn test_mux16_exhaustive() {
+        let table = TruthTable::from_fn(|x: &[bool; 20]| {
+            let x = Bus::new(*x);
+            mux16(&x.slice::<0, 4>(), &x.slice::<4, 16>())
+        });
+
+        let reference = |x: &[bool; 20]| {
+            let x = Bus::new(*x);
+            let select = u8::try_from(x.slice::<0, 4>()).unwrap() as usize;
+            x.slice::<4, 16>()[select]
+        };
+
+        if let Err(mismatches) = table.verify_fn(reference) {
+            panic!("{}", mismatches);
+        }
+    }
+
+    #[test]
+    fn test_mux_netlists() {
+        let netlist = mux_netlist::<1, 2>(|b, s, i| build_mux2(b, s[0], i));
+        let table = TruthTable::from_fn(|x: &[bool; 3]| mux2(x[0], &[x[1], x[2]]));
+        assert_eq!(TruthTable::from_netlist(&netlist).verify(&table), Ok(()));
+
+        let netlist = mux_netlist(build_mux4);
+        let table = TruthTable::from_fn(|x: &[bool; 6]| {
+            let x = Bus::new(*x);
+            mux4(&x.slice::<0, 2>(), &x.slice::<2, 4>())
+        });
+        assert_eq!(TruthTable::from_netlist(&netlist).verify(&table), Ok(()));
+
+        let netlist = mux_netlist(build_mux8);
+        let table = TruthTable::from_fn(|x: &[bool; 11]| {
+            let x = Bus::new(*x);
+            mux8(&x.slice::<0, 3>(), &x.slice::<3, 8>())
+        });
+        assert_eq!(TruthTable::from_netlist(&netlist).verify(&table), Ok(()));
+        assert!(netlist.find_cell("mux80/and3/nand0").is_some());
+    }
 }
@@ -0,0 +1,1303 @@
+use core::fmt;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// A wire in a netlist
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Net(usize);
+
+impl Net {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// A cell in a netlist
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CellId(usize);
+
+impl CellId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// A level of the design hierarchy, such as an instance of a derived gate or a component
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScopeId(usize);
+
+impl ScopeId {
+    /// The top level of every netlist
+    pub const TOP: ScopeId = ScopeId(0);
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CellKind {
+    /// NAND gate with any number of inputs. This is the only logic primitive
+    Nand,
+    /// Drives a constant value
+    Const(bool),
+    /// Rising edge triggered D flip-flop with the inputs [clk, d], behaving like
+    /// [DFlipflop](crate::flipflop::DFlipflop)
+    Dff,
+}
+
+impl CellKind {
+    /// Base name used when naming instances of the cell
+    pub fn name(&self) -> &'static str {
+        match self {
+            CellKind::Nand => "nand",
+            CellKind::Const(false) => "tie0",
+            CellKind::Const(true) => "tie1",
+            CellKind::Dff => "dff",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cell {
+    kind: CellKind,
+    inputs: Vec<Net>,
+    output: Net,
+    name: String,
+    scope: ScopeId,
+}
+
+impl Cell {
+    pub fn kind(&self) -> CellKind {
+        self.kind
+    }
+
+    pub fn inputs(&self) -> &[Net] {
+        &self.inputs
+    }
+
+    pub fn output(&self) -> Net {
+        self.output
+    }
+
+    /// Instance name of the cell within its scope, e.g. "nand2"
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn scope(&self) -> ScopeId {
+        self.scope
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scope {
+    name: String,
+    parent: Option<ScopeId>,
+}
+
+impl Scope {
+    /// Instance name of the scope within its parent, e.g. "full_add[3]"
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn parent(&self) -> Option<ScopeId> {
+        self.parent
+    }
+
+    /// Whether the scope is a numbered instance of a base name, e.g. "xor3" of "xor"
+    pub fn is_instance_of(&self, base: &str) -> bool {
+        self.name
+            .strip_prefix(base)
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|c| c.is_ascii_digit()))
+    }
+}
+
+/// A named input or output of a netlist
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Port {
+    name: String,
+    net: Net,
+}
+
+impl Port {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn net(&self) -> Net {
+        self.net
+    }
+}
+
+/// What drives the value of a net
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Driver {
+    /// The input port with the given index
+    Input(usize),
+    Cell(CellId),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct NetData {
+    name: Option<String>,
+    driver: Option<Driver>,
+}
+
+/// A circuit of NAND gates, constants and D flip-flops connected by nets. Cells are stored in
+/// topological order, so every cell comes after the cells driving its inputs. Flip-flop outputs
+/// are treated as sources, which breaks feedback through the flip-flops
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Netlist {
+    name: String,
+    nets: Vec<NetData>,
+    cells: Vec<Cell>,
+    scopes: Vec<Scope>,
+    inputs: Vec<Port>,
+    outputs: Vec<Port>,
+}
+
+impl Netlist {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn inputs(&self) -> &[Port] {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &[Port] {
+        &self.outputs
+    }
+
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    pub fn cell(&self, id: CellId) -> &Cell {
+        &self.cells[id.0]
+    }
+
+    pub fn cell_ids(&self) -> impl Iterator<Item = CellId> {
+        (0..self.cells.len()).map(CellId)
+    }
+
+    pub fn num_nets(&self) -> usize {
+        self.nets.len()
+    }
+
+    pub fn nets(&self) -> impl Iterator<Item = Net> {
+        (0..self.nets.len()).map(Net)
+    }
+
+    pub fn scopes(&self) -> &[Scope] {
+        &self.scopes
+    }
+
+    pub fn scope(&self, id: ScopeId) -> &Scope {
+        &self.scopes[id.0]
+    }
+
+    /// Number of cells of the given kind
+    pub fn count(&self, kind: CellKind) -> usize {
+        self.cells.iter().filter(|c| c.kind == kind).count()
+    }
+
+    pub fn nand_count(&self) -> usize {
+        self.count(CellKind::Nand)
+    }
+
+    /// Whether the netlist contains flip-flops
+    pub fn is_sequential(&self) -> bool {
+        self.cells.iter().any(|c| c.kind == CellKind::Dff)
+    }
+
+    /// Flip-flop cells in cell order. The state of a netlist lists one value per flip-flop in
+    /// this order
+    pub fn flipflops(&self) -> Vec<CellId> {
+        self.cell_ids()
+            .filter(|id| self.cell(*id).kind == CellKind::Dff)
+            .collect()
+    }
+
+    pub fn driver(&self, net: Net) -> Option<Driver> {
+        self.nets[net.0].driver
+    }
+
+    /// Cells reading each net, indexed by net
+    pub fn fanout(&self) -> Vec<Vec<CellId>> {
+        let mut fanout = vec![Vec::new(); self.nets.len()];
+        for (id, cell) in self.cells.iter().enumerate() {
+            for input in &cell.inputs {
+                if !fanout[input.0].contains(&CellId(id)) {
+                    fanout[input.0].push(CellId(id));
+                }
+            }
+        }
+        fanout
+    }
+
+    /// Hierarchical path of a scope, e.g. "full_add[3]/xor0". The top level has an empty path
+    pub fn scope_path(&self, id: ScopeId) -> String {
+        let mut names = Vec::new();
+        let mut scope = Some(id);
+        while let Some(s) = scope.filter(|s| *s != ScopeId::TOP) {
+            names.push(self.scopes[s.0].name.as_str());
+            scope = self.scopes[s.0].parent;
+        }
+        names.reverse();
+        names.join("/")
+    }
+
+    /// Scopes from the top level down to and including the given scope
+    pub fn scope_ancestry(&self, id: ScopeId) -> Vec<ScopeId> {
+        let mut ancestry = vec![id];
+        while let Some(parent) = self.scopes[ancestry[ancestry.len() - 1].0].parent {
+            ancestry.push(parent);
+        }
+        ancestry.reverse();
+        ancestry
+    }
+
+    /// Hierarchical path of a cell, e.g. "full_add[3]/xor0/xnor0/nand2"
+    pub fn cell_path(&self, id: CellId) -> String {
+        let cell = &self.cells[id.0];
+        let scope = self.scope_path(cell.scope);
+        if scope.is_empty() {
+            cell.name.clone()
+        } else {
+            format!("{}/{}", scope, cell.name)
+        }
+    }
+
+    /// Name of a net. Nets without an explicit name are named after the port or cell driving them
+    pub fn net_name(&self, net: Net) -> String {
+        let data = &self.nets[net.0];
+        match (&data.name, data.driver) {
+            (Some(name), _) => name.clone(),
+            (None, Some(Driver::Input(i))) => self.inputs[i].name.clone(),
+            (None, Some(Driver::Cell(id))) => self.cell_path(id),
+            (None, None) => format!("n{}", net.0),
+        }
+    }
+
+    /// Name given to a net with [Builder::name_net], if any
+    pub fn explicit_net_name(&self, net: Net) -> Option<&str> {
+        self.nets[net.0].name.as_deref()
+    }
+
+    /// Finds a net by name or by the path of the cell driving it
+    pub fn find_net(&self, name: &str) -> Option<Net> {
+        self.nets().find(|net| self.net_name(*net) == name)
+    }
+
+    /// Finds a cell by its hierarchical path
+    pub fn find_cell(&self, path: &str) -> Option<CellId> {
+        self.cell_ids().find(|id| self.cell_path(*id) == path)
+    }
+
+    pub fn find_input(&self, name: &str) -> Option<Net> {
+        self.inputs.iter().find(|p| p.name == name).map(|p| p.net)
+    }
+
+    pub fn find_output(&self, name: &str) -> Option<Net> {
+        self.outputs.iter().find(|p| p.name == name).map(|p| p.net)
+    }
+
+    /// Evaluates a combinational netlist, returning the value of each output. Panics if the
+    /// netlist contains flip-flops
+    pub fn evaluate(&self, inputs: &[bool]) -> Vec<bool> {
+        assert!(
+            !self.is_sequential(),
+            "netlist {} is sequential and needs a state to evaluate",
+            self.name
+        );
+        let values = self.evaluate_nets(inputs, &[]);
+        self.outputs.iter().map(|p| values[p.net.0]).collect()
+    }
+
+    /// Evaluates the value of every net from the inputs and the output of each flip-flop
+    pub fn evaluate_nets(&self, inputs: &[bool], state: &[bool]) -> Vec<bool> {
+        assert_eq!(inputs.len(), self.inputs.len(), "wrong number of inputs");
+
+        let mut values = vec![false; self.nets.len()];
+        for (port, value) in self.inputs.iter().zip(inputs) {
+            values[port.net.0] = *value;
+        }
+
+        let mut state = state.iter();
+        for cell in &self.cells {
+            values[cell.output.0] = match cell.kind {
+                CellKind::Nand => nand_nets(&cell.inputs, &values),
+                CellKind::Const(value) => value,
+                CellKind::Dff => *state.next().expect("missing flip-flop state"),
+            };
+        }
+        values
+    }
+
+    /// State after a reset, with every flip-flop cleared and every input low
+    pub fn reset_state(&self) -> State {
+        let q = vec![false; self.flipflops().len()];
+        let values = self.evaluate_nets(&vec![false; self.inputs.len()], &q);
+        let clk = self
+            .flipflops()
+            .iter()
+            .map(|id| values[self.cell(*id).inputs[0].0])
+            .collect();
+        State { q, clk }
+    }
+
+    /// Applies the inputs for one step of a sequential netlist, returning the value of every net
+    /// once it has settled
+    ///
+    /// Flip-flops whose clock rises load their D input, all at once. Flip-flops clocked by the
+    /// output of another flip-flop, as in a ripple counter, see their edge in a following round,
+    /// so the netlist settles after at most [Netlist::clock_depth] rounds.
+    pub fn step(&self, state: &mut State, inputs: &[bool]) -> Vec<bool> {
+        self.step_with(state, |q| Some(self.evaluate_nets(inputs, q)))
+            .unwrap_or_else(|| panic!("netlist {} has a flip-flop clocked by itself", self.name))
+    }
+
+    /// Steps the netlist like [Netlist::step], evaluating the nets from the flip-flop outputs
+    /// with the given function. Returns None if the evaluation does, or if the flip-flops never
+    /// settle because one is clocked by itself
+    pub(crate) fn step_with(
+        &self,
+        state: &mut State,
+        evaluate: impl Fn(&[bool]) -> Option<Vec<bool>>,
+    ) -> Option<Vec<bool>> {
+        let flipflops = self.flipflops();
+        for _ in 0..=flipflops.len() {
+            let values = evaluate(&state.q)?;
+            let mut changed = false;
+            for (i, id) in flipflops.iter().enumerate() {
+                let [clk, d] = self.cell(*id).inputs[..] else {
+                    unreachable!("flip-flops have two inputs")
+                };
+                if values[clk.0] && !state.clk[i] && state.q[i] != values[d.0] {
+                    state.q[i] = values[d.0];
+                    changed = true;
+                }
+                state.clk[i] = values[clk.0];
+            }
+            if !changed {
+                return Some(values);
+            }
+        }
+        None
+    }
+
+    /// Number of rounds of flip-flop updates needed for a step to settle: 1 when every flip-flop
+    /// shares a clock, or the length of the longest chain of flip-flops clocking each other.
+    /// Returns None if a flip-flop's clock depends on its own output
+    pub fn clock_depth(&self) -> Option<usize> {
+        let flipflops = self.flipflops();
+        let index: HashMap<CellId, usize> = flipflops
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (*id, i))
+            .collect();
+
+        // Flip-flops feeding each net through combinational logic
+        let mut sources: Vec<Vec<usize>> = vec![Vec::new(); self.nets.len()];
+        for (id, cell) in self.cells.iter().enumerate() {
+            sources[cell.output.0] = match cell.kind {
+                CellKind::Dff => vec![index[&CellId(id)]],
+                _ => {
+                    let mut s: Vec<usize> = cell
+                        .inputs
+                        .iter()
+                        .flat_map(|n| sources[n.0].iter().copied())
+                        .collect();
+                    s.sort_unstable();
+                    s.dedup();
+                    s
+                }
+            };
+        }
+
+        // Longest chain ending at each flip-flop, found depth first
+        fn depth(
+            ff: usize,
+            clocks: &[&[usize]],
+            depths: &mut [Option<usize>],
+            visiting: &mut [bool],
+        ) -> Option<usize> {
+            if let Some(d) = depths[ff] {
+                return Some(d);
+            }
+            if visiting[ff] {
+                return None;
+            }
+            visiting[ff] = true;
+            let mut d = 1;
+            for source in clocks[ff] {
+                d = d.max(depth(*source, clocks, depths, visiting)? + 1);
+            }
+            depths[ff] = Some(d);
+            Some(d)
+        }
+
+        let clocks: Vec<&[usize]> = flipflops
+            .iter()
+            .map(|id| &sources[self.cell(*id).inputs[0].0][..])
+            .collect();
+        let mut depths = vec![None; flipflops.len()];
+        let mut visiting = vec![false; flipflops.len()];
+        let mut max = 1;
+        for ff in 0..flipflops.len() {
+            max = max.max(depth(ff, &clocks, &mut depths, &mut visiting)?);
+        }
+        Some(max)
+    }
+}
+
+/// State of a sequential netlist: the Q output of each flip-flop and the clock it last saw, in
+/// the order of [Netlist::flipflops]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct State {
+    pub q: Vec<bool>,
+    pub clk: Vec<bool>,
+}
+
+fn nand_nets(inputs: &[Net], values: &[bool]) -> bool {
+    !inputs.iter().all(|net| values[net.0])
+}
+
+/// NAND of three valued inputs, where None is unknown: high if any input is low, low if every
+/// input is high, and unknown otherwise
+pub(crate) fn nand3(inputs: impl IntoIterator<Item = Option<bool>>) -> Option<bool> {
+    let mut all_high = true;
+    for value in inputs {
+        match value {
+            Some(false) => return Some(true),
+            None => all_high = false,
+            Some(true) => {}
+        }
+    }
+    all_high.then_some(false)
+}
+
+impl fmt::Display for Netlist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = |ports: &[Port]| -> String {
+            ports
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        writeln!(f, "netlist {}", self.name)?;
+        writeln!(f, "  inputs: {}", names(&self.inputs))?;
+        writeln!(f, "  outputs: {}", names(&self.outputs))?;
+        write!(
+            f,
+            "  {} nands, {} flip-flops, {} nets",
+            self.nand_count(),
+            self.count(CellKind::Dff),
+            self.nets.len()
+        )
+    }
+}
+
+/// Error returned when a netlist being built is malformed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NetlistError {
+    /// A net is read but nothing drives it
+    UndrivenNet(String),
+    /// A net is driven by more than one port or cell
+    MultipleDrivers(String),
+    /// The named cell is part of a loop which doesn't pass through a flip-flop
+    CombinationalLoop(String),
+    /// A cell has the wrong number of inputs
+    WrongInputCount(String),
+}
+
+impl fmt::Display for NetlistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetlistError::UndrivenNet(net) => write!(f, "net {} has no driver", net),
+            NetlistError::MultipleDrivers(net) => write!(f, "net {} has multiple drivers", net),
+            NetlistError::CombinationalLoop(cell) => {
+                write!(f, "cell {} is part of a combinational loop", cell)
+            }
+            NetlistError::WrongInputCount(cell) => {
+                write!(f, "cell {} has the wrong number of inputs", cell)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NetlistError {}
+
+/// Builds a netlist. The derived gates are built from NAND gates in the same way as the functions
+/// in [gate](crate::gate), with each one placed in its own scope
+pub struct Builder {
+    netlist: Netlist,
+    scope: ScopeId,
+    /// Number of instances created so far of each base name in each scope
+    instances: HashMap<(ScopeId, String), usize>,
+    constants: [Option<Net>; 2],
+    multiple_drivers: Vec<Net>,
+    /// Nets declared with [Builder::placeholder] and not yet driven
+    placeholders: HashSet<Net>,
+    /// Values driving placeholders, which replace them when the netlist is finished
+    replacements: BTreeMap<Net, Net>,
+}
+
+impl Builder {
+    pub fn new(name: &str) -> Self {
+        Builder {
+            netlist: Netlist {
+                name: name.to_string(),
+                nets: Vec::new(),
+                cells: Vec::new(),
+                scopes: vec![Scope {
+                    name: name.to_string(),
+                    parent: None,
+                }],
+                inputs: Vec::new(),
+                outputs: Vec::new(),
+            },
+            scope: ScopeId::TOP,
+            instances: HashMap::new(),
+            constants: [None, None],
+            multiple_drivers: Vec::new(),
+            placeholders: HashSet::new(),
+            replacements: BTreeMap::new(),
+        }
+    }
+
+    /// Continues building a finished netlist, e.g. to insert test logic into it. New instances
+    /// are numbered after the existing ones
+    pub fn from_netlist(netlist: Netlist) -> Self {
+        let mut instances = HashMap::new();
+        let names = netlist
+            .cells
+            .iter()
+            .map(|c| (c.scope, c.name.as_str()))
+            .chain(
+                netlist
+                    .scopes
+                    .iter()
+                    .filter_map(|s| Some((s.parent?, s.name.as_str()))),
+            );
+        for (scope, name) in names {
+            // Base names may end in digits, as in "mux20", so count every way of splitting it
+            let digits = name.len() - name.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+            // Numbers too large for a counter to reach can't clash with new instances
+            for split in name.len() - digits..name.len() {
+                let next = name[split..]
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| n.checked_add(1));
+                let Some(next) = next else { continue };
+                let count = instances
+                    .entry((scope, name[..split].to_string()))
+                    .or_insert(0);
+                *count = (*count).max(next);
+            }
+        }
+
+        let mut constants = [None, None];
+        for cell in &netlist.cells {
+            if let CellKind::Const(value) = cell.kind {
+                if cell.scope == ScopeId::TOP {
+                    constants[value as usize] = Some(cell.output);
+                }
+            }
+        }
+
+        Builder {
+            netlist,
+            scope: ScopeId::TOP,
+            instances,
+            constants,
+            multiple_drivers: Vec::new(),
+            placeholders: HashSet::new(),
+            replacements: BTreeMap::new(),
+        }
+    }
+
+    /// Adds an input port
+    pub fn input(&mut self, name: &str) -> Net {
+        let net = self.new_net(None);
+        self.input_net(name, net);
+        net
+    }
+
+    /// Adds an input port driving a previously declared net
+    pub(crate) fn input_net(&mut self, name: &str, net: Net) {
+        let data = &mut self.netlist.nets[net.0];
+        if data.driver.is_some() {
+            self.multiple_drivers.push(net);
+        } else {
+            data.driver = Some(Driver::Input(self.netlist.inputs.len()));
+        }
+        self.netlist.inputs.push(Port {
+            name: name.to_string(),
+            net,
+        });
+    }
+
+    /// Adds an input port for each bit of a bus, named "name[i]"
+    pub fn input_bus<const N: usize>(&mut self, name: &str) -> [Net; N] {
+        core::array::from_fn(|i| self.input(&format!("{}[{}]", name, i)))
+    }
+
+    /// Adds an output port
+    pub fn output(&mut self, name: &str, net: Net) {
+        self.netlist.outputs.push(Port {
+            name: name.to_string(),
+            net,
+        });
+    }
+
+    /// Adds an output port for each bit of a bus, named "name[i]"
+    pub fn output_bus(&mut self, name: &str, nets: &[Net]) {
+        for (i, net) in nets.iter().enumerate() {
+            self.output(&format!("{}[{}]", name, i), *net);
+        }
+    }
+
+    /// Declares a net which will be driven later with [Builder::add_cell]
+    pub fn net(&mut self) -> Net {
+        self.new_net(None)
+    }
+
+    /// Gives a net an explicit name
+    pub fn name_net(&mut self, net: Net, name: &str) {
+        self.netlist.nets[net.0].name = Some(name.to_string());
+    }
+
+    /// Adds a cell driving a previously declared net
+    pub fn add_cell(&mut self, kind: CellKind, inputs: &[Net], output: Net) -> CellId {
+        let name = self.instance_name(kind.name());
+        self.add_named_cell(kind, inputs, output, &name, self.scope)
+    }
+
+    /// Adds a cell with the given instance name to a scope, e.g. when reading a netlist which
+    /// already names its cells
+    pub(crate) fn add_named_cell(
+        &mut self,
+        kind: CellKind,
+        inputs: &[Net],
+        output: Net,
+        name: &str,
+        scope: ScopeId,
+    ) -> CellId {
+        let id = CellId(self.netlist.cells.len());
+        let data = &mut self.netlist.nets[output.0];
+        if data.driver.is_some() {
+            self.multiple_drivers.push(output);
+        } else {
+            data.driver = Some(Driver::Cell(id));
+        }
+
+        self.netlist.cells.push(Cell {
+            kind,
+            inputs: inputs.to_vec(),
+            output,
+            name: name.to_string(),
+            scope,
+        });
+        id
+    }
+
+    /// Reconnects one input of a cell to another net
+    pub fn connect(&mut self, cell: CellId, input: usize, net: Net) {
+        self.netlist.cells[cell.0].inputs[input] = net;
+    }
+
+    /// Declares a named net standing for a value which is given later with
+    /// [Builder::drive_placeholder], e.g. when reading a design which uses wires before it drives
+    /// them
+    pub(crate) fn placeholder(&mut self, name: &str) -> Net {
+        let net = self.new_net(Some(name.to_string()));
+        self.placeholders.insert(net);
+        net
+    }
+
+    /// Gives the value of a placeholder, which is replaced by the value when the netlist is
+    /// finished. Fails if the placeholder already has a value, or if the net isn't a placeholder,
+    /// or if the value is the placeholder itself through a chain of placeholders
+    pub(crate) fn drive_placeholder(
+        &mut self,
+        target: Net,
+        value: Net,
+    ) -> Result<(), NetlistError> {
+        if !self.placeholders.remove(&target) {
+            return Err(NetlistError::MultipleDrivers(self.netlist.net_name(target)));
+        }
+        self.replacements.insert(target, value);
+        if resolve(&self.replacements, target).is_none() {
+            return Err(NetlistError::CombinationalLoop(
+                self.netlist.net_name(target),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Replaces placeholders by the values given to [Builder::drive_placeholder]. Cell inputs and
+    /// output ports reading a placeholder read its value instead, which takes the placeholder's
+    /// name if it's driven by an unnamed NAND gate or flip-flop. Placeholders driven by themselves
+    /// through a chain of others, as in a loop of buffers, are a combinational loop
+    fn replace_nets(&mut self, replacements: &BTreeMap<Net, Net>) -> Result<(), NetlistError> {
+        for net in replacements.keys() {
+            if resolve(replacements, *net).is_none() {
+                return Err(NetlistError::CombinationalLoop(self.netlist.net_name(*net)));
+            }
+        }
+        let resolve = |net: Net| resolve(replacements, net).unwrap();
+        for cell in &mut self.netlist.cells {
+            for net in &mut cell.inputs {
+                *net = resolve(*net);
+            }
+        }
+        for port in &mut self.netlist.outputs {
+            port.net = resolve(port.net);
+        }
+
+        for old in replacements.keys() {
+            let new = resolve(*old);
+            let named = match self.netlist.nets[new.0].driver {
+                Some(Driver::Cell(id)) => {
+                    matches!(self.netlist.cells[id.0].kind, CellKind::Const(_))
+                        || self.netlist.nets[new.0].name.is_some()
+                }
+                _ => true,
+            };
+            let name = self.netlist.nets[old.0].name.take();
+            if !named {
+                self.netlist.nets[new.0].name = name;
+            }
+        }
+        Ok(())
+    }
+
+    /// Places everything built by f in a new scope with the given instance name
+    pub fn scoped<T>(&mut self, name: &str, f: impl FnOnce(&mut Self) -> T) -> T {
+        let parent = self.scope;
+        self.scope = self.add_scope(name, parent);
+        let result = f(self);
+        self.scope = parent;
+        result
+    }
+
+    /// Adds an empty scope with the given instance name to a parent scope
+    pub(crate) fn add_scope(&mut self, name: &str, parent: ScopeId) -> ScopeId {
+        self.netlist.scopes.push(Scope {
+            name: name.to_string(),
+            parent: Some(parent),
+        });
+        ScopeId(self.netlist.scopes.len() - 1)
+    }
+
+    /// Places everything built by f in a new scope named after the base name and a count, e.g.
+    /// "xor3"
+    pub fn instance<T>(&mut self, base: &str, f: impl FnOnce(&mut Self) -> T) -> T {
+        let name = self.instance_name(base);
+        self.scoped(&name, f)
+    }
+
+    /// Returns a net tied to a constant value
+    pub fn constant(&mut self, value: bool) -> Net {
+        if let Some(net) = self.constants[value as usize] {
+            return net;
+        }
+
+        // Constants live at the top level so they can be shared by every scope
+        let scope = self.scope;
+        self.scope = ScopeId::TOP;
+        let net = self.new_net(None);
+        self.add_cell(CellKind::Const(value), &[], net);
+        self.scope = scope;
+
+        self.constants[value as usize] = Some(net);
+        net
+    }
+
+    pub fn nand(&mut self, inputs: &[Net]) -> Net {
+        let net = self.new_net(None);
+        self.add_cell(CellKind::Nand, inputs, net);
+        net
+    }
+
+    pub fn not(&mut self, input: Net) -> Net {
+        self.instance("not", |b| b.nand(&[input, input]))
+    }
+
+    pub fn and(&mut self, inputs: &[Net]) -> Net {
+        self.instance("and", |b| {
+            let nand = b.nand(inputs);
+            b.not(nand)
+        })
+    }
+
+    pub fn or(&mut self, inputs: &[Net]) -> Net {
+        self.instance("or", |b| {
+            let inverted: Vec<Net> = inputs.iter().map(|i| b.not(*i)).collect();
+            b.nand(&inverted)
+        })
+    }
+
+    pub fn nor(&mut self, inputs: &[Net]) -> Net {
+        self.instance("nor", |b| {
+            let or = b.or(inputs);
+            b.not(or)
+        })
+    }
+
+    pub fn xor(&mut self, inputs: &[Net]) -> Net {
+        self.instance("xor", |b| {
+            let xnor = b.xnor(inputs);
+            b.not(xnor)
+        })
+    }
+
+    pub fn xnor(&mut self, inputs: &[Net]) -> Net {
+        self.instance("xnor", |b| {
+            let inverted: Vec<Net> = inputs.iter().map(|i| b.not(*i)).collect();
+            let any_low = b.nand(&inverted);
+            let any_high = b.nand(inputs);
+            b.nand(&[any_low, any_high])
+        })
+    }
+
+    /// Adds a rising edge triggered D flip-flop and returns its Q output
+    pub fn dff(&mut self, clk: Net, d: Net) -> Net {
+        let net = self.new_net(None);
+        self.add_cell(CellKind::Dff, &[clk, d], net);
+        net
+    }
+
+    /// The netlist built so far, which may still be malformed, e.g. with the loops of a latch
+    pub(crate) fn unfinished(&self) -> &Netlist {
+        &self.netlist
+    }
+
+    /// Finishes the netlist. Panics if it is malformed
+    pub fn finish(self) -> Netlist {
+        self.try_finish().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Finishes the netlist, checking that every net has a single driver and that there are no
+    /// combinational loops
+    pub fn try_finish(mut self) -> Result<Netlist, NetlistError> {
+        if let Some(net) = self.multiple_drivers.first() {
+            return Err(NetlistError::MultipleDrivers(self.netlist.net_name(*net)));
+        }
+        let replacements = core::mem::take(&mut self.replacements);
+        self.replace_nets(&replacements)?;
+
+        let netlist = &self.netlist;
+        for cell in netlist.cell_ids() {
+            let c = netlist.cell(cell);
+            let expected = match c.kind {
+                CellKind::Nand => !c.inputs.is_empty(),
+                CellKind::Const(_) => c.inputs.is_empty(),
+                CellKind::Dff => c.inputs.len() == 2,
+            };
+            if !expected {
+                return Err(NetlistError::WrongInputCount(netlist.cell_path(cell)));
+            }
+        }
+
+        let read = netlist
+            .cells
+            .iter()
+            .flat_map(|c| c.inputs.iter())
+            .chain(netlist.outputs.iter().map(|p| &p.net));
+        for net in read {
+            if netlist.nets[net.0].driver.is_none() {
+                return Err(NetlistError::UndrivenNet(netlist.net_name(*net)));
+            }
+        }
+
+        self.sort_cells()?;
+        Ok(self.netlist)
+    }
+
+    /// Reorders the cells so that each one comes after the cells driving its inputs
+    fn sort_cells(&mut self) -> Result<(), NetlistError> {
+        let netlist = &self.netlist;
+        let n = netlist.cells.len();
+
+        // Flip-flops are sources, so their inputs aren't dependencies
+        let dependencies = |cell: &Cell| -> Vec<usize> {
+            if cell.kind == CellKind::Dff {
+                return Vec::new();
+            }
+            cell.inputs
+                .iter()
+                .filter_map(|net| match netlist.nets[net.0].driver {
+                    Some(Driver::Cell(id)) => Some(id.0),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        const UNVISITED: u8 = 0;
+        const VISITING: u8 = 1;
+        const DONE: u8 = 2;
+        let mut mark = vec![UNVISITED; n];
+        let mut order = Vec::with_capacity(n);
+        for root in 0..n {
+            if mark[root] != UNVISITED {
+                continue;
+            }
+            // Iterative depth first search, so deep circuits don't overflow the stack
+            let mut stack = vec![(root, dependencies(&netlist.cells[root]))];
+            mark[root] = VISITING;
+            while let Some((cell, deps)) = stack.last_mut() {
+                if let Some(dep) = deps.pop() {
+                    match mark[dep] {
+                        UNVISITED => {
+                            mark[dep] = VISITING;
+                            let deps = dependencies(&netlist.cells[dep]);
+                            stack.push((dep, deps));
+                        }
+                        VISITING => {
+                            return Err(NetlistError::CombinationalLoop(
+                                netlist.cell_path(CellId(dep)),
+                            ))
+                        }
+                        _ => {}
+                    }
+                } else {
+                    mark[*cell] = DONE;
+                    order.push(*cell);
+                    stack.pop();
+                }
+            }
+        }
+
+        // Cells built from the gate functions are already in order
+        if order.iter().enumerate().all(|(i, cell)| i == *cell) {
+            return Ok(());
+        }
+
+        let mut new_index = vec![0; n];
+        for (new, old) in order.iter().enumerate() {
+            new_index[*old] = new;
+        }
+        let mut cells: Vec<Option<Cell>> = self.netlist.cells.drain(..).map(Some).collect();
+        self.netlist.cells = order
+            .iter()
+            .map(|old| cells[*old].take().unwrap())
+            .collect();
+        for data in &mut self.netlist.nets {
+            if let Some(Driver::Cell(id)) = &mut data.driver {
+                id.0 = new_index[id.0];
+            }
+        }
+        Ok(())
+    }
+
+    fn new_net(&mut self, name: Option<String>) -> Net {
+        self.netlist.nets.push(NetData { name, driver: None });
+        Net(self.netlist.nets.len() - 1)
+    }
+
+    fn instance_name(&mut self, base: &str) -> String {
+        let count = self
+            .instances
+            .entry((self.scope, base.to_string()))
+            .or_insert(0);
+        *count += 1;
+        format!("{}{}", base, *count - 1)
+    }
+}
+
+/// Follows a chain of replacements to the net at its end, or returns None if it loops
+fn resolve(replacements: &BTreeMap<Net, Net>, mut net: Net) -> Option<Net> {
+    for _ in 0..=replacements.len() {
+        match replacements.get(&net) {
+            Some(next) => net = *next,
+            None => return Some(net),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gate;
+    use crate::truth_table::TruthTable;
+
+    #[test]
+    fn test_gates_match_gate_functions() {
+        let mut b = Builder::new("gates");
+        let x = b.input_bus::<3>("x");
+        let outputs = [
+            b.nand(&x),
+            b.and(&x),
+            b.or(&x),
+            b.nor(&x),
+            b.xor(&x),
+            b.xnor(&x),
+            b.not(x[0]),
+        ];
+        b.output_bus("y", &outputs);
+        let netlist = b.finish();
+
+        let table = TruthTable::from_netlist(&netlist);
+        let result = table.verify_fn(|x: &[bool; 3]| {
+            [
+                gate::nand(x),
+                gate::and(x),
+                gate::or(x),
+                gate::nor(x),
+                gate::xor(x),
+                gate::xnor(x),
+                gate::not(x[0]),
+            ]
+        });
+        if let Err(mismatches) = result {
+            panic!("{}", mismatches);
+        }
+    }
+
+    #[test]
+    fn test_nand_counts() {
+        for (build, expected) in [
+            (Builder::not as fn(&mut Builder, Net) -> Net, 1),
+            (|b: &mut Builder, a| b.and(&[a, a]), 2),
+            (|b: &mut Builder, a| b.or(&[a, a]), 3),
+            (|b: &mut Builder, a| b.nor(&[a, a]), 4),
+            (|b: &mut Builder, a| b.xnor(&[a, a]), 5),
+            (|b: &mut Builder, a| b.xor(&[a, a]), 6),
+        ] {
+            let mut b = Builder::new("gate");
+            let a = b.input("a");
+            let y = build(&mut b, a);
+            b.output("y", y);
+            assert_eq!(b.finish().nand_count(), expected);
+        }
+    }
+
+    #[test]
+    fn test_hierarchical_names() {
+        let mut b = Builder::new("top");
+        let a = b.input("a");
+        let c = b.input("c");
+        let y = b.scoped("half_add[1]", |b| b.xor(&[a, c]));
+        b.output("y", y);
+        let netlist = b.finish();
+
+        let driver = match netlist.driver(y) {
+            Some(Driver::Cell(id)) => id,
+            _ => panic!("output should be driven by a cell"),
+        };
+        assert_eq!(netlist.cell_path(driver), "half_add[1]/xor0/not0/nand0");
+        assert_eq!(netlist.net_name(y), "half_add[1]/xor0/not0/nand0");
+        assert_eq!(
+            netlist.find_cell("half_add[1]/xor0/xnor0/nand2"),
+            Some(CellId(4))
+        );
+        assert_eq!(netlist.find_net("a"), Some(a));
+    }
+
+    #[test]
+    fn test_from_netlist() {
+        let mut b = Builder::new("top");
+        let a = b.input("a");
+        let y = b.not(a);
+        b.output("y", y);
+        let netlist = b.finish();
+
+        // Rewire the inverter to a new input, adding a second inverter after the first
+        let mut b = Builder::from_netlist(netlist);
+        let c = b.input("c");
+        b.connect(CellId(0), 1, c);
+        let z = b.not(y);
+        b.output("z", z);
+        let netlist = b.finish();
+
+        assert_eq!(netlist.cell_path(CellId(1)), "not1/nand0");
+        for (inputs, expected) in [
+            ([true, true], [false, true]),
+            ([true, false], [true, false]),
+            ([false, true], [true, false]),
+        ] {
+            assert_eq!(
+                netlist.evaluate(&inputs),
+                expected,
+                "failed for inputs: {:?}",
+                inputs
+            );
+        }
+
+        // Numbers too large to count up from are left alone
+        let mut b = Builder::new("top");
+        let a = b.input("a");
+        for name in [
+            "nand18446744073709551615",
+            "nand99999999999999999999",
+            "nand3",
+        ] {
+            let y = b.net();
+            b.add_named_cell(CellKind::Nand, &[a], y, name, ScopeId::TOP);
+            b.output(name, y);
+        }
+        let mut b = Builder::from_netlist(b.finish());
+        let y = b.nand(&[a]);
+        b.output("y", y);
+        let netlist = b.finish();
+        assert_eq!(netlist.cell_path(CellId(3)), "nand4");
+    }
+
+    #[test]
+    fn test_cells_sorted() {
+        // Build an inverter chain backwards using forward declared nets
+        let mut b = Builder::new("chain");
+        let a = b.input("a");
+        let middle = b.net();
+        let y = b.net();
+        b.add_cell(CellKind::Nand, &[middle], y);
+        b.add_cell(CellKind::Nand, &[a], middle);
+        b.output("y", y);
+        let netlist = b.finish();
+
+        assert_eq!(netlist.cells()[0].output(), middle);
+        assert_eq!(netlist.evaluate(&[true]), [true]);
+        assert_eq!(netlist.evaluate(&[false]), [false]);
+    }
+
+    #[test]
+    fn test_nand3() {
+        for (inputs, expected) in [
+            (vec![Some(true), Some(true)], Some(false)),
+            (vec![Some(true), Some(false)], Some(true)),
+            (vec![None, Some(false)], Some(true)),
+            (vec![None, Some(true)], None),
+        ] {
+            assert_eq!(
+                nand3(inputs.clone()),
+                expected,
+                "failed for inputs: {:?}",
+                inputs
+            );
+        }
+    }
+
+    #[test]
+    fn test_placeholders() {
+        // y is read before it's driven, and takes the name of its placeholder
+        let mut b = Builder::new("placeholders");
+        let a = b.input("a");
+        let y = b.placeholder("y");
+        let buffer = b.placeholder("buffer");
+        let z = b.not(buffer);
+        b.output("y", y);
+        b.output("z", z);
+        let value = b.not(a);
+        assert_eq!(b.drive_placeholder(y, value), Ok(()));
+        assert_eq!(b.drive_placeholder(buffer, y), Ok(()));
+        assert_eq!(
+            b.drive_placeholder(y, a),
+            Err(NetlistError::MultipleDrivers("y".to_string()))
+        );
+        assert_eq!(
+            b.drive_placeholder(a, y),
+            Err(NetlistError::MultipleDrivers("a".to_string()))
+        );
+        let netlist = b.finish();
+        assert_eq!(netlist.evaluate(&[false]), [true, false]);
+        assert_eq!(netlist.net_name(netlist.outputs()[0].net()), "y");
+
+        let mut b = Builder::new("buffer loop");
+        let x = b.placeholder("x");
+        let y = b.placeholder("y");
+        assert_eq!(b.drive_placeholder(x, y), Ok(()));
+        assert_eq!(
+            b.drive_placeholder(y, x),
+            Err(NetlistError::CombinationalLoop("y".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_malformed_netlists() {
+        let mut b = Builder::new("loop");
+        let a = b.input("a");
+        let x = b.net();
+        let y = b.nand(&[a, x]);
+        b.add_cell(CellKind::Nand, &[y], x);
+        b.output("y", y);
+        assert!(matches!(
+            b.try_finish(),
+            Err(NetlistError::CombinationalLoop(_))
+        ));
+
+        let mut b = Builder::new("undriven");
+        let x = b.net();
+        b.name_net(x, "x");
+        let y = b.nand(&[x]);
+        b.output("y", y);
+        assert_eq!(
+            b.try_finish(),
+            Err(NetlistError::UndrivenNet("x".to_string()))
+        );
+
+        let mut b = Builder::new("multiple drivers");
+        let a = b.input("a");
+        b.add_cell(CellKind::Const(true), &[], a);
+        assert_eq!(
+            b.try_finish(),
+            Err(NetlistError::MultipleDrivers("a".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_flipflop_feedback() {
+        // A toggle flip-flop feeds back its inverted output without forming a combinational loop
+        let mut b = Builder::new("toggle");
+        let clk = b.input("clk");
+        let d = b.net();
+        let q = b.dff(clk, d);
+        b.add_cell(CellKind::Nand, &[q], d);
+        b.output("q", q);
+        let netlist = b.finish();
+
+        assert!(netlist.is_sequential());
+        assert_eq!(netlist.flipflops().len(), 1);
+        let values = netlist.evaluate_nets(&[false], &[true]);
+        assert!(values[q.index()]);
+        assert!(!values[d.index()]);
+
+        // The output toggles on each rising edge of the clock
+        let mut state = netlist.reset_state();
+        let outputs: Vec<bool> = [true, true, false, true, false, false, true]
+            .iter()
+            .map(|clk| netlist.step(&mut state, &[*clk])[q.index()])
+            .collect();
+        assert_eq!(outputs, [true, true, true, false, false, false, true]);
+        assert_eq!(netlist.clock_depth(), Some(1));
+    }
+
+    #[test]
+    fn test_clock_depth() {
+        // Each flip-flop is clocked by the one before it
+        let mut b = Builder::new("chain");
+        let mut clk = b.input("clk");
+        for _ in 0..3 {
+            let d = b.net();
+            let q = b.dff(clk, d);
+            b.add_cell(CellKind::Nand, &[q], d);
+            clk = q;
+        }
+        b.output("q", clk);
+        assert_eq!(b.finish().clock_depth(), Some(3));
+
+        // A flip-flop clocked by its own output
+        let mut b = Builder::new("loop");
+        let d = b.input("d");
+        let clk = b.net();
+        let q = b.dff(clk, d);
+        b.add_cell(CellKind::Nand, &[q, d], clk);
+        b.output("q", q);
+        assert_eq!(b.finish().clock_depth(), None);
+    }
+}
@@ -0,0 +1,385 @@
+use core::fmt;
+
+use crate::netlist::{Driver, Net, Netlist};
+
+/// Number of times each net changes value during a simulation, counted per clock cycle
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Activity {
+    toggles: Vec<u64>,
+    /// Indices of the nets which changed in each finished cycle, once per change
+    cycles: Vec<Vec<usize>>,
+    current: Vec<usize>,
+    last: Option<Vec<bool>>,
+}
+
+impl Activity {
+    pub fn new(netlist: &Netlist) -> Self {
+        Activity {
+            toggles: vec![0; netlist.num_nets()],
+            cycles: Vec::new(),
+            current: Vec::new(),
+            last: None,
+        }
+    }
+
+    /// Records the value of every net, counting the nets which changed since the last record
+    pub fn record(&mut self, values: &[bool]) {
+        assert_eq!(values.len(), self.toggles.len(), "wrong number of nets");
+        if let Some(last) = &self.last {
+            for (i, (old, new)) in last.iter().zip(values).enumerate() {
+                if old != new {
+                    self.toggles[i] += 1;
+                    self.current.push(i);
+                }
+            }
+        }
+        self.last = Some(values.to_vec());
+    }
+
+    /// Finishes the current clock cycle
+    pub fn end_cycle(&mut self) {
+        self.cycles.push(core::mem::take(&mut self.current));
+    }
+
+    pub fn toggles(&self, net: Net) -> u64 {
+        self.toggles[net.index()]
+    }
+
+    pub fn total_toggles(&self) -> u64 {
+        self.toggles.iter().sum()
+    }
+
+    pub fn num_cycles(&self) -> usize {
+        self.cycles.len()
+    }
+
+    /// Average number of times the net changes per clock cycle
+    pub fn rate(&self, net: Net) -> f64 {
+        if self.cycles.is_empty() {
+            0.0
+        } else {
+            self.toggles(net) as f64 / self.cycles.len() as f64
+        }
+    }
+}
+
+/// Simulates a workload from reset, counting toggles. Each cycle is a list of input vectors
+/// applied one after another, e.g. the clock high and then low. Toggles are counted once the
+/// nets settle after each vector, so glitches aren't seen
+pub fn simulate(netlist: &Netlist, cycles: &[Vec<Vec<bool>>]) -> Activity {
+    let mut activity = Activity::new(netlist);
+    let mut state = netlist.reset_state();
+    activity.record(&netlist.evaluate_nets(&vec![false; netlist.inputs().len()], &state.q));
+    for cycle in cycles {
+        for inputs in cycle {
+            activity.record(&netlist.step(&mut state, inputs));
+        }
+        activity.end_cycle();
+    }
+    activity
+}
+
+/// Capacitance and supply used to turn switching activity into power. Capacitances are in
+/// femtofarads, so energies are in femtojoules
+#[derive(Clone, Debug, PartialEq)]
+pub struct PowerModel {
+    /// Supply voltage in volts
+    pub vdd: f64,
+    /// Clock frequency in megahertz
+    pub frequency: f64,
+    /// Capacitance of the wire and driver of each net
+    pub wire_capacitance: f64,
+    /// Capacitance added to a net by each cell input reading it
+    pub pin_capacitance: f64,
+    /// Scale factors for the capacitance of nets driven inside components, by scope name or
+    /// instance base name
+    weights: Vec<(String, f64)>,
+}
+
+impl PowerModel {
+    pub fn new(vdd: f64, frequency: f64) -> Self {
+        PowerModel {
+            vdd,
+            frequency,
+            wire_capacitance: 0.5,
+            pin_capacitance: 1.0,
+            weights: Vec::new(),
+        }
+    }
+
+    /// Scales the capacitance of nets driven inside a component, given by its scope name, e.g.
+    /// "bit[0]", or the base name of its instances, e.g. "xor". The innermost match wins
+    pub fn with_weight(mut self, component: &str, weight: f64) -> Self {
+        self.weights.push((component.to_string(), weight));
+        self
+    }
+
+    /// Capacitance charged or discharged each time the net toggles
+    pub fn net_capacitance(&self, netlist: &Netlist, net: Net, pins: usize) -> f64 {
+        let weight = match netlist.driver(net) {
+            Some(Driver::Cell(id)) => netlist
+                .scope_ancestry(netlist.cell(id).scope())
+                .iter()
+                .rev()
+                .find_map(|s| {
+                    let scope = netlist.scope(*s);
+                    self.weights
+                        .iter()
+                        .find(|(name, _)| scope.name() == name || scope.is_instance_of(name))
+                })
+                .map_or(1.0, |(_, weight)| *weight),
+            _ => 1.0,
+        };
+        weight * (self.wire_capacitance + pins as f64 * self.pin_capacitance)
+    }
+
+    /// Energy of each toggle is half the net's capacitance times the square of the supply
+    pub fn estimate(&self, netlist: &Netlist, activity: &Activity) -> PowerReport {
+        let mut pins = vec![0; netlist.num_nets()];
+        for net in netlist.cells().iter().flat_map(|c| c.inputs()) {
+            pins[net.index()] += 1;
+        }
+        let energy: Vec<f64> = netlist
+            .nets()
+            .map(|net| {
+                0.5 * self.net_capacitance(netlist, net, pins[net.index()]) * self.vdd * self.vdd
+            })
+            .collect();
+
+        let mut modules: Vec<ModulePower> = Vec::new();
+        for net in netlist.nets() {
+            let name = match netlist.driver(net) {
+                Some(Driver::Input(_)) => "(inputs)".to_string(),
+                Some(Driver::Cell(id)) => {
+                    match netlist.scope_ancestry(netlist.cell(id).scope()).get(1) {
+                        Some(scope) => netlist.scope(*scope).name().to_string(),
+                        None => "(top)".to_string(),
+                    }
+                }
+                None => continue,
+            };
+            let toggles = activity.toggles(net);
+            let index = match modules.iter().position(|m| m.name == name) {
+                Some(index) => index,
+                None => {
+                    modules.push(ModulePower {
+                        name,
+                        toggles: 0,
+                        energy: 0.0,
+                    });
+                    modules.len() - 1
+                }
+            };
+            modules[index].toggles += toggles;
+            modules[index].energy += toggles as f64 * energy[net.index()];
+        }
+        modules.sort_by(|a, b| b.energy.total_cmp(&a.energy));
+
+        let cycles = activity
+            .cycles
+            .iter()
+            .map(|nets| nets.iter().map(|i| energy[*i]).sum())
+            .collect();
+        PowerReport {
+            vdd: self.vdd,
+            frequency: self.frequency,
+            cycles,
+            modules,
+        }
+    }
+}
+
+impl Default for PowerModel {
+    fn default() -> Self {
+        PowerModel::new(1.0, 100.0)
+    }
+}
+
+/// Switching of the nets driven inside one top level component
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModulePower {
+    pub name: String,
+    pub toggles: u64,
+    /// Energy in femtojoules
+    pub energy: f64,
+}
+
+/// Dynamic power of a workload, broken down by component and by clock cycle
+#[derive(Clone, Debug, PartialEq)]
+pub struct PowerReport {
+    vdd: f64,
+    frequency: f64,
+    /// Energy of each clock cycle in femtojoules
+    cycles: Vec<f64>,
+    /// Components using the most energy first
+    modules: Vec<ModulePower>,
+}
+
+impl PowerReport {
+    /// Energy of each clock cycle in femtojoules
+    pub fn cycles(&self) -> &[f64] {
+        &self.cycles
+    }
+
+    pub fn modules(&self) -> &[ModulePower] {
+        &self.modules
+    }
+
+    pub fn module(&self, name: &str) -> Option<&ModulePower> {
+        self.modules.iter().find(|m| m.name == name)
+    }
+
+    /// Total energy in femtojoules
+    pub fn energy(&self) -> f64 {
+        self.cycles.iter().sum()
+    }
+
+    /// Mean energy per clock cycle in femtojoules
+    pub fn energy_per_cycle(&self) -> f64 {
+        if self.cycles.is_empty() {
+            0.0
+        } else {
+            self.energy() / self.cycles.len() as f64
+        }
+    }
+
+    /// Mean power in microwatts when running at the model's clock frequency
+    pub fn power(&self) -> f64 {
+        // Femtojoules per cycle times megahertz gives nanowatts
+        self.energy_per_cycle() * self.frequency / 1000.0
+    }
+}
+
+impl fmt::Display for PowerReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let peak = self.cycles.iter().copied().fold(0.0, f64::max);
+        writeln!(
+            f,
+            "dynamic power {:.3} uW at {:.2} V and {:.1} MHz over {} cycles",
+            self.power(),
+            self.vdd,
+            self.frequency,
+            self.cycles.len()
+        )?;
+        writeln!(
+            f,
+            "energy per cycle {:.2} fJ mean, {:.2} fJ peak",
+            self.energy_per_cycle(),
+            peak
+        )?;
+        let total = self.energy();
+        for module in &self.modules {
+            let share = if total > 0.0 {
+                module.energy / total * 100.0
+            } else {
+                0.0
+            };
+            writeln!(
+                f,
+                "  {:>10.2} fJ {:>5.1}% {:>8} toggles  {}",
+                module.energy, share, module.toggles, module.name
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::{RippleCounter, SynchronousCounter};
+    use crate::math::{CarryLookaheadAdder, RippleCarryAdder};
+    use crate::netlist::Builder;
+
+    fn clock(cycles: usize) -> Vec<Vec<Vec<bool>>> {
+        vec![vec![vec![true], vec![false]]; cycles]
+    }
+
+    #[test]
+    fn test_toggles() {
+        let mut b = Builder::new("buffer");
+        let a = b.input("a");
+        let x = b.not(a);
+        let y = b.not(x);
+        b.output("y", y);
+        let netlist = b.finish();
+
+        let workload: Vec<Vec<Vec<bool>>> = [true, true, false, true]
+            .iter()
+            .map(|a| vec![vec![*a]])
+            .collect();
+        let activity = simulate(&netlist, &workload);
+        assert_eq!(activity.num_cycles(), 4);
+        for net in [a, x, y] {
+            assert_eq!(activity.toggles(net), 3);
+        }
+        assert_eq!(activity.rate(y), 0.75);
+
+        // Toggling a or x charges a wire and two input pins, while y only has its wire
+        let model = PowerModel::new(2.0, 10.0);
+        let report = model.estimate(&netlist, &activity);
+        let (pins, wire) = (0.5 * 2.5 * 4.0, 0.5 * 0.5 * 4.0);
+        let toggle = 2.0 * pins + wire;
+        assert_eq!(report.cycles(), [toggle, 0.0, toggle, toggle]);
+        assert_eq!(report.module("not0").unwrap().toggles, 3);
+        assert_eq!(report.power(), 3.0 * toggle / 4.0 * 10.0 / 1000.0);
+
+        // Nets driven by the inverters count double, but not the input
+        let report = model.with_weight("not", 2.0).estimate(&netlist, &activity);
+        assert_eq!(report.cycles()[0], 3.0 * pins + 2.0 * wire);
+    }
+
+    #[test]
+    fn test_counters() {
+        let ripple = RippleCounter::<8>::new().netlist();
+        let synchronous = SynchronousCounter::<8>::new().netlist();
+        let model = PowerModel::default();
+        let ripple = model.estimate(&ripple, &simulate(&ripple, &clock(256)));
+        let synchronous = model.estimate(&synchronous, &simulate(&synchronous, &clock(256)));
+
+        // The ripple counter only clocks the bits which change
+        assert!(
+            synchronous.energy_per_cycle() > 1.5 * ripple.energy_per_cycle(),
+            "{}\n{}",
+            synchronous,
+            ripple
+        );
+        // The top bit and its inverse change twice in a full count
+        assert_eq!(ripple.module("bit[7]").unwrap().toggles, 4);
+        let text = ripple.to_string();
+        assert!(text.starts_with("dynamic power "), "{}", text);
+        assert!(text.contains(" over 256 cycles\n"), "{}", text);
+    }
+
+    #[test]
+    fn test_adders() {
+        // Add a sequence of pseudo random numbers
+        let mut x: u64 = 1;
+        let workload: Vec<Vec<Vec<bool>>> = (0..64)
+            .map(|_| {
+                x = x
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                vec![(0..32).map(|i| x >> (i + 32) & 1 == 1).collect()]
+            })
+            .collect();
+
+        let model = PowerModel::default();
+        let ripple = RippleCarryAdder::<16>::new().netlist();
+        let lookahead = CarryLookaheadAdder::<16>::new().netlist();
+        let ripple = model.estimate(&ripple, &simulate(&ripple, &workload));
+        let lookahead = model.estimate(&lookahead, &simulate(&lookahead, &workload));
+        assert!(ripple.module("full_add[15]").is_some());
+        assert!(lookahead.module("bit[15]").is_some());
+        assert!(ripple.energy_per_cycle() > 0.0);
+
+        // The lookahead adder spends its extra gates on generate and propagate terms which toggle
+        // with the operands
+        assert!(
+            lookahead.energy_per_cycle() > 1.5 * ripple.energy_per_cycle(),
+            "{}\n{}",
+            lookahead,
+            ripple
+        );
+    }
+}
@@ -0,0 +1,903 @@
+use core::fmt;
+use core::ops::Not;
+
+use crate::bdd::{EquivalenceError, PortMap};
+use crate::netlist::{CellKind, Netlist};
+
+/// Conflicts before the first restart, scaled by the Luby sequence for later restarts
+const RESTART_INTERVAL: usize = 100;
+/// Factor by which variable activities decay after each conflict
+const ACTIVITY_DECAY: f64 = 0.95;
+
+/// Variable or negated variable in a [Cnf]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Lit(usize);
+
+impl Lit {
+    pub fn positive(var: usize) -> Self {
+        Lit(var << 1)
+    }
+
+    pub fn negative(var: usize) -> Self {
+        Lit(var << 1 | 1)
+    }
+
+    pub fn var(self) -> usize {
+        self.0 >> 1
+    }
+
+    pub fn is_negated(self) -> bool {
+        self.0 & 1 == 1
+    }
+
+    /// Value of the literal for an assignment of every variable
+    pub fn evaluate(self, vars: &[bool]) -> bool {
+        vars[self.var()] != self.is_negated()
+    }
+
+    /// DIMACS form of the literal, where variables are numbered from 1 and negative numbers are
+    /// negated
+    pub fn to_dimacs(self) -> i64 {
+        let var = self.var() as i64 + 1;
+        if self.is_negated() {
+            -var
+        } else {
+            var
+        }
+    }
+}
+
+impl Not for Lit {
+    type Output = Lit;
+
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+
+/// Boolean formula in conjunctive normal form, built from circuits with the Tseitin encoding
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cnf {
+    num_vars: usize,
+    clauses: Vec<Vec<Lit>>,
+    /// Variable fixed to true, shared by every constant
+    true_lit: Option<Lit>,
+}
+
+impl Cnf {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.num_vars
+    }
+
+    pub fn clauses(&self) -> &[Vec<Lit>] {
+        &self.clauses
+    }
+
+    /// Adds a variable, returning its positive literal
+    pub fn new_var(&mut self) -> Lit {
+        self.num_vars += 1;
+        Lit::positive(self.num_vars - 1)
+    }
+
+    /// Adds a clause requiring at least one of the literals to be true
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        for lit in lits {
+            assert!(
+                lit.var() < self.num_vars,
+                "variable {} is out of range",
+                lit.var()
+            );
+        }
+        self.clauses.push(lits.to_vec());
+    }
+
+    /// Requires a literal to be true
+    pub fn assert(&mut self, lit: Lit) {
+        self.add_clause(&[lit]);
+    }
+
+    /// Returns a literal fixed to a constant value
+    pub fn constant(&mut self, value: bool) -> Lit {
+        let lit = match self.true_lit {
+            Some(lit) => lit,
+            None => {
+                let lit = self.new_var();
+                self.assert(lit);
+                self.true_lit = Some(lit);
+                lit
+            }
+        };
+        if value {
+            lit
+        } else {
+            !lit
+        }
+    }
+
+    /// Adds clauses making out the NAND of the inputs
+    pub fn define_nand(&mut self, out: Lit, inputs: &[Lit]) {
+        // out is low only if every input is high
+        let mut clause: Vec<Lit> = inputs.iter().map(|i| !*i).collect();
+        clause.push(!out);
+        self.add_clause(&clause);
+        for input in inputs {
+            self.add_clause(&[*input, out]);
+        }
+    }
+
+    pub fn nand(&mut self, inputs: &[Lit]) -> Lit {
+        let out = self.new_var();
+        self.define_nand(out, inputs);
+        out
+    }
+
+    pub fn and(&mut self, inputs: &[Lit]) -> Lit {
+        !self.nand(inputs)
+    }
+
+    pub fn or(&mut self, inputs: &[Lit]) -> Lit {
+        let inverted: Vec<Lit> = inputs.iter().map(|i| !*i).collect();
+        self.nand(&inverted)
+    }
+
+    pub fn xor(&mut self, a: Lit, b: Lit) -> Lit {
+        let out = self.new_var();
+        self.add_clause(&[!out, a, b]);
+        self.add_clause(&[!out, !a, !b]);
+        self.add_clause(&[out, !a, b]);
+        self.add_clause(&[out, a, !b]);
+        out
+    }
+
+    /// Returns a literal equal to low when select is false and to high when it is true
+    pub fn mux(&mut self, select: Lit, low: Lit, high: Lit) -> Lit {
+        let out = self.new_var();
+        self.add_clause(&[select, !low, out]);
+        self.add_clause(&[select, low, !out]);
+        self.add_clause(&[!select, !high, out]);
+        self.add_clause(&[!select, high, !out]);
+        out
+    }
+
+    /// Returns a literal which is true when a and b are equal
+    pub fn equal(&mut self, a: Lit, b: Lit) -> Lit {
+        !self.xor(a, b)
+    }
+
+    /// Encodes a netlist given literals for its inputs and for the Q output of each flip-flop, in
+    /// the order of [Netlist::flipflops]. Returns a literal for every net
+    pub fn encode_netlist(&mut self, netlist: &Netlist, inputs: &[Lit], state: &[Lit]) -> Vec<Lit> {
+        assert_eq!(
+            inputs.len(),
+            netlist.inputs().len(),
+            "wrong number of inputs"
+        );
+        let flipflops = netlist.flipflops();
+        assert_eq!(state.len(), flipflops.len(), "wrong number of flip-flops");
+
+        let mut nets: Vec<Option<Lit>> = vec![None; netlist.num_nets()];
+        for (port, lit) in netlist.inputs().iter().zip(inputs) {
+            nets[port.net().index()] = Some(*lit);
+        }
+        for (id, lit) in flipflops.iter().zip(state) {
+            nets[netlist.cell(*id).output().index()] = Some(*lit);
+        }
+        for cell in netlist.cells() {
+            let value = match cell.kind() {
+                CellKind::Nand => {
+                    let inputs: Vec<Lit> = cell
+                        .inputs()
+                        .iter()
+                        .map(|n| nets[n.index()].expect("cells are in topological order"))
+                        .collect();
+                    self.nand(&inputs)
+                }
+                CellKind::Const(value) => self.constant(value),
+                CellKind::Dff => continue,
+            };
+            nets[cell.output().index()] = Some(value);
+        }
+
+        // Nets without a driver are left unconstrained
+        nets.into_iter()
+            .map(|lit| lit.unwrap_or_else(|| self.new_var()))
+            .collect()
+    }
+
+    /// Searches for an assignment of the variables satisfying every clause
+    pub fn solve(&self) -> Option<Vec<bool>> {
+        Solver::new(self).solve()
+    }
+
+    /// Writes the formula in the DIMACS CNF format used by most SAT solvers
+    pub fn to_dimacs(&self) -> String {
+        let mut text = format!("p cnf {} {}\n", self.num_vars, self.clauses.len());
+        for clause in &self.clauses {
+            for lit in clause {
+                text.push_str(&format!("{} ", lit.to_dimacs()));
+            }
+            text.push_str("0\n");
+        }
+        text
+    }
+
+    /// Reads a formula in the DIMACS CNF format, ignoring comment lines
+    pub fn from_dimacs(text: &str) -> Result<Self, DimacsError> {
+        let mut cnf = Cnf::new();
+        let mut header = None;
+        let mut clause = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('c') || line.starts_with('%') {
+                continue;
+            }
+            if let Some(rest) = line.strip_prefix('p') {
+                let fields: Vec<&str> = rest.split_whitespace().collect();
+                let ["cnf", vars, clauses] = fields[..] else {
+                    return Err(DimacsError::Syntax { line: i + 1 });
+                };
+                let (Ok(vars), Ok(clauses)) = (vars.parse(), clauses.parse::<usize>()) else {
+                    return Err(DimacsError::Syntax { line: i + 1 });
+                };
+                cnf.num_vars = vars;
+                header = Some(clauses);
+                continue;
+            }
+            if header.is_none() {
+                return Err(DimacsError::MissingHeader);
+            }
+
+            for field in line.split_whitespace() {
+                let value: i64 = field
+                    .parse()
+                    .map_err(|_| DimacsError::Syntax { line: i + 1 })?;
+                if value == 0 {
+                    cnf.clauses.push(core::mem::take(&mut clause));
+                    continue;
+                }
+                let var = value.unsigned_abs() as usize - 1;
+                if var >= cnf.num_vars {
+                    return Err(DimacsError::VariableOutOfRange {
+                        line: i + 1,
+                        var: value.unsigned_abs() as usize,
+                    });
+                }
+                clause.push(if value < 0 {
+                    Lit::negative(var)
+                } else {
+                    Lit::positive(var)
+                });
+            }
+        }
+
+        match header {
+            None => Err(DimacsError::MissingHeader),
+            Some(_) if !clause.is_empty() => Err(DimacsError::UnterminatedClause),
+            Some(expected) if expected != cnf.clauses.len() => Err(DimacsError::WrongClauseCount {
+                found: cnf.clauses.len(),
+                expected,
+            }),
+            Some(_) => Ok(cnf),
+        }
+    }
+}
+
+impl fmt::Display for Cnf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_dimacs())
+    }
+}
+
+/// Error returned when a DIMACS file can't be parsed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DimacsError {
+    MissingHeader,
+    Syntax { line: usize },
+    VariableOutOfRange { line: usize, var: usize },
+    UnterminatedClause,
+    WrongClauseCount { found: usize, expected: usize },
+}
+
+impl fmt::Display for DimacsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DimacsError::MissingHeader => write!(f, "missing \"p cnf\" header"),
+            DimacsError::Syntax { line } => write!(f, "syntax error on line {}", line),
+            DimacsError::VariableOutOfRange { line, var } => {
+                write!(f, "variable {} on line {} is out of range", var, line)
+            }
+            DimacsError::UnterminatedClause => write!(f, "last clause is missing its 0"),
+            DimacsError::WrongClauseCount { found, expected } => {
+                write!(f, "found {} clauses but expected {}", found, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DimacsError {}
+
+/// Conflict driven clause learning solver
+struct Solver {
+    clauses: Vec<Vec<Lit>>,
+    /// Clauses watching each literal, indexed by literal. Each clause watches its first two
+    /// literals and is visited when one of them becomes false
+    watches: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    /// Clause which implied each assigned variable, with the implied literal first
+    reasons: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    /// Length of the trail at the start of each decision level
+    trail_limits: Vec<usize>,
+    propagated: usize,
+    activity: Vec<f64>,
+    activity_increment: f64,
+    /// Last value of each variable, reused when deciding it again
+    phases: Vec<bool>,
+    unsatisfiable: bool,
+}
+
+impl Solver {
+    fn new(cnf: &Cnf) -> Self {
+        let n = cnf.num_vars;
+        let mut solver = Solver {
+            clauses: Vec::new(),
+            watches: vec![Vec::new(); 2 * n],
+            values: vec![None; n],
+            levels: vec![0; n],
+            reasons: vec![None; n],
+            trail: Vec::new(),
+            trail_limits: Vec::new(),
+            propagated: 0,
+            activity: vec![0.0; n],
+            activity_increment: 1.0,
+            phases: vec![false; n],
+            unsatisfiable: false,
+        };
+        for clause in &cnf.clauses {
+            solver.add_clause(clause);
+        }
+        solver
+    }
+
+    fn add_clause(&mut self, clause: &[Lit]) {
+        let mut clause = clause.to_vec();
+        clause.sort();
+        clause.dedup();
+        if clause.windows(2).any(|w| w[0] == !w[1]) {
+            // Always satisfied
+            return;
+        }
+
+        match clause[..] {
+            [] => self.unsatisfiable = true,
+            [lit] => match self.value(lit) {
+                Some(true) => {}
+                Some(false) => self.unsatisfiable = true,
+                None => self.assign(lit, None),
+            },
+            _ => {
+                self.watches[clause[0].0].push(self.clauses.len());
+                self.watches[clause[1].0].push(self.clauses.len());
+                self.clauses.push(clause);
+            }
+        }
+    }
+
+    fn value(&self, lit: Lit) -> Option<bool> {
+        self.values[lit.var()].map(|v| v != lit.is_negated())
+    }
+
+    fn level(&self) -> usize {
+        self.trail_limits.len()
+    }
+
+    fn assign(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = lit.var();
+        self.values[var] = Some(!lit.is_negated());
+        self.levels[var] = self.level();
+        self.reasons[var] = reason;
+        self.trail.push(lit);
+    }
+
+    /// Propagates unit clauses, returning a clause which is false if there is a conflict
+    fn propagate(&mut self) -> Option<usize> {
+        while self.propagated < self.trail.len() {
+            let false_lit = !self.trail[self.propagated];
+            self.propagated += 1;
+
+            let watching = core::mem::take(&mut self.watches[false_lit.0]);
+            let mut kept = Vec::with_capacity(watching.len());
+            let mut conflict = None;
+
+            for (i, id) in watching.iter().enumerate() {
+                if conflict.is_some() {
+                    kept.extend_from_slice(&watching[i..]);
+                    break;
+                }
+
+                let clause = &mut self.clauses[*id];
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                let first = clause[0];
+                if self.values[first.var()].map(|v| v != first.is_negated()) == Some(true) {
+                    kept.push(*id);
+                    continue;
+                }
+
+                // Look for another literal which isn't false to watch instead
+                let values = &self.values;
+                let replacement = clause[2..].iter().position(|lit| {
+                    values[lit.var()].map(|v| v != lit.is_negated()) != Some(false)
+                });
+                if let Some(k) = replacement {
+                    clause.swap(1, k + 2);
+                    let lit = clause[1];
+                    self.watches[lit.0].push(*id);
+                    continue;
+                }
+
+                kept.push(*id);
+                match self.value(first) {
+                    Some(false) => conflict = Some(*id),
+                    _ => self.assign(first, Some(*id)),
+                }
+            }
+
+            self.watches[false_lit.0] = kept;
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        None
+    }
+
+    /// Learns a clause from a conflict with the first unique implication point, returning it
+    /// with the asserting literal first and the level to backtrack to
+    fn analyze(&mut self, conflict: usize) -> (Vec<Lit>, usize) {
+        let mut seen = vec![false; self.values.len()];
+        let mut learnt = vec![Lit(0)];
+        let mut pending = 0;
+        let mut index = self.trail.len();
+        let mut clause = conflict;
+        let mut first = true;
+
+        let asserting = loop {
+            let lits: Vec<Lit> = self.clauses[clause][if first { 0 } else { 1 }..].to_vec();
+            first = false;
+            for lit in lits {
+                let var = lit.var();
+                if seen[var] || self.levels[var] == 0 {
+                    continue;
+                }
+                seen[var] = true;
+                self.bump(var);
+                if self.levels[var] == self.level() {
+                    pending += 1;
+                } else {
+                    learnt.push(lit);
+                }
+            }
+
+            // Walk back along the trail to the next literal involved in the conflict
+            loop {
+                index -= 1;
+                if seen[self.trail[index].var()] {
+                    break;
+                }
+            }
+            let lit = self.trail[index];
+            pending -= 1;
+            if pending == 0 {
+                break lit;
+            }
+            clause = self.reasons[lit.var()].expect("only the decision has no reason");
+        };
+        learnt[0] = !asserting;
+
+        // Watch the literal from the highest remaining level second, so it is the first to be
+        // unassigned when backtracking further
+        let mut backtrack = 0;
+        if learnt.len() > 1 {
+            let (i, level) = learnt[1..]
+                .iter()
+                .enumerate()
+                .map(|(i, lit)| (i + 1, self.levels[lit.var()]))
+                .max_by_key(|(_, level)| *level)
+                .unwrap();
+            learnt.swap(1, i);
+            backtrack = level;
+        }
+        self.activity_increment /= ACTIVITY_DECAY;
+        (learnt, backtrack)
+    }
+
+    fn bump(&mut self, var: usize) {
+        self.activity[var] += self.activity_increment;
+        if self.activity[var] > 1e100 {
+            for a in &mut self.activity {
+                *a *= 1e-100;
+            }
+            self.activity_increment *= 1e-100;
+        }
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.level() <= level {
+            return;
+        }
+        let limit = self.trail_limits[level];
+        for lit in self.trail.drain(limit..) {
+            self.phases[lit.var()] = !lit.is_negated();
+            self.values[lit.var()] = None;
+            self.reasons[lit.var()] = None;
+        }
+        self.trail_limits.truncate(level);
+        self.propagated = limit;
+    }
+
+    /// Unassigned variable with the highest activity
+    fn decide(&self) -> Option<usize> {
+        (0..self.values.len())
+            .filter(|v| self.values[*v].is_none())
+            .max_by(|a, b| self.activity[*a].total_cmp(&self.activity[*b]))
+    }
+
+    fn solve(mut self) -> Option<Vec<bool>> {
+        if self.unsatisfiable {
+            return None;
+        }
+
+        let mut restarts = 0;
+        let mut conflicts = 0;
+        loop {
+            if let Some(conflict) = self.propagate() {
+                if self.level() == 0 {
+                    return None;
+                }
+                let (learnt, level) = self.analyze(conflict);
+                self.backtrack(level);
+                if learnt.len() == 1 {
+                    self.assign(learnt[0], None);
+                } else {
+                    let id = self.clauses.len();
+                    self.watches[learnt[0].0].push(id);
+                    self.watches[learnt[1].0].push(id);
+                    self.assign(learnt[0], Some(id));
+                    self.clauses.push(learnt);
+                }
+
+                conflicts += 1;
+                if conflicts >= RESTART_INTERVAL * luby(restarts) {
+                    restarts += 1;
+                    conflicts = 0;
+                    self.backtrack(0);
+                }
+                continue;
+            }
+
+            let Some(var) = self.decide() else {
+                return Some(self.values.iter().map(|v| v.unwrap()).collect());
+            };
+            self.trail_limits.push(self.trail.len());
+            let lit = if self.phases[var] {
+                Lit::positive(var)
+            } else {
+                Lit::negative(var)
+            };
+            self.assign(lit, None);
+        }
+    }
+}
+
+/// Element i of the Luby sequence 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, ...
+fn luby(i: usize) -> usize {
+    let mut i = i + 1;
+    loop {
+        // Find the smallest k with i <= 2^k - 1
+        let mut k = 1;
+        while (1 << k) - 1 < i {
+            k += 1;
+        }
+        if i == (1 << k) - 1 {
+            return 1 << (k - 1);
+        }
+        i -= (1 << (k - 1)) - 1;
+    }
+}
+
+/// Searches for inputs to a combinational netlist for which a property holds. The property is
+/// built from the literal of every net, and the inputs are returned if it can be true
+pub fn find_inputs(
+    netlist: &Netlist,
+    property: impl FnOnce(&mut Cnf, &[Lit]) -> Lit,
+) -> Option<Vec<bool>> {
+    assert!(
+        !netlist.is_sequential(),
+        "netlist {} is sequential",
+        netlist.name()
+    );
+    let mut cnf = Cnf::new();
+    let inputs: Vec<Lit> = netlist.inputs().iter().map(|_| cnf.new_var()).collect();
+    let nets = cnf.encode_netlist(netlist, &inputs, &[]);
+    let lit = property(&mut cnf, &nets);
+    cnf.assert(lit);
+
+    let model = cnf.solve()?;
+    Some(inputs.iter().map(|lit| lit.evaluate(&model)).collect())
+}
+
+/// Checks that two combinational netlists compute the same outputs with a SAT solver. Ports are
+/// matched by name, so they may be declared in different orders
+pub fn check_equivalence(a: &Netlist, b: &Netlist) -> Result<(), EquivalenceError> {
+    let ports = PortMap::new(a, b)?;
+
+    let mut cnf = Cnf::new();
+    let inputs_a: Vec<Lit> = a.inputs().iter().map(|_| cnf.new_var()).collect();
+    let inputs_b: Vec<Lit> = ports.inputs.iter().map(|i| inputs_a[*i]).collect();
+    let nets_a = cnf.encode_netlist(a, &inputs_a, &[]);
+    let nets_b = cnf.encode_netlist(b, &inputs_b, &[]);
+
+    let differences: Vec<Lit> = ports
+        .outputs
+        .iter()
+        .enumerate()
+        .map(|(j, i)| {
+            let x = nets_a[a.outputs()[*i].net().index()];
+            let y = nets_b[b.outputs()[j].net().index()];
+            cnf.xor(x, y)
+        })
+        .collect();
+    cnf.add_clause(&differences);
+
+    match cnf.solve() {
+        Some(model) => {
+            let inputs = inputs_a.iter().map(|lit| lit.evaluate(&model)).collect();
+            Err(EquivalenceError::NotEquivalent(
+                ports.counterexample(a, b, inputs),
+            ))
+        }
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latch::SRLatchActiveLow;
+    use crate::math::{CarryLookaheadAdder, RippleCarryAdder};
+    use crate::netlist::Builder;
+
+    /// Checks a model against every clause
+    fn satisfies(cnf: &Cnf, model: &[bool]) -> bool {
+        cnf.clauses()
+            .iter()
+            .all(|clause| clause.iter().any(|lit| lit.evaluate(model)))
+    }
+
+    #[test]
+    fn test_luby() {
+        let sequence: Vec<usize> = (0..15).map(luby).collect();
+        assert_eq!(sequence, [1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]);
+    }
+
+    #[test]
+    fn test_solve() {
+        let mut cnf = Cnf::new();
+        let x: Vec<Lit> = (0..3).map(|_| cnf.new_var()).collect();
+        cnf.add_clause(&[x[0], x[1]]);
+        cnf.add_clause(&[!x[0], x[2]]);
+        cnf.add_clause(&[!x[1], x[2]]);
+        cnf.add_clause(&[!x[2], !x[0]]);
+        let model = cnf.solve().unwrap();
+        assert!(satisfies(&cnf, &model));
+        assert_eq!(model, [false, true, true]);
+
+        cnf.add_clause(&[!x[1]]);
+        assert_eq!(cnf.solve(), None);
+        assert_eq!(Cnf::new().solve(), Some(vec![]));
+    }
+
+    #[test]
+    fn test_pigeonhole() {
+        // n + 1 pigeons can't fit in n holes, which needs many conflicts to prove
+        for holes in 1..6 {
+            let mut cnf = Cnf::new();
+            let vars: Vec<Vec<Lit>> = (0..=holes)
+                .map(|_| (0..holes).map(|_| cnf.new_var()).collect())
+                .collect();
+            for pigeon in &vars {
+                cnf.add_clause(pigeon);
+            }
+            // No two pigeons share a hole
+            for (p, first) in vars.iter().enumerate() {
+                for second in &vars[p + 1..] {
+                    for (x, y) in first.iter().zip(second) {
+                        cnf.add_clause(&[!*x, !*y]);
+                    }
+                }
+            }
+            assert_eq!(cnf.solve(), None, "failed for holes: {}", holes);
+        }
+    }
+
+    #[test]
+    fn test_gates() {
+        let mut cnf = Cnf::new();
+        let a = cnf.new_var();
+        let b = cnf.new_var();
+        let gates = [
+            cnf.nand(&[a, b]),
+            cnf.and(&[a, b]),
+            cnf.or(&[a, b]),
+            cnf.xor(a, b),
+            cnf.equal(a, b),
+            cnf.mux(a, b, !b),
+            cnf.constant(true),
+            cnf.constant(false),
+        ];
+
+        for (x, y) in [(false, false), (false, true), (true, false), (true, true)] {
+            let mut fixed = cnf.clone();
+            fixed.assert(if x { a } else { !a });
+            fixed.assert(if y { b } else { !b });
+            let model = fixed.solve().unwrap();
+            let values: Vec<bool> = gates.iter().map(|g| g.evaluate(&model)).collect();
+            assert_eq!(
+                values,
+                [
+                    !(x && y),
+                    x && y,
+                    x || y,
+                    x != y,
+                    x == y,
+                    x != y,
+                    true,
+                    false
+                ],
+                "failed for inputs: {:?}",
+                (x, y)
+            );
+        }
+    }
+
+    #[test]
+    fn test_dimacs() {
+        let mut cnf = Cnf::new();
+        let a = cnf.new_var();
+        let b = cnf.new_var();
+        cnf.add_clause(&[a, !b]);
+        cnf.add_clause(&[b]);
+        assert_eq!(cnf.to_dimacs(), "p cnf 2 2\n1 -2 0\n2 0\n");
+        assert_eq!(Cnf::from_dimacs(&cnf.to_dimacs()), Ok(cnf));
+
+        let cnf = Cnf::from_dimacs("c example\np cnf 3 2\n1 -3\n 0 2\n3 0\n").unwrap();
+        assert_eq!(cnf.clauses().len(), 2);
+        assert_eq!(cnf.clauses()[1], [Lit::positive(1), Lit::positive(2)]);
+
+        for (text, error) in [
+            ("1 2 0\n", DimacsError::MissingHeader),
+            ("p cnf 2\n", DimacsError::Syntax { line: 1 }),
+            ("p wcnf 2 1\n1 2 0\n", DimacsError::Syntax { line: 1 }),
+            (
+                "c comment\np foo 2 1\n1 2 0\n",
+                DimacsError::Syntax { line: 2 },
+            ),
+            ("p cnf 2 1\n1 x 0\n", DimacsError::Syntax { line: 2 }),
+            (
+                "p cnf 2 1\n1 3 0\n",
+                DimacsError::VariableOutOfRange { line: 2, var: 3 },
+            ),
+            ("p cnf 2 1\n1 2\n", DimacsError::UnterminatedClause),
+            (
+                "p cnf 2 2\n1 2 0\n",
+                DimacsError::WrongClauseCount {
+                    found: 1,
+                    expected: 2,
+                },
+            ),
+        ] {
+            assert_eq!(
+                Cnf::from_dimacs(text),
+                Err(error),
+                "failed for input: {:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_restricted_combination() {
+        // A gated D latch drives the set and reset inputs from one data bit, so they can't both
+        // be active
+        let mut builder = Builder::new("d_latch_inputs");
+        let d = builder.input("d");
+        let e = builder.input("e");
+        let not_d = builder.not(d);
+        let s = builder.nand(&[d, e]);
+        let r = builder.nand(&[not_d, e]);
+        builder.output("s", s);
+        builder.output("r", r);
+        let netlist = builder.finish();
+
+        let restricted = |netlist: &Netlist| {
+            let s = netlist.find_output("s").unwrap().index();
+            let r = netlist.find_output("r").unwrap().index();
+            find_inputs(netlist, |cnf, nets| {
+                let (q, qn) = SRLatchActiveLow::encode_stable_state(cnf, nets[s], nets[r]);
+                cnf.and(&[q, qn])
+            })
+        };
+        assert_eq!(restricted(&netlist), None);
+
+        // Driving reset from a separate input lets both be active
+        let mut builder = Builder::new("sr_latch_inputs");
+        let [d, r_in, e] = builder.input_bus::<3>("in");
+        let s = builder.nand(&[d, e]);
+        let r = builder.nand(&[r_in, e]);
+        builder.output("s", s);
+        builder.output("r", r);
+        let netlist = builder.finish();
+        assert_eq!(restricted(&netlist), Some(vec![true, true, true]));
+    }
+
+    #[test]
+    fn test_adder_equivalence() {
+        let ripple = RippleCarryAdder::<16>::new().netlist();
+        let lookahead = CarryLookaheadAdder::<16>::new().netlist();
+        assert_eq!(check_equivalence(&ripple, &lookahead), Ok(()));
+
+        // An adder which forgets the carry into bit 9
+        let mut builder = Builder::new("buggy_adder");
+        let a = builder.input_bus::<16>("a");
+        let b = builder.input_bus::<16>("b");
+        let mut sum = RippleCarryAdder::<16>::new().build(&mut builder, &a, &b);
+        sum[9] = builder.xor(&[a[9], b[9]]);
+        builder.output_bus("sum", &sum);
+        let buggy = builder.finish();
+
+        let Err(EquivalenceError::NotEquivalent(counterexample)) =
+            check_equivalence(&lookahead, &buggy)
+        else {
+            panic!("the adders should differ");
+        };
+        assert_eq!(counterexample.differing_outputs(), ["sum[9]"]);
+        assert_eq!(
+            counterexample.expected,
+            lookahead.evaluate(&counterexample.inputs)
+        );
+        assert_eq!(
+            counterexample.actual,
+            buggy.evaluate(&counterexample.inputs)
+        );
+    }
+
+    #[test]
+    fn test_encode_sequential() {
+        // A toggle flip-flop: the next state is the inverse of the current one
+        let mut builder = Builder::new("toggle");
+        let clk = builder.input("clk");
+        let d = builder.net();
+        let q = builder.dff(clk, d);
+        builder.add_cell(CellKind::Nand, &[q, q], d);
+        builder.output("q", q);
+        let netlist = builder.finish();
+
+        let mut cnf = Cnf::new();
+        let clk = cnf.new_var();
+        let state = cnf.new_var();
+        let nets = cnf.encode_netlist(&netlist, &[clk], &[state]);
+        let next = nets[netlist.cell(netlist.flipflops()[0]).inputs()[1].index()];
+
+        // The next state can never equal the current one
+        let same = cnf.equal(state, next);
+        cnf.assert(same);
+        assert_eq!(cnf.solve(), None);
+    }
+}
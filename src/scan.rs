@@ -0,0 +1,298 @@
+use crate::mux::build_mux2;
+use crate::netlist::{Builder, Driver, Netlist, State};
+
+/// A netlist whose flip-flops have been made into mux-D scan flip-flops and stitched into scan
+/// chains. The original ports come first, followed by "scan_enable", "scan_in[i]" for each
+/// chain, and "scan_clk" if any flip-flop is clocked by internal logic, then the original
+/// outputs and "scan_out[i]" for each chain
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScanDesign {
+    netlist: Netlist,
+    /// Flip-flops of each chain from scan in to scan out, as indices into the state
+    chains: Vec<Vec<usize>>,
+    scan_enable: usize,
+    scan_in: Vec<usize>,
+    scan_out: Vec<usize>,
+    /// Inputs clocking the flip-flops while shifting
+    clocks: Vec<usize>,
+}
+
+/// Replaces each flip-flop's D input with a mux selecting the previous flip-flop in its chain
+/// while scan enable is high, splitting the flip-flops into chains of similar length in
+/// netlist order. Flip-flops clocked by internal logic, as in a ripple counter, are clocked by
+/// "scan_clk" while scan enable is high
+pub fn insert_scan(netlist: &Netlist, num_chains: usize) -> ScanDesign {
+    let flipflops = netlist.flipflops();
+    assert!(
+        num_chains > 0 && num_chains <= flipflops.len(),
+        "netlist {} has {} flip-flops, which can't form {} scan chains",
+        netlist.name(),
+        flipflops.len(),
+        num_chains
+    );
+    // The first chains take one flip-flop more when they don't divide evenly
+    let (chain_len, longer) = (flipflops.len() / num_chains, flipflops.len() % num_chains);
+    let mut rest = &flipflops[..];
+    let chains: Vec<&[_]> = (0..num_chains)
+        .map(|i| {
+            let (chain, next) = rest.split_at(chain_len + (i < longer) as usize);
+            rest = next;
+            chain
+        })
+        .collect();
+    let paths: Vec<String> = flipflops.iter().map(|id| netlist.cell_path(*id)).collect();
+
+    let mut builder = Builder::from_netlist(netlist.clone());
+    let scan_enable = builder.input("scan_enable");
+    let scan_in: Vec<_> = (0..chains.len())
+        .map(|i| builder.input(&format!("scan_in[{}]", i)))
+        .collect();
+    let internal_clock = flipflops.iter().any(|id| {
+        !matches!(
+            netlist.driver(netlist.cell(*id).inputs()[0]),
+            Some(Driver::Input(_))
+        )
+    });
+    let scan_clk = internal_clock.then(|| builder.input("scan_clk"));
+
+    let scan_out = builder.scoped("scan", |builder| {
+        let mut scan_out = Vec::new();
+        let mut index = 0;
+        for (chain, scan_in) in chains.iter().zip(&scan_in) {
+            let mut previous = *scan_in;
+            for id in chain.iter() {
+                let cell = netlist.cell(*id);
+                let (clk, d) = (cell.inputs()[0], cell.inputs()[1]);
+                builder.scoped(&format!("ff[{}]", index), |builder| {
+                    let d = build_mux2(builder, scan_enable, &[d, previous]);
+                    builder.connect(*id, 1, d);
+                    if let (Some(scan_clk), false) = (
+                        scan_clk,
+                        matches!(netlist.driver(clk), Some(Driver::Input(_))),
+                    ) {
+                        let clk = build_mux2(builder, scan_enable, &[clk, scan_clk]);
+                        builder.connect(*id, 0, clk);
+                    }
+                });
+                previous = cell.output();
+                index += 1;
+            }
+            scan_out.push(previous);
+        }
+        scan_out
+    });
+    for (i, net) in scan_out.iter().enumerate() {
+        builder.output(&format!("scan_out[{}]", i), *net);
+    }
+    let scanned = builder.finish();
+
+    // Finishing may reorder the cells, so find the flip-flops again by name
+    let position = |path: &String| {
+        scanned
+            .flipflops()
+            .iter()
+            .position(|id| scanned.cell_path(*id) == *path)
+            .expect("scan insertion keeps every flip-flop")
+    };
+    let mut start = 0;
+    let chains = chains
+        .iter()
+        .map(|chain| {
+            let indices = paths[start..start + chain.len()].iter().map(position);
+            start += chain.len();
+            indices.collect()
+        })
+        .collect();
+
+    let num_inputs = netlist.inputs().len();
+    let mut clocks: Vec<usize> = flipflops
+        .iter()
+        .filter_map(|id| match netlist.driver(netlist.cell(*id).inputs()[0]) {
+            Some(Driver::Input(i)) => Some(i),
+            _ => None,
+        })
+        .collect();
+    if scan_clk.is_some() {
+        clocks.push(num_inputs + 1 + scan_in.len());
+    }
+    clocks.sort_unstable();
+    clocks.dedup();
+
+    let num_outputs = netlist.outputs().len();
+    ScanDesign {
+        netlist: scanned,
+        chains,
+        scan_enable: num_inputs,
+        scan_in: (num_inputs + 1..num_inputs + 1 + scan_in.len()).collect(),
+        scan_out: (num_outputs..num_outputs + scan_out.len()).collect(),
+        clocks,
+    }
+}
+
+impl ScanDesign {
+    pub fn netlist(&self) -> &Netlist {
+        &self.netlist
+    }
+
+    /// Flip-flops of each chain from scan in to scan out, as indices into [State::q]
+    pub fn chains(&self) -> &[Vec<usize>] {
+        &self.chains
+    }
+
+    /// Inputs of the scan netlist for normal operation, with scan enable low
+    pub fn inputs(&self, functional: &[bool]) -> Vec<bool> {
+        let mut inputs = functional.to_vec();
+        inputs.resize(self.netlist.inputs().len(), false);
+        inputs
+    }
+
+    /// Shifts a new value into every flip-flop, one bit per chain on each clock pulse, and
+    /// returns the values shifted out. Both are in the order of [State::q]. Every other input is
+    /// held low, and scan enable and the clocks are left low
+    pub fn shift(&self, state: &mut State, load: &[bool]) -> Vec<bool> {
+        assert_eq!(load.len(), state.q.len(), "wrong number of flip-flops");
+        let length = self.chains.iter().map(Vec::len).max().unwrap_or(0);
+        let mut unloaded = vec![false; load.len()];
+        let mut inputs = vec![false; self.netlist.inputs().len()];
+        inputs[self.scan_enable] = true;
+
+        for cycle in 0..length {
+            for (chain, scan_in) in self.chains.iter().zip(&self.scan_in) {
+                // The first bit shifted in ends up furthest along the chain
+                inputs[*scan_in] = chain.get(length - 1 - cycle).is_some_and(|ff| load[*ff]);
+            }
+            let values = self.pulse(state, &mut inputs, false);
+            for (chain, scan_out) in self.chains.iter().zip(&self.scan_out) {
+                if let Some(ff) = chain.len().checked_sub(cycle + 1).map(|i| chain[i]) {
+                    unloaded[ff] = values[self.netlist.outputs()[*scan_out].net().index()];
+                }
+            }
+            self.pulse(state, &mut inputs, true);
+        }
+        self.pulse(state, &mut inputs, false);
+
+        // Hand the clocks back to the functional logic without an edge, as a glitch free clock
+        // mux would
+        inputs[self.scan_enable] = false;
+        let values = self.netlist.evaluate_nets(&inputs, &state.q);
+        for (clk, id) in state.clk.iter_mut().zip(self.netlist.flipflops()) {
+            *clk = values[self.netlist.cell(id).inputs()[0].index()];
+        }
+        unloaded
+    }
+
+    fn pulse(&self, state: &mut State, inputs: &mut [bool], level: bool) -> Vec<bool> {
+        for clk in &self.clocks {
+            inputs[*clk] = level;
+        }
+        self.netlist.step(state, inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::RippleCounter;
+    use crate::math::RippleCarryAdder;
+    use crate::netlist::CellKind;
+
+    /// A register adding its input to itself on each rising edge of "clk"
+    fn accumulator() -> Netlist {
+        let mut builder = Builder::new("accumulator");
+        let clk = builder.input("clk");
+        let x = builder.input_bus::<4>("x");
+        let q: [_; 4] = core::array::from_fn(|_| builder.net());
+        let sum = RippleCarryAdder::<4>::new().build(&mut builder, &q, &x);
+        for (q, sum) in q.iter().zip(&sum) {
+            builder.add_cell(CellKind::Dff, &[clk, *sum], *q);
+        }
+        builder.output_bus("q", &q);
+        builder.finish()
+    }
+
+    fn bits(value: usize, width: usize) -> Vec<bool> {
+        (0..width).map(|i| value >> i & 1 == 1).collect()
+    }
+
+    #[test]
+    fn test_functional_mode() {
+        let netlist = accumulator();
+        let scan = insert_scan(&netlist, 1);
+        let names: Vec<&str> = scan.netlist().inputs().iter().map(|p| p.name()).collect();
+        assert_eq!(names[5..], ["scan_enable", "scan_in[0]"]);
+        assert_eq!(scan.netlist().outputs()[4].name(), "scan_out[0]");
+        assert!(scan
+            .netlist()
+            .find_cell("scan/ff[3]/mux20/or0/nand0")
+            .is_some());
+
+        // With scan enable low the design behaves as before
+        let mut state = netlist.reset_state();
+        let mut scan_state = scan.netlist().reset_state();
+        for step in 0..20 {
+            let mut inputs = bits(step * 7 % 16, 4);
+            inputs.insert(0, step % 2 == 1);
+            let values = netlist.step(&mut state, &inputs);
+            let scan_values = scan.netlist().step(&mut scan_state, &scan.inputs(&inputs));
+            for (port, scan_port) in netlist.outputs().iter().zip(scan.netlist().outputs()) {
+                assert_eq!(
+                    values[port.net().index()],
+                    scan_values[scan_port.net().index()],
+                    "failed for step: {}",
+                    step
+                );
+            }
+        }
+        assert_eq!(state.q, scan_state.q);
+    }
+
+    #[test]
+    fn test_shift() {
+        let scan = insert_scan(&accumulator(), 1);
+        let netlist = scan.netlist();
+        let mut state = netlist.reset_state();
+        assert_eq!(scan.shift(&mut state, &bits(11, 4)), bits(0, 4));
+        assert_eq!(state.q, bits(11, 4));
+
+        // Capture one clock edge with 3 on the input, then unload the sum
+        let mut inputs = bits(3, 4);
+        inputs.insert(0, true);
+        netlist.step(&mut state, &scan.inputs(&inputs));
+        assert_eq!(scan.shift(&mut state, &bits(5, 4)), bits(14, 4));
+        assert_eq!(scan.shift(&mut state, &bits(0, 4)), bits(5, 4));
+    }
+
+    #[test]
+    fn test_multiple_chains() {
+        // The ripple counter's upper bits are clocked by the bits below them
+        let counter = RippleCounter::<5>::new().netlist();
+        for (num_chains, chains) in [
+            (2, vec![vec![0, 1, 2], vec![3, 4]]),
+            (4, vec![vec![0, 1], vec![2], vec![3], vec![4]]),
+        ] {
+            let scan = insert_scan(&counter, num_chains);
+            assert_eq!(scan.chains(), chains);
+            let netlist = scan.netlist();
+            let scan_in = netlist
+                .inputs()
+                .iter()
+                .filter(|p| p.name().starts_with("scan_in"));
+            assert_eq!(scan_in.count(), num_chains);
+            assert_eq!(netlist.inputs().last().map(|p| p.name()), Some("scan_clk"));
+
+            let mut state = netlist.reset_state();
+            for value in [6, 31, 0, 19] {
+                scan.shift(&mut state, &bits(value, 5));
+                for clk in [true, false] {
+                    netlist.step(&mut state, &scan.inputs(&[clk]));
+                }
+                assert_eq!(
+                    scan.shift(&mut state, &bits(0, 5)),
+                    bits((value + 1) % 32, 5),
+                    "failed for inputs: {:?}",
+                    (num_chains, value)
+                );
+            }
+        }
+    }
+}
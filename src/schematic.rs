@@ -0,0 +1,747 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::netlist::{Builder, CellId, Net, Netlist, ScopeId};
+
+/// Scopes built by the derived gates of [Builder](crate::netlist::Builder), which are drawn as a
+/// single gate rather than as their NAND cells
+const GATES: &[&str] = &["not", "and", "or", "nor", "xor", "xnor"];
+
+/// Something drawn in a schematic: a cell, or a derived gate standing for the cells of its scope
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Symbol {
+    /// Gate or cell name, e.g. "xor" or "dff"
+    kind: &'static str,
+    inputs: Vec<Net>,
+    output: Net,
+    /// Scope the symbol is drawn in
+    scope: ScopeId,
+    /// Hierarchical path of the gate's scope or the cell
+    path: String,
+}
+
+/// Gates and cells of a netlist, in topological order like the cells
+fn symbols(netlist: &Netlist) -> Vec<Symbol> {
+    // Cells in a derived gate are grouped under the outermost one
+    let mut groups: Vec<(Option<ScopeId>, Vec<CellId>)> = Vec::new();
+    let mut group_of: HashMap<ScopeId, usize> = HashMap::new();
+    for id in netlist.cell_ids() {
+        let gate = netlist
+            .scope_ancestry(netlist.cell(id).scope())
+            .into_iter()
+            .find(|s| gate_kind(netlist, *s).is_some());
+        match gate.and_then(|s| group_of.get(&s)) {
+            Some(group) => groups[*group].1.push(id),
+            None => {
+                if let Some(scope) = gate {
+                    group_of.insert(scope, groups.len());
+                }
+                groups.push((gate, vec![id]));
+            }
+        }
+    }
+
+    let mut readers = vec![0; netlist.num_nets()];
+    for net in netlist.cells().iter().flat_map(|c| c.inputs()) {
+        readers[net.index()] += 1;
+    }
+    for port in netlist.outputs() {
+        readers[port.net().index()] += 1;
+    }
+
+    let mut symbols = Vec::new();
+    for (gate, ids) in groups {
+        let cell_symbols = ids.iter().map(|id| {
+            let cell = netlist.cell(*id);
+            Symbol {
+                kind: cell.kind().name(),
+                inputs: cell.inputs().to_vec(),
+                output: cell.output(),
+                scope: cell.scope(),
+                path: netlist.cell_path(*id),
+            }
+        });
+        let Some(scope) = gate else {
+            symbols.extend(cell_symbols);
+            continue;
+        };
+
+        // The gate's inputs are the nets its cells read from outside, and its output the one
+        // net read outside it. A gate whose output nothing reads still has its last cell
+        let cells: Vec<_> = ids.iter().map(|id| netlist.cell(*id)).collect();
+        let driven: Vec<Net> = cells.iter().map(|c| c.output()).collect();
+        let mut inputs = Vec::new();
+        for net in cells.iter().flat_map(|c| c.inputs()) {
+            if !driven.contains(net) && !inputs.contains(net) {
+                inputs.push(*net);
+            }
+        }
+        let outputs: Vec<Net> = driven
+            .iter()
+            .filter(|net| {
+                let inside = cells.iter().flat_map(|c| c.inputs()).filter(|n| n == net);
+                readers[net.index()] > inside.count()
+            })
+            .copied()
+            .collect();
+        let output = match outputs[..] {
+            [output] => output,
+            [] => driven[driven.len() - 1],
+            _ => {
+                symbols.extend(cell_symbols);
+                continue;
+            }
+        };
+        symbols.push(Symbol {
+            kind: gate_kind(netlist, scope).unwrap(),
+            inputs,
+            output,
+            scope: netlist.scope(scope).parent().unwrap(),
+            path: netlist.scope_path(scope),
+        });
+    }
+    symbols
+}
+
+/// Which derived gate a scope is an instance of, if any
+fn gate_kind(netlist: &Netlist, scope: ScopeId) -> Option<&'static str> {
+    GATES
+        .iter()
+        .find(|g| netlist.scope(scope).is_instance_of(g))
+        .copied()
+}
+
+/// What drives a net in a schematic
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Node {
+    Input(usize),
+    Symbol(usize),
+    Output(usize),
+}
+
+fn drivers(netlist: &Netlist, symbols: &[Symbol]) -> HashMap<Net, Node> {
+    let mut drivers: HashMap<Net, Node> = netlist
+        .inputs()
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (p.net(), Node::Input(i)))
+        .collect();
+    for (i, symbol) in symbols.iter().enumerate() {
+        drivers.insert(symbol.output, Node::Symbol(i));
+    }
+    drivers
+}
+
+/// Quotes a string for DOT
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Renders a netlist as a Graphviz DOT graph, to be laid out with e.g. `dot -Tsvg`
+///
+/// Ports, cells and derived gates such as AND and XOR are nodes, and each net is an edge from
+/// its driver to each of its readers, with the net's name as a tooltip. Scopes of larger
+/// components, such as muxes and full adders, are nested clusters. Flip-flops are drawn as single
+/// cells, so use [builder_to_dot] to see the latches inside one
+pub fn to_dot(netlist: &Netlist) -> String {
+    let symbols = symbols(netlist);
+    let drivers = drivers(netlist, &symbols);
+    let mut dot = format!(
+        "digraph {} {{\n    rankdir=LR;\n    node [fontname=\"Helvetica\", fontsize=10];\n    \
+         edge [arrowsize=0.5];\n",
+        quote(netlist.name())
+    );
+    for (i, port) in netlist.inputs().iter().enumerate() {
+        dot.push_str(&format!(
+            "    in{} [label={}, shape=cds];\n",
+            i,
+            quote(port.name())
+        ));
+    }
+
+    // Symbols directly inside each scope, and the scopes inside each that hold any
+    let mut contents: BTreeMap<ScopeId, Vec<usize>> = BTreeMap::new();
+    let mut children: BTreeMap<ScopeId, Vec<ScopeId>> = BTreeMap::new();
+    for (i, symbol) in symbols.iter().enumerate() {
+        for pair in netlist.scope_ancestry(symbol.scope).windows(2) {
+            let siblings = children.entry(pair[0]).or_default();
+            if !siblings.contains(&pair[1]) {
+                siblings.push(pair[1]);
+            }
+        }
+        contents.entry(symbol.scope).or_default().push(i);
+    }
+    let mut stack = vec![(ScopeId::TOP, 1, false)];
+    while let Some((scope, depth, done)) = stack.pop() {
+        let indent = "    ".repeat(depth);
+        if done {
+            dot.push_str(&format!("{}}}\n", &indent[4..]));
+            continue;
+        }
+        if scope != ScopeId::TOP {
+            dot.push_str(&format!(
+                "{}subgraph cluster_{} {{\n{}label={};\n{}tooltip={};\n",
+                &indent[4..],
+                scope.index(),
+                indent,
+                quote(netlist.scope(scope).name()),
+                indent,
+                quote(&netlist.scope_path(scope))
+            ));
+            stack.push((scope, depth, true));
+        }
+        for i in contents.get(&scope).into_iter().flatten() {
+            let symbol = &symbols[*i];
+            let (label, shape) = match symbol.kind {
+                "tie0" => ("0", "plaintext"),
+                "tie1" => ("1", "plaintext"),
+                "dff" => ("dff", "box"),
+                kind => (kind, "box, style=rounded"),
+            };
+            dot.push_str(&format!(
+                "{}s{} [label={}, shape={}, tooltip={}];\n",
+                indent,
+                i,
+                quote(label),
+                shape,
+                quote(&symbol.path)
+            ));
+        }
+        for child in children.get(&scope).into_iter().flatten().rev() {
+            stack.push((*child, depth + 1, false));
+        }
+    }
+    for (i, port) in netlist.outputs().iter().enumerate() {
+        dot.push_str(&format!(
+            "    out{} [label={}, shape=cds];\n",
+            i,
+            quote(port.name())
+        ));
+    }
+
+    let node = |net: &Net| match drivers[net] {
+        Node::Input(i) => format!("in{}", i),
+        Node::Symbol(i) => format!("s{}", i),
+        Node::Output(_) => unreachable!("outputs don't drive nets"),
+    };
+    for (i, symbol) in symbols.iter().enumerate() {
+        for (pin, net) in symbol.inputs.iter().enumerate() {
+            let pin = match symbol.kind {
+                "dff" => format!(", headlabel={}", quote(["clk", "d"][pin])),
+                _ => String::new(),
+            };
+            dot.push_str(&format!(
+                "    {} -> s{} [tooltip={}{}];\n",
+                node(net),
+                i,
+                quote(&netlist.net_name(*net)),
+                pin
+            ));
+        }
+    }
+    for (i, port) in netlist.outputs().iter().enumerate() {
+        dot.push_str(&format!(
+            "    {} -> out{} [tooltip={}];\n",
+            node(&port.net()),
+            i,
+            quote(&netlist.net_name(port.net()))
+        ));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Distance between input pins
+const PITCH: i64 = 20;
+/// Width of a gate, including its pins
+const GATE_WIDTH: i64 = 68;
+/// Distance between wires running side by side
+const TRACK: i64 = 8;
+/// Space around the drawing and between symbols
+const MARGIN: i64 = 20;
+
+/// Renders a netlist which is still being built like [to_dot]. Unlike a finished netlist it may
+/// hold combinational loops, such as the cross-coupled NAND gates of the latches built by
+/// [DFlipflop::latch_builder](crate::flipflop::DFlipflop::latch_builder)
+pub fn builder_to_dot(builder: &Builder) -> String {
+    to_dot(builder.unfinished())
+}
+
+/// Approximate width of text in the schematic's font
+fn text_width(text: &str) -> i64 {
+    7 * text.chars().count() as i64
+}
+
+/// Escapes text for use in SVG elements and attributes
+pub(crate) fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Renders a netlist as a self-contained SVG schematic with standard gate symbols
+///
+/// Input ports are on the left and output ports on the right, with each gate in the column after
+/// the gates driving it. Derived gates such as AND and XOR are drawn as one symbol, but the
+/// scopes of larger components aren't drawn, so use [to_dot] to see those. Wires skipping
+/// columns, or running back from flip-flops, go around the bottom. Hovering over a symbol or wire
+/// shows its name
+pub fn to_svg(netlist: &Netlist) -> String {
+    let symbols = symbols(netlist);
+    let drivers = drivers(netlist, &symbols);
+
+    // Flip-flop outputs count as the first column, so feedback doesn't push gates right
+    let mut column_of = vec![0; symbols.len()];
+    for (i, symbol) in symbols.iter().enumerate() {
+        column_of[i] = match symbol.kind {
+            "tie0" | "tie1" => 0,
+            _ => {
+                let column = |net: &Net| match drivers[net] {
+                    Node::Symbol(j) if symbols[j].kind != "dff" => column_of[j],
+                    _ => 0,
+                };
+                symbol.inputs.iter().map(column).max().unwrap_or(0) + 1
+            }
+        };
+    }
+    let outputs = column_of.iter().max().map_or(1, |c| c + 1);
+    let column = |node: Node| match node {
+        Node::Input(_) => 0,
+        Node::Symbol(i) => column_of[i],
+        Node::Output(_) => outputs,
+    };
+    let mut columns: Vec<Vec<Node>> = vec![Vec::new(); outputs + 1];
+    let nodes = (0..netlist.inputs().len())
+        .map(Node::Input)
+        .chain((0..symbols.len()).map(Node::Symbol))
+        .chain((0..netlist.outputs().len()).map(Node::Output));
+    for node in nodes {
+        columns[column(node)].push(node);
+    }
+    let size = |node: Node| match node {
+        Node::Input(i) => (text_width(netlist.inputs()[i].name()) + MARGIN, PITCH),
+        Node::Output(i) => (text_width(netlist.outputs()[i].name()) + MARGIN, PITCH),
+        Node::Symbol(i) => match symbols[i].kind {
+            "tie0" | "tie1" => (30, PITCH),
+            _ => (GATE_WIDTH, PITCH * symbols[i].inputs.len().max(2) as i64),
+        },
+    };
+
+    // A wire runs from a net's driver to each pin reading it, turning on a track of its own in
+    // the gap after the driver's column. Wires to any column but the next also turn on a lane
+    // below the drawing and a track in the gap before the reader
+    let mut wires: Vec<(Net, Node, usize)> = Vec::new();
+    for (i, symbol) in symbols.iter().enumerate() {
+        for (pin, net) in symbol.inputs.iter().enumerate() {
+            wires.push((*net, Node::Symbol(i), pin));
+        }
+    }
+    for (i, port) in netlist.outputs().iter().enumerate() {
+        wires.push((port.net(), Node::Output(i), 0));
+    }
+    let mut tracks: Vec<Vec<Net>> = vec![Vec::new(); columns.len()];
+    let mut lanes: Vec<Net> = Vec::new();
+    let add = |tracks: &mut Vec<Net>, net: Net| {
+        if !tracks.contains(&net) {
+            tracks.push(net);
+        }
+    };
+    for (net, reader, _) in &wires {
+        let from = column(drivers[net]);
+        add(&mut tracks[from], *net);
+        if from + 1 != column(*reader) {
+            add(&mut tracks[column(*reader) - 1], *net);
+            add(&mut lanes, *net);
+        }
+    }
+
+    // Place the columns left to right and the nodes in each top to bottom, with the input ports
+    // aligned right
+    let mut position: HashMap<Node, (i64, i64)> = HashMap::new();
+    let mut gaps = Vec::new();
+    let mut x = MARGIN;
+    let mut height = 0;
+    for (c, nodes) in columns.iter().enumerate() {
+        let width = nodes.iter().map(|n| size(*n).0).max().unwrap_or(0);
+        let mut y = MARGIN;
+        for node in nodes {
+            let (w, h) = size(*node);
+            let left = match node {
+                Node::Input(_) => x + width - w,
+                _ => x,
+            };
+            position.insert(*node, (left, y));
+            y += h + MARGIN;
+        }
+        height = height.max(y);
+        x += width;
+        gaps.push(x);
+        if c + 1 < columns.len() {
+            x += 2 * MARGIN + TRACK * tracks[c].len() as i64;
+        }
+    }
+    let width = x + MARGIN;
+    let track = |c: usize, net: Net| {
+        gaps[c] + MARGIN + TRACK * tracks[c].iter().position(|n| *n == net).unwrap() as i64
+    };
+    let bottom = height;
+    if !lanes.is_empty() {
+        height += TRACK * lanes.len() as i64 + MARGIN;
+    }
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+         viewBox=\"0 0 {0} {1}\" font-family=\"sans-serif\" font-size=\"12\">\n\
+         <title>{2}</title>\n<g fill=\"none\" stroke=\"black\">\n",
+        width,
+        height,
+        escape_xml(netlist.name())
+    );
+    for (net, reader, pin) in &wires {
+        let driver = drivers[net];
+        let (x1, y1) = {
+            let ((x, y), (w, h)) = (position[&driver], size(driver));
+            (x + w, y + h / 2)
+        };
+        let (x2, y2) = (
+            position[reader].0,
+            position[reader].1 + PITCH / 2 + PITCH * *pin as i64,
+        );
+        let from = column(driver);
+        let path = if from + 1 == column(*reader) {
+            format!("M{} {}H{}V{}H{}", x1, y1, track(from, *net), y2, x2)
+        } else {
+            let below = bottom + TRACK * lanes.iter().position(|n| n == net).unwrap() as i64;
+            format!(
+                "M{} {}H{}V{}H{}V{}H{}",
+                x1,
+                y1,
+                track(from, *net),
+                below,
+                track(column(*reader) - 1, *net),
+                y2,
+                x2
+            )
+        };
+        svg.push_str(&format!(
+            "<path d=\"{}\"><title>{}</title></path>\n",
+            path,
+            escape_xml(&netlist.net_name(*net))
+        ));
+    }
+    svg.push_str("</g>\n<g fill=\"white\" stroke=\"black\">\n");
+
+    let label = |x: i64, y: i64, anchor: &str, text: &str| {
+        format!(
+            "<text x=\"{}\" y=\"{}\"{} fill=\"black\" stroke=\"none\">{}</text>\n",
+            x,
+            y + 4,
+            anchor,
+            escape_xml(text)
+        )
+    };
+    for (i, port) in netlist.inputs().iter().enumerate() {
+        let (x, y) = position[&Node::Input(i)];
+        let w = size(Node::Input(i)).0;
+        svg.push_str(&label(
+            x + w - 4,
+            y + PITCH / 2,
+            " text-anchor=\"end\"",
+            port.name(),
+        ));
+    }
+    for (i, symbol) in symbols.iter().enumerate() {
+        let (x, y) = position[&Node::Symbol(i)];
+        let h = size(Node::Symbol(i)).1;
+        svg.push_str(&format!("<g><title>{}</title>\n", escape_xml(&symbol.path)));
+        svg.push_str(&match symbol.kind {
+            "tie0" | "tie1" => {
+                label(x, y + h / 2, "", &symbol.kind[3..])
+                    + &format!("<path d=\"M{} {}H{}\"/>\n", x + 12, y + h / 2, x + 30)
+            }
+            _ => gate_symbol(symbol, x, y, h),
+        });
+        svg.push_str("</g>\n");
+    }
+    for (i, port) in netlist.outputs().iter().enumerate() {
+        let (x, y) = position[&Node::Output(i)];
+        svg.push_str(&label(x + 4, y + PITCH / 2, "", port.name()));
+    }
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
+
+/// SVG elements drawing a gate or flip-flop of the given height with its top left corner at
+/// (x, y)
+fn gate_symbol(symbol: &Symbol, x: i64, y: i64, h: i64) -> String {
+    // Input pins run under the body, which is drawn filled over their ends
+    let body = x + 10;
+    let mid = y + h / 2;
+    let mut svg = String::new();
+    for pin in 0..symbol.inputs.len() as i64 {
+        let py = y + PITCH / 2 + PITCH * pin;
+        svg.push_str(&format!("<path d=\"M{} {}H{}\"/>\n", x, py, body + 12));
+    }
+
+    let or = |left: i64| {
+        format!(
+            "M{l} {t}Q{c} {t} {r} {m}Q{c} {b} {l} {b}Q{k} {m} {l} {t}Z",
+            l = left,
+            t = y,
+            b = y + h,
+            m = mid,
+            r = body + 40,
+            c = body + 25,
+            k = left + 10,
+        )
+    };
+    let shape = match symbol.kind {
+        "and" | "nand" => format!(
+            "M{l} {t}H{c}A20 {r} 0 0 1 {c} {b}H{l}Z",
+            l = body,
+            t = y,
+            b = y + h,
+            c = body + 20,
+            r = h / 2,
+        ),
+        "or" | "nor" => or(body),
+        "xor" | "xnor" => {
+            svg.push_str(&format!(
+                "<path d=\"M{l} {b}Q{k} {m} {l} {t}\" fill=\"none\"/>\n",
+                l = body,
+                t = y,
+                b = y + h,
+                m = mid,
+                k = body + 10,
+            ));
+            or(body + 6)
+        }
+        "not" => format!(
+            "M{l} {t}L{r} {m}L{l} {b}Z",
+            l = body,
+            t = mid - 14,
+            b = mid + 14,
+            r = body + 40,
+            m = mid,
+        ),
+        _ => {
+            // Flip-flop, with the clock wedge on its first pin and D on its second
+            let clk = y + PITCH / 2;
+            svg.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"40\" height=\"{}\"/>\n\
+                 <path d=\"M{} {}L{} {}L{} {}\" fill=\"none\"/>\n",
+                body,
+                y,
+                h,
+                body,
+                clk - 5,
+                body + 7,
+                clk,
+                body,
+                clk + 5
+            ));
+            for (text, tx, ty) in [("D", body + 4, clk + PITCH), ("Q", body + 28, mid)] {
+                svg.push_str(&format!(
+                    "<text x=\"{}\" y=\"{}\" font-size=\"10\" fill=\"black\" stroke=\"none\">{}\
+                     </text>\n",
+                    tx,
+                    ty + 4,
+                    text
+                ));
+            }
+            svg.push_str(&format!(
+                "<path d=\"M{} {}H{}\"/>\n",
+                body + 40,
+                mid,
+                x + GATE_WIDTH
+            ));
+            return svg;
+        }
+    };
+    svg.push_str(&format!("<path d=\"{}\"/>\n", shape));
+
+    // Inverting gates end in a bubble
+    let end = if matches!(symbol.kind, "nand" | "nor" | "xnor" | "not") {
+        svg.push_str(&format!(
+            "<circle cx=\"{}\" cy=\"{}\" r=\"4\"/>\n",
+            body + 44,
+            mid
+        ));
+        body + 48
+    } else {
+        body + 40
+    };
+    svg.push_str(&format!(
+        "<path d=\"M{} {}H{}\"/>\n",
+        end,
+        mid,
+        x + GATE_WIDTH
+    ));
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::RippleCounter;
+    use crate::flipflop::DFlipflop;
+    use crate::netlist::Builder;
+    use crate::shift::build_rotate_left_8;
+
+    /// y = nand(a & (a ^ b), 1)
+    fn demo() -> Netlist {
+        let mut builder = Builder::new("demo");
+        let a = builder.input("a");
+        let b = builder.input("b");
+        let x = builder.xor(&[a, b]);
+        let y = builder.and(&[a, x]);
+        let one = builder.constant(true);
+        let y = builder.nand(&[y, one]);
+        builder.output("y", y);
+        builder.finish()
+    }
+
+    #[test]
+    fn test_symbols() {
+        let netlist = demo();
+        let gates = symbols(&netlist);
+        let kinds: Vec<&str> = gates.iter().map(|s| s.kind).collect();
+        assert_eq!(kinds, ["xor", "and", "tie1", "nand"]);
+        let a = netlist.find_input("a").unwrap();
+        let b = netlist.find_input("b").unwrap();
+        assert_eq!(gates[0].inputs, [a, b]);
+        assert_eq!(gates[1].inputs, [a, gates[0].output]);
+        assert_eq!(gates[0].path, "xor0");
+        assert_eq!(gates[3].path, "nand0");
+
+        // Gates nested in a component belong to its scope
+        let netlist = RippleCounter::<2>::new().netlist();
+        let paths: Vec<String> = symbols(&netlist).into_iter().map(|s| s.path).collect();
+        assert_eq!(
+            paths,
+            ["bit[0]/dff0", "bit[0]/not0", "bit[1]/dff0", "bit[1]/not0"]
+        );
+    }
+
+    #[test]
+    fn test_to_dot() {
+        assert_eq!(
+            to_dot(&demo()),
+            "digraph \"demo\" {\n    \
+             rankdir=LR;\n    \
+             node [fontname=\"Helvetica\", fontsize=10];\n    \
+             edge [arrowsize=0.5];\n    \
+             in0 [label=\"a\", shape=cds];\n    \
+             in1 [label=\"b\", shape=cds];\n    \
+             s0 [label=\"xor\", shape=box, style=rounded, tooltip=\"xor0\"];\n    \
+             s1 [label=\"and\", shape=box, style=rounded, tooltip=\"and0\"];\n    \
+             s2 [label=\"1\", shape=plaintext, tooltip=\"tie10\"];\n    \
+             s3 [label=\"nand\", shape=box, style=rounded, tooltip=\"nand0\"];\n    \
+             out0 [label=\"y\", shape=cds];\n    \
+             in0 -> s0 [tooltip=\"a\"];\n    \
+             in1 -> s0 [tooltip=\"b\"];\n    \
+             in0 -> s1 [tooltip=\"a\"];\n    \
+             s0 -> s1 [tooltip=\"xor0/not0/nand0\"];\n    \
+             s1 -> s3 [tooltip=\"and0/not0/nand0\"];\n    \
+             s2 -> s3 [tooltip=\"tie10\"];\n    \
+             s3 -> out0 [tooltip=\"nand0\"];\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_dot_clusters() {
+        // Each of the eight muxes is a cluster of its gates
+        let mut builder = Builder::new("rotate");
+        let shift = builder.input_bus::<3>("shift");
+        let value = builder.input_bus::<8>("value");
+        let out = build_rotate_left_8(&mut builder, &shift, &value);
+        builder.output_bus("out", &out);
+        let dot = to_dot(&builder.finish());
+        let muxes: Vec<&str> = dot
+            .lines()
+            .filter_map(|l| l.trim().strip_prefix("label=\"mux8"))
+            .collect();
+        assert_eq!(
+            muxes,
+            ["0\";", "1\";", "2\";", "3\";", "4\";", "5\";", "6\";", "7\";"]
+        );
+        assert_eq!(
+            dot.matches("subgraph").count(),
+            dot.matches("    }\n").count()
+        );
+        assert!(!dot.contains("label=\"nand\""));
+
+        let dot = to_dot(&DFlipflop::new().netlist());
+        assert!(dot.contains("    in0 -> s0 [tooltip=\"clk\", headlabel=\"clk\"];\n"));
+        assert!(dot.contains("    in1 -> s0 [tooltip=\"d\", headlabel=\"d\"];\n"));
+        assert!(
+            dot.contains("    s1 [label=\"not\", shape=box, style=rounded, tooltip=\"not0\"];\n")
+        );
+
+        // The latches inside the flip-flop, each with its cross-coupled NAND gates
+        let dot = builder_to_dot(&DFlipflop::new().latch_builder());
+        let clusters: Vec<&str> = dot
+            .lines()
+            .filter_map(|l| l.trim().strip_prefix("tooltip=\""))
+            .collect();
+        assert_eq!(
+            clusters,
+            [
+                "d_latch0\";",
+                "d_latch0/sr_latch0\";",
+                "d_latch1\";",
+                "d_latch1/sr_latch0\";"
+            ]
+        );
+        for latch in ["d_latch0", "d_latch1"] {
+            let node = |cell: &str| {
+                let tooltip = format!("tooltip=\"{}/sr_latch0/{}\"]", latch, cell);
+                let line = dot.lines().find(|l| l.ends_with(&format!("{};", tooltip)));
+                line.unwrap().trim().split(' ').next().unwrap().to_string()
+            };
+            let (q, qn) = (node("nand0"), node("nand1"));
+            assert!(dot.contains(&format!("{} -> {} ", q, qn)), "{}", latch);
+            assert!(dot.contains(&format!("{} -> {} ", qn, q)), "{}", latch);
+        }
+    }
+
+    #[test]
+    fn test_to_svg() {
+        let svg = to_svg(&demo());
+        assert!(svg.starts_with(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"525\" height=\"176\" "
+        ));
+        assert!(svg.ends_with("</g>\n</svg>\n"));
+        assert_eq!(svg.matches("<g").count(), svg.matches("</g>").count());
+        let titles: Vec<&str> = svg
+            .lines()
+            .filter_map(|l| l.strip_prefix("<g><title>"))
+            .collect();
+        assert_eq!(
+            titles,
+            [
+                "xor0</title>",
+                "and0</title>",
+                "tie10</title>",
+                "nand0</title>"
+            ]
+        );
+
+        // a skips the xor's column, so it runs below the gates rather than through them
+        assert!(svg.contains("<path d=\"M50 30H70V30H114\"><title>a</title></path>\n"));
+        assert!(svg.contains("<path d=\"M50 30H70V140H202V30H238\"><title>a</title></path>\n"));
+        assert!(svg.contains("<circle cx=\"416\" cy=\"40\" r=\"4\"/>\n"));
+
+        // Each flip-flop's output runs back around to the inverter beside it, as does q[0] on its
+        // way past the later bits to its port
+        let svg = to_svg(&RippleCounter::<3>::new().netlist());
+        assert_eq!(svg.matches("<rect").count(), 3);
+        assert_eq!(svg.matches(">D</text>").count(), 3);
+        let around = svg
+            .lines()
+            .filter(|l| l.ends_with("dff0</title></path>") && l.matches('V').count() == 2)
+            .count();
+        assert_eq!(around, 4);
+    }
+}
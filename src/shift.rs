@@ -1,4 +1,5 @@
-use crate::mux;
+use crate::mux::{self, build_mux8};
+use crate::netlist::{Builder, Net};
 
 /// Shifts the bits in value left by shift amount. Replacement bits are all false
 pub fn logical_shift_left_8(shift: &[bool; 3], value: &[bool; 8]) -> [bool; 8] {
@@ -9,8 +10,8 @@ pub fn logical_shift_left_8(shift: &[bool; 3], value: &[bool; 8]) -> [bool; 8] {
         let mut input = [false; N];
         let mut j = 0;
         while j < N {
-            if i != 0 {
-                input[j] = value[(N + i - j) % N];
+            if j <= i {
+                input[j] = value[i - j];
             }
             j += 1;
         }
@@ -34,8 +35,8 @@ pub fn logical_shift_right_8(shift: &[bool; 3], value: &[bool; 8]) -> [bool; 8]
         let mut input = [false; N];
         let mut j = 0;
         while j < N {
-            if i != N - 1 {
-                input[j] = value[(j + i) % N];
+            if i + j < N {
+                input[j] = value[i + j];
             }
             j += 1;
         }
@@ -74,6 +75,16 @@ pub fn rotate_left_8(shift: &[bool; 3], value: &[bool; 8]) -> [bool; 8] {
     output
 }
 
+/// Builds a rotate_left_8 in its own scope, mirroring [rotate_left_8]
+pub fn build_rotate_left_8(builder: &mut Builder, shift: &[Net; 3], value: &[Net; 8]) -> [Net; 8] {
+    builder.instance("rotate_left_8", |builder| {
+        core::array::from_fn(|i| {
+            let input = core::array::from_fn(|j| value[(8 + i - j) % 8]);
+            build_mux8(builder, shift, &input)
+        })
+    })
+}
+
 /// Shifts the bits in value right by shift amount. Bits shifted off of value are concatinated to
 /// the other side of value
 pub fn rotate_right_8(shift: &[bool; 3], value: &[bool; 8]) -> [bool; 8] {
@@ -101,7 +112,30 @@ pub fn rotate_right_8(shift: &[bool; 3], value: &[bool; 8]) -> [bool; 8] {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::bus::u8_to_bus;
+    use crate::bus::{u8_to_bus, Bus};
+    use crate::truth_table::TruthTable;
+
+    /// Checks a shifter against a reference for every shift amount and value
+    fn verify_shifter(
+        shifter: fn(&[bool; 3], &[bool; 8]) -> [bool; 8],
+        reference: fn(u8, u32) -> u8,
+    ) {
+        let table = TruthTable::from_fn(|x: &[bool; 11]| {
+            let x = Bus::new(*x);
+            shifter(&x.slice::<0, 3>(), &x.slice::<3, 8>())
+        });
+
+        let result = table.verify_fn(|x: &[bool; 11]| {
+            let x = Bus::new(*x);
+            let shift = u32::try_from(x.slice::<0, 3>()).unwrap();
+            let value = u8::try_from(x.slice::<3, 8>()).unwrap();
+            Bus::from(reference(value, shift))
+        });
+
+        if let Err(mismatches) = result {
+            panic!("{}", mismatches);
+        }
+    }
 
     #[test]
     fn test_logical_shift_left_8() {
@@ -216,4 +250,28 @@ mod tests {
             )
         }
     }
+
+    #[test]
+    fn test_rotate_left_8_netlist() {
+        let mut builder = Builder::new("rotate");
+        let shift = builder.input_bus::<3>("shift");
+        let value = builder.input_bus::<8>("value");
+        let out = build_rotate_left_8(&mut builder, &shift, &value);
+        builder.output_bus("out", &out);
+        let netlist = builder.finish();
+
+        let table = TruthTable::from_fn(|x: &[bool; 11]| {
+            let x = Bus::new(*x);
+            rotate_left_8(&x.slice::<0, 3>(), &x.slice::<3, 8>())
+        });
+        assert_eq!(TruthTable::from_netlist(&netlist).verify(&table), Ok(()));
+    }
+
+    #[test]
+    fn test_shifters_exhaustive() {
+        verify_shifter(logical_shift_left_8, |value, shift| value << shift);
+        verify_shifter(logical_shift_right_8, |value, shift| value >> shift);
+        verify_shifter(rotate_left_8, u8::rotate_left);
+        verify_shifter(rotate_right_8, u8::rotate_right);
+    }
 }
@@ -0,0 +1,229 @@
+use core::fmt;
+use std::collections::BTreeMap;
+
+use crate::netlist::{CellKind, Driver, Net, Netlist};
+
+/// Structural statistics of a netlist, independent of any delay model
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructureReport {
+    /// Number of NAND levels between the inputs or flip-flops and each net
+    depths: Vec<usize>,
+    /// Depth of each output and flip-flop D input, deepest first
+    pub endpoints: Vec<(String, usize)>,
+    /// Number of NAND cells with each number of inputs
+    pub fan_in: BTreeMap<usize, usize>,
+    /// Number of driven nets with each number of readers, counting cell input pins and output
+    /// ports
+    pub fan_out: BTreeMap<usize, usize>,
+    pub fanout_limit: usize,
+    /// Nets with more readers than the limit, most readers first
+    pub high_fanout: Vec<(String, usize)>,
+    /// Input ports which nothing reads
+    pub unused_inputs: Vec<String>,
+    /// Cells whose output nothing reads
+    pub dangling: Vec<String>,
+}
+
+/// Measures the logic depth and fan-in and fan-out of a netlist, flagging nets read by more than
+/// the fan-out limit
+pub fn analyze(netlist: &Netlist, fanout_limit: usize) -> StructureReport {
+    let mut depths = vec![0; netlist.num_nets()];
+    let mut fan_in = BTreeMap::new();
+    for cell in netlist.cells() {
+        if cell.kind() == CellKind::Nand {
+            depths[cell.output().index()] = cell
+                .inputs()
+                .iter()
+                .map(|n| depths[n.index()])
+                .max()
+                .unwrap_or(0)
+                + 1;
+            *fan_in.entry(cell.inputs().len()).or_insert(0) += 1;
+        }
+    }
+
+    let mut readers = vec![0; netlist.num_nets()];
+    for net in netlist.cells().iter().flat_map(|c| c.inputs()) {
+        readers[net.index()] += 1;
+    }
+    for port in netlist.outputs() {
+        readers[port.net().index()] += 1;
+    }
+    let driven: Vec<Net> = netlist
+        .nets()
+        .filter(|net| netlist.driver(*net).is_some())
+        .collect();
+    let mut fan_out = BTreeMap::new();
+    for net in &driven {
+        *fan_out.entry(readers[net.index()]).or_insert(0) += 1;
+    }
+    let mut high_fanout: Vec<(String, usize)> = driven
+        .iter()
+        .filter(|net| readers[net.index()] > fanout_limit)
+        .map(|net| (netlist.net_name(*net), readers[net.index()]))
+        .collect();
+    high_fanout.sort_by_key(|(_, readers)| core::cmp::Reverse(*readers));
+
+    let unused_inputs = netlist
+        .inputs()
+        .iter()
+        .filter(|p| readers[p.net().index()] == 0)
+        .map(|p| p.name().to_string())
+        .collect();
+    let dangling = driven
+        .iter()
+        .filter_map(|net| match netlist.driver(*net) {
+            Some(Driver::Cell(id)) if readers[net.index()] == 0 => Some(netlist.cell_path(id)),
+            _ => None,
+        })
+        .collect();
+
+    let outputs = netlist
+        .outputs()
+        .iter()
+        .map(|p| (p.name().to_string(), depths[p.net().index()]));
+    let flipflops = netlist.flipflops().into_iter().map(|id| {
+        (
+            format!("{}/d", netlist.cell_path(id)),
+            depths[netlist.cell(id).inputs()[1].index()],
+        )
+    });
+    let mut endpoints: Vec<(String, usize)> = outputs.chain(flipflops).collect();
+    endpoints.sort_by_key(|(_, depth)| core::cmp::Reverse(*depth));
+
+    StructureReport {
+        depths,
+        endpoints,
+        fan_in,
+        fan_out,
+        fanout_limit,
+        high_fanout,
+        unused_inputs,
+        dangling,
+    }
+}
+
+impl StructureReport {
+    /// Number of NAND levels between the inputs or flip-flops and the net
+    pub fn depth(&self, net: Net) -> usize {
+        self.depths[net.index()]
+    }
+
+    /// Depth of the deepest output or flip-flop D input
+    pub fn max_depth(&self) -> usize {
+        self.endpoints.first().map_or(0, |(_, depth)| *depth)
+    }
+
+    /// Mean depth of the outputs and flip-flop D inputs
+    pub fn average_depth(&self) -> f64 {
+        if self.endpoints.is_empty() {
+            return 0.0;
+        }
+        let total: usize = self.endpoints.iter().map(|(_, depth)| depth).sum();
+        total as f64 / self.endpoints.len() as f64
+    }
+}
+
+impl fmt::Display for StructureReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let histogram = |counts: &BTreeMap<usize, usize>| -> String {
+            counts
+                .iter()
+                .map(|(n, count)| format!("{}: {}", n, count))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let list = |names: &[String]| -> String {
+            if names.is_empty() {
+                "none".to_string()
+            } else {
+                names.join(", ")
+            }
+        };
+
+        writeln!(
+            f,
+            "logic depth {} max, {:.2} average (NAND levels)",
+            self.max_depth(),
+            self.average_depth()
+        )?;
+        writeln!(f, "fan-in  {}", histogram(&self.fan_in))?;
+        writeln!(f, "fan-out {}", histogram(&self.fan_out))?;
+        if !self.high_fanout.is_empty() {
+            writeln!(f, "fan-out over {}:", self.fanout_limit)?;
+            for (name, readers) in &self.high_fanout {
+                writeln!(f, "  {:>4}  {}", readers, name)?;
+            }
+        }
+        writeln!(f, "unused inputs: {}", list(&self.unused_inputs))?;
+        writeln!(f, "dangling cells: {}", list(&self.dangling))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mux::build_mux32;
+    use crate::netlist::Builder;
+    use crate::shift::build_rotate_left_8;
+    use crate::timing::{self, Delays};
+
+    #[test]
+    fn test_report() {
+        let mut b = Builder::new("small");
+        let [a, c, _unused] = b.input_bus::<3>("in");
+        let x = b.and(&[a, c]);
+        b.nand(&[x, a, c]);
+        b.output("x", x);
+        b.output("a", a);
+        let netlist = b.finish();
+
+        let report = analyze(&netlist, 2);
+        assert_eq!(report.max_depth(), 2);
+        assert_eq!(report.average_depth(), 1.0);
+        assert_eq!(
+            report.to_string(),
+            "logic depth 2 max, 1.00 average (NAND levels)\n\
+             fan-in  2: 2, 3: 1\n\
+             fan-out 0: 2, 2: 3, 3: 1\n\
+             fan-out over 2:\n\
+             \x20    3  in[0]\n\
+             unused inputs: in[2]\n\
+             dangling cells: nand0\n"
+        );
+    }
+
+    #[test]
+    fn test_wide_circuits() {
+        let mut b = Builder::new("mux32");
+        let select = b.input_bus::<5>("select");
+        let input = b.input_bus::<32>("input");
+        let out = build_mux32(&mut b, &select, &input);
+        b.output("out", out);
+        let mux = b.finish();
+
+        let mut b = Builder::new("rotate");
+        let shift = b.input_bus::<3>("shift");
+        let value = b.input_bus::<8>("value");
+        let out = build_rotate_left_8(&mut b, &shift, &value);
+        b.output_bus("out", &out);
+        let rotate = b.finish();
+
+        for netlist in [mux, rotate] {
+            // Depth matches the critical path when every NAND takes one unit
+            let report = analyze(&netlist, 8);
+            let timing = timing::analyze(&netlist, &Delays::new(1.0));
+            assert_eq!(report.max_depth() as f64, timing.min_period());
+            assert!(report.unused_inputs.is_empty() && report.dangling.is_empty());
+
+            // The select lines drive every mux in the tree
+            let (name, readers) = &report.high_fanout[0];
+            assert!(
+                name.starts_with("select[") || name.starts_with("shift["),
+                "{}",
+                report
+            );
+            assert!(*readers > 8, "{}", report);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use crate::mux;
+
+/// Reverses the order of the bits in value. The circuit is wiring only
+pub const fn reverse_bits<const N: usize>(value: &[bool; N]) -> [bool; N] {
+    let mut output = [false; N];
+    let mut i = 0;
+    while i < N {
+        output[i] = value[N - 1 - i];
+        i += 1;
+    }
+    output
+}
+
+/// Reverses the order of the bytes in value, keeping the order of the bits within each byte. The
+/// circuit is wiring only
+pub const fn byte_swap<const N: usize>(value: &[bool; N]) -> [bool; N] {
+    const {
+        assert!(
+            N.is_multiple_of(8),
+            "byte swap needs a whole number of bytes"
+        )
+    };
+
+    let mut output = [false; N];
+    let mut i = 0;
+    while i < N {
+        let byte = i / 8;
+        let bit = i % 8;
+        output[i] = value[(N / 8 - 1 - byte) * 8 + bit];
+        i += 1;
+    }
+    output
+}
+
+/// Reverses the order of the bits in an 8 bit word
+pub const fn reverse_bits_8(value: &[bool; 8]) -> [bool; 8] {
+    reverse_bits(value)
+}
+
+/// Reverses the order of the bits in a 16 bit word
+pub const fn reverse_bits_16(value: &[bool; 16]) -> [bool; 16] {
+    reverse_bits(value)
+}
+
+/// Reverses the order of the bits in a 32 bit word
+pub const fn reverse_bits_32(value: &[bool; 32]) -> [bool; 32] {
+    reverse_bits(value)
+}
+
+/// Swaps the two bytes of a 16 bit half word
+pub const fn byte_swap_16(value: &[bool; 16]) -> [bool; 16] {
+    byte_swap(value)
+}
+
+/// Reverses the four bytes of a 32 bit word
+pub const fn byte_swap_32(value: &[bool; 32]) -> [bool; 32] {
+    byte_swap(value)
+}
+
+/// Reverses the eight bytes of a 64 bit double word
+pub const fn byte_swap_64(value: &[bool; 64]) -> [bool; 64] {
+    byte_swap(value)
+}
+
+/// Converts a 32 bit word between little-endian and big-endian byte order when big_endian is set,
+/// otherwise passes it through unchanged. Used on the load/store path to access big-endian data
+pub fn endian_convert_32(big_endian: bool, value: &[bool; 32]) -> [bool; 32] {
+    let swapped = byte_swap_32(value);
+
+    let mut output = [false; 32];
+    (0..32).for_each(|i| {
+        output[i] = mux::mux2(big_endian, &[value[i], swapped[i]]);
+    });
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::{u16_to_bus, u32_to_bus, u64_to_bus, u8_to_bus};
+
+    #[test]
+    fn test_reverse_bits() {
+        for (value, expected) in [
+            (0b0000_0000u8, 0b0000_0000u8),
+            (0b0000_0001, 0b1000_0000),
+            (0b1010_0011, 0b1100_0101),
+            (0b1111_0000, 0b0000_1111),
+        ] {
+            assert_eq!(
+                reverse_bits_8(&u8_to_bus(value)),
+                u8_to_bus(expected),
+                "failed for inputs: {:?}",
+                value
+            )
+        }
+
+        assert_eq!(reverse_bits_16(&u16_to_bus(0x0001)), u16_to_bus(0x8000));
+        assert_eq!(
+            reverse_bits_32(&u32_to_bus(0x0000_00f1)),
+            u32_to_bus(0x8f00_0000)
+        );
+    }
+
+    #[test]
+    fn test_byte_swap() {
+        assert_eq!(byte_swap_16(&u16_to_bus(0x1234)), u16_to_bus(0x3412));
+        assert_eq!(
+            byte_swap_32(&u32_to_bus(0x1234_5678)),
+            u32_to_bus(0x7856_3412)
+        );
+        assert_eq!(
+            byte_swap_64(&u64_to_bus(0x0102_0304_0506_0708)),
+            u64_to_bus(0x0807_0605_0403_0201)
+        );
+        assert_eq!(byte_swap(&u8_to_bus(0xa5)), u8_to_bus(0xa5));
+    }
+
+    #[test]
+    fn test_endian_convert_32() {
+        for (big_endian, value, expected) in [
+            (false, 0x1234_5678, 0x1234_5678),
+            (true, 0x1234_5678, 0x7856_3412),
+            (true, 0xff00_0000, 0x0000_00ff),
+        ] {
+            assert_eq!(
+                endian_convert_32(big_endian, &u32_to_bus(value)),
+                u32_to_bus(expected),
+                "failed for inputs: {:?}",
+                (big_endian, value)
+            )
+        }
+    }
+}
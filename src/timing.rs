@@ -0,0 +1,506 @@
+use core::cmp::Reverse;
+use core::fmt;
+use std::collections::{BTreeMap, BinaryHeap};
+
+use crate::netlist::{CellId, CellKind, Driver, Net, Netlist};
+
+/// Number of endpoints listed in a timing report
+const ENDPOINTS_SHOWN: usize = 8;
+
+/// Propagation delays of the cells of a netlist, in arbitrary time units
+#[derive(Clone, Debug, PartialEq)]
+pub struct Delays {
+    /// Delay of a two input NAND gate
+    pub nand: f64,
+    /// Extra delay of a NAND gate for each input beyond two
+    pub per_input: f64,
+    /// Delay from a rising clock to a flip-flop's output
+    pub clk_to_q: f64,
+    /// Time a flip-flop's D input must be stable before the clock rises
+    pub setup: f64,
+    /// Time a flip-flop's D input must be stable after the clock rises
+    pub hold: f64,
+    /// NAND delays inside instances of derived gates, by base name
+    pub(crate) gates: Vec<(String, f64)>,
+    /// NAND delays of individual cells, by path
+    pub(crate) cells: Vec<(String, f64)>,
+}
+
+impl Delays {
+    /// Every NAND gate takes the same time, and flip-flops take twice as long
+    pub fn new(nand: f64) -> Self {
+        Delays {
+            nand,
+            per_input: 0.0,
+            clk_to_q: 2.0 * nand,
+            setup: nand,
+            hold: 0.0,
+            gates: Vec::new(),
+            cells: Vec::new(),
+        }
+    }
+
+    /// Sets the delay of the NAND gates inside every instance of a derived gate, e.g. "xor".
+    /// The innermost instance with a delay wins
+    pub fn with_gate(mut self, base: &str, nand: f64) -> Self {
+        self.gates.push((base.to_string(), nand));
+        self
+    }
+
+    /// Sets the delay of one cell, given by its hierarchical path
+    pub fn with_cell(mut self, path: &str, delay: f64) -> Self {
+        self.cells.push((path.to_string(), delay));
+        self
+    }
+
+    /// Delay from any input of a NAND cell to its output
+    pub fn nand_delay(&self, netlist: &Netlist, id: CellId) -> f64 {
+        let cell = netlist.cell(id);
+        let path = netlist.cell_path(id);
+        if let Some((_, delay)) = self.cells.iter().find(|(p, _)| *p == path) {
+            return *delay;
+        }
+
+        let extra = cell.inputs().len().saturating_sub(2) as f64 * self.per_input;
+        let gate = netlist
+            .scope_ancestry(cell.scope())
+            .iter()
+            .rev()
+            .find_map(|s| {
+                self.gates
+                    .iter()
+                    .find(|(base, _)| netlist.scope(*s).is_instance_of(base))
+            })
+            .map(|(_, delay)| *delay);
+        gate.unwrap_or(self.nand) + extra
+    }
+}
+
+impl Default for Delays {
+    fn default() -> Self {
+        Delays::new(1.0)
+    }
+}
+
+/// Computes the time each net settles after a rising clock edge, with the inputs changing at the
+/// edge and clocks arriving at every flip-flop at once
+pub fn analyze(netlist: &Netlist, delays: &Delays) -> Timing {
+    // Constant nets never change, so they have no arrival time
+    let mut arrival: Vec<Option<f64>> = vec![None; netlist.num_nets()];
+    for port in netlist.inputs() {
+        arrival[port.net().index()] = Some(0.0);
+    }
+    for id in netlist.cell_ids() {
+        let cell = netlist.cell(id);
+        arrival[cell.output().index()] = match cell.kind() {
+            CellKind::Nand => cell
+                .inputs()
+                .iter()
+                .filter_map(|n| arrival[n.index()])
+                .reduce(f64::max)
+                .map(|t| t + delays.nand_delay(netlist, id)),
+            CellKind::Const(_) => None,
+            CellKind::Dff => Some(delays.clk_to_q),
+        };
+    }
+
+    // Every output and flip-flop D input is the end of a path
+    let outputs = netlist
+        .outputs()
+        .iter()
+        .map(|p| (p.name().to_string(), p.net(), 0.0));
+    let flipflops = netlist.flipflops().into_iter().map(|id| {
+        (
+            format!("{}/d", netlist.cell_path(id)),
+            netlist.cell(id).inputs()[1],
+            delays.setup,
+        )
+    });
+    let times = arrival_times(&arrival);
+    let mut paths: Vec<Path> = outputs
+        .chain(flipflops)
+        .filter_map(|(end, net, setup)| {
+            let time = arrival[net.index()]?;
+            Some(trace(netlist, &times, end, net, time, setup))
+        })
+        .collect();
+    paths.sort_by(|a, b| b.required().total_cmp(&a.required()));
+
+    Timing { arrival, paths }
+}
+
+fn arrival_times(arrival: &[Option<f64>]) -> Vec<f64> {
+    arrival
+        .iter()
+        .map(|t| t.unwrap_or(f64::NEG_INFINITY))
+        .collect()
+}
+
+/// Follows the latest arriving input of each cell back from an endpoint to an input or
+/// flip-flop
+fn trace(netlist: &Netlist, arrival: &[f64], end: String, net: Net, time: f64, setup: f64) -> Path {
+    let mut cells = Vec::new();
+    let mut net = net;
+    let start = loop {
+        match netlist.driver(net) {
+            Some(Driver::Cell(id)) if netlist.cell(id).kind() == CellKind::Nand => {
+                cells.push(id);
+                net = *netlist
+                    .cell(id)
+                    .inputs()
+                    .iter()
+                    .reduce(|a, b| {
+                        if arrival[b.index()] > arrival[a.index()] {
+                            b
+                        } else {
+                            a
+                        }
+                    })
+                    .expect("NAND gates have inputs");
+            }
+            Some(Driver::Cell(id)) => {
+                cells.push(id);
+                break netlist.cell_path(id);
+            }
+            _ => break netlist.net_name(net),
+        }
+    };
+    cells.reverse();
+
+    Path {
+        through: components(netlist, &cells),
+        start,
+        end,
+        cells,
+        arrival: time,
+        setup,
+    }
+}
+
+/// Top level components a path passes through, with runs of bus indices collapsed, e.g.
+/// "full_add[0..31]"
+fn components(netlist: &Netlist, cells: &[CellId]) -> String {
+    let mut names: Vec<String> = Vec::new();
+    for id in cells {
+        let cell = netlist.cell(*id);
+        let name = match netlist.scope_ancestry(cell.scope()).get(1) {
+            Some(scope) => netlist.scope(*scope).name().to_string(),
+            None if cell.kind() == CellKind::Dff => continue,
+            None => cell.name().to_string(),
+        };
+        if names.last() != Some(&name) {
+            names.push(name);
+        }
+    }
+
+    let index = |name: &str| -> Option<(String, i64)> {
+        let (base, rest) = name.split_once('[')?;
+        Some((base.to_string(), rest.strip_suffix(']')?.parse().ok()?))
+    };
+    // Names with consecutive indices in one direction form a run, kept as the base name and
+    // the first and last index
+    let mut runs: Vec<(String, Option<(i64, i64)>)> = Vec::new();
+    for name in names {
+        let Some((base, i)) = index(&name) else {
+            runs.push((name, None));
+            continue;
+        };
+        if let Some((b, Some((first, last)))) = runs.last_mut() {
+            let extends = match (*last - *first).signum() {
+                0 => (i - *last).abs() == 1,
+                step => i - *last == step,
+            };
+            if *b == base && extends {
+                *last = i;
+                continue;
+            }
+        }
+        runs.push((base, Some((i, i))));
+    }
+    runs.iter()
+        .map(|(name, run)| match run {
+            Some((first, last)) if first != last => format!("{}[{}..{}]", name, first, last),
+            Some((first, _)) => format!("{}[{}]", name, first),
+            None => name.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The latest arriving path to one endpoint
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path {
+    /// Input or flip-flop the path starts from
+    pub start: String,
+    /// Output or flip-flop D input the path ends at
+    pub end: String,
+    /// Cells along the path in signal order
+    pub cells: Vec<CellId>,
+    /// Summary of the components the path passes through
+    pub through: String,
+    pub arrival: f64,
+    /// Setup time needed at the endpoint, which is zero for outputs
+    pub setup: f64,
+}
+
+impl Path {
+    /// Shortest clock period the path allows
+    pub fn required(&self) -> f64 {
+        self.arrival + self.setup
+    }
+
+    /// Time left over at a clock period, negative if the path is too slow
+    pub fn slack(&self, period: f64) -> f64 {
+        period - self.required()
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}", self.start, self.end)?;
+        if !self.through.is_empty() {
+            write!(f, " through {}", self.through)?;
+        }
+        write!(f, " ({:.2})", self.required())
+    }
+}
+
+/// Arrival times of a netlist and the latest path to each endpoint
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timing {
+    arrival: Vec<Option<f64>>,
+    paths: Vec<Path>,
+}
+
+impl Timing {
+    /// Time the net settles after a clock edge, or None for constant nets
+    pub fn arrival(&self, net: Net) -> Option<f64> {
+        self.arrival[net.index()]
+    }
+
+    /// Latest path to each endpoint, slowest first
+    pub fn paths(&self) -> &[Path] {
+        &self.paths
+    }
+
+    /// The slowest path, or None if no endpoint depends on an input or flip-flop
+    pub fn critical_path(&self) -> Option<&Path> {
+        self.paths.first()
+    }
+
+    /// Shortest clock period at which every path settles in time
+    pub fn min_period(&self) -> f64 {
+        self.critical_path().map_or(0.0, Path::required)
+    }
+
+    /// Slack of the critical path at a clock period
+    pub fn worst_slack(&self, period: f64) -> f64 {
+        period - self.min_period()
+    }
+
+    pub fn report(&self, period: f64) -> TimingReport<'_> {
+        TimingReport {
+            timing: self,
+            period,
+        }
+    }
+}
+
+/// Summary of the critical path and the slack of the slowest endpoints at a clock period
+pub struct TimingReport<'a> {
+    timing: &'a Timing,
+    period: f64,
+}
+
+impl fmt::Display for TimingReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let timing = self.timing;
+        let slack = timing.worst_slack(self.period);
+        writeln!(
+            f,
+            "clock period {:.2}, minimum {:.2}, worst slack {:.2} ({})",
+            self.period,
+            timing.min_period(),
+            slack,
+            if slack < 0.0 { "violated" } else { "met" }
+        )?;
+        if let Some(path) = timing.critical_path() {
+            writeln!(f, "critical path {}", path)?;
+        }
+        for path in timing.paths.iter().take(ENDPOINTS_SHOWN) {
+            writeln!(f, "  {:>8.2}  {}", path.slack(self.period), path.end)?;
+        }
+        if timing.paths.len() > ENDPOINTS_SHOWN {
+            writeln!(f, "  ...")?;
+        }
+        Ok(())
+    }
+}
+
+/// Event driven simulation of a netlist: changes of nets are scheduled for later times, and
+/// applied one time step at a time so the cells reading them can be re-evaluated
+pub(crate) struct EventQueue<V> {
+    /// Time as bits, order of scheduling, net index and value of each change. Times are never
+    /// negative, so their bit patterns sort in time order
+    queue: BinaryHeap<Reverse<(u64, usize, usize, V)>>,
+    sequence: usize,
+    fanout: Vec<Vec<CellId>>,
+    /// Current value of each net
+    pub values: Vec<V>,
+    /// Time and new value of each change of each net
+    pub events: Vec<Vec<(f64, V)>>,
+}
+
+/// Nets which changed at one time step of an [EventQueue]
+pub(crate) struct Step<V> {
+    pub time: f64,
+    /// Value of each changed net before the step, by net index
+    pub previous: BTreeMap<usize, V>,
+    /// Cells reading a changed net, each once and in order
+    pub cells: Vec<CellId>,
+}
+
+impl<V: Copy + Ord> EventQueue<V> {
+    pub fn new(netlist: &Netlist, initial: Vec<V>) -> Self {
+        EventQueue {
+            queue: BinaryHeap::new(),
+            sequence: 0,
+            fanout: netlist.fanout(),
+            values: initial,
+            events: vec![Vec::new(); netlist.num_nets()],
+        }
+    }
+
+    pub fn schedule(&mut self, time: f64, net: Net, value: V) {
+        self.queue
+            .push(Reverse((time.to_bits(), self.sequence, net.index(), value)));
+        self.sequence += 1;
+    }
+
+    /// Applies the changes scheduled for the earliest time, or returns None once there are none.
+    /// The last value scheduled on each net at that time wins
+    pub fn step(&mut self) -> Option<Step<V>> {
+        let Reverse((time, ..)) = *self.queue.peek()?;
+        let mut changes = BTreeMap::new();
+        while let Some(Reverse((t, _, net, value))) = self.queue.peek().copied() {
+            if t != time {
+                break;
+            }
+            self.queue.pop();
+            changes.insert(net, value);
+        }
+
+        let time = f64::from_bits(time);
+        let mut previous = BTreeMap::new();
+        for (net, value) in changes {
+            if self.values[net] != value {
+                previous.insert(net, self.values[net]);
+                self.values[net] = value;
+                self.events[net].push((time, value));
+            }
+        }
+        let mut cells: Vec<CellId> = previous
+            .keys()
+            .flat_map(|n| &self.fanout[*n])
+            .copied()
+            .collect();
+        cells.sort_unstable();
+        cells.dedup();
+        Some(Step {
+            time,
+            previous,
+            cells,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::RippleCounter;
+    use crate::math::{CarryLookaheadAdder, RippleCarryAdder};
+    use crate::netlist::Builder;
+
+    #[test]
+    fn test_ripple_carry_adder() {
+        let netlist = RippleCarryAdder::<32>::new().netlist();
+        let timing = analyze(&netlist, &Delays::default());
+        let path = timing.critical_path().unwrap();
+        assert_eq!(path.end, "sum[31]");
+        assert_eq!(path.through, "full_add[0..31]");
+
+        // Each bit adds the same delay to the carry chain
+        let period = |netlist: Netlist| analyze(&netlist, &Delays::default()).min_period();
+        let delays = [
+            period(RippleCarryAdder::<8>::new().netlist()),
+            period(RippleCarryAdder::<16>::new().netlist()),
+            period(RippleCarryAdder::<24>::new().netlist()),
+        ];
+        assert_eq!(delays[2] - delays[1], delays[1] - delays[0]);
+        assert!(timing.min_period() > delays[2]);
+
+        // The carry lookahead adder doesn't wait for the carry to ripple
+        let lookahead = analyze(
+            &CarryLookaheadAdder::<32>::new().netlist(),
+            &Delays::default(),
+        );
+        assert!(
+            lookahead.min_period() < timing.min_period() / 2.0,
+            "{} vs {}",
+            lookahead.min_period(),
+            timing.min_period()
+        );
+    }
+
+    #[test]
+    fn test_delays() {
+        let mut b = Builder::new("chain");
+        let a = b.input_bus::<3>("a");
+        let x = b.xor(&a[..2]);
+        let y = b.nand(&[x, a[0], a[1], a[2]]);
+        b.output("y", y);
+        let netlist = b.finish();
+
+        // The xor is four NANDs deep, followed by a four input NAND
+        for (delays, expected) in [
+            (Delays::new(1.0), 5.0),
+            (
+                Delays {
+                    per_input: 0.5,
+                    ..Delays::new(1.0)
+                },
+                6.0,
+            ),
+            (Delays::new(1.0).with_gate("xor", 2.0), 9.0),
+            (Delays::new(1.0).with_gate("not", 0.5), 4.0),
+            (Delays::new(1.0).with_cell("nand0", 3.0), 7.0),
+        ] {
+            let timing = analyze(&netlist, &delays);
+            assert_eq!(
+                timing.arrival(y),
+                Some(expected),
+                "failed for delays: {:?}",
+                delays
+            );
+        }
+    }
+
+    #[test]
+    fn test_sequential_report() {
+        // Each bit's D input is its inverted output
+        let netlist = RippleCounter::<4>::new().netlist();
+        let timing = analyze(&netlist, &Delays::new(1.0));
+        assert_eq!(timing.min_period(), 4.0);
+        let path = timing.critical_path().unwrap();
+        assert_eq!(path.start, "bit[0]/dff0");
+        assert_eq!(path.end, "bit[0]/dff0/d");
+        assert_eq!(path.through, "bit[0]");
+
+        let report = timing.report(3.0).to_string();
+        assert!(report.starts_with(
+            "clock period 3.00, minimum 4.00, worst slack -1.00 (violated)\n\
+             critical path bit[0]/dff0 -> bit[0]/dff0/d through bit[0] (4.00)\n\
+             \x20    -1.00  bit[0]/dff0/d\n"
+        ));
+    }
+}
@@ -0,0 +1,817 @@
+use core::fmt;
+
+use crate::bus::Bus;
+use crate::netlist::Netlist;
+
+/// Largest number of inputs a truth table can be generated for
+pub const MAX_INPUTS: usize = 24;
+
+/// Number of mismatching rows printed when displaying a failed comparison
+const MISMATCHES_SHOWN: usize = 8;
+
+/// Outputs of a combinational function which can be listed in a truth table
+pub trait Outputs {
+    fn to_bits(&self) -> Vec<bool>;
+}
+
+impl Outputs for bool {
+    fn to_bits(&self) -> Vec<bool> {
+        vec![*self]
+    }
+}
+
+impl<const M: usize> Outputs for [bool; M] {
+    fn to_bits(&self) -> Vec<bool> {
+        self.to_vec()
+    }
+}
+
+impl<const M: usize> Outputs for Bus<M> {
+    fn to_bits(&self) -> Vec<bool> {
+        self.to_vec()
+    }
+}
+
+impl<A: Outputs, B: Outputs> Outputs for (A, B) {
+    fn to_bits(&self) -> Vec<bool> {
+        let mut bits = self.0.to_bits();
+        bits.extend(self.1.to_bits());
+        bits
+    }
+}
+
+/// Truth table of a combinational function. Row r holds the outputs for the inputs given by the
+/// bits of r, with input 0 as the least significant bit
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TruthTable {
+    input_names: Vec<String>,
+    output_names: Vec<String>,
+    /// Outputs of every row, one row after another
+    outputs: Vec<bool>,
+}
+
+impl TruthTable {
+    /// Generates the truth table of a function by evaluating it for all 2^N inputs
+    pub fn from_fn<const N: usize, O: Outputs>(f: impl Fn(&[bool; N]) -> O) -> Self {
+        assert!(N <= MAX_INPUTS, "too many inputs for a truth table");
+
+        let mut outputs = Vec::new();
+        let mut num_outputs = 0;
+        for row in 0..1usize << N {
+            let bits = f(&input_bits(row));
+            let bits = bits.to_bits();
+            num_outputs = bits.len();
+            outputs.extend(bits);
+        }
+
+        TruthTable {
+            input_names: default_names("in", N),
+            output_names: default_names("out", num_outputs),
+            outputs,
+        }
+    }
+
+    /// Generates the truth table of a combinational netlist, naming the columns after its ports
+    pub fn from_netlist(netlist: &Netlist) -> Self {
+        let num_inputs = netlist.inputs().len();
+        assert!(
+            num_inputs <= MAX_INPUTS,
+            "too many inputs for a truth table"
+        );
+
+        let mut outputs = Vec::new();
+        for row in 0..1usize << num_inputs {
+            let inputs: Vec<bool> = (0..num_inputs).map(|i| (row >> i) & 1 == 1).collect();
+            outputs.extend(netlist.evaluate(&inputs));
+        }
+
+        TruthTable {
+            input_names: netlist
+                .inputs()
+                .iter()
+                .map(|p| p.name().to_string())
+                .collect(),
+            output_names: netlist
+                .outputs()
+                .iter()
+                .map(|p| p.name().to_string())
+                .collect(),
+            outputs,
+        }
+    }
+
+    /// Creates a truth table from the outputs of each row
+    pub fn from_rows(num_inputs: usize, rows: &[Vec<bool>]) -> Result<Self, TableError> {
+        if num_inputs > MAX_INPUTS {
+            return Err(TableError::TooManyInputs(num_inputs));
+        }
+        if rows.len() != 1 << num_inputs {
+            return Err(TableError::WrongRowCount {
+                found: rows.len(),
+                expected: 1 << num_inputs,
+            });
+        }
+
+        let num_outputs = rows.first().map_or(0, |row| row.len());
+        if let Some(row) = rows.iter().position(|row| row.len() != num_outputs) {
+            return Err(TableError::WrongOutputCount {
+                row,
+                found: rows[row].len(),
+                expected: num_outputs,
+            });
+        }
+
+        Ok(TruthTable {
+            input_names: default_names("in", num_inputs),
+            output_names: default_names("out", num_outputs),
+            outputs: rows.concat(),
+        })
+    }
+
+    /// Parses a table previously written with [TruthTable::to_csv]
+    pub fn from_csv(csv: &str) -> Result<Self, TableError> {
+        let Csv {
+            input_names,
+            output_names,
+            rows: lines,
+        } = read_csv(csv, MAX_INPUTS)?;
+
+        let mut rows = vec![None; 1 << input_names.len()];
+        for (line, inputs, outputs) in lines {
+            let row = inputs
+                .iter()
+                .enumerate()
+                .fold(0, |row, (i, bit)| row | (*bit as usize) << i);
+            if rows[row].is_some() {
+                return Err(TableError::DuplicateRow { line, row });
+            }
+            rows[row] = Some(outputs);
+        }
+
+        let rows = rows
+            .into_iter()
+            .enumerate()
+            .map(|(row, outputs)| outputs.ok_or(TableError::MissingRow(row)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut table = TruthTable::from_rows(input_names.len(), &rows)?;
+        table.input_names = input_names;
+        table.output_names = output_names;
+        Ok(table)
+    }
+
+    /// Names the input columns. Panics if the number of names doesn't match the inputs
+    pub fn with_input_names(mut self, names: &[&str]) -> Self {
+        assert_eq!(
+            names.len(),
+            self.num_inputs(),
+            "wrong number of input names"
+        );
+        self.input_names = names.iter().map(|n| n.to_string()).collect();
+        self
+    }
+
+    /// Names the output columns. Panics if the number of names doesn't match the outputs
+    pub fn with_output_names(mut self, names: &[&str]) -> Self {
+        assert_eq!(
+            names.len(),
+            self.num_outputs(),
+            "wrong number of output names"
+        );
+        self.output_names = names.iter().map(|n| n.to_string()).collect();
+        self
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.input_names.len()
+    }
+
+    pub fn num_outputs(&self) -> usize {
+        self.output_names.len()
+    }
+
+    pub fn num_rows(&self) -> usize {
+        1 << self.num_inputs()
+    }
+
+    pub fn input_names(&self) -> &[String] {
+        &self.input_names
+    }
+
+    pub fn output_names(&self) -> &[String] {
+        &self.output_names
+    }
+
+    /// Inputs of the given row, with input 0 first
+    pub fn inputs(&self, row: usize) -> Vec<bool> {
+        (0..self.num_inputs())
+            .map(|i| (row >> i) & 1 == 1)
+            .collect()
+    }
+
+    /// Outputs of the given row
+    pub fn outputs(&self, row: usize) -> &[bool] {
+        let n = self.num_outputs();
+        &self.outputs[row * n..(row + 1) * n]
+    }
+
+    /// Value of one output in the given row
+    pub fn output(&self, row: usize, output: usize) -> bool {
+        self.outputs(row)[output]
+    }
+
+    /// Rows in which the given output is true
+    pub fn minterms(&self, output: usize) -> Vec<usize> {
+        (0..self.num_rows())
+            .filter(|row| self.output(*row, output))
+            .collect()
+    }
+
+    /// Compares the table against an expected table, reporting every row which differs
+    pub fn verify(&self, expected: &TruthTable) -> Result<(), VerifyError> {
+        let shape = (self.num_inputs(), self.num_outputs());
+        let expected_shape = (expected.num_inputs(), expected.num_outputs());
+        if shape != expected_shape {
+            return Err(VerifyError::Shape {
+                found: shape,
+                expected: expected_shape,
+            });
+        }
+
+        let mismatches: Vec<Mismatch> = (0..self.num_rows())
+            .filter(|row| self.outputs(*row) != expected.outputs(*row))
+            .map(|row| Mismatch {
+                row,
+                inputs: self.inputs(row),
+                expected: expected.outputs(row).to_vec(),
+                actual: self.outputs(row).to_vec(),
+            })
+            .collect();
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(VerifyError::Mismatches(Mismatches {
+                input_names: self.input_names.clone(),
+                output_names: self.output_names.clone(),
+                mismatches,
+            }))
+        }
+    }
+
+    /// Compares the table against a reference implementation of the function
+    pub fn verify_fn<const N: usize, O: Outputs>(
+        &self,
+        reference: impl Fn(&[bool; N]) -> O,
+    ) -> Result<(), VerifyError> {
+        self.verify(&TruthTable::from_fn(reference))
+    }
+
+    /// Renders the table as aligned plain text
+    pub fn to_text(&self) -> String {
+        let header: Vec<&str> = self.columns().collect();
+        let widths: Vec<usize> = header.iter().map(|h| h.len().max(1)).collect();
+
+        let mut text = String::new();
+        let mut write_line = |cells: &[&str]| {
+            let line: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:^width$}", cell, width = width))
+                .collect();
+            text.push_str(line.join(" ").trim_end());
+            text.push('\n');
+        };
+
+        write_line(&header);
+        let rule: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(i, w)| {
+                if i == self.num_inputs() {
+                    "|".to_string()
+                } else {
+                    "-".repeat(*w)
+                }
+            })
+            .collect();
+        write_line(&rule.iter().map(String::as_str).collect::<Vec<_>>());
+        for row in 0..self.num_rows() {
+            write_line(&self.row_cells(row));
+        }
+        text
+    }
+
+    /// Renders the table as a Markdown table
+    pub fn to_markdown(&self) -> String {
+        let names: Vec<&String> = self
+            .input_names
+            .iter()
+            .chain(self.output_names.iter())
+            .collect();
+
+        let mut md = String::new();
+        md.push_str(&format!(
+            "| {} |\n",
+            names
+                .iter()
+                .map(|n| n.as_str())
+                .collect::<Vec<_>>()
+                .join(" | ")
+        ));
+        md.push_str(&format!("|{}\n", " - |".repeat(names.len())));
+        for row in 0..self.num_rows() {
+            let cells: Vec<&str> = self
+                .row_cells(row)
+                .into_iter()
+                .filter(|c| *c != "|")
+                .collect();
+            md.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+        md
+    }
+
+    /// Renders the table as comma separated values. An empty column separates the inputs from the
+    /// outputs, and names which are empty or contain commas or quotes are quoted
+    pub fn to_csv(&self) -> String {
+        let rows = (0..self.num_rows()).map(|row| (self.inputs(row), self.outputs(row).to_vec()));
+        write_csv(&self.input_names, &self.output_names, rows)
+    }
+
+    /// Column headings, with a "|" column between the inputs and outputs
+    fn columns(&self) -> impl Iterator<Item = &str> {
+        self.input_names
+            .iter()
+            .map(String::as_str)
+            .chain(core::iter::once("|"))
+            .chain(self.output_names.iter().map(String::as_str))
+    }
+
+    fn row_cells(&self, row: usize) -> Vec<&'static str> {
+        let bit = |b: bool| if b { "1" } else { "0" };
+        self.inputs(row)
+            .into_iter()
+            .map(bit)
+            .chain(core::iter::once("|"))
+            .chain(self.outputs(row).iter().map(|b| bit(*b)))
+            .collect()
+    }
+}
+
+impl fmt::Display for TruthTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_text())
+    }
+}
+
+/// A row in which two truth tables differ
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    pub row: usize,
+    pub inputs: Vec<bool>,
+    pub expected: Vec<bool>,
+    pub actual: Vec<bool>,
+}
+
+/// Every row in which two truth tables differ, in row order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatches {
+    input_names: Vec<String>,
+    output_names: Vec<String>,
+    mismatches: Vec<Mismatch>,
+}
+
+impl Mismatches {
+    pub(crate) fn new(
+        input_names: Vec<String>,
+        output_names: Vec<String>,
+        mismatches: Vec<Mismatch>,
+    ) -> Self {
+        Mismatches {
+            input_names,
+            output_names,
+            mismatches,
+        }
+    }
+
+    /// The first row which differs
+    pub fn first(&self) -> &Mismatch {
+        &self.mismatches[0]
+    }
+
+    pub fn rows(&self) -> &[Mismatch] {
+        &self.mismatches
+    }
+}
+
+impl fmt::Display for Mismatches {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bits =
+            |bits: &[bool]| -> String { bits.iter().map(|b| if *b { '1' } else { '0' }).collect() };
+
+        writeln!(
+            f,
+            "{} mismatching rows (inputs {} -> outputs {}):",
+            self.mismatches.len(),
+            self.input_names.join(","),
+            self.output_names.join(",")
+        )?;
+        for m in self.mismatches.iter().take(MISMATCHES_SHOWN) {
+            writeln!(
+                f,
+                "  row {}: inputs {} expected {} actual {}",
+                m.row,
+                bits(&m.inputs),
+                bits(&m.expected),
+                bits(&m.actual)
+            )?;
+        }
+        if self.mismatches.len() > MISMATCHES_SHOWN {
+            writeln!(f, "  ...")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Mismatches {}
+
+/// Error returned when a truth table doesn't match the expected one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The tables have different numbers of (inputs, outputs)
+    Shape {
+        found: (usize, usize),
+        expected: (usize, usize),
+    },
+    Mismatches(Mismatches),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::Shape { found, expected } => write!(
+                f,
+                "table has {} inputs and {} outputs but expected {} inputs and {} outputs",
+                found.0, found.1, expected.0, expected.1
+            ),
+            VerifyError::Mismatches(mismatches) => write!(f, "{}", mismatches),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Error returned when a truth table can't be built or parsed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TableError {
+    TooManyInputs(usize),
+    WrongRowCount {
+        found: usize,
+        expected: usize,
+    },
+    WrongOutputCount {
+        row: usize,
+        found: usize,
+        expected: usize,
+    },
+    MissingHeader,
+    MissingRow(usize),
+    /// The inputs of a row were already listed on an earlier line
+    DuplicateRow {
+        line: usize,
+        row: usize,
+    },
+    Syntax {
+        line: usize,
+    },
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TableError::TooManyInputs(n) => {
+                write!(f, "{} inputs is more than the limit of {}", n, MAX_INPUTS)
+            }
+            TableError::WrongRowCount { found, expected } => {
+                write!(f, "found {} rows but expected {}", found, expected)
+            }
+            TableError::WrongOutputCount {
+                row,
+                found,
+                expected,
+            } => write!(
+                f,
+                "row {} has {} outputs but expected {}",
+                row, found, expected
+            ),
+            TableError::MissingHeader => write!(f, "missing header separating inputs and outputs"),
+            TableError::MissingRow(row) => write!(f, "row {} is missing", row),
+            TableError::DuplicateRow { line, row } => {
+                write!(f, "row {} on line {} is listed more than once", row, line)
+            }
+            TableError::Syntax { line } => write!(f, "syntax error on line {}", line),
+        }
+    }
+}
+
+impl std::error::Error for TableError {}
+
+fn input_bits<const N: usize>(row: usize) -> [bool; N] {
+    core::array::from_fn(|i| (row >> i) & 1 == 1)
+}
+
+fn default_names(prefix: &str, n: usize) -> Vec<String> {
+    (0..n).map(|i| format!("{}[{}]", prefix, i)).collect()
+}
+
+/// Rows of inputs and outputs read from CSV, as written by [write_csv]
+pub(crate) struct Csv {
+    pub input_names: Vec<String>,
+    pub output_names: Vec<String>,
+    /// Line number, inputs and outputs of each row
+    pub rows: Vec<(usize, Vec<bool>, Vec<bool>)>,
+}
+
+/// Writes named inputs and outputs as CSV, with an empty column between them
+pub(crate) fn write_csv(
+    input_names: &[String],
+    output_names: &[String],
+    rows: impl Iterator<Item = (Vec<bool>, Vec<bool>)>,
+) -> String {
+    let name = |name: &String| {
+        let plain = !name.is_empty() && name.trim() == name && !name.contains([',', '"']);
+        if plain {
+            name.clone()
+        } else {
+            format!("\"{}\"", name.replace('"', "\"\""))
+        }
+    };
+    let bit = |b: &bool| if *b { "1" } else { "0" }.to_string();
+    let line = |inputs: Vec<String>, outputs: Vec<String>| {
+        let mut cells = inputs;
+        cells.push(String::new());
+        cells.extend(outputs);
+        cells.join(",") + "\n"
+    };
+
+    let mut csv = line(
+        input_names.iter().map(name).collect(),
+        output_names.iter().map(name).collect(),
+    );
+    for (inputs, outputs) in rows {
+        csv.push_str(&line(
+            inputs.iter().map(bit).collect(),
+            outputs.iter().map(bit).collect(),
+        ));
+    }
+    csv
+}
+
+/// Reads CSV written by [write_csv], skipping blank lines. Fails before reading any rows if
+/// there are more than the given number of inputs
+pub(crate) fn read_csv(csv: &str, max_inputs: usize) -> Result<Csv, TableError> {
+    let mut lines = csv
+        .lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty());
+    let (header_line, header) = lines.next().ok_or(TableError::MissingHeader)?;
+
+    // The one unquoted empty column separates the outputs from the inputs
+    let columns = split_header(header).ok_or(TableError::Syntax {
+        line: header_line + 1,
+    })?;
+    let mut separators = columns.iter().enumerate().filter(|(_, c)| c.is_none());
+    let (split, _) = separators.next().ok_or(TableError::MissingHeader)?;
+    if separators.next().is_some() {
+        return Err(TableError::Syntax {
+            line: header_line + 1,
+        });
+    }
+    let names = |columns: &[Option<String>]| columns.iter().flatten().cloned().collect();
+    let input_names: Vec<String> = names(&columns[..split]);
+    let output_names = names(&columns[split + 1..]);
+    if input_names.len() > max_inputs {
+        return Err(TableError::TooManyInputs(input_names.len()));
+    }
+
+    let mut rows = Vec::new();
+    for (line, text) in lines {
+        let cells: Vec<&str> = text.split(',').map(str::trim).collect();
+        if cells.len() != columns.len() || !cells[split].is_empty() {
+            return Err(TableError::Syntax { line: line + 1 });
+        }
+        let bits = |cells: &[&str]| {
+            cells
+                .iter()
+                .map(|cell| parse_bit(cell, line))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        rows.push((line + 1, bits(&cells[..split])?, bits(&cells[split + 1..])?));
+    }
+    Ok(Csv {
+        input_names,
+        output_names,
+        rows,
+    })
+}
+
+/// Splits a header into names, or None for an unquoted empty column. Returns None if a quote
+/// isn't closed
+fn split_header(header: &str) -> Option<Vec<Option<String>>> {
+    let mut columns = Vec::new();
+    let mut rest = header;
+    loop {
+        let trimmed = rest.trim_start();
+        let (column, next) = if let Some(quoted) = trimmed.strip_prefix('"') {
+            // A doubled quote stands for one quote
+            let mut name = String::new();
+            let mut chars = quoted.char_indices();
+            let end = loop {
+                match chars.next()? {
+                    (i, '"') if quoted[i + 1..].starts_with('"') => {
+                        name.push('"');
+                        chars.next();
+                    }
+                    (i, '"') => break i + 1,
+                    (_, c) => name.push(c),
+                }
+            };
+            let after = quoted[end..].trim_start();
+            if !after.is_empty() && !after.starts_with(',') {
+                return None;
+            }
+            (Some(name), after)
+        } else {
+            let end = trimmed.find(',').unwrap_or(trimmed.len());
+            let name = trimmed[..end].trim();
+            (
+                (!name.is_empty()).then(|| name.to_string()),
+                &trimmed[end..],
+            )
+        };
+        columns.push(column);
+        match next.strip_prefix(',') {
+            Some(next) => rest = next,
+            None => return Some(columns),
+        }
+    }
+}
+
+fn parse_bit(cell: &str, line: usize) -> Result<bool, TableError> {
+    match cell {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(TableError::Syntax { line: line + 1 }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gate::{and, xor};
+    use crate::logic::xor_n;
+    use crate::mux::mux2;
+
+    #[test]
+    fn test_from_fn() {
+        let table = TruthTable::from_fn(|x: &[bool; 2]| and(x));
+        assert_eq!((table.num_inputs(), table.num_outputs()), (2, 1));
+        for (row, expected) in [(0, false), (1, false), (2, false), (3, true)] {
+            assert_eq!(table.output(row, 0), expected, "failed for row: {}", row);
+        }
+        assert_eq!(table.minterms(0), [3]);
+        assert_eq!(table.inputs(2), [false, true]);
+    }
+
+    #[test]
+    fn test_multiple_outputs() {
+        let table = TruthTable::from_fn(|x: &[bool; 4]| xor_n(&[x[0], x[1]], &[x[2], x[3]]));
+        assert_eq!(table.num_outputs(), 2);
+        assert_eq!(table.outputs(0b0110), [true, true]);
+        assert_eq!(table.outputs(0b0101), [false, false]);
+
+        let table = TruthTable::from_fn(|x: &[bool; 2]| (and(x), xor(x)));
+        assert_eq!(table.outputs(0b11), [true, false]);
+    }
+
+    #[test]
+    fn test_verify() {
+        let table = TruthTable::from_fn(|x: &[bool; 3]| mux2(x[0], &[x[1], x[2]]));
+        assert!(table
+            .verify_fn(|x: &[bool; 3]| if x[0] { x[2] } else { x[1] })
+            .is_ok());
+
+        // A broken reference which ignores the select line
+        let Err(VerifyError::Mismatches(mismatches)) = table.verify_fn(|x: &[bool; 3]| x[1]) else {
+            panic!("expected mismatches");
+        };
+        assert_eq!(mismatches.rows().len(), 2);
+        assert_eq!(
+            *mismatches.first(),
+            Mismatch {
+                row: 0b011,
+                inputs: vec![true, true, false],
+                expected: vec![true],
+                actual: vec![false],
+            }
+        );
+        assert!(mismatches
+            .to_string()
+            .contains("row 3: inputs 110 expected 1 actual 0"));
+
+        assert_eq!(
+            table.verify_fn(|x: &[bool; 2]| [x[0], x[1]]),
+            Err(VerifyError::Shape {
+                found: (3, 1),
+                expected: (2, 2)
+            })
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let table = TruthTable::from_fn(|x: &[bool; 2]| xor(x))
+            .with_input_names(&["a", "b"])
+            .with_output_names(&["y"]);
+
+        assert_eq!(
+            table.to_text(),
+            "a b | y\n- - | -\n0 0 | 0\n1 0 | 1\n0 1 | 1\n1 1 | 0\n"
+        );
+        assert_eq!(
+            table.to_markdown(),
+            "| a | b | y |\n| - | - | - |\n| 0 | 0 | 0 |\n| 1 | 0 | 1 |\n| 0 | 1 | 1 |\n| 1 | 1 | 0 |\n"
+        );
+        assert_eq!(table.to_csv(), "a,b,,y\n0,0,,0\n1,0,,1\n0,1,,1\n1,1,,0\n");
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let table = TruthTable::from_fn(|x: &[bool; 3]| (and(x), xor(x)));
+        assert_eq!(TruthTable::from_csv(&table.to_csv()), Ok(table));
+
+        // Tables without inputs or outputs, and names needing quotes
+        for table in [
+            TruthTable::from_rows(1, &[vec![], vec![]]).unwrap(),
+            TruthTable::from_rows(0, &[vec![true, false]]).unwrap(),
+            TruthTable::from_fn(|x: &[bool; 2]| xor(x))
+                .with_input_names(&["a,b", "\"quoted\""])
+                .with_output_names(&[""]),
+        ] {
+            assert_eq!(
+                TruthTable::from_csv(&table.to_csv()).as_ref(),
+                Ok(&table),
+                "failed for inputs: {:?}",
+                table.to_csv()
+            );
+        }
+        assert_eq!(
+            TruthTable::from_rows(0, &[vec![true]]).unwrap().to_csv(),
+            ",out[0]\n,1\n"
+        );
+
+        // Rows may be stored in any order
+        let stored = "a,b,,y\n1,1,,0\n0,0,,0\n0,1,,1\n1,0,,1\n";
+        assert!(TruthTable::from_csv(stored)
+            .unwrap()
+            .verify_fn(|x: &[bool; 2]| xor(x))
+            .is_ok());
+
+        for (csv, expected) in [
+            ("a,b,y\n", TableError::MissingHeader),
+            ("a,,,y\n", TableError::Syntax { line: 1 }),
+            ("\"a,,y\n", TableError::Syntax { line: 1 }),
+            ("\"a\"b,,y\n", TableError::Syntax { line: 1 }),
+            ("a,,y\n0,,1\n", TableError::MissingRow(1)),
+            ("a,,y\n0,,1\n1,,2\n", TableError::Syntax { line: 3 }),
+            ("a,,y\n0,1\n", TableError::Syntax { line: 2 }),
+            ("\na,,y\n\n0,,1\n\n1,,2\n", TableError::Syntax { line: 6 }),
+            (
+                "a,,y\n0,,1\n\n0,,0\n1,,0\n",
+                TableError::DuplicateRow { line: 4, row: 0 },
+            ),
+        ] {
+            assert_eq!(
+                TruthTable::from_csv(csv),
+                Err(expected),
+                "failed for inputs: {:?}",
+                csv
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_rows() {
+        let table = TruthTable::from_rows(1, &[vec![true], vec![false]]).unwrap();
+        assert_eq!(table, TruthTable::from_fn(|x: &[bool; 1]| !x[0]));
+
+        assert_eq!(
+            TruthTable::from_rows(2, &[vec![true]]),
+            Err(TableError::WrongRowCount {
+                found: 1,
+                expected: 4
+            })
+        );
+    }
+}
@@ -0,0 +1,2702 @@
+use core::fmt;
+use core::ops::Range;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::mux::build_mux2;
+use crate::netlist::{Builder, CellKind, Driver, Net, Netlist, NetlistError, Port};
+use crate::truth_table::TruthTable;
+
+/// Name of the behavioural flip-flop module written with [CellStyle::Primitives]
+const DFF_MODULE: &str = "nandverse_dff";
+
+/// Widest vector, sized literal or replication read, so malformed designs can't exhaust memory
+const MAX_WIDTH: usize = 1 << 16;
+
+/// Deepest nesting of expressions the parser accepts, so malformed designs can't exhaust the stack
+const MAX_DEPTH: usize = 64;
+
+/// Reserved words which can't be used as plain identifiers
+const KEYWORDS: &[&str] = &[
+    "always",
+    "and",
+    "assign",
+    "begin",
+    "buf",
+    "case",
+    "casex",
+    "casez",
+    "default",
+    "defparam",
+    "else",
+    "end",
+    "endcase",
+    "endfunction",
+    "endgenerate",
+    "endmodule",
+    "endtask",
+    "for",
+    "forever",
+    "function",
+    "generate",
+    "genvar",
+    "if",
+    "initial",
+    "inout",
+    "input",
+    "integer",
+    "localparam",
+    "module",
+    "nand",
+    "negedge",
+    "nor",
+    "not",
+    "or",
+    "output",
+    "parameter",
+    "posedge",
+    "reg",
+    "repeat",
+    "signed",
+    "supply0",
+    "supply1",
+    "task",
+    "tri",
+    "while",
+    "wire",
+    "xnor",
+    "xor",
+];
+
+/// How the cells of a netlist are written
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CellStyle {
+    /// Verilog `nand` gate primitives and constant assignments, with flip-flops instances of a
+    /// behavioural module written after the design
+    Primitives,
+    /// Instances of the library cells NAND1, NAND2, ... with inputs A1, A2, ... and output ZN,
+    /// TIELO and TIEHI with output Z, and DFF with inputs CK and D and output Q. Behavioural
+    /// models of the cells used are written after the design
+    Library,
+}
+
+/// Ports written as one Verilog port, either a bus of ports named "name[0]", "name[1]", ... or a
+/// single port
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct PortGroup {
+    name: String,
+    ports: Range<usize>,
+    bus: bool,
+}
+
+impl PortGroup {
+    /// Verilog expression for a port of the group
+    fn port(&self, index: usize) -> String {
+        let name = identifier(&self.name);
+        if self.bus {
+            format!("{}[{}]", name, index - self.ports.start)
+        } else {
+            name
+        }
+    }
+
+    /// Declaration of the port's range, e.g. "[7:0] "
+    fn range(&self) -> String {
+        if self.bus {
+            format!("[{}:0] ", self.ports.len() - 1)
+        } else {
+            String::new()
+        }
+    }
+}
+
+/// Writes a netlist as a structural Verilog-2001 module named after it, followed by the modules
+/// its cells need. Ports named "name[i]" for i from 0 up are written as buses. Nets keep their
+/// names, and each cell is named "u_" followed by its path, as escaped identifiers where needed
+pub fn to_verilog(netlist: &Netlist, style: CellStyle) -> String {
+    let inputs = port_groups(netlist.inputs());
+    let outputs = port_groups(netlist.outputs());
+
+    // Inputs are referred to through their ports, and every other net by its name
+    let mut used: HashSet<String> = inputs
+        .iter()
+        .chain(&outputs)
+        .map(|g| g.name.clone())
+        .collect();
+    let mut names = vec![String::new(); netlist.num_nets()];
+    for net in netlist.nets() {
+        names[net.index()] = match netlist.driver(net) {
+            Some(Driver::Input(i)) => {
+                let group = inputs.iter().find(|g| g.ports.contains(&i)).unwrap();
+                group.port(i)
+            }
+            _ => {
+                let mut name = netlist.net_name(net);
+                if !used.insert(name.clone()) {
+                    name = format!("n{}", net.index());
+                    used.insert(name.clone());
+                }
+                identifier(&name)
+            }
+        };
+    }
+
+    let ports: Vec<String> = inputs
+        .iter()
+        .map(|g| format!("    input {}{}", g.range(), identifier(&g.name)))
+        .chain(
+            outputs
+                .iter()
+                .map(|g| format!("    output {}{}", g.range(), identifier(&g.name))),
+        )
+        .collect();
+    let mut verilog = format!(
+        "// Generated by nandverse from netlist {}\nmodule {} (\n{}\n);\n",
+        netlist.name(),
+        identifier(netlist.name()),
+        ports.join(",\n")
+    );
+
+    for cell in netlist.cells() {
+        verilog.push_str(&format!("    wire {};\n", names[cell.output().index()]));
+    }
+    let mut nands = BTreeSet::new();
+    let mut consts = BTreeSet::new();
+    for id in netlist.cell_ids() {
+        let cell = netlist.cell(id);
+        let instance = identifier(&format!("u_{}", netlist.cell_path(id)));
+        let output = &names[cell.output().index()];
+        let inputs: Vec<&str> = cell
+            .inputs()
+            .iter()
+            .map(|n| names[n.index()].as_str())
+            .collect();
+        verilog.push_str(&match (cell.kind(), style) {
+            (CellKind::Nand, CellStyle::Primitives) => {
+                format!(
+                    "    nand {} ({}, {});\n",
+                    instance,
+                    output,
+                    inputs.join(", ")
+                )
+            }
+            (CellKind::Nand, CellStyle::Library) => {
+                nands.insert(inputs.len());
+                let pins: Vec<String> = inputs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, net)| format!(".A{}({})", i + 1, net))
+                    .collect();
+                format!(
+                    "    NAND{} {} ({}, .ZN({}));\n",
+                    inputs.len(),
+                    instance,
+                    pins.join(", "),
+                    output
+                )
+            }
+            (CellKind::Const(value), CellStyle::Primitives) => {
+                format!("    assign {} = 1'b{};\n", output, value as u8)
+            }
+            (CellKind::Const(value), CellStyle::Library) => {
+                consts.insert(value);
+                format!("    {} {} (.Z({}));\n", tie_cell(value), instance, output)
+            }
+            (CellKind::Dff, CellStyle::Primitives) => format!(
+                "    {} {} (.clk({}), .d({}), .q({}));\n",
+                DFF_MODULE, instance, inputs[0], inputs[1], output
+            ),
+            (CellKind::Dff, CellStyle::Library) => format!(
+                "    DFF {} (.CK({}), .D({}), .Q({}));\n",
+                instance, inputs[0], inputs[1], output
+            ),
+        });
+    }
+    for group in &outputs {
+        for i in group.ports.clone() {
+            let net = netlist.outputs()[i].net();
+            verilog.push_str(&format!(
+                "    assign {} = {};\n",
+                group.port(i),
+                names[net.index()]
+            ));
+        }
+    }
+    verilog.push_str("endmodule\n");
+
+    // Flip-flops load on a rising edge from a known low clock, so nets settling from X at the
+    // start of a simulation don't clock them, as in Netlist::reset_state
+    let has_dff = netlist.is_sequential();
+    match style {
+        CellStyle::Primitives => {
+            if has_dff {
+                verilog.push_str(&dff_module(DFF_MODULE, "clk", "d", "q"));
+            }
+        }
+        CellStyle::Library => {
+            for width in nands {
+                let pins: Vec<String> = (1..=width).map(|i| format!("A{}", i)).collect();
+                verilog.push_str(&format!(
+                    "\nmodule NAND{} (input {}, output ZN);\n    assign ZN = ~({});\nendmodule\n",
+                    width,
+                    pins.join(", input "),
+                    pins.join(" & ")
+                ));
+            }
+            for value in consts {
+                verilog.push_str(&format!(
+                    "\nmodule {} (output Z);\n    assign Z = 1'b{};\nendmodule\n",
+                    tie_cell(value),
+                    value as u8
+                ));
+            }
+            if has_dff {
+                verilog.push_str(&dff_module("DFF", "CK", "D", "Q"));
+            }
+        }
+    }
+    verilog
+}
+
+/// Writes a testbench which applies each vector to the netlist's module and checks its outputs
+/// against the netlist's simulation in Rust. Sequential netlists start from their reset state
+/// and take one step per vector, like [Netlist::step]
+pub fn testbench(netlist: &Netlist, vectors: &[Vec<bool>]) -> String {
+    let rows: Vec<(Vec<bool>, Vec<bool>)> = if netlist.is_sequential() {
+        let mut state = netlist.reset_state();
+        vectors
+            .iter()
+            .map(|inputs| {
+                let values = netlist.step(&mut state, inputs);
+                let outputs = netlist
+                    .outputs()
+                    .iter()
+                    .map(|p| values[p.net().index()])
+                    .collect();
+                (inputs.clone(), outputs)
+            })
+            .collect()
+    } else {
+        vectors
+            .iter()
+            .map(|inputs| (inputs.clone(), netlist.evaluate(inputs)))
+            .collect()
+    };
+    write_testbench(netlist, &rows)
+}
+
+/// Writes a testbench which applies every row of a truth table to the netlist's module and
+/// checks its outputs against the table
+pub fn table_testbench(netlist: &Netlist, table: &TruthTable) -> String {
+    assert_eq!(
+        table.num_inputs(),
+        netlist.inputs().len(),
+        "wrong number of inputs"
+    );
+    assert_eq!(
+        table.num_outputs(),
+        netlist.outputs().len(),
+        "wrong number of outputs"
+    );
+    let rows: Vec<(Vec<bool>, Vec<bool>)> = (0..table.num_rows())
+        .map(|row| (table.inputs(row), table.outputs(row).to_vec()))
+        .collect();
+    write_testbench(netlist, &rows)
+}
+
+fn write_testbench(netlist: &Netlist, rows: &[(Vec<bool>, Vec<bool>)]) -> String {
+    let inputs = port_groups(netlist.inputs());
+    let outputs = port_groups(netlist.outputs());
+    let module = identifier(netlist.name());
+
+    // Inputs clocking a flip-flop change after the others, so the flip-flops load D from the
+    // new inputs
+    let clocks: HashSet<usize> = netlist
+        .flipflops()
+        .iter()
+        .filter_map(|id| match netlist.driver(netlist.cell(*id).inputs()[0]) {
+            Some(Driver::Input(i)) => Some(i),
+            _ => None,
+        })
+        .collect();
+    let (clock_groups, data_groups): (Vec<&PortGroup>, Vec<&PortGroup>) = inputs
+        .iter()
+        .partition(|g| g.ports.clone().any(|i| clocks.contains(&i)));
+
+    let mut tb = format!(
+        "`timescale 1ns / 1ps\n\nmodule {};\n",
+        identifier(&format!("{}_tb", netlist.name()))
+    );
+    for group in &inputs {
+        tb.push_str(&format!(
+            "    reg {}{};\n",
+            group.range(),
+            identifier(&group.name)
+        ));
+    }
+    for group in &outputs {
+        tb.push_str(&format!(
+            "    wire {}{};\n",
+            group.range(),
+            identifier(&group.name)
+        ));
+    }
+    tb.push_str("    integer errors = 0;\n\n");
+    let connections: Vec<String> = inputs
+        .iter()
+        .chain(&outputs)
+        .map(|g| {
+            let name = identifier(&g.name);
+            format!("        .{}({})", name, name)
+        })
+        .collect();
+    tb.push_str(&format!(
+        "    {} dut (\n{}\n    );\n\n    initial begin\n",
+        module,
+        connections.join(",\n")
+    ));
+
+    let assign = |groups: &[&PortGroup], values: &[bool]| -> String {
+        groups
+            .iter()
+            .map(|g| {
+                format!(
+                    "        {} = {};\n",
+                    identifier(&g.name),
+                    literal(&values[g.ports.clone()])
+                )
+            })
+            .collect()
+    };
+    let zeros = vec![false; netlist.inputs().len()];
+    tb.push_str(&assign(&data_groups, &zeros));
+    tb.push_str(&assign(&clock_groups, &zeros));
+    tb.push_str("        #10;\n");
+
+    let outputs_concat = format!(
+        "{{{}}}",
+        outputs
+            .iter()
+            .map(|g| identifier(&g.name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    for (i, (inputs, expected)) in rows.iter().enumerate() {
+        tb.push_str(&assign(&data_groups, inputs));
+        tb.push_str("        #5;\n");
+        tb.push_str(&assign(&clock_groups, inputs));
+        tb.push_str("        #5;\n");
+        if outputs.is_empty() {
+            continue;
+        }
+        let expected: String = outputs
+            .iter()
+            .map(|g| digits(&expected[g.ports.clone()]))
+            .collect();
+        let expected = format!("{}'b{}", netlist.outputs().len(), expected);
+        tb.push_str(&format!(
+            "        if ({} !== {}) begin\n            \
+             $display(\"vector {}: outputs %b, expected %b\", {}, {});\n            \
+             errors = errors + 1;\n        end\n",
+            outputs_concat, expected, i, outputs_concat, expected
+        ));
+    }
+    tb.push_str(&format!(
+        "        if (errors == 0)\n            $display(\"PASS: {} vectors\");\n        \
+         else\n            $display(\"FAIL: %0d of {} vectors\", errors);\n        \
+         $finish;\n    end\nendmodule\n",
+        rows.len(),
+        rows.len()
+    ));
+    tb
+}
+
+/// Splits ports into buses of consecutive ports named "name[0]", "name[1]", ... and single ports.
+/// Names shared with another group are written as single ports
+fn port_groups(ports: &[Port]) -> Vec<PortGroup> {
+    let bus_index = |name: &str| -> Option<(String, usize)> {
+        let (base, index) = name.strip_suffix(']')?.rsplit_once('[')?;
+        let index = index.parse().ok()?;
+        Some((base.to_string(), index))
+    };
+
+    let mut groups: Vec<PortGroup> = Vec::new();
+    for (i, port) in ports.iter().enumerate() {
+        let bus = bus_index(port.name());
+        if let (Some((base, index)), Some(last)) = (&bus, groups.last_mut()) {
+            if last.bus && last.name == *base && last.ports.len() == *index {
+                last.ports.end = i + 1;
+                continue;
+            }
+        }
+        groups.push(match bus {
+            Some((base, 0)) => PortGroup {
+                name: base,
+                ports: i..i + 1,
+                bus: true,
+            },
+            _ => PortGroup {
+                name: port.name().to_string(),
+                ports: i..i + 1,
+                bus: false,
+            },
+        });
+    }
+
+    // A name used twice, or by a port and a bus, can't be a bus
+    let mut split = Vec::new();
+    for group in &groups {
+        let clash = groups.iter().filter(|g| g.name == group.name).count() > 1;
+        if group.bus && clash {
+            split.extend(group.ports.clone().map(|i| PortGroup {
+                name: ports[i].name().to_string(),
+                ports: i..i + 1,
+                bus: false,
+            }));
+        } else {
+            split.push(group.clone());
+        }
+    }
+    split
+}
+
+/// Verilog identifier for a name, escaped if it isn't a plain identifier. Escaped identifiers
+/// end with a space
+fn identifier(name: &str) -> String {
+    let plain = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+        && !KEYWORDS.contains(&name);
+    if plain {
+        name.to_string()
+    } else {
+        format!("\\{} ", name.replace(char::is_whitespace, "_"))
+    }
+}
+
+/// Sized binary literal of bits given least significant first, e.g. "3'b110"
+fn literal(bits: &[bool]) -> String {
+    format!("{}'b{}", bits.len(), digits(bits))
+}
+
+/// Binary digits of bits given least significant first, most significant first
+fn digits(bits: &[bool]) -> String {
+    bits.iter()
+        .rev()
+        .map(|b| if *b { '1' } else { '0' })
+        .collect()
+}
+
+fn tie_cell(value: bool) -> &'static str {
+    if value {
+        "TIEHI"
+    } else {
+        "TIELO"
+    }
+}
+
+fn dff_module(name: &str, clk: &str, d: &str, q: &str) -> String {
+    format!(
+        "\nmodule {name} (input {clk}, input {d}, output reg {q});\n    \
+         reg last;\n    \
+         initial {q} = 1'b0;\n    \
+         always @({clk}) begin\n        \
+         if ({clk} === 1'b1 && last === 1'b0)\n            \
+         {q} <= {d};\n        \
+         last = {clk};\n    \
+         end\n\
+         endmodule\n"
+    )
+}
+
+/// Error returned when Verilog can't be read into a netlist
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerilogError {
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    /// A construct outside the subset [from_verilog] reads
+    Unsupported {
+        line: usize,
+        construct: String,
+    },
+    Undeclared {
+        line: usize,
+        name: String,
+    },
+    UnknownModule {
+        line: usize,
+        name: String,
+    },
+    /// The given top module isn't defined, or no single module is left uninstantiated
+    NoTopModule,
+    /// The design doesn't form a valid netlist, e.g. because a wire has two drivers
+    Netlist(NetlistError),
+}
+
+impl fmt::Display for VerilogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerilogError::Syntax { line, message } => {
+                write!(f, "syntax error on line {}: {}", line, message)
+            }
+            VerilogError::Unsupported { line, construct } => {
+                write!(f, "{} on line {} is not supported", construct, line)
+            }
+            VerilogError::Undeclared { line, name } => {
+                write!(f, "{} on line {} is not declared", name, line)
+            }
+            VerilogError::UnknownModule { line, name } => {
+                write!(f, "module {} on line {} is not defined", name, line)
+            }
+            VerilogError::NoTopModule => write!(f, "no top module"),
+            VerilogError::Netlist(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for VerilogError {}
+
+/// Reads a design written in a structural subset of Verilog-2001 into a netlist of its top
+/// module, with each module instance flattened into a scope named after it. Without a name, the
+/// top module is the one no other module instantiates
+///
+/// The subset covers:
+/// - ANSI and non-ANSI port lists, and `input`, `output`, `wire` and `reg` declarations of bits
+///   and vectors
+/// - `assign` with the operators `~ ! & | ^ ~& ~| ~^ && || == != ?:`, bit and part selects,
+///   concatenation and replication
+/// - the gate primitives `and`, `or`, `nand`, `nor`, `xor`, `xnor`, `not` and `buf`
+/// - `always @(posedge clk)` and `always @(negedge clk)` blocks of `begin`, `if`, `else` and
+///   assignments, which become D flip-flops, and `always @*` blocks which assign each of their
+///   signals on every path
+/// - instances of modules, with ports connected by name or position, including the cells
+///   written by [to_verilog]
+///
+/// `initial` blocks are skipped, since every flip-flop starts low, and unsized numbers are only
+/// as wide as their value
+pub fn from_verilog(source: &str, top: Option<&str>) -> Result<Netlist, VerilogError> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+        depth: 0,
+    };
+    let mut modules: Vec<Module> = Vec::new();
+    while parser.peek().is_some() {
+        if !parser.eat_keyword("module") {
+            return Err(parser.unexpected());
+        }
+        let module = parser.module()?;
+        if modules.iter().any(|m| m.name == module.name) {
+            return Err(VerilogError::Syntax {
+                line: module.line,
+                message: format!("module {} is defined twice", module.name),
+            });
+        }
+        modules.push(module);
+    }
+
+    let instantiated: HashSet<&str> = modules
+        .iter()
+        .flat_map(|m| &m.items)
+        .filter_map(|item| match item {
+            Item::Instance { module, .. } => Some(module.as_str()),
+            _ => None,
+        })
+        .collect();
+    let candidates: Vec<&Module> = match top {
+        Some(name) => modules.iter().filter(|m| m.name == name).collect(),
+        None => modules
+            .iter()
+            .filter(|m| !instantiated.contains(m.name.as_str()) && !m.is_library_cell())
+            .collect(),
+    };
+    let [top] = candidates[..] else {
+        return Err(VerilogError::NoTopModule);
+    };
+
+    let modules: HashMap<&str, &Module> = modules.iter().map(|m| (m.name.as_str(), m)).collect();
+    let mut builder = Builder::new(&top.name);
+    let mut elaborator = Elaborator {
+        modules: &modules,
+        stack: Vec::new(),
+    };
+    let signals = elaborator.instantiate(&mut builder, top, "", None)?;
+    for port in &top.ports {
+        let declaration = top.declaration(port).unwrap();
+        if declaration.direction == Some(Direction::Output) {
+            for (k, net) in signals[port].nets.iter().enumerate() {
+                builder.output(&declaration.bit_name(k), *net);
+            }
+        }
+    }
+    builder.try_finish().map_err(VerilogError::Netlist)
+}
+
+/// Drives a net declared for a signal with a value
+fn drive(builder: &mut Builder, target: Net, value: Net) -> Result<(), VerilogError> {
+    builder
+        .drive_placeholder(target, value)
+        .map_err(VerilogError::Netlist)
+}
+
+/// Symbols in the order they're matched, longest first
+const SYMBOLS: &[&str] = &[
+    "===", "!==", "<=", ">=", "==", "!=", "&&", "||", "~&", "~|", "~^", "^~", "(", ")", "[", "]",
+    "{", "}", ",", ";", ":", ".", "=", "?", "@", "#", "&", "|", "^", "~", "!", "+", "-", "*", "/",
+    "%", "<", ">",
+];
+
+const GATES: &[&str] = &["and", "buf", "nand", "nor", "not", "or", "xnor", "xor"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    /// Identifier, unescaped if it was an escaped identifier
+    Ident(String),
+    Keyword(String),
+    Number(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(s) | Token::Keyword(s) | Token::Number(s) => write!(f, "{}", s),
+            Token::Symbol(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// Splits Verilog into tokens and the line of each, skipping comments, attributes and compiler
+/// directives
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, VerilogError> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        let attribute = rest.starts_with("(*") && !rest[2..].trim_start().starts_with(')');
+        if c.is_whitespace() {
+            line += (c == '\n') as usize;
+            rest = &rest[c.len_utf8()..];
+            continue;
+        } else if rest.starts_with("//") || c == '`' {
+            rest = &rest[rest.find('\n').unwrap_or(rest.len())..];
+            continue;
+        } else if rest.starts_with("/*") || attribute {
+            let close = if attribute { "*)" } else { "*/" };
+            let end = rest[2..].find(close).ok_or_else(|| VerilogError::Syntax {
+                line,
+                message: "unterminated comment".to_string(),
+            })? + 4;
+            line += rest[..end].matches('\n').count();
+            rest = &rest[end..];
+            continue;
+        }
+
+        let word_end = |s: &str, f: fn(char) -> bool| s.find(|c| !f(c)).unwrap_or(s.len());
+        let (token, len) = if c == '\\' {
+            let end = word_end(rest, |c| !c.is_whitespace());
+            (Token::Ident(rest[1..end].to_string()), end)
+        } else if c.is_ascii_alphabetic() || c == '_' || c == '$' {
+            let end = word_end(rest, |c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+            let word = rest[..end].to_string();
+            if KEYWORDS.contains(&word.as_str()) {
+                (Token::Keyword(word), end)
+            } else {
+                (Token::Ident(word), end)
+            }
+        } else if c.is_ascii_digit() || c == '\'' {
+            let mut end = word_end(rest, |c| c.is_ascii_digit() || c == '_');
+            if rest[end..].starts_with('\'') {
+                end += 1;
+                end += word_end(&rest[end..], |c| {
+                    c.is_ascii_alphanumeric() || c == '_' || c == '?'
+                });
+            }
+            (Token::Number(rest[..end].to_string()), end)
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            (Token::Symbol(symbol), symbol.len())
+        } else {
+            return Err(VerilogError::Syntax {
+                line,
+                message: format!("unexpected character {:?}", c),
+            });
+        };
+        tokens.push((token, line));
+        rest = &rest[len..];
+    }
+    Ok(tokens)
+}
+
+/// Bits of a number, least significant first
+fn parse_number(text: &str, line: usize) -> Result<Vec<bool>, VerilogError> {
+    let syntax = || VerilogError::Syntax {
+        line,
+        message: format!("malformed number {}", text),
+    };
+    let text = text.replace('_', "");
+    let decimal = |digits: &str| -> Result<Vec<bool>, VerilogError> {
+        let value: u128 = digits.parse().map_err(|_| syntax())?;
+        let width = (128 - value.leading_zeros()).max(1) as usize;
+        Ok((0..width).map(|i| value >> i & 1 == 1).collect())
+    };
+    let Some((size, value)) = text.split_once('\'') else {
+        return decimal(&text);
+    };
+
+    let value = value.trim_start_matches(['s', 'S']);
+    let mut chars = value.chars();
+    let base = chars.next().ok_or_else(syntax)?.to_ascii_lowercase();
+    let digits = chars.as_str();
+    if digits.is_empty() {
+        return Err(syntax());
+    }
+    if digits.contains(['x', 'X', 'z', 'Z', '?']) {
+        return Err(VerilogError::Unsupported {
+            line,
+            construct: "x or z value".to_string(),
+        });
+    }
+    let mut bits = match base {
+        'd' => decimal(digits)?,
+        'b' | 'o' | 'h' => {
+            let (radix, per_digit) = match base {
+                'b' => (2, 1),
+                'o' => (8, 3),
+                _ => (16, 4),
+            };
+            let mut bits = Vec::new();
+            for digit in digits.chars().rev() {
+                let value = digit.to_digit(radix).ok_or_else(syntax)?;
+                bits.extend((0..per_digit).map(|i| value >> i & 1 == 1));
+            }
+            bits
+        }
+        _ => return Err(syntax()),
+    };
+    if !size.is_empty() {
+        let width: usize = size.parse().map_err(|_| syntax())?;
+        if width == 0 || width > MAX_WIDTH {
+            return Err(VerilogError::Syntax {
+                line,
+                message: format!("width of {} is not between 1 and {}", text, MAX_WIDTH),
+            });
+        }
+        bits.resize(width, false);
+    }
+    Ok(bits)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    Input,
+    Output,
+}
+
+/// Indices of the most and least significant bits of a vector
+type Bounds = (i64, i64);
+
+#[derive(Clone, Debug)]
+struct Declaration {
+    name: String,
+    direction: Option<Direction>,
+    range: Option<Bounds>,
+    line: usize,
+}
+
+impl Declaration {
+    fn width(&self) -> usize {
+        self.range
+            .map_or(1, |(msb, lsb)| msb.abs_diff(lsb) as usize + 1)
+    }
+
+    /// Index of the bit at an offset from the least significant bit
+    fn index(&self, offset: usize) -> i64 {
+        match self.range {
+            Some((msb, lsb)) if msb < lsb => lsb - offset as i64,
+            Some((_, lsb)) => lsb + offset as i64,
+            None => 0,
+        }
+    }
+
+    /// Offset from the least significant bit of the bit with an index
+    fn offset(&self, index: i64) -> Option<usize> {
+        (0..self.width()).find(|k| self.index(*k) == index && self.range.is_some())
+    }
+
+    /// Name of the bit at an offset, e.g. "sum[3]"
+    fn bit_name(&self, offset: usize) -> String {
+        match self.range {
+            Some(_) => format!("{}[{}]", self.name, self.index(offset)),
+            None => self.name.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Expression {
+    Signal {
+        name: String,
+        line: usize,
+    },
+    Bit {
+        name: String,
+        index: i64,
+        line: usize,
+    },
+    Slice {
+        name: String,
+        msb: i64,
+        lsb: i64,
+        line: usize,
+    },
+    /// Bits, least significant first
+    Const(Vec<bool>),
+    Concat(Vec<Expression>),
+    Repeat {
+        count: usize,
+        part: Box<Expression>,
+        line: usize,
+    },
+    Unary(&'static str, Box<Expression>),
+    /// Operator applied from left to right over two or more operands
+    Binary(&'static str, Vec<Expression>),
+    Ternary(Box<[Expression; 3]>),
+}
+
+#[derive(Clone, Debug)]
+enum Statement {
+    Block(Vec<Statement>),
+    Assign {
+        target: Expression,
+        value: Expression,
+        blocking: bool,
+        line: usize,
+    },
+    If {
+        condition: Expression,
+        then: Box<Statement>,
+        otherwise: Option<Box<Statement>>,
+        line: usize,
+    },
+}
+
+/// Port name of a module instance's connection, if connected by name, and the expression
+/// connected to it
+type Connection = (Option<String>, Option<Expression>);
+
+#[derive(Clone, Debug)]
+enum Item {
+    Assign {
+        target: Expression,
+        value: Expression,
+        line: usize,
+    },
+    Gate {
+        gate: String,
+        terminals: Vec<Expression>,
+        line: usize,
+    },
+    Instance {
+        module: String,
+        name: String,
+        connections: Vec<Connection>,
+        line: usize,
+    },
+    /// Block triggered by a clock edge, rising if true, or by any change of its inputs
+    Always {
+        edge: Option<(bool, Expression)>,
+        body: Statement,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct Module {
+    name: String,
+    line: usize,
+    ports: Vec<String>,
+    declarations: Vec<Declaration>,
+    index: HashMap<String, usize>,
+    items: Vec<Item>,
+}
+
+impl Module {
+    fn declaration(&self, name: &str) -> Option<&Declaration> {
+        self.index.get(name).map(|i| &self.declarations[*i])
+    }
+
+    /// Declares a signal, or adds a direction or range to an earlier declaration of it
+    fn declare(
+        &mut self,
+        name: String,
+        direction: Option<Direction>,
+        range: Option<Bounds>,
+        line: usize,
+    ) -> Result<(), VerilogError> {
+        let Some(i) = self.index.get(&name) else {
+            self.index.insert(name.clone(), self.declarations.len());
+            self.declarations.push(Declaration {
+                name,
+                direction,
+                range,
+                line,
+            });
+            return Ok(());
+        };
+        let declaration = &mut self.declarations[*i];
+        let conflict = (direction.is_some() && declaration.direction.is_some())
+            || (range.is_some() && declaration.range.is_some() && range != declaration.range);
+        if conflict {
+            return Err(VerilogError::Syntax {
+                line,
+                message: format!("{} is declared twice", name),
+            });
+        }
+        declaration.direction = declaration.direction.or(direction);
+        declaration.range = declaration.range.or(range);
+        Ok(())
+    }
+
+    /// Whether the module has the name and ports of a cell written by [to_verilog], which is
+    /// read as that cell rather than from its body
+    fn is_library_cell(&self) -> bool {
+        library_cell(&self.name).is_some_and(|(_, pins)| pins == self.ports)
+    }
+}
+
+/// Kind and pins of a cell written by [to_verilog], inputs first
+fn library_cell(name: &str) -> Option<(CellKind, Vec<String>)> {
+    let pins = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+    match name {
+        DFF_MODULE => Some((CellKind::Dff, pins(&["clk", "d", "q"]))),
+        "DFF" => Some((CellKind::Dff, pins(&["CK", "D", "Q"]))),
+        "TIELO" => Some((CellKind::Const(false), pins(&["Z"]))),
+        "TIEHI" => Some((CellKind::Const(true), pins(&["Z"]))),
+        _ => {
+            let width: usize = name.strip_prefix("NAND")?.parse().ok().filter(|w| *w > 0)?;
+            let mut pins: Vec<String> = (1..=width).map(|i| format!("A{}", i)).collect();
+            pins.push("ZN".to_string());
+            Some((CellKind::Nand, pins))
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// Number of expressions being parsed, each inside the last
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn unexpected(&self) -> VerilogError {
+        let message = match self.peek() {
+            Some(token) => format!("unexpected {}", token),
+            None => "unexpected end of file".to_string(),
+        };
+        VerilogError::Syntax {
+            line: self.line(),
+            message,
+        }
+    }
+
+    fn unsupported(&self, construct: &str) -> VerilogError {
+        VerilogError::Unsupported {
+            line: self.line(),
+            construct: construct.to_string(),
+        }
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Keyword(k)) if k == keyword)
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = self.is_symbol(symbol);
+        self.pos += found as usize;
+        found
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), VerilogError> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, VerilogError> {
+        match self.peek() {
+            Some(Token::Ident(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// A constant integer, such as a bit index
+    fn integer(&mut self) -> Result<i64, VerilogError> {
+        match self.peek() {
+            Some(Token::Number(text)) => {
+                let bits = parse_number(text, self.line())?;
+                self.pos += 1;
+                Ok(bits
+                    .iter()
+                    .take(63)
+                    .enumerate()
+                    .map(|(i, b)| (*b as i64) << i)
+                    .sum())
+            }
+            Some(Token::Ident(_)) => Err(self.unsupported("non-constant index")),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// An optional range such as "[7:0]"
+    fn range(&mut self) -> Result<Option<Bounds>, VerilogError> {
+        if !self.eat_symbol("[") {
+            return Ok(None);
+        }
+        let msb = self.integer()?;
+        self.expect_symbol(":")?;
+        let lsb = self.integer()?;
+        self.expect_symbol("]")?;
+        if msb.abs_diff(lsb) >= MAX_WIDTH as u64 {
+            return Err(VerilogError::Syntax {
+                line: self.line(),
+                message: format!("range [{}:{}] is wider than {} bits", msb, lsb, MAX_WIDTH),
+            });
+        }
+        Ok(Some((msb, lsb)))
+    }
+
+    /// A port direction and the signal kind and range following it, e.g. "output reg [3:0]"
+    fn direction(&mut self) -> Result<Option<(Direction, Option<Bounds>)>, VerilogError> {
+        let direction = if self.eat_keyword("input") {
+            Direction::Input
+        } else if self.eat_keyword("output") {
+            Direction::Output
+        } else if self.is_keyword("inout") {
+            return Err(self.unsupported("inout port"));
+        } else {
+            return Ok(None);
+        };
+        let _ = self.eat_keyword("wire") || self.eat_keyword("reg");
+        if self.is_keyword("signed") {
+            return Err(self.unsupported("signed signal"));
+        }
+        Ok(Some((direction, self.range()?)))
+    }
+
+    /// Parses a module after its "module" keyword
+    fn module(&mut self) -> Result<Module, VerilogError> {
+        let line = self.line();
+        let mut module = Module {
+            name: self.ident()?,
+            line,
+            ports: Vec::new(),
+            declarations: Vec::new(),
+            index: HashMap::new(),
+            items: Vec::new(),
+        };
+        if self.is_symbol("#") {
+            return Err(self.unsupported("module parameters"));
+        }
+
+        // Ports of an ANSI port list take the direction written before them
+        if self.eat_symbol("(") && !self.eat_symbol(")") {
+            let mut current = None;
+            loop {
+                if let Some(direction) = self.direction()? {
+                    current = Some(direction);
+                }
+                let line = self.line();
+                let name = self.ident()?;
+                if let Some((direction, range)) = current {
+                    module.declare(name.clone(), Some(direction), range, line)?;
+                }
+                module.ports.push(name);
+                if self.eat_symbol(")") {
+                    break;
+                }
+                self.expect_symbol(",")?;
+            }
+        }
+        self.expect_symbol(";")?;
+
+        while !self.eat_keyword("endmodule") {
+            let line = self.line();
+            match self.peek().cloned() {
+                Some(Token::Keyword(k)) if k == "input" || k == "output" || k == "inout" => {
+                    let (direction, range) = self.direction()?.unwrap();
+                    loop {
+                        let line = self.line();
+                        module.declare(self.ident()?, Some(direction), range, line)?;
+                        if self.eat_symbol(";") {
+                            break;
+                        }
+                        self.expect_symbol(",")?;
+                    }
+                }
+                Some(Token::Keyword(k)) if k == "wire" || k == "reg" => {
+                    self.pos += 1;
+                    if self.is_keyword("signed") {
+                        return Err(self.unsupported("signed signal"));
+                    }
+                    let range = self.range()?;
+                    loop {
+                        let line = self.line();
+                        let name = self.ident()?;
+                        module.declare(name.clone(), None, range, line)?;
+                        if self.eat_symbol("=") {
+                            let value = self.expression()?;
+                            module.items.push(Item::Assign {
+                                target: Expression::Signal { name, line },
+                                value,
+                                line,
+                            });
+                        }
+                        if self.eat_symbol(";") {
+                            break;
+                        }
+                        self.expect_symbol(",")?;
+                    }
+                }
+                Some(Token::Keyword(k)) if k == "assign" => {
+                    self.pos += 1;
+                    loop {
+                        let line = self.line();
+                        let target = self.expression()?;
+                        self.expect_symbol("=")?;
+                        let value = self.expression()?;
+                        module.items.push(Item::Assign {
+                            target,
+                            value,
+                            line,
+                        });
+                        if self.eat_symbol(";") {
+                            break;
+                        }
+                        self.expect_symbol(",")?;
+                    }
+                }
+                Some(Token::Keyword(k)) if GATES.contains(&k.as_str()) => {
+                    self.pos += 1;
+                    if self.eat_symbol("#") {
+                        self.delay()?;
+                    }
+                    loop {
+                        if matches!(self.peek(), Some(Token::Ident(_))) {
+                            self.pos += 1;
+                        }
+                        self.expect_symbol("(")?;
+                        let mut terminals = vec![self.expression()?];
+                        while self.eat_symbol(",") {
+                            terminals.push(self.expression()?);
+                        }
+                        self.expect_symbol(")")?;
+                        if terminals.len() < 2 {
+                            return Err(VerilogError::Syntax {
+                                line,
+                                message: format!("{} gate needs an output and an input", k),
+                            });
+                        }
+                        module.items.push(Item::Gate {
+                            gate: k.clone(),
+                            terminals,
+                            line,
+                        });
+                        if self.eat_symbol(";") {
+                            break;
+                        }
+                        self.expect_symbol(",")?;
+                    }
+                }
+                Some(Token::Keyword(k)) if k == "always" => {
+                    self.pos += 1;
+                    let edge = self.event()?;
+                    let body = self.statement()?;
+                    module.items.push(Item::Always { edge, body });
+                }
+                Some(Token::Keyword(k)) if k == "initial" => {
+                    self.pos += 1;
+                    self.statement()?;
+                }
+                Some(Token::Ident(name)) => {
+                    self.pos += 1;
+                    if self.is_symbol("#") {
+                        return Err(self.unsupported("module parameters"));
+                    }
+                    loop {
+                        let instance = self.ident()?;
+                        if self.is_symbol("[") {
+                            return Err(self.unsupported("array of instances"));
+                        }
+                        self.expect_symbol("(")?;
+                        let connections = self.connections()?;
+                        module.items.push(Item::Instance {
+                            module: name.clone(),
+                            name: instance,
+                            connections,
+                            line,
+                        });
+                        if self.eat_symbol(";") {
+                            break;
+                        }
+                        self.expect_symbol(",")?;
+                    }
+                }
+                Some(Token::Keyword(k)) => return Err(self.unsupported(&k)),
+                _ => return Err(self.unexpected()),
+            }
+        }
+
+        // Identifiers connected to gates and instances are implicitly declared as wires
+        let mut implicit = Vec::new();
+        for item in &module.items {
+            let terminals: Vec<&Expression> = match item {
+                Item::Gate { terminals, .. } => terminals.iter().collect(),
+                Item::Instance { connections, .. } => {
+                    connections.iter().filter_map(|(_, e)| e.as_ref()).collect()
+                }
+                _ => Vec::new(),
+            };
+            for terminal in terminals {
+                if let Expression::Signal { name, line } = terminal {
+                    if module.declaration(name).is_none() {
+                        implicit.push((name.clone(), *line));
+                    }
+                }
+            }
+        }
+        for (name, line) in implicit {
+            module.declare(name, None, None, line)?;
+        }
+        for port in &module.ports {
+            let declaration = module.declaration(port);
+            if declaration.is_none_or(|d| d.direction.is_none()) {
+                return Err(VerilogError::Syntax {
+                    line: declaration.map_or(module.line, |d| d.line),
+                    message: format!("port {} has no direction", port),
+                });
+            }
+        }
+        Ok(module)
+    }
+
+    /// Skips a delay after its "#"
+    fn delay(&mut self) -> Result<(), VerilogError> {
+        if self.eat_symbol("(") {
+            while !self.eat_symbol(")") {
+                if self.peek().is_none() {
+                    return Err(self.unexpected());
+                }
+                self.pos += 1;
+            }
+        } else {
+            self.integer()?;
+        }
+        Ok(())
+    }
+
+    /// Connections of a module instance after its "("
+    fn connections(&mut self) -> Result<Vec<Connection>, VerilogError> {
+        let mut connections = Vec::new();
+        if self.eat_symbol(")") {
+            return Ok(connections);
+        }
+        loop {
+            if self.eat_symbol(".") {
+                let port = self.ident()?;
+                self.expect_symbol("(")?;
+                let value = if self.is_symbol(")") {
+                    None
+                } else {
+                    Some(self.expression()?)
+                };
+                self.expect_symbol(")")?;
+                connections.push((Some(port), value));
+            } else if self.is_symbol(",") || self.is_symbol(")") {
+                connections.push((None, None));
+            } else {
+                connections.push((None, Some(self.expression()?)));
+            }
+            if self.eat_symbol(")") {
+                return Ok(connections);
+            }
+            self.expect_symbol(",")?;
+        }
+    }
+
+    /// Event control of an always block after its "always", returning the clock edge if there is
+    /// one
+    fn event(&mut self) -> Result<Option<(bool, Expression)>, VerilogError> {
+        self.expect_symbol("@")?;
+        if self.eat_symbol("*") {
+            return Ok(None);
+        }
+        self.expect_symbol("(")?;
+        if self.eat_symbol("*") {
+            self.expect_symbol(")")?;
+            return Ok(None);
+        }
+
+        let edge = if self.eat_keyword("posedge") {
+            Some(true)
+        } else if self.eat_keyword("negedge") {
+            Some(false)
+        } else {
+            None
+        };
+        let first = self.expression()?;
+        if let Some(rising) = edge {
+            if !self.is_symbol(")") {
+                return Err(self.unsupported("asynchronous set or reset"));
+            }
+            self.expect_symbol(")")?;
+            return Ok(Some((rising, first)));
+        }
+
+        // A list of signals is taken to cover everything the block reads
+        while self.eat_keyword("or") || self.eat_symbol(",") {
+            if self.is_keyword("posedge") || self.is_keyword("negedge") {
+                return Err(self.unsupported("mixed edge and level events"));
+            }
+            self.expression()?;
+        }
+        self.expect_symbol(")")?;
+        Ok(None)
+    }
+
+    fn statement(&mut self) -> Result<Statement, VerilogError> {
+        let line = self.line();
+        if self.eat_keyword("begin") {
+            if self.eat_symbol(":") {
+                self.ident()?;
+            }
+            let mut statements = Vec::new();
+            while !self.eat_keyword("end") {
+                statements.push(self.statement()?);
+            }
+            return Ok(Statement::Block(statements));
+        }
+        if self.eat_keyword("if") {
+            self.expect_symbol("(")?;
+            let condition = self.expression()?;
+            self.expect_symbol(")")?;
+            let then = Box::new(self.statement()?);
+            let otherwise = if self.eat_keyword("else") {
+                Some(Box::new(self.statement()?))
+            } else {
+                None
+            };
+            return Ok(Statement::If {
+                condition,
+                then,
+                otherwise,
+                line,
+            });
+        }
+        if self.eat_symbol(";") {
+            return Ok(Statement::Block(Vec::new()));
+        }
+        match self.peek() {
+            Some(Token::Keyword(k)) => return Err(self.unsupported(&k.clone())),
+            Some(Token::Ident(name)) if name.starts_with('$') => {
+                return Err(self.unsupported("system task"))
+            }
+            _ => {}
+        }
+
+        let target = self.expression()?;
+        let blocking = if self.eat_symbol("=") {
+            true
+        } else if self.eat_symbol("<=") {
+            false
+        } else {
+            return Err(self.unexpected());
+        };
+        if self.eat_symbol("#") {
+            self.delay()?;
+        }
+        let value = self.expression()?;
+        self.expect_symbol(";")?;
+        Ok(Statement::Assign {
+            target,
+            value,
+            blocking,
+            line,
+        })
+    }
+
+    /// Parses a subexpression, failing if expressions are nested too deeply
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Expression, VerilogError>,
+    ) -> Result<Expression, VerilogError> {
+        if self.depth == MAX_DEPTH {
+            return Err(VerilogError::Syntax {
+                line: self.line(),
+                message: format!("expression is nested more than {} deep", MAX_DEPTH),
+            });
+        }
+        self.depth += 1;
+        let expression = parse(self);
+        self.depth -= 1;
+        expression
+    }
+
+    fn expression(&mut self) -> Result<Expression, VerilogError> {
+        self.nested(Self::ternary)
+    }
+
+    fn ternary(&mut self) -> Result<Expression, VerilogError> {
+        let condition = self.binary(0)?;
+        if !self.eat_symbol("?") {
+            return Ok(condition);
+        }
+        let then = self.expression()?;
+        self.expect_symbol(":")?;
+        let otherwise = self.expression()?;
+        Ok(Expression::Ternary(Box::new([condition, then, otherwise])))
+    }
+
+    /// Binary operators from the given precedence level up, left associative
+    fn binary(&mut self, level: usize) -> Result<Expression, VerilogError> {
+        const LEVELS: &[&[&str]] = &[
+            &["||"],
+            &["&&"],
+            &["|"],
+            &["^", "~^", "^~"],
+            &["&"],
+            &["==", "!=", "===", "!=="],
+        ];
+        let Some(operators) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1)?;
+        while let Some(Token::Symbol(op)) = self.peek() {
+            let Some(op) = operators.iter().find(|o| *o == op).copied() else {
+                break;
+            };
+            self.pos += 1;
+            let right = self.binary(level + 1)?;
+            let associative = matches!(op, "||" | "&&" | "|" | "^" | "&");
+            left = match left {
+                Expression::Binary(o, mut operands) if o == op && associative => {
+                    operands.push(right);
+                    Expression::Binary(o, operands)
+                }
+                left => Expression::Binary(op, vec![left, right]),
+            };
+        }
+        if let Some(Token::Symbol(op)) = self.peek() {
+            if level == 0 && ["+", "-", "*", "/", "%", "<", ">", ">="].contains(op) {
+                return Err(self.unsupported(&format!("operator {}", op)));
+            }
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression, VerilogError> {
+        const UNARY: &[&str] = &["~", "!", "&", "|", "^", "~&", "~|", "~^", "^~"];
+        if let Some(Token::Symbol(op)) = self.peek() {
+            if let Some(op) = UNARY.iter().find(|o| *o == op).copied() {
+                self.pos += 1;
+                let operand = self.nested(Self::unary)?;
+                return Ok(Expression::Unary(op, Box::new(operand)));
+            }
+            if *op == "-" || *op == "+" {
+                return Err(self.unsupported(&format!("operator {}", op)));
+            }
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expression, VerilogError> {
+        let line = self.line();
+        match self.peek().cloned() {
+            Some(Token::Number(text)) => {
+                self.pos += 1;
+                Ok(Expression::Const(parse_number(&text, line)?))
+            }
+            Some(Token::Symbol("(")) => {
+                self.pos += 1;
+                let e = self.expression()?;
+                self.expect_symbol(")")?;
+                Ok(e)
+            }
+            Some(Token::Symbol("{")) => {
+                self.pos += 1;
+                let first = self.expression()?;
+                if let (Expression::Const(count), true) = (&first, self.is_symbol("{")) {
+                    let count = count.iter().rev().fold(0usize, |n, b| {
+                        n.saturating_mul(2).saturating_add(*b as usize)
+                    });
+                    if count == 0 || count > MAX_WIDTH {
+                        return Err(VerilogError::Syntax {
+                            line,
+                            message: format!(
+                                "replication count {} is not between 1 and {}",
+                                count, MAX_WIDTH
+                            ),
+                        });
+                    }
+                    self.pos += 1;
+                    let mut parts = vec![self.expression()?];
+                    while self.eat_symbol(",") {
+                        parts.push(self.expression()?);
+                    }
+                    self.expect_symbol("}")?;
+                    self.expect_symbol("}")?;
+                    return Ok(Expression::Repeat {
+                        count,
+                        part: Box::new(Expression::Concat(parts)),
+                        line,
+                    });
+                }
+                let mut parts = vec![first];
+                while self.eat_symbol(",") {
+                    parts.push(self.expression()?);
+                }
+                self.expect_symbol("}")?;
+                Ok(Expression::Concat(parts))
+            }
+            Some(Token::Ident(name)) if !name.starts_with('$') => {
+                self.pos += 1;
+                if !self.eat_symbol("[") {
+                    return Ok(Expression::Signal { name, line });
+                }
+                let msb = self.integer()?;
+                let e = if self.eat_symbol(":") {
+                    let lsb = self.integer()?;
+                    Expression::Slice {
+                        name,
+                        msb,
+                        lsb,
+                        line,
+                    }
+                } else {
+                    Expression::Bit {
+                        name,
+                        index: msb,
+                        line,
+                    }
+                };
+                self.expect_symbol("]")?;
+                Ok(e)
+            }
+            Some(Token::Ident(_)) => Err(self.unsupported("system function")),
+            _ => Err(self.unexpected()),
+        }
+    }
+}
+
+/// Nets of a signal of a module instance, least significant first
+#[derive(Clone, Debug)]
+struct Signal {
+    nets: Vec<Net>,
+    declaration: Declaration,
+}
+
+/// Values assigned to the bits of each signal by the statements of an always block so far
+type Assigned = BTreeMap<String, Vec<Option<Net>>>;
+
+struct Elaborator<'a> {
+    modules: &'a HashMap<&'a str, &'a Module>,
+    /// Modules being instantiated, to catch a module instantiating itself
+    stack: Vec<String>,
+}
+
+impl Elaborator<'_> {
+    /// Builds an instance of a module, with the nets connected to its inputs or with new input
+    /// ports at the top level, and returns its signals
+    fn instantiate(
+        &mut self,
+        builder: &mut Builder,
+        module: &Module,
+        prefix: &str,
+        mut inputs: Option<HashMap<String, Vec<Net>>>,
+    ) -> Result<HashMap<String, Signal>, VerilogError> {
+        if self.stack.contains(&module.name) {
+            return Err(VerilogError::Syntax {
+                line: module.line,
+                message: format!("module {} instantiates itself", module.name),
+            });
+        }
+        self.stack.push(module.name.clone());
+
+        let mut signals = HashMap::new();
+        let top = inputs.is_none();
+        let top_inputs = module
+            .ports
+            .iter()
+            .filter_map(|p| module.declaration(p))
+            .filter(|d| top && d.direction == Some(Direction::Input));
+        let others = module
+            .declarations
+            .iter()
+            .filter(|d| !top || d.direction != Some(Direction::Input));
+        for declaration in top_inputs.chain(others) {
+            let nets: Vec<Net> = match (&mut inputs, declaration.direction) {
+                (None, Some(Direction::Input)) => (0..declaration.width())
+                    .map(|k| builder.input(&declaration.bit_name(k)))
+                    .collect(),
+                (Some(inputs), Some(Direction::Input)) => inputs.remove(&declaration.name).unwrap(),
+                _ => (0..declaration.width())
+                    .map(|k| builder.placeholder(&format!("{}{}", prefix, declaration.bit_name(k))))
+                    .collect(),
+            };
+            signals.insert(
+                declaration.name.clone(),
+                Signal {
+                    nets,
+                    declaration: declaration.clone(),
+                },
+            );
+        }
+
+        for item in &module.items {
+            self.item(builder, &signals, prefix, item)?;
+        }
+        self.stack.pop();
+        Ok(signals)
+    }
+
+    fn item(
+        &mut self,
+        builder: &mut Builder,
+        signals: &HashMap<String, Signal>,
+        prefix: &str,
+        item: &Item,
+    ) -> Result<(), VerilogError> {
+        let none = HashMap::new();
+        match item {
+            Item::Assign {
+                target,
+                value,
+                line,
+            } => {
+                let targets = self.target(signals, target, *line)?;
+                let values = self.eval(builder, signals, &none, value)?;
+                let values = fit(builder, values, targets.len());
+                for (target, value) in targets.iter().zip(values) {
+                    drive(builder, *target, value)?;
+                }
+            }
+            Item::Gate {
+                gate,
+                terminals,
+                line,
+            } => {
+                let split = match gate.as_str() {
+                    "not" | "buf" => terminals.len() - 1,
+                    _ => 1,
+                };
+                let mut inputs = Vec::new();
+                for terminal in &terminals[split..] {
+                    let bits = self.eval(builder, signals, &none, terminal)?;
+                    inputs.push(fit(builder, bits, 1)[0]);
+                }
+                let value = match gate.as_str() {
+                    "and" => builder.and(&inputs),
+                    "or" => builder.or(&inputs),
+                    "nand" => builder.nand(&inputs),
+                    "nor" => builder.nor(&inputs),
+                    "xor" => parity(builder, &inputs, false),
+                    "xnor" => parity(builder, &inputs, true),
+                    "not" => builder.not(inputs[0]),
+                    "buf" => inputs[0],
+                    _ => unreachable!("unknown gate {} on line {}", gate, line),
+                };
+                for terminal in &terminals[..split] {
+                    let targets = self.target(signals, terminal, *line)?;
+                    drive(builder, targets[0], value)?;
+                }
+            }
+            Item::Instance {
+                module,
+                name,
+                connections,
+                line,
+            } => {
+                self.instance(builder, signals, prefix, module, name, connections, *line)?;
+            }
+            Item::Always { edge, body } => {
+                let mut assigned = Assigned::new();
+                let mut visible = HashMap::new();
+                let clocked = edge.is_some();
+                self.execute(builder, signals, body, clocked, &mut assigned, &mut visible)?;
+                let clock = match edge {
+                    Some((rising, clock)) => {
+                        let bits = self.eval(builder, signals, &none, clock)?;
+                        let clock = fit(builder, bits, 1)[0];
+                        Some(if *rising { clock } else { builder.not(clock) })
+                    }
+                    None => None,
+                };
+                for (name, values) in assigned {
+                    for (net, value) in signals[&name].nets.iter().zip(values) {
+                        let Some(value) = value else { continue };
+                        let value = match clock {
+                            Some(clock) => builder.dff(clock, value),
+                            None => value,
+                        };
+                        drive(builder, *net, value)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn instance(
+        &mut self,
+        builder: &mut Builder,
+        signals: &HashMap<String, Signal>,
+        prefix: &str,
+        module: &str,
+        name: &str,
+        connections: &[Connection],
+        line: usize,
+    ) -> Result<(), VerilogError> {
+        let definition = self.modules.get(module).copied();
+        let cell =
+            library_cell(module).filter(|(_, pins)| definition.is_none_or(|m| m.ports == *pins));
+        let ports = match (&cell, definition) {
+            (Some((_, pins)), _) => pins.clone(),
+            (None, Some(m)) => m.ports.clone(),
+            (None, None) => {
+                return Err(VerilogError::UnknownModule {
+                    line,
+                    name: module.to_string(),
+                })
+            }
+        };
+
+        // Expression connected to each port, in port order
+        let mut connected: Vec<Option<&Expression>> = vec![None; ports.len()];
+        for (i, (port, value)) in connections.iter().enumerate() {
+            let position = match port {
+                Some(port) => ports.iter().position(|p| p == port),
+                None => (i < ports.len()).then_some(i),
+            };
+            let Some(position) = position else {
+                return Err(VerilogError::Syntax {
+                    line,
+                    message: format!(
+                        "module {} has no port {}",
+                        module,
+                        port.clone().unwrap_or_else(|| (i + 1).to_string())
+                    ),
+                });
+            };
+            connected[position] = value.as_ref();
+        }
+        let none = HashMap::new();
+        let unconnected = |port: &str| VerilogError::Syntax {
+            line,
+            message: format!("input {} of {} is not connected", port, name),
+        };
+
+        if let Some((kind, pins)) = cell {
+            let mut inputs = Vec::new();
+            for (pin, value) in pins.iter().zip(&connected).take(pins.len() - 1) {
+                let value = value.ok_or_else(|| unconnected(pin))?;
+                let bits = self.eval(builder, signals, &none, value)?;
+                inputs.push(fit(builder, bits, 1)[0]);
+            }
+            let value = match kind {
+                CellKind::Nand => builder.nand(&inputs),
+                CellKind::Const(value) => builder.constant(value),
+                CellKind::Dff => builder.dff(inputs[0], inputs[1]),
+            };
+            if let Some(target) = connected[pins.len() - 1] {
+                let targets = self.target(signals, target, line)?;
+                if let Some(target) = targets.first() {
+                    drive(builder, *target, value)?;
+                }
+            }
+            return Ok(());
+        }
+
+        let definition = definition.unwrap();
+        let mut inputs = HashMap::new();
+        for (port, value) in ports.iter().zip(&connected) {
+            let declaration = definition.declaration(port).unwrap();
+            if declaration.direction == Some(Direction::Input) {
+                let value = value.ok_or_else(|| unconnected(port))?;
+                let bits = self.eval(builder, signals, &none, value)?;
+                inputs.insert(port.clone(), fit(builder, bits, declaration.width()));
+            }
+        }
+        let child_prefix = format!("{}{}/", prefix, name);
+        let child = builder.scoped(name, |builder| {
+            self.instantiate(builder, definition, &child_prefix, Some(inputs))
+        })?;
+        for (port, value) in ports.iter().zip(&connected) {
+            let declaration = definition.declaration(port).unwrap();
+            if let (Some(Direction::Output), Some(value)) = (declaration.direction, value) {
+                let targets = self.target(signals, value, line)?;
+                let values = fit(builder, child[port].nets.clone(), targets.len());
+                for (target, value) in targets.iter().zip(values) {
+                    drive(builder, *target, value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the statements of an always block, recording the value each assigns to each bit
+    /// and, for blocking assignments, the values later statements read
+    fn execute(
+        &mut self,
+        builder: &mut Builder,
+        signals: &HashMap<String, Signal>,
+        statement: &Statement,
+        clocked: bool,
+        assigned: &mut Assigned,
+        visible: &mut HashMap<String, Vec<Net>>,
+    ) -> Result<(), VerilogError> {
+        match statement {
+            Statement::Block(statements) => {
+                for statement in statements {
+                    self.execute(builder, signals, statement, clocked, assigned, visible)?;
+                }
+            }
+            Statement::Assign {
+                target,
+                value,
+                blocking,
+                line,
+            } => {
+                let bits = self.target_bits(signals, target, *line)?;
+                let values = self.eval(builder, signals, visible, value)?;
+                let values = fit(builder, values, bits.len());
+                for ((name, k), value) in bits.into_iter().zip(values) {
+                    let nets = &signals[&name].nets;
+                    if *blocking {
+                        visible.entry(name.clone()).or_insert_with(|| nets.clone())[k] = value;
+                    }
+                    assigned
+                        .entry(name)
+                        .or_insert_with(|| vec![None; nets.len()])[k] = Some(value);
+                }
+            }
+            Statement::If {
+                condition,
+                then,
+                otherwise,
+                line,
+            } => {
+                let bits = self.eval(builder, signals, visible, condition)?;
+                let condition = any(builder, &bits);
+                let (mut assigned_then, mut visible_then) = (assigned.clone(), visible.clone());
+                self.execute(
+                    builder,
+                    signals,
+                    then,
+                    clocked,
+                    &mut assigned_then,
+                    &mut visible_then,
+                )?;
+                let (mut assigned_else, mut visible_else) = (assigned.clone(), visible.clone());
+                if let Some(otherwise) = otherwise {
+                    self.execute(
+                        builder,
+                        signals,
+                        otherwise,
+                        clocked,
+                        &mut assigned_else,
+                        &mut visible_else,
+                    )?;
+                }
+
+                // Bits assigned on only one path keep their value on the other, which needs a
+                // flip-flop
+                let names: BTreeSet<String> = assigned_then
+                    .keys()
+                    .chain(assigned_else.keys())
+                    .cloned()
+                    .collect();
+                for name in names {
+                    let nets = &signals[&name].nets;
+                    let unassigned = vec![None; nets.len()];
+                    let then = assigned_then.get(&name).unwrap_or(&unassigned);
+                    let otherwise = assigned_else.get(&name).unwrap_or(&unassigned);
+                    let mut merged = Vec::new();
+                    for k in 0..nets.len() {
+                        merged.push(match (then[k], otherwise[k]) {
+                            (a, b) if a == b => a,
+                            (Some(_), None) | (None, Some(_)) if !clocked => {
+                                return Err(VerilogError::Unsupported {
+                                    line: *line,
+                                    construct: format!("latch for {}", name),
+                                })
+                            }
+                            (a, b) => Some(build_mux2(
+                                builder,
+                                condition,
+                                &[b.unwrap_or(nets[k]), a.unwrap_or(nets[k])],
+                            )),
+                        });
+                    }
+                    assigned.insert(name, merged);
+                }
+                let names: BTreeSet<String> = visible_then
+                    .keys()
+                    .chain(visible_else.keys())
+                    .cloned()
+                    .collect();
+                for name in names {
+                    let base = visible.get(&name).unwrap_or(&signals[&name].nets).clone();
+                    let then = visible_then.get(&name).unwrap_or(&base);
+                    let otherwise = visible_else.get(&name).unwrap_or(&base);
+                    let merged = then
+                        .iter()
+                        .zip(otherwise)
+                        .map(|(a, b)| {
+                            if a == b {
+                                *a
+                            } else {
+                                build_mux2(builder, condition, &[*b, *a])
+                            }
+                        })
+                        .collect();
+                    visible.insert(name, merged);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn signal<'s>(
+        &self,
+        signals: &'s HashMap<String, Signal>,
+        name: &str,
+        line: usize,
+    ) -> Result<&'s Signal, VerilogError> {
+        signals.get(name).ok_or_else(|| VerilogError::Undeclared {
+            line,
+            name: name.to_string(),
+        })
+    }
+
+    /// Signal and offset of each bit an assignment writes, least significant first
+    fn target_bits(
+        &self,
+        signals: &HashMap<String, Signal>,
+        target: &Expression,
+        line: usize,
+    ) -> Result<Vec<(String, usize)>, VerilogError> {
+        let offsets = |name: &str, offsets: Range<usize>| -> Vec<(String, usize)> {
+            offsets.map(|k| (name.to_string(), k)).collect()
+        };
+        match target {
+            Expression::Signal { name, line } => {
+                let width = self.signal(signals, name, *line)?.nets.len();
+                Ok(offsets(name, 0..width))
+            }
+            Expression::Bit { name, index, line } => {
+                let k = bit_offset(self.signal(signals, name, *line)?, *index, *line)?;
+                Ok(offsets(name, k..k + 1))
+            }
+            Expression::Slice {
+                name,
+                msb,
+                lsb,
+                line,
+            } => {
+                let range = slice_offsets(self.signal(signals, name, *line)?, *msb, *lsb, *line)?;
+                Ok(offsets(name, range))
+            }
+            Expression::Concat(parts) => {
+                let mut bits = Vec::new();
+                for part in parts.iter().rev() {
+                    bits.extend(self.target_bits(signals, part, line)?);
+                }
+                Ok(bits)
+            }
+            _ => Err(VerilogError::Syntax {
+                line,
+                message: "expression can't be assigned to".to_string(),
+            }),
+        }
+    }
+
+    fn target(
+        &self,
+        signals: &HashMap<String, Signal>,
+        target: &Expression,
+        line: usize,
+    ) -> Result<Vec<Net>, VerilogError> {
+        Ok(self
+            .target_bits(signals, target, line)?
+            .into_iter()
+            .map(|(name, k)| signals[&name].nets[k])
+            .collect())
+    }
+
+    /// Builds an expression, returning its bits least significant first. Signals in visible
+    /// are read from there, as assigned earlier in an always block
+    fn eval(
+        &mut self,
+        builder: &mut Builder,
+        signals: &HashMap<String, Signal>,
+        visible: &HashMap<String, Vec<Net>>,
+        expression: &Expression,
+    ) -> Result<Vec<Net>, VerilogError> {
+        let read = |name: &str, line: usize| -> Result<(Vec<Net>, &Signal), VerilogError> {
+            let signal = self.signal(signals, name, line)?;
+            let nets = visible.get(name).unwrap_or(&signal.nets).clone();
+            Ok((nets, signal))
+        };
+        Ok(match expression {
+            Expression::Signal { name, line } => read(name, *line)?.0,
+            Expression::Bit { name, index, line } => {
+                let (nets, signal) = read(name, *line)?;
+                vec![nets[bit_offset(signal, *index, *line)?]]
+            }
+            Expression::Slice {
+                name,
+                msb,
+                lsb,
+                line,
+            } => {
+                let (nets, signal) = read(name, *line)?;
+                nets[slice_offsets(signal, *msb, *lsb, *line)?].to_vec()
+            }
+            Expression::Const(bits) => bits.iter().map(|b| builder.constant(*b)).collect(),
+            Expression::Concat(parts) => {
+                let mut bits = Vec::new();
+                for part in parts.iter().rev() {
+                    bits.extend(self.eval(builder, signals, visible, part)?);
+                }
+                bits
+            }
+            Expression::Repeat { count, part, line } => {
+                let bits = self.eval(builder, signals, visible, part)?;
+                if bits.len() * count > MAX_WIDTH {
+                    return Err(VerilogError::Syntax {
+                        line: *line,
+                        message: format!("replication is wider than {} bits", MAX_WIDTH),
+                    });
+                }
+                bits.repeat(*count)
+            }
+            Expression::Unary(op, operand) => {
+                let bits = self.eval(builder, signals, visible, operand)?;
+                match *op {
+                    "~" => bits.iter().map(|b| builder.not(*b)).collect(),
+                    "!" => {
+                        let any = any(builder, &bits);
+                        vec![builder.not(any)]
+                    }
+                    "&" => vec![all(builder, &bits)],
+                    "~&" => vec![builder.nand(&bits)],
+                    "|" => vec![any(builder, &bits)],
+                    "~|" => {
+                        let any = any(builder, &bits);
+                        vec![builder.not(any)]
+                    }
+                    "^" => vec![parity(builder, &bits, false)],
+                    _ => vec![parity(builder, &bits, true)],
+                }
+            }
+            Expression::Binary(op, operands) => {
+                let mut values = Vec::new();
+                for operand in operands {
+                    let bits = self.eval(builder, signals, visible, operand)?;
+                    values.push(match *op {
+                        "&&" | "||" => vec![any(builder, &bits)],
+                        _ => bits,
+                    });
+                }
+                let width = values.iter().map(Vec::len).max().unwrap_or(1);
+                let values: Vec<Vec<Net>> = values
+                    .into_iter()
+                    .map(|bits| fit(builder, bits, width))
+                    .collect();
+                let bits: Vec<Vec<Net>> = (0..width)
+                    .map(|k| values.iter().map(|v| v[k]).collect())
+                    .collect();
+                match *op {
+                    "&" | "&&" => bits.iter().map(|b| builder.and(b)).collect(),
+                    "|" | "||" => bits.iter().map(|b| builder.or(b)).collect(),
+                    "^" => bits.iter().map(|b| parity(builder, b, false)).collect(),
+                    "~^" | "^~" => bits.iter().map(|b| parity(builder, b, true)).collect(),
+                    _ => {
+                        let equal: Vec<Net> =
+                            bits.iter().map(|b| parity(builder, b, true)).collect();
+                        let equal = all(builder, &equal);
+                        match *op {
+                            "==" | "===" => vec![equal],
+                            _ => vec![builder.not(equal)],
+                        }
+                    }
+                }
+            }
+            Expression::Ternary(parts) => {
+                let [condition, then, otherwise] = &**parts;
+                let bits = self.eval(builder, signals, visible, condition)?;
+                let condition = any(builder, &bits);
+                let then = self.eval(builder, signals, visible, then)?;
+                let otherwise = self.eval(builder, signals, visible, otherwise)?;
+                let width = then.len().max(otherwise.len());
+                let then = fit(builder, then, width);
+                let otherwise = fit(builder, otherwise, width);
+                then.iter()
+                    .zip(&otherwise)
+                    .map(|(a, b)| build_mux2(builder, condition, &[*b, *a]))
+                    .collect()
+            }
+        })
+    }
+}
+
+fn bit_offset(signal: &Signal, index: i64, line: usize) -> Result<usize, VerilogError> {
+    signal
+        .declaration
+        .offset(index)
+        .ok_or_else(|| VerilogError::Syntax {
+            line,
+            message: format!("{}[{}] is out of range", signal.declaration.name, index),
+        })
+}
+
+fn slice_offsets(
+    signal: &Signal,
+    msb: i64,
+    lsb: i64,
+    line: usize,
+) -> Result<Range<usize>, VerilogError> {
+    let high = bit_offset(signal, msb, line)?;
+    let low = bit_offset(signal, lsb, line)?;
+    if high < low {
+        return Err(VerilogError::Syntax {
+            line,
+            message: format!("{}[{}:{}] is reversed", signal.declaration.name, msb, lsb),
+        });
+    }
+    Ok(low..high + 1)
+}
+
+/// Truncates or zero extends bits to a width
+fn fit(builder: &mut Builder, mut bits: Vec<Net>, width: usize) -> Vec<Net> {
+    if bits.len() < width {
+        let zero = builder.constant(false);
+        bits.resize(width, zero);
+    }
+    bits.truncate(width);
+    bits
+}
+
+fn any(builder: &mut Builder, bits: &[Net]) -> Net {
+    match bits {
+        [bit] => *bit,
+        _ => builder.or(bits),
+    }
+}
+
+fn all(builder: &mut Builder, bits: &[Net]) -> Net {
+    match bits {
+        [bit] => *bit,
+        _ => builder.and(bits),
+    }
+}
+
+/// XOR of any number of bits, or XNOR if inverted, built from two input gates since a wider
+/// [Builder::xor] isn't a parity
+fn parity(builder: &mut Builder, bits: &[Net], inverted: bool) -> Net {
+    let (last, rest) = bits.split_last().expect("parity of no bits");
+    let Some((first, middle)) = rest.split_first() else {
+        return if inverted { builder.not(*last) } else { *last };
+    };
+    let mut acc = *first;
+    for bit in middle {
+        acc = builder.xor(&[acc, *bit]);
+    }
+    if inverted {
+        builder.xnor(&[acc, *last])
+    } else {
+        builder.xor(&[acc, *last])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::RippleCounter;
+    use crate::flipflop::DFlipflop;
+    use crate::math::RippleCarryAdder;
+    use crate::netlist::Builder;
+
+    #[test]
+    fn test_identifier() {
+        for (name, expected) in [
+            ("sum", "sum"),
+            ("_carry$1", "_carry$1"),
+            ("full_add[3]/xor0/nand1", "\\full_add[3]/xor0/nand1 "),
+            ("3state", "\\3state "),
+            ("wire", "\\wire "),
+            ("a b", "\\a_b "),
+        ] {
+            assert_eq!(identifier(name), expected, "failed for inputs: {:?}", name);
+        }
+    }
+
+    #[test]
+    fn test_port_groups() {
+        let mut b = Builder::new("ports");
+        let a = b.input_bus::<2>("a");
+        let c = b.input("c[1]");
+        let x = b.input("x");
+        let x0 = b.input("x[0]");
+        b.output_bus("y", &[a[0], a[1], c, x, x0]);
+        let netlist = b.finish();
+
+        let names = |groups: Vec<PortGroup>| -> Vec<(String, usize, bool)> {
+            groups
+                .into_iter()
+                .map(|g| (g.name, g.ports.len(), g.bus))
+                .collect()
+        };
+        assert_eq!(
+            names(port_groups(netlist.inputs())),
+            [
+                ("a".to_string(), 2, true),
+                ("c[1]".to_string(), 1, false),
+                ("x".to_string(), 1, false),
+                ("x[0]".to_string(), 1, false),
+            ]
+        );
+        assert_eq!(
+            names(port_groups(netlist.outputs())),
+            [("y".to_string(), 5, true)]
+        );
+    }
+
+    #[test]
+    fn test_to_verilog() {
+        let mut b = Builder::new("small");
+        let a = b.input_bus::<2>("a");
+        let x = b.nand(&[a[0], a[1]]);
+        let zero = b.constant(false);
+        b.output("x", x);
+        b.output("zero", zero);
+        let netlist = b.finish();
+
+        assert_eq!(
+            to_verilog(&netlist, CellStyle::Primitives),
+            "// Generated by nandverse from netlist small\n\
+             module small (\n    input [1:0] a,\n    output x,\n    output zero\n);\n    \
+             wire nand0;\n    \
+             wire tie00;\n    \
+             nand u_nand0 (nand0, a[0], a[1]);\n    \
+             assign tie00 = 1'b0;\n    \
+             assign x = nand0;\n    \
+             assign zero = tie00;\n\
+             endmodule\n"
+        );
+        assert_eq!(
+            to_verilog(&netlist, CellStyle::Library),
+            "// Generated by nandverse from netlist small\n\
+             module small (\n    input [1:0] a,\n    output x,\n    output zero\n);\n    \
+             wire nand0;\n    \
+             wire tie00;\n    \
+             NAND2 u_nand0 (.A1(a[0]), .A2(a[1]), .ZN(nand0));\n    \
+             TIELO u_tie00 (.Z(tie00));\n    \
+             assign x = nand0;\n    \
+             assign zero = tie00;\n\
+             endmodule\n\n\
+             module NAND2 (input A1, input A2, output ZN);\n    \
+             assign ZN = ~(A1 & A2);\n\
+             endmodule\n\n\
+             module TIELO (output Z);\n    \
+             assign Z = 1'b0;\n\
+             endmodule\n"
+        );
+    }
+
+    #[test]
+    fn test_components() {
+        let verilog = to_verilog(
+            &RippleCarryAdder::<4>::new().netlist(),
+            CellStyle::Primitives,
+        );
+        assert!(verilog.contains("module ripple_carry_adder (\n    input [3:0] a,"));
+        assert!(verilog.contains("    output [3:0] sum\n);"));
+        assert!(verilog.contains("    assign sum[3] = \\full_add[3]/"));
+        assert!(!verilog.contains(DFF_MODULE));
+
+        let verilog = to_verilog(&DFlipflop::new().netlist(), CellStyle::Library);
+        assert!(verilog.contains("    DFF u_dff0 (.CK(clk), .D(d), .Q(dff0));\n"));
+        assert!(verilog.contains("\nmodule NAND2 (input A1, input A2, output ZN);\n"));
+        assert!(verilog.contains("\nmodule DFF (input CK, input D, output reg Q);\n"));
+    }
+
+    #[test]
+    fn test_testbench() {
+        let netlist = RippleCarryAdder::<2>::new().netlist();
+        let table = TruthTable::from_netlist(&netlist);
+        let tb = table_testbench(&netlist, &table);
+        assert_eq!(
+            tb,
+            testbench(
+                &netlist,
+                &(0..16).map(|r| table.inputs(r)).collect::<Vec<_>>()
+            )
+        );
+        assert!(tb.starts_with(
+            "`timescale 1ns / 1ps\n\nmodule ripple_carry_adder_tb;\n    \
+             reg [1:0] a;\n    \
+             reg [1:0] b;\n    \
+             wire [1:0] sum;\n    \
+             integer errors = 0;\n\n    \
+             ripple_carry_adder dut (\n        .a(a),\n        .b(b),\n        .sum(sum)\n    );\n"
+        ));
+
+        // 3 + 2 = 1 in two bits
+        let row = (0..16)
+            .find(|r| table.inputs(*r) == [true, true, false, true])
+            .unwrap();
+        assert!(tb.contains(&format!(
+            "        a = 2'b11;\n        b = 2'b10;\n        #5;\n        #5;\n        \
+             if ({{sum}} !== 2'b01) begin\n            \
+             $display(\"vector {}: outputs %b, expected %b\", {{sum}}, 2'b01);\n",
+            row
+        )));
+        assert!(tb.ends_with("$display(\"PASS: 16 vectors\");\n        else\n            \
+             $display(\"FAIL: %0d of 16 vectors\", errors);\n        $finish;\n    end\nendmodule\n"));
+    }
+
+    #[test]
+    fn test_sequential_testbench() {
+        // The clock rises after the other inputs have settled, and the outputs follow the
+        // counter's steps
+        let netlist = RippleCounter::<2>::new().netlist();
+        let vectors: Vec<Vec<bool>> = (0..6).map(|i| vec![i % 2 == 1]).collect();
+        let tb = testbench(&netlist, &vectors);
+        assert!(tb.contains(
+            "        #5;\n        clk = 1'b1;\n        #5;\n        if ({q} !== 2'b01) begin\n"
+        ));
+        let expected: Vec<&str> = tb
+            .lines()
+            .filter_map(|l| l.strip_prefix("        if ({q} !== "))
+            .collect();
+        assert_eq!(
+            expected,
+            [
+                "2'b00) begin",
+                "2'b01) begin",
+                "2'b01) begin",
+                "2'b10) begin",
+                "2'b10) begin",
+                "2'b11) begin"
+            ]
+        );
+    }
+
+    /// Outputs of a sequential netlist after each step of the inputs
+    fn run(netlist: &Netlist, vectors: &[Vec<bool>]) -> Vec<Vec<bool>> {
+        let mut state = netlist.reset_state();
+        vectors
+            .iter()
+            .map(|inputs| {
+                let values = netlist.step(&mut state, inputs);
+                netlist
+                    .outputs()
+                    .iter()
+                    .map(|p| values[p.net().index()])
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_from_verilog() {
+        let netlist = from_verilog(
+            "// Two bit register with enable, and an inverted copy\n\
+             module reg2 (clk, en, d, q, qn, any);\n    \
+             input clk, en;\n    \
+             input [1:0] d;\n    \
+             output reg [1:0] q;\n    \
+             output [1:0] qn;\n    \
+             output any;\n    \
+             wire w;\n\n    \
+             assign qn = ~q;\n    \
+             or g1 (w, q[0], q[1]);\n    \
+             buf (any, w);\n\n    \
+             always @(posedge clk) begin\n        \
+             if (en)\n            \
+             q <= d;\n    \
+             end\n\
+             endmodule\n",
+            None,
+        )
+        .unwrap();
+        assert_eq!(netlist.name(), "reg2");
+        let inputs: Vec<&str> = netlist.inputs().iter().map(|p| p.name()).collect();
+        assert_eq!(inputs, ["clk", "en", "d[0]", "d[1]"]);
+        let outputs: Vec<&str> = netlist.outputs().iter().map(|p| p.name()).collect();
+        assert_eq!(outputs, ["q[0]", "q[1]", "qn[0]", "qn[1]", "any"]);
+        assert_eq!(netlist.flipflops().len(), 2);
+
+        let vectors = [
+            [false, true, true, false],
+            [true, true, true, false],
+            [false, false, false, true],
+            [true, false, false, true],
+            [false, true, false, true],
+            [true, true, false, true],
+        ]
+        .map(|v| v.to_vec());
+        let expected = [
+            [false, false, true, true, false],
+            [true, false, false, true, true],
+            [true, false, false, true, true],
+            [true, false, false, true, true],
+            [true, false, false, true, true],
+            [false, true, true, false, true],
+        ];
+        for (outputs, expected) in run(&netlist, &vectors).iter().zip(expected) {
+            assert_eq!(outputs, &expected, "failed for inputs: {:?}", vectors);
+        }
+    }
+
+    #[test]
+    fn test_expressions() {
+        let netlist = from_verilog(
+            "module ops (input [2:0] a, input s, output [7:0] y);\n\
+             assign y[0] = ^a;\n\
+             assign y[1] = ~&a;\n\
+             assign y[2] = a[0] && !a[1] || a[2];\n\
+             assign y[3] = a[1:0] == 2'b10;\n\
+             assign {y[5], y[4]} = s ? a[2:1] : {2{a[0]}};\n\
+             assign y[7:6] = a[0] ~^ a[1];\n\
+             endmodule\n",
+            None,
+        )
+        .unwrap();
+        let expected = TruthTable::from_fn(|[a0, a1, a2, s]: &[bool; 4]| {
+            let (pick_hi, pick_lo) = if *s { (*a2, *a1) } else { (*a0, *a0) };
+            [
+                a0 ^ a1 ^ a2,
+                !(a0 & a1 & a2),
+                (*a0 && !*a1) || *a2,
+                !*a0 && *a1,
+                pick_lo,
+                pick_hi,
+                a0 == a1,
+                false,
+            ]
+        });
+        assert_eq!(TruthTable::from_netlist(&netlist).verify(&expected), Ok(()));
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        // Half adders instantiated by name and by position make a full adder
+        let source = "module half (input a, input b, output s, output c);\n\
+             xor (s, a, b);\n\
+             and (c, a, b);\n\
+             endmodule\n\n\
+             module full (input a, input b, input cin, output s, output cout);\n\
+             wire s1, c1, c2;\n\
+             half h1 (.a(a), .b(b), .s(s1), .c(c1));\n\
+             half h2 (s1, cin, s, c2);\n\
+             assign cout = c1 | c2;\n\
+             endmodule\n";
+        let netlist = from_verilog(source, None).unwrap();
+        assert_eq!(netlist.name(), "full");
+        assert!(netlist.scopes().iter().any(|s| s.name() == "h2"));
+        let expected = TruthTable::from_fn(|[a, b, cin]: &[bool; 3]| {
+            let sum = *a as u8 + *b as u8 + *cin as u8;
+            [sum & 1 == 1, sum >= 2]
+        });
+        assert_eq!(TruthTable::from_netlist(&netlist).verify(&expected), Ok(()));
+
+        let half = from_verilog(source, Some("half")).unwrap();
+        let mut builder = Builder::new("half");
+        let [a, b] = [builder.input("a"), builder.input("b")];
+        let s = builder.xor(&[a, b]);
+        let c = builder.and(&[a, b]);
+        builder.output("s", s);
+        builder.output("c", c);
+        assert_eq!(half.nand_count(), builder.finish().nand_count());
+        assert_eq!(
+            from_verilog(source, Some("third")).unwrap_err(),
+            VerilogError::NoTopModule
+        );
+    }
+
+    #[test]
+    fn test_from_verilog_errors() {
+        let cases = [
+            (
+                "module m (input a, output y);\nassign y = a;\nassign y = ~a;\nendmodule",
+                VerilogError::Netlist(NetlistError::MultipleDrivers("y".to_string())),
+            ),
+            (
+                "module m (input a, output y);\nassign y = a & b;\nendmodule",
+                VerilogError::Undeclared {
+                    line: 2,
+                    name: "b".to_string(),
+                },
+            ),
+            (
+                "module m (input [3:0] a, output [3:0] y);\n\nassign y = a + 1;\nendmodule",
+                VerilogError::Unsupported {
+                    line: 3,
+                    construct: "operator +".to_string(),
+                },
+            ),
+            (
+                "module m (input a, output reg y);\nalways @*\n  if (a) y = 1;\nendmodule",
+                VerilogError::Unsupported {
+                    line: 3,
+                    construct: "latch for y".to_string(),
+                },
+            ),
+            (
+                "module m (input a, output y);\nadder u (a, y);\nendmodule",
+                VerilogError::UnknownModule {
+                    line: 2,
+                    name: "adder".to_string(),
+                },
+            ),
+            (
+                "module m (input a, output y)\nendmodule",
+                VerilogError::Syntax {
+                    line: 2,
+                    message: "unexpected endmodule".to_string(),
+                },
+            ),
+            (
+                "module m (output y);\nassign y = 99999999999'b1;\nendmodule",
+                VerilogError::Syntax {
+                    line: 2,
+                    message: "width of 99999999999'b1 is not between 1 and 65536".to_string(),
+                },
+            ),
+            (
+                "module m (input a, output y);\nassign y = {0{a}};\nendmodule",
+                VerilogError::Syntax {
+                    line: 2,
+                    message: "replication count 0 is not between 1 and 65536".to_string(),
+                },
+            ),
+            (
+                "module m (input a, output y);\nassign y = {99999999999{a}};\nendmodule",
+                VerilogError::Syntax {
+                    line: 2,
+                    message: "replication count 99999999999 is not between 1 and 65536".to_string(),
+                },
+            ),
+            (
+                "module m (input a, output y);\nassign y = {257{ {256{a}} }};\nendmodule",
+                VerilogError::Syntax {
+                    line: 2,
+                    message: "replication is wider than 65536 bits".to_string(),
+                },
+            ),
+            (
+                "module m (input [99999999:0] a, output y);\nendmodule",
+                VerilogError::Syntax {
+                    line: 1,
+                    message: "range [99999999:0] is wider than 65536 bits".to_string(),
+                },
+            ),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(
+                from_verilog(source, None).unwrap_err(),
+                expected,
+                "failed for inputs: {:?}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let nested = |open: &str, close: &str, depth: usize| {
+            format!(
+                "module m (input a, output y);\nassign y = {}a{};\nendmodule",
+                open.repeat(depth),
+                close.repeat(depth)
+            )
+        };
+        let netlist = from_verilog(&nested("(", ")", 63), None).unwrap();
+        assert_eq!(netlist.evaluate(&[true]), [true]);
+        let netlist = from_verilog(&nested("~", "", 63), None).unwrap();
+        assert_eq!(netlist.evaluate(&[true]), [false]);
+        for (open, close) in [("(", ")"), ("~", ""), ("a ? a : ", "")] {
+            let source = nested(open, close, 200_000);
+            assert_eq!(
+                from_verilog(&source, None).unwrap_err(),
+                VerilogError::Syntax {
+                    line: 2,
+                    message: "expression is nested more than 64 deep".to_string(),
+                },
+                "failed for inputs: {:?}",
+                open
+            );
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for style in [CellStyle::Primitives, CellStyle::Library] {
+            let netlist = RippleCarryAdder::<3>::new().netlist();
+            let imported = from_verilog(&to_verilog(&netlist, style), None).unwrap();
+            assert_eq!(imported.name(), netlist.name());
+            assert_eq!(imported.nand_count(), netlist.nand_count());
+            assert_eq!(
+                TruthTable::from_netlist(&imported),
+                TruthTable::from_netlist(&netlist),
+                "failed for inputs: {:?}",
+                style
+            );
+
+            let netlist = RippleCounter::<3>::new().netlist();
+            let imported = from_verilog(&to_verilog(&netlist, style), None).unwrap();
+            assert_eq!(imported.flipflops().len(), 3);
+            let vectors: Vec<Vec<bool>> = (0..20).map(|i| vec![i % 2 == 1]).collect();
+            assert_eq!(
+                run(&imported, &vectors),
+                run(&netlist, &vectors),
+                "failed for inputs: {:?}",
+                style
+            );
+        }
+    }
+}
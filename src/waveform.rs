@@ -0,0 +1,165 @@
+/// Values of a set of named signals sampled over time
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Waveform {
+    names: Vec<String>,
+    times: Vec<u64>,
+    samples: Vec<Vec<bool>>,
+}
+
+impl Waveform {
+    pub fn new(names: Vec<String>) -> Self {
+        Waveform {
+            names,
+            times: Vec::new(),
+            samples: Vec::new(),
+        }
+    }
+
+    /// Adds the value of every signal at a time, which must not be before the last sample
+    pub fn push(&mut self, time: u64, values: &[bool]) {
+        assert_eq!(values.len(), self.names.len(), "wrong number of values");
+        if let Some(last) = self.times.last() {
+            assert!(time >= *last, "samples must be pushed in time order");
+        }
+        self.times.push(time);
+        self.samples.push(values.to_vec());
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn times(&self) -> &[u64] {
+        &self.times
+    }
+
+    pub fn samples(&self) -> &[Vec<bool>] {
+        &self.samples
+    }
+
+    /// Values of one signal in sample order
+    pub fn signal(&self, name: &str) -> Option<Vec<bool>> {
+        let i = self.names.iter().position(|n| n == name)?;
+        Some(self.samples.iter().map(|s| s[i]).collect())
+    }
+
+    /// Writes the waveform as a Value Change Dump, which waveform viewers such as GTKWave can
+    /// open. Times are in units of the timescale, e.g. "1ns"
+    pub fn to_vcd(&self, module: &str, timescale: &str) -> String {
+        let mut vcd = format!(
+            "$timescale {} $end\n$scope module {} $end\n",
+            timescale, module
+        );
+        for (i, name) in self.names.iter().enumerate() {
+            vcd.push_str(&format!(
+                "$var wire 1 {} {} $end\n",
+                vcd_id(i),
+                name.replace(' ', "_")
+            ));
+        }
+        vcd.push_str("$upscope $end\n$enddefinitions $end\n");
+
+        let mut last: Option<&Vec<bool>> = None;
+        for (time, values) in self.times.iter().zip(&self.samples) {
+            let changes: Vec<String> = values
+                .iter()
+                .enumerate()
+                .filter(|(i, v)| last.is_none_or(|l| l[*i] != **v))
+                .map(|(i, v)| format!("{}{}", *v as u8, vcd_id(i)))
+                .collect();
+            if last.is_none() {
+                vcd.push_str(&format!("#{}\n$dumpvars\n", time));
+                vcd.push_str(&changes.join("\n"));
+                vcd.push_str("\n$end\n");
+            } else if !changes.is_empty() {
+                vcd.push_str(&format!("#{}\n{}\n", time, changes.join("\n")));
+            }
+            last = Some(values);
+        }
+        vcd
+    }
+
+    /// Draws each signal on a line, with two characters per sample
+    pub fn to_text(&self) -> String {
+        let width = self.names.iter().map(|n| n.len()).max().unwrap_or(0);
+        let mut text = String::new();
+        for (i, name) in self.names.iter().enumerate() {
+            let trace: String = self
+                .samples
+                .iter()
+                .map(|s| if s[i] { "‾‾" } else { "__" })
+                .collect();
+            text.push_str(&format!("{:>width$} {}\n", name, trace));
+        }
+        text
+    }
+}
+
+/// Short identifier of a signal in a VCD file, made of printable characters
+fn vcd_id(index: usize) -> String {
+    let mut id = String::new();
+    let mut i = index;
+    loop {
+        id.push((b'!' + (i % 94) as u8) as char);
+        i /= 94;
+        if i == 0 {
+            return id;
+        }
+        i -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Waveform {
+        let mut waveform = Waveform::new(vec!["clk".to_string(), "q[0]".to_string()]);
+        for (time, values) in [(0, [false, false]), (5, [true, true]), (10, [false, true])] {
+            waveform.push(time, &values);
+        }
+        waveform
+    }
+
+    #[test]
+    fn test_vcd_id() {
+        for (index, id) in [
+            (0, "!"),
+            (93, "~"),
+            (94, "!!"),
+            (95, "\"!"),
+            (94 + 94 * 94, "!!!"),
+        ] {
+            assert_eq!(vcd_id(index), id, "failed for index: {}", index);
+        }
+    }
+
+    #[test]
+    fn test_to_vcd() {
+        assert_eq!(
+            example().to_vcd("counter", "1ns"),
+            "$timescale 1ns $end\n\
+             $scope module counter $end\n\
+             $var wire 1 ! clk $end\n\
+             $var wire 1 \" q[0] $end\n\
+             $upscope $end\n\
+             $enddefinitions $end\n\
+             #0\n\
+             $dumpvars\n\
+             0!\n\
+             0\"\n\
+             $end\n\
+             #5\n\
+             1!\n\
+             1\"\n\
+             #10\n\
+             0!\n"
+        );
+    }
+
+    #[test]
+    fn test_to_text() {
+        assert_eq!(example().to_text(), " clk __‾‾__\nq[0] __‾‾‾‾\n");
+        assert_eq!(example().signal("q[0]"), Some(vec![false, true, true]));
+    }
+}